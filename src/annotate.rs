@@ -0,0 +1,228 @@
+//! Annotated copies of the analyzed files, with each function's complexity
+//! in a comment at the end of its `def` line.
+
+use crate::AnalysisResult;
+use anyhow::{Context, Result};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+/// Start of an annotation. Annotations are always written with this exact
+/// spacing so that they can be recognized and removed again.
+const MARKER: &[u8] = b"  # analyzr: ";
+
+/// The annotation of a function with the given complexity.
+pub fn annotation(complexity: u32) -> String {
+    format!("complexity={}", complexity)
+}
+
+/// `source` with the annotation of every line in `annotations` (keyed by
+/// 1-based line number) appended, replacing any earlier one, and the
+/// annotations of all other lines removed. Every other byte, line endings
+/// included, is kept as it is.
+pub fn rewrite(source: &[u8], annotations: &HashMap<u32, String>) -> Vec<u8> {
+    let mut output = Vec::with_capacity(source.len());
+    for (index, line) in source.split_inclusive(|&b| b == b'\n').enumerate() {
+        let (content, ending) = split_ending(line);
+        let content = strip(content);
+        output.extend_from_slice(content);
+        if let Some(annotation) = annotations.get(&(index as u32 + 1)) {
+            // A comment after a line continuation would be a syntax error.
+            if !content.ends_with(b"\\") {
+                output.extend_from_slice(MARKER);
+                output.extend_from_slice(annotation.as_bytes());
+            }
+        }
+        output.extend_from_slice(ending);
+    }
+    output
+}
+
+fn split_ending(line: &[u8]) -> (&[u8], &[u8]) {
+    let ending = if line.ends_with(b"\r\n") {
+        2
+    } else if line.ends_with(b"\n") {
+        1
+    } else {
+        0
+    };
+    line.split_at(line.len() - ending)
+}
+
+/// `content` without its trailing annotation, if it has one.
+fn strip(content: &[u8]) -> &[u8] {
+    let Some(start) = content
+        .windows(MARKER.len())
+        .rposition(|window| window == MARKER)
+    else {
+        return content;
+    };
+    let fields = &content[start + MARKER.len()..];
+    let is_field = |field: &[u8]| match field.iter().position(|&b| b == b'=') {
+        Some(equals) => {
+            equals > 0
+                && field[..equals].iter().all(|&b| b.is_ascii_lowercase() || b == b'_')
+                && field.len() > equals + 1
+                && field[equals + 1..].iter().all(|&b| b.is_ascii_digit() || b == b'.')
+        }
+        None => false,
+    };
+    if fields.split(|&b| b == b' ').all(is_field) {
+        &content[..start]
+    } else {
+        content
+    }
+}
+
+/// A root the functions of `result` were found under, with its label in a
+/// workspace.
+pub struct Root<'a> {
+    pub label: Option<&'a str>,
+    pub path: &'a Path,
+}
+
+/// Writes a copy of every file with functions in `result` under `out`,
+/// mirroring its path relative to its root (under the project label in a
+/// workspace). Functions with a complexity above `floor` are annotated; with
+/// no floor, annotations are only removed. Returns the number of files
+/// written.
+pub fn write_copies(
+    result: &AnalysisResult,
+    roots: &[Root],
+    out: &Path,
+    floor: Option<u32>,
+) -> Result<usize> {
+    let mut files: BTreeMap<PathBuf, (PathBuf, HashMap<u32, String>)> = BTreeMap::new();
+    for func in &result.functions {
+        let Some(root) = roots.iter().find(|root| root.label == func.project.as_deref()) else {
+            continue;
+        };
+        let (source, relative) = match root.label {
+            Some(label) => (root.path.join(&func.file), Path::new(label).join(&func.file)),
+            None => {
+                let source = PathBuf::from(&func.file);
+                let relative = match source.strip_prefix(root.path) {
+                    Ok(relative) if !relative.as_os_str().is_empty() => relative.to_path_buf(),
+                    // A single analyzed file.
+                    _ => PathBuf::from(source.file_name().unwrap_or(source.as_os_str())),
+                };
+                (source, relative)
+            }
+        };
+        let (_, annotations) = files.entry(source).or_insert_with(|| (relative, HashMap::new()));
+        if floor.is_some_and(|floor| func.complexity > floor) {
+            let line = if func.signature_end_line > 0 {
+                func.signature_end_line
+            } else {
+                func.line
+            };
+            annotations.insert(line, annotation(func.complexity));
+        }
+    }
+
+    for (source, (relative, annotations)) in &files {
+        let dest = out.join(relative);
+        if let (Ok(a), Ok(b)) = (source.canonicalize(), dest.canonicalize()) {
+            if a == b {
+                anyhow::bail!(
+                    "refusing to overwrite {}: annotated copies are never written in place",
+                    source.display()
+                );
+            }
+        }
+        let content =
+            std::fs::read(source).with_context(|| format!("Failed to read {}", source.display()))?;
+        if let Some(parent) = dest.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        std::fs::write(&dest, rewrite(&content, annotations))
+            .with_context(|| format!("Failed to write {}", dest.display()))?;
+    }
+    Ok(files.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn annotations(lines: &[(u32, u32)]) -> HashMap<u32, String> {
+        lines
+            .iter()
+            .map(|&(line, complexity)| (line, annotation(complexity)))
+            .collect()
+    }
+
+    #[test]
+    fn test_rewrite_is_idempotent_and_reversible() {
+        // CRLF endings, Latin-1 bytes and an existing comment are all kept.
+        let source: &[u8] = b"# caf\xe9\r\ndef f(x):  # noqa\r\n    return x\r\n\r\ndef g(\r\n    y,\r\n):\r\n    pass";
+        let annotated = rewrite(source, &annotations(&[(2, 12), (7, 3)]));
+        assert_eq!(
+            annotated,
+            b"# caf\xe9\r\ndef f(x):  # noqa  # analyzr: complexity=12\r\n    return x\r\n\r\ndef g(\r\n    y,\r\n):  # analyzr: complexity=3\r\n    pass"
+        );
+
+        let again = rewrite(&annotated, &annotations(&[(2, 14), (7, 3)]));
+        assert_eq!(
+            again,
+            b"# caf\xe9\r\ndef f(x):  # noqa  # analyzr: complexity=14\r\n    return x\r\n\r\ndef g(\r\n    y,\r\n):  # analyzr: complexity=3\r\n    pass"
+        );
+
+        // A function that is no longer above the floor loses its annotation.
+        let fewer = rewrite(&again, &annotations(&[(2, 14)]));
+        assert_eq!(fewer, rewrite(source, &annotations(&[(2, 14)])));
+
+        assert_eq!(rewrite(&again, &HashMap::new()), source);
+    }
+
+    #[test]
+    fn test_only_annotations_are_stripped() {
+        let source: &[u8] = b"def f():  # analyzr: not ours\n    pass\n";
+        assert_eq!(rewrite(source, &HashMap::new()), source);
+        let source: &[u8] = b"def f(): \\\n    pass\n";
+        assert_eq!(rewrite(source, &annotations(&[(1, 20)])), source);
+    }
+
+    #[test]
+    fn test_write_copies() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("src");
+        std::fs::create_dir_all(root.join("pkg")).unwrap();
+        let branchy = "def branchy(a, b):\n    if a:\n        return 1\n    if b:\n        return 2\n    return 3\n";
+        std::fs::write(root.join("pkg/app.py"), branchy).unwrap();
+        std::fs::write(root.join("simple.py"), "def simple():\n    pass\n").unwrap();
+        let result = crate::analyze_directory(&root, 10).unwrap();
+        let roots = [Root {
+            label: None,
+            path: &root,
+        }];
+
+        let out = temp_dir.path().join("annotated");
+        assert_eq!(write_copies(&result, &roots, &out, Some(1)).unwrap(), 2);
+        let annotated = std::fs::read_to_string(out.join("pkg/app.py")).unwrap();
+        assert!(annotated.starts_with("def branchy(a, b):  # analyzr: complexity=3\n"));
+        let simple = std::fs::read_to_string(out.join("simple.py")).unwrap();
+        assert_eq!(simple, "def simple():\n    pass\n");
+        // The originals are untouched.
+        assert_eq!(std::fs::read_to_string(root.join("pkg/app.py")).unwrap(), branchy);
+
+        // Annotated copies analyze the same and strip back to the originals.
+        let copies = crate::analyze_directory(&out, 10).unwrap();
+        let stripped = temp_dir.path().join("stripped");
+        let roots = [Root {
+            label: None,
+            path: &out,
+        }];
+        write_copies(&copies, &roots, &stripped, None).unwrap();
+        assert_eq!(std::fs::read_to_string(stripped.join("pkg/app.py")).unwrap(), branchy);
+
+        let roots = [Root {
+            label: None,
+            path: &root,
+        }];
+        let error = write_copies(&result, &roots, &root, Some(1)).unwrap_err();
+        assert!(error.to_string().contains("never written in place"));
+        assert_eq!(std::fs::read_to_string(root.join("pkg/app.py")).unwrap(), branchy);
+    }
+}
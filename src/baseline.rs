@@ -0,0 +1,255 @@
+//! Change of each function's complexity since a baseline result.
+
+use crate::severity::Severity;
+use crate::{AnalysisResult, FunctionComplexity};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::{HashMap, HashSet};
+
+/// A function's change against the baseline: `"new"` in JSON when it is not
+/// in the baseline, the signed difference otherwise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delta {
+    New,
+    Change(i64),
+}
+
+impl Serialize for Delta {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Delta::New => serializer.serialize_str("new"),
+            Delta::Change(change) => serializer.serialize_i64(*change),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Delta {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Change(i64),
+            Label(String),
+        }
+        match Repr::deserialize(deserializer)? {
+            Repr::Change(change) => Ok(Delta::Change(change)),
+            Repr::Label(label) if label == "new" => Ok(Delta::New),
+            Repr::Label(label) => Err(serde::de::Error::custom(format!(
+                "expected a number or \"new\", got \"{}\"",
+                label
+            ))),
+        }
+    }
+}
+
+/// Sets the delta of every function of `result` against the baseline
+/// function with the same fingerprint, which survives line drift, and
+/// returns the baseline functions that no longer exist. A function without
+/// one is matched with a remaining baseline function with the same body
+/// hash, so that moving or renaming it does not make it new.
+pub fn annotate(result: &mut AnalysisResult, baseline: &AnalysisResult) -> Vec<FunctionComplexity> {
+    let mut before: HashMap<&str, u32> = HashMap::new();
+    for func in &baseline.functions {
+        before.entry(&func.fingerprint).or_insert(func.complexity);
+    }
+    let mut current = HashSet::new();
+    let mut unmatched = Vec::new();
+    for (index, func) in result.functions.iter_mut().enumerate() {
+        func.delta = Some(match before.get(func.fingerprint.as_str()) {
+            Some(&complexity) => Delta::Change(func.complexity as i64 - complexity as i64),
+            None => {
+                unmatched.push(index);
+                Delta::New
+            }
+        });
+        current.insert(func.fingerprint.clone());
+    }
+    let mut removed: Vec<FunctionComplexity> = baseline
+        .functions
+        .iter()
+        .filter(|func| !current.contains(&func.fingerprint))
+        .cloned()
+        .collect();
+    for index in unmatched {
+        let func = &mut result.functions[index];
+        let moved = removed
+            .iter()
+            .position(|old| !func.body_hash.is_empty() && old.body_hash == func.body_hash);
+        if let Some(position) = moved {
+            let old = removed.remove(position);
+            func.delta = Some(Delta::Change(func.complexity as i64 - old.complexity as i64));
+        }
+    }
+    removed
+}
+
+/// Whether `func` regressed against the baseline: it is above its threshold
+/// and either new or more complex than it was. Functions that were already
+/// above it and did not grow are tolerated, so that a legacy codebase can
+/// ratchet its complexity down.
+pub fn is_regression(func: &FunctionComplexity) -> bool {
+    func.severity >= Some(Severity::Warning)
+        && match func.delta {
+            Some(Delta::New) => true,
+            Some(Delta::Change(change)) => change > 0,
+            None => false,
+        }
+}
+
+/// Orders functions from the biggest regression to the biggest improvement.
+/// A new function counts as a regression by its whole complexity.
+pub fn sort_by_delta(functions: &mut [FunctionComplexity]) {
+    functions.sort_by_key(|func| {
+        std::cmp::Reverse(match func.delta {
+            Some(Delta::Change(change)) => change,
+            Some(Delta::New) => func.complexity as i64,
+            None => 0,
+        })
+    });
+}
+
+/// Table rendering: `+3`, `-2`, `new`, or blank when unchanged.
+pub fn label(delta: Delta) -> String {
+    match delta {
+        Delta::New => "new".to_string(),
+        Delta::Change(0) => String::new(),
+        Delta::Change(change) => format!("{:+}", change),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn function(name: &str, line: u32, complexity: u32) -> FunctionComplexity {
+        FunctionComplexity {
+            name: name.to_string(),
+            file: "app.py".to_string(),
+            line,
+            complexity,
+            fingerprint: crate::fingerprint::fingerprint("app.py", name),
+            ..Default::default()
+        }
+    }
+
+    fn result(functions: Vec<FunctionComplexity>) -> AnalysisResult {
+        AnalysisResult {
+            functions,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_annotate_matches_across_line_drift() {
+        let baseline = result(vec![
+            function("grew", 1, 4),
+            function("shrank", 10, 9),
+            function("same", 20, 2),
+            function("gone", 30, 5),
+        ]);
+        let mut current = result(vec![
+            function("added", 1, 2),
+            function("grew", 12, 7),
+            function("shrank", 25, 7),
+            function("same", 40, 2),
+        ]);
+
+        let removed = annotate(&mut current, &baseline);
+
+        let deltas: Vec<(&str, Option<Delta>)> = current
+            .functions
+            .iter()
+            .map(|f| (f.name.as_str(), f.delta))
+            .collect();
+        assert_eq!(
+            deltas,
+            [
+                ("added", Some(Delta::New)),
+                ("grew", Some(Delta::Change(3))),
+                ("shrank", Some(Delta::Change(-2))),
+                ("same", Some(Delta::Change(0))),
+            ]
+        );
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].name, "gone");
+
+        sort_by_delta(&mut current.functions);
+        let order: Vec<&str> = current.functions.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(order, ["grew", "added", "same", "shrank"]);
+    }
+
+    #[test]
+    fn test_annotate_follows_moved_functions() {
+        let with_body = |mut func: FunctionComplexity, file: &str, body: &str| {
+            func.fingerprint = crate::fingerprint::fingerprint(file, &func.name);
+            func.file = file.to_string();
+            func.body_hash = body.to_string();
+            func
+        };
+        let baseline = result(vec![
+            with_body(function("parse", 1, 12), "app.py", "h1"),
+            with_body(function("load", 20, 6), "app.py", "h2"),
+        ]);
+        let mut current = result(vec![
+            with_body(function("parse", 1, 12), "parsing.py", "h1"),
+            with_body(function("load", 1, 8), "loading.py", "h3"),
+        ]);
+
+        let removed = annotate(&mut current, &baseline);
+
+        // The clean move keeps its baseline; the edited one is new.
+        let deltas: Vec<Option<Delta>> = current.functions.iter().map(|f| f.delta).collect();
+        assert_eq!(deltas, [Some(Delta::Change(0)), Some(Delta::New)]);
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].name, "load");
+    }
+
+    #[test]
+    fn test_regressions() {
+        let with_body = |mut func: FunctionComplexity, body: &str| {
+            func.body_hash = body.to_string();
+            func
+        };
+        let baseline = result(vec![
+            with_body(function("worsened", 1, 12), "h1"),
+            with_body(function("improved", 20, 15), "h2"),
+            with_body(function("tolerated", 40, 14), "h3"),
+            with_body(function("old_name", 60, 11), "h4"),
+            with_body(function("renamed_and_grown", 80, 11), "h5"),
+        ]);
+        let mut current = result(vec![
+            with_body(function("worsened", 3, 13), "h1'"),
+            with_body(function("improved", 22, 12), "h2'"),
+            with_body(function("tolerated", 42, 14), "h3"),
+            with_body(function("new_name", 62, 11), "h4"),
+            with_body(function("grown", 80, 12), "h5'"),
+            with_body(function("added", 100, 11), "h6"),
+            with_body(function("added_simple", 120, 3), "h7"),
+        ]);
+        annotate(&mut current, &baseline);
+        crate::severity::annotate(&mut current, |_| crate::severity::Thresholds::new(10, None, None));
+
+        let regressions: Vec<&str> = current
+            .functions
+            .iter()
+            .filter(|func| is_regression(func))
+            .map(|func| func.name.as_str())
+            .collect();
+        // A renamed function keeps its baseline while its body is unchanged.
+        assert_eq!(regressions, ["worsened", "grown", "added"]);
+    }
+
+    #[test]
+    fn test_delta_json_and_labels() {
+        let json = serde_json::to_string(&[Delta::Change(3), Delta::Change(-2), Delta::New]);
+        assert_eq!(json.unwrap(), r#"[3,-2,"new"]"#);
+        let parsed: Vec<Delta> = serde_json::from_str(r#"[3,-2,"new"]"#).unwrap();
+        assert_eq!(parsed, [Delta::Change(3), Delta::Change(-2), Delta::New]);
+        assert!(serde_json::from_str::<Delta>(r#""old""#).is_err());
+
+        let labels: Vec<String> = [Delta::Change(3), Delta::Change(-2), Delta::Change(0), Delta::New]
+            .into_iter()
+            .map(label)
+            .collect();
+        assert_eq!(labels, ["+3", "-2", "", "new"]);
+    }
+}
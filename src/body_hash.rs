@@ -0,0 +1,168 @@
+//! Hash of a function's normalized source, which changes when its code does
+//! but not when it is only reformatted.
+//!
+//! Normalization keeps the tokens of the function after its name: the
+//! parameters, the return annotation and the body, docstring included. Tokens
+//! are joined with a single space, so indentation, line breaks (escaped or
+//! not), blank lines and spacing do not matter, and comments are dropped. String literals are
+//! kept verbatim. The name is left out so that a rename alone keeps the hash,
+//! and so are decorators, which are outside the `def`.
+
+use crate::fingerprint::fingerprint;
+use crate::language::{function_name, Language};
+use anyhow::{Context, Result};
+use std::path::Path;
+use tree_sitter::{Node, Parser as TSParser, QueryCursor};
+
+/// The tokens of `function`, a function node of any grammar, normalized.
+pub fn normalize(function: Node, source: &str) -> String {
+    let mut tokens = Vec::new();
+    let name = function.child_by_field_name("name").map(|node| node.id());
+    // Nodes still to collect, the next one on top: a stack of its own rather
+    // than recursion, so that no nesting overflows the thread's.
+    let mut stack = vec![function];
+    while let Some(node) = stack.pop() {
+        if Some(node.id()) == name || matches!(node.kind(), "comment" | "line_continuation") {
+            continue;
+        }
+        // Strings are kept whole: their content is not a child node.
+        if node.child_count() == 0 || node.kind() == "string" {
+            tokens.push(&source[node.byte_range()]);
+            continue;
+        }
+        let mut cursor = node.walk();
+        let children: Vec<Node> = node.children(&mut cursor).collect();
+        stack.extend(children.into_iter().rev());
+    }
+    tokens.join(" ")
+}
+
+pub fn hash(function: Node, source: &str) -> String {
+    fingerprint("", &normalize(function, source))
+}
+
+/// A function of a file with its normalized source, for `hash`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Normalized {
+    pub name: String,
+    pub line: u32,
+    pub normalized: String,
+    pub hash: String,
+}
+
+/// Every function named `name` of `source`, the content of the file at
+/// `path`, in order.
+pub fn find(source: &str, path: &Path, name: &str) -> Result<Vec<Normalized>> {
+    let language = Language::of(path).unwrap_or_default();
+    let grammar = language.grammar(path);
+    let mut parser = TSParser::new();
+    parser.set_language(grammar).unwrap();
+    let tree = parser
+        .parse(source, None)
+        .with_context(|| format!("Failed to parse {} code", language.name()))?;
+    crate::depth::check(tree.root_node(), crate::depth::DEFAULT_MAX_DEPTH)?;
+    let query = language.compiled_functions_query(path);
+    let function_index = query.capture_index_for_name("function").unwrap();
+    let mut query_cursor = QueryCursor::new();
+    let mut functions = Vec::new();
+    for m in query_cursor.matches(query, tree.root_node(), source.as_bytes()) {
+        let Some(capture) = m.captures.iter().find(|c| c.index == function_index) else {
+            continue;
+        };
+        let function = capture.node;
+        if function_name(function, source) != name {
+            continue;
+        }
+        let normalized = normalize(function, source);
+        functions.push(Normalized {
+            name: name.to_string(),
+            line: function.start_position().row as u32 + 1,
+            hash: fingerprint("", &normalized),
+            normalized,
+        });
+    }
+    Ok(functions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hash_of(source: &str) -> String {
+        let functions = find(source, Path::new("a.py"), "f").unwrap();
+        assert_eq!(functions.len(), 1, "{}", source);
+        functions[0].hash.clone()
+    }
+
+    const ORIGINAL: &str = "\
+def f(items, limit=10):
+    \"\"\"Sum the small items.\"\"\"
+    total = 0
+    for item in items:
+        if item < limit and item != 3:
+            total += item
+    return total, 'a  b'
+";
+
+    #[test]
+    fn test_normalized_form() {
+        let functions = find(ORIGINAL, Path::new("a.py"), "f").unwrap();
+        assert_eq!(
+            functions[0].normalized,
+            "def ( items , limit = 10 ) : \"\"\"Sum the small items.\"\"\" total = 0 \
+             for item in items : if item < limit and item != 3 : total += item \
+             return total , 'a  b'"
+        );
+        // The hash is part of the JSON output and must not change across
+        // releases.
+        assert_eq!(functions[0].hash, "8569f2575ecf842b");
+    }
+
+    #[test]
+    fn test_reformatting_keeps_the_hash() {
+        let reformatted = "\
+# A comment before the function.
+def f(
+    items,
+    limit = 10,
+):
+        \"\"\"Sum the small items.\"\"\"
+
+        total=0  # running total
+        for item in items:
+            # Skip the big ones.
+            if (item < limit
+                    and item != 3):
+                total += item
+        return (total, 'a  b')
+";
+        let renamed = ORIGINAL.replacen("def f(", "def g(", 1);
+        let renamed = find(&renamed, Path::new("a.py"), "g").unwrap();
+        assert_eq!(renamed[0].hash, hash_of(ORIGINAL));
+
+        // Parentheses and trailing commas are tokens: wrapping that adds
+        // them is a change.
+        assert_ne!(hash_of(reformatted), hash_of(ORIGINAL));
+        let unparenthesized = reformatted
+            .replace("(item < limit\n                    and item != 3)", "item < limit \\\n                    and item != 3")
+            .replace("(total, 'a  b')", "total, 'a  b'")
+            .replace("limit = 10,\n)", "limit = 10\n)");
+        assert_eq!(hash_of(&unparenthesized), hash_of(ORIGINAL));
+    }
+
+    #[test]
+    fn test_logic_changes_change_the_hash() {
+        let original = hash_of(ORIGINAL);
+        for (from, to) in [
+            ("item < limit", "item <= limit"),
+            ("limit=10", "limit=11"),
+            ("(items, limit=10)", "(items, limit=10, *rest)"),
+            ("total += item", "total -= item"),
+            ("'a  b'", "'a b'"),
+            ("Sum the small items.", "Sum the items."),
+        ] {
+            let changed = ORIGINAL.replacen(from, to, 1);
+            assert_ne!(hash_of(&changed), original, "{} -> {}", from, to);
+        }
+    }
+}
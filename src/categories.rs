@@ -0,0 +1,285 @@
+//! Categories of functions such as HTTP handlers or background tasks, set
+//! with `[[categories]]` in `analyzr.toml`, so that each kind of framework
+//! entry point can be tracked and held to its own threshold.
+//!
+//! A function is in a category when one of its decorators, written without
+//! `@` or arguments (`app.route`), matches one of the category's decorator
+//! globs, or when it is a method of a class with a base matching one of its
+//! base globs, either as written (`views.View`) or by its last component
+//! (`View`).
+
+use crate::generated::wildcard;
+use crate::severity::{Metric, Severity};
+use crate::{above, FunctionComplexity};
+use serde::{Deserialize, Serialize};
+
+/// Category of the functions no category matches, once categories are set.
+pub const UNCATEGORIZED: &str = "uncategorized";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Category {
+    pub name: String,
+    /// Globs of decorator names, e.g. `app.route` or `*.get`.
+    pub decorators: Vec<String>,
+    /// Globs of the base classes of a method's class, e.g. `*View`.
+    pub bases: Vec<String>,
+    /// Complexity above which a function of the category is reported,
+    /// instead of the thresholds.
+    pub max_cyclomatic: Option<u32>,
+    /// Severity a function above `max_cyclomatic` is reported with.
+    pub severity: Severity,
+}
+
+impl Category {
+    fn matches(&self, func: &FunctionComplexity) -> bool {
+        let decorator = |pattern: &String| func.decorators.iter().any(|d| wildcard(pattern, d));
+        let base = |pattern: &String| {
+            func.bases.iter().any(|base| {
+                let last = base.rsplit('.').next().unwrap_or(base);
+                wildcard(pattern, base) || wildcard(pattern, last)
+            })
+        };
+        self.decorators.iter().any(decorator) || self.bases.iter().any(base)
+    }
+}
+
+/// Why `pattern` cannot match a decorator or base class name, if it cannot:
+/// such names are dotted identifiers, with `*` and `?` as wildcards.
+pub fn check_pattern(pattern: &str) -> Result<(), String> {
+    if pattern.starts_with('@') {
+        return Err(format!("`{}`: write decorators without `@`", pattern));
+    }
+    if pattern.contains('(') {
+        return Err(format!(
+            "`{}`: decorators are matched by name, without arguments",
+            pattern
+        ));
+    }
+    let valid = !pattern.is_empty()
+        && pattern.split('.').all(|part| {
+            !part.is_empty()
+                && part
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || matches!(c, '_' | '*' | '?'))
+        });
+    if !valid {
+        return Err(format!("`{}` is not a dotted name", pattern));
+    }
+    Ok(())
+}
+
+/// Sets the category of every function: the last of `categories` that
+/// matches it, or [`UNCATEGORIZED`].
+pub fn classify(categories: &[Category], functions: &mut [FunctionComplexity]) {
+    for func in functions {
+        let category = categories
+            .iter()
+            .rev()
+            .find(|category| category.matches(func));
+        func.category = Some(category.map_or(UNCATEGORIZED, |c| &c.name).to_string());
+    }
+}
+
+/// Sets the severity of every function in a category with a
+/// `max_cyclomatic` from that category instead of the thresholds.
+pub fn apply_thresholds(categories: &[Category], functions: &mut [FunctionComplexity]) {
+    for func in functions {
+        let category = categories
+            .iter()
+            .find(|category| func.category.as_deref() == Some(&category.name));
+        if let Some((category, max)) = category.and_then(|c| c.max_cyclomatic.map(|max| (c, max))) {
+            func.severity = (!func.dunder && func.complexity > max).then_some(category.severity);
+        }
+    }
+}
+
+/// Statistics of the functions of one category.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CategorySummary {
+    pub category: String,
+    /// The category's `max_cyclomatic`, or the threshold.
+    pub threshold: u32,
+    pub total_functions: usize,
+    /// `None` for a category without functions.
+    pub mean_complexity: Option<f64>,
+    pub max_complexity: u32,
+    pub functions_above_threshold: usize,
+}
+
+/// Statistics of every category, in the order of the configuration and then
+/// [`UNCATEGORIZED`], including those without functions.
+pub fn summarize(
+    categories: &[Category],
+    functions: &[FunctionComplexity],
+    threshold: u32,
+) -> Vec<CategorySummary> {
+    let names = categories
+        .iter()
+        .map(|category| (category.name.as_str(), category.max_cyclomatic))
+        .chain([(UNCATEGORIZED, None)]);
+    names
+        .map(|(name, max_cyclomatic)| {
+            let threshold = max_cyclomatic.unwrap_or(threshold);
+            let members: Vec<&FunctionComplexity> = functions
+                .iter()
+                .filter(|func| func.category.as_deref() == Some(name))
+                .collect();
+            let total: u64 = members.iter().map(|func| func.complexity as u64).sum();
+            CategorySummary {
+                category: name.to_string(),
+                threshold,
+                total_functions: members.len(),
+                mean_complexity: crate::mean(total, members.len()),
+                max_complexity: members
+                    .iter()
+                    .map(|func| func.complexity)
+                    .max()
+                    .unwrap_or(0),
+                functions_above_threshold: members
+                    .iter()
+                    .filter(|func| above(func, threshold, Metric::Cyclomatic))
+                    .count(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn category(name: &str, decorators: &[&str], bases: &[&str]) -> Category {
+        Category {
+            name: name.to_string(),
+            decorators: decorators.iter().map(|d| d.to_string()).collect(),
+            bases: bases.iter().map(|b| b.to_string()).collect(),
+            max_cyclomatic: None,
+            severity: Severity::Warning,
+        }
+    }
+
+    fn function(
+        name: &str,
+        complexity: u32,
+        decorators: &[&str],
+        bases: &[&str],
+    ) -> FunctionComplexity {
+        FunctionComplexity {
+            name: name.to_string(),
+            complexity,
+            decorators: decorators.iter().map(|d| d.to_string()).collect(),
+            bases: bases.iter().map(|b| b.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_classify() {
+        let categories = [
+            category("http", &["app.route", "*.get", "router.*"], &["*View"]),
+            category("task", &["shared_task", "celery.task"], &[]),
+            category("cli", &["click.command"], &[]),
+            // The last matching category wins.
+            category("admin", &[], &["admin.ModelAdmin", "LoginRequiredMixin"]),
+        ];
+        let mut functions = vec![
+            function("index", 3, &["app.route"], &[]),
+            function("items", 3, &["api.v1.get"], &[]),
+            function("create", 3, &["router.post", "require_auth"], &[]),
+            function("get", 3, &[], &["generic.ListView"]),
+            function("post", 3, &[], &["LoginRequiredMixin", "View"]),
+            function("send", 3, &["shared_task"], &[]),
+            function("main", 3, &["click.command"], &[]),
+            function("changelist", 3, &[], &["admin.ModelAdmin"]),
+            function("helper", 3, &["functools.cache"], &["object"]),
+        ];
+        classify(&categories, &mut functions);
+        let assigned: Vec<(&str, &str)> = functions
+            .iter()
+            .map(|f| (f.name.as_str(), f.category.as_deref().unwrap()))
+            .collect();
+        assert_eq!(
+            assigned,
+            [
+                ("index", "http"),
+                ("items", "http"),
+                ("create", "http"),
+                ("get", "http"),
+                ("post", "admin"),
+                ("send", "task"),
+                ("main", "cli"),
+                ("changelist", "admin"),
+                ("helper", UNCATEGORIZED),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_thresholds_and_summary() {
+        let mut http = category("http", &["app.route"], &[]);
+        http.max_cyclomatic = Some(5);
+        http.severity = Severity::Error;
+        let categories = [http, category("task", &["shared_task"], &[])];
+        let mut functions = vec![
+            function("index", 6, &["app.route"], &[]),
+            function("show", 4, &["app.route"], &[]),
+            function("helper", 12, &[], &[]),
+        ];
+        classify(&categories, &mut functions);
+        apply_thresholds(&categories, &mut functions);
+        let severities: Vec<Option<Severity>> = functions.iter().map(|f| f.severity).collect();
+        assert_eq!(severities, [Some(Severity::Error), None, None]);
+
+        let summaries = summarize(&categories, &functions, 10);
+        let rows: Vec<(&str, u32, usize, u32, usize)> = summaries
+            .iter()
+            .map(|s| {
+                (
+                    s.category.as_str(),
+                    s.threshold,
+                    s.total_functions,
+                    s.max_complexity,
+                    s.functions_above_threshold,
+                )
+            })
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                ("http", 5, 2, 6, 1),
+                ("task", 10, 0, 0, 0),
+                (UNCATEGORIZED, 10, 1, 12, 1),
+            ]
+        );
+        let means: Vec<Option<f64>> = summaries.iter().map(|s| s.mean_complexity).collect();
+        assert_eq!(means, [Some(5.0), None, Some(12.0)]);
+    }
+
+    #[test]
+    fn test_check_pattern() {
+        for valid in [
+            "app.route",
+            "*.get",
+            "router.*",
+            "shared_task",
+            "*View",
+            "v?.api",
+        ] {
+            assert_eq!(check_pattern(valid), Ok(()), "{}", valid);
+        }
+        assert_eq!(
+            check_pattern("@app.route").unwrap_err(),
+            "`@app.route`: write decorators without `@`"
+        );
+        assert_eq!(
+            check_pattern("app.route(\"/\")").unwrap_err(),
+            "`app.route(\"/\")`: decorators are matched by name, without arguments"
+        );
+        assert_eq!(
+            check_pattern("app..route").unwrap_err(),
+            "`app..route` is not a dotted name"
+        );
+        assert!(check_pattern("").is_err());
+        assert!(check_pattern("views/View").is_err());
+    }
+}
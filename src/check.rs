@@ -0,0 +1,394 @@
+//! A pass/fail verdict for CI gates, computed without building the full
+//! result: files are scored one at a time and only running counts and the
+//! worst offenders are kept. With `[budgets]`, the check fails only when an
+//! owner has more functions above the threshold than its budget.
+
+use crate::owners::{self, Budget, BudgetStatus, CodeOwners};
+use crate::severity::Metric;
+use crate::{above, analyze_file, discover, generated, AnalyzeOptions, FunctionComplexity};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// A function above the threshold.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Offender {
+    pub name: String,
+    pub file: String,
+    pub line: u32,
+    pub complexity: u32,
+}
+
+#[derive(Debug)]
+pub struct Verdict {
+    pub threshold: u32,
+    pub files: usize,
+    pub functions: usize,
+    /// Number of functions above the threshold.
+    pub above: usize,
+    /// The worst offenders, most complex first; ties keep the order in which
+    /// they were found.
+    pub worst: Vec<Offender>,
+    /// Files that could not be read or parsed.
+    pub skipped: usize,
+    /// Each owner's usage of its budget, when there are budgets.
+    pub budgets: Option<Vec<BudgetStatus>>,
+    pub elapsed: Duration,
+}
+
+/// Budgets of functions above the threshold, with the owners of the files.
+#[derive(Debug, Clone, Default)]
+pub struct Budgets {
+    /// Every file is unowned without a `CODEOWNERS` file.
+    pub codeowners: Option<CodeOwners>,
+    pub budgets: Vec<Budget>,
+}
+
+impl Budgets {
+    /// The owners of `file`, or [`owners::UNOWNED`].
+    fn owners(&self, file: &Path) -> Vec<String> {
+        let owners = self.codeowners.as_ref().and_then(|codeowners| {
+            let file = file.canonicalize().ok()?;
+            let relative = file.strip_prefix(&codeowners.base).ok()?;
+            let relative: Vec<_> = relative.iter().map(|c| c.to_string_lossy()).collect();
+            Some(codeowners.owners(&relative.join("/")).to_vec())
+        });
+        match owners {
+            Some(owners) if !owners.is_empty() => owners,
+            _ => vec![owners::UNOWNED.to_string()],
+        }
+    }
+}
+
+impl Verdict {
+    pub fn passed(&self) -> bool {
+        match &self.budgets {
+            Some(budgets) => budgets.iter().all(|budget| budget.passed),
+            None => self.above == 0,
+        }
+    }
+
+    /// The verdict in one line, e.g. `FAIL: 3 functions exceed 10; worst:
+    /// foo (17) at src/a.py:42`.
+    pub fn line(&self) -> String {
+        if let Some(budgets) = &self.budgets {
+            return self.budget_line(budgets);
+        }
+        let Some(worst) = self.worst.first().filter(|_| !self.passed()) else {
+            return format!(
+                "PASS: no function exceeds {} ({} functions in {} files, {:.2}s)",
+                self.threshold,
+                self.functions,
+                self.files,
+                self.elapsed.as_secs_f64()
+            );
+        };
+        let functions = if self.above == 1 {
+            "function exceeds"
+        } else {
+            "functions exceed"
+        };
+        format!(
+            "FAIL: {} {} {}; worst: {} ({}) at {}:{} ({:.2}s)",
+            self.above,
+            functions,
+            self.threshold,
+            worst.name,
+            worst.complexity,
+            worst.file,
+            worst.line,
+            self.elapsed.as_secs_f64()
+        )
+    }
+
+    fn budget_line(&self, budgets: &[BudgetStatus]) -> String {
+        let over: Vec<String> = budgets
+            .iter()
+            .filter(|budget| !budget.passed)
+            .map(|budget| {
+                format!(
+                    "{} {} of {}",
+                    budget.owner, budget.above_threshold, budget.budget
+                )
+            })
+            .collect();
+        let functions = if self.above == 1 {
+            "function exceeds"
+        } else {
+            "functions exceed"
+        };
+        if over.is_empty() {
+            format!(
+                "PASS: every owner is within budget ({} {} {}, {:.2}s)",
+                self.above,
+                functions,
+                self.threshold,
+                self.elapsed.as_secs_f64()
+            )
+        } else {
+            format!(
+                "FAIL: {} over budget ({} {} {}, {:.2}s)",
+                over.join(", "),
+                self.above,
+                functions,
+                self.threshold,
+                self.elapsed.as_secs_f64()
+            )
+        }
+    }
+}
+
+/// Checks every function under `path` against the threshold of `options`,
+/// keeping at most `keep` offenders, and against the budgets of their owners
+/// when given.
+/// Dunder methods set apart with `--dunder separate` and functions of
+/// generated modules that are not included are not checked, as they are
+/// never reported.
+pub fn check(
+    path: &Path,
+    options: &AnalyzeOptions,
+    keep: usize,
+    budgets: Option<&Budgets>,
+) -> Verdict {
+    let threshold = options.threshold;
+    let started = Instant::now();
+    let mut verdict = Verdict {
+        threshold,
+        files: 0,
+        functions: 0,
+        above: 0,
+        worst: Vec::with_capacity(keep + 1),
+        skipped: 0,
+        budgets: None,
+        elapsed: Duration::ZERO,
+    };
+    let mut usage: BTreeMap<String, usize> = BTreeMap::new();
+    let mut errors = Vec::new();
+    for entry in discover(path, None, options.language, &options.walk) {
+        let Ok(file_path) = entry else {
+            verdict.skipped += 1;
+            continue;
+        };
+        verdict.files += 1;
+        let functions = analyze_file(path, &file_path, None, options, &mut errors);
+        verdict.skipped += errors.len();
+        errors.clear();
+        if options.generated_mode != generated::GeneratedMode::Include
+            && functions.iter().any(|f| f.generated)
+        {
+            continue;
+        }
+        let mut owners = None;
+        for func in functions {
+            verdict.functions += 1;
+            if !above(&func, threshold, options.metric) {
+                continue;
+            }
+            verdict.above += 1;
+            if let Some(budgets) = budgets {
+                for owner in owners
+                    .get_or_insert_with(|| budgets.owners(&file_path))
+                    .iter()
+                {
+                    *usage.entry(owner.clone()).or_default() += 1;
+                }
+            }
+            record(&mut verdict.worst, func, keep, options.metric);
+        }
+    }
+    verdict.budgets = budgets.map(|budgets| owners::evaluate(&usage, &budgets.budgets));
+    verdict.elapsed = started.elapsed();
+    verdict
+}
+
+/// Inserts `func` into `worst` if it is among the `keep` most complex by
+/// `metric`.
+fn record(worst: &mut Vec<Offender>, func: FunctionComplexity, keep: usize, metric: Metric) {
+    let complexity = metric.value(&func);
+    let at = worst.partition_point(|o| o.complexity >= complexity);
+    if at >= keep {
+        return;
+    }
+    worst.insert(
+        at,
+        Offender {
+            name: func.name,
+            file: func.file,
+            line: func.line,
+            complexity,
+        },
+    );
+    worst.truncate(keep);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn branchy(name: &str, branches: usize) -> String {
+        let mut source = format!("def {}(x):\n", name);
+        for i in 0..branches {
+            source.push_str(&format!("    if x == {}:\n        return {}\n", i, i));
+        }
+        source.push_str("    return x\n");
+        source
+    }
+
+    fn threshold(threshold: u32) -> AnalyzeOptions {
+        AnalyzeOptions {
+            threshold,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_check() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        std::fs::write(root.join("a.py"), branchy("small", 2) + &branchy("big", 6)).unwrap();
+        std::fs::write(
+            root.join("b.py"),
+            branchy("huge", 9) + &branchy("medium", 5),
+        )
+        .unwrap();
+        std::fs::write(root.join("c.py"), branchy("tied", 5)).unwrap();
+
+        let verdict = check(root, &threshold(4), 2, None);
+        assert_eq!((verdict.files, verdict.functions, verdict.above), (3, 5, 4));
+        let worst: Vec<(&str, u32)> = verdict
+            .worst
+            .iter()
+            .map(|o| (o.name.as_str(), o.complexity))
+            .collect();
+        assert_eq!(worst, [("huge", 10), ("big", 7)]);
+        assert!(!verdict.passed());
+        let line = verdict.line();
+        assert!(line.starts_with("FAIL: 4 functions exceed 4; worst: huge (10) at "));
+        assert!(line.contains("b.py:1 ("));
+
+        let verdict = check(root, &threshold(10), 2, None);
+        assert!(verdict.passed() && verdict.worst.is_empty());
+        assert!(verdict
+            .line()
+            .starts_with("PASS: no function exceeds 10 (5 functions in 3 files, "));
+    }
+
+    #[test]
+    fn test_check_metric() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        // Cyclomatic 6 and cognitive 5, then cyclomatic 4 and cognitive 6.
+        let deep =
+            "def deep(x):\n    if x:\n        if x:\n            if x:\n                return x\n";
+        std::fs::write(root.join("a.py"), branchy("flat", 5) + deep).unwrap();
+
+        let verdict = check(root, &threshold(5), 1, None);
+        assert_eq!(verdict.above, 1);
+        assert_eq!(verdict.worst[0].name, "flat");
+
+        let options = AnalyzeOptions {
+            metric: Metric::Cognitive,
+            ..threshold(5)
+        };
+        let verdict = check(root, &options, 1, None);
+        assert_eq!(verdict.above, 1);
+        assert_eq!(
+            (verdict.worst[0].name.as_str(), verdict.worst[0].complexity),
+            ("deep", 6)
+        );
+    }
+
+    #[test]
+    fn test_check_agrees_with_analysis() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        std::fs::create_dir(root.join("pkg")).unwrap();
+        std::fs::write(root.join("pkg/a.py"), branchy("f", 3) + &branchy("g", 12)).unwrap();
+        std::fs::write(root.join("pkg/b.py"), "def h(:\n    pass\n").unwrap();
+
+        let result = crate::analyze_directory(root, 3).unwrap();
+        let verdict = check(root, &threshold(3), 1, None);
+        let summary = result.summary.unwrap();
+        assert_eq!(verdict.functions, summary.total_functions);
+        assert_eq!(verdict.above, summary.functions_above_threshold);
+        assert_eq!(verdict.worst[0].complexity, summary.max_complexity);
+    }
+
+    #[test]
+    fn test_budgets() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        for dir in ["payments", "search", "scripts"] {
+            std::fs::create_dir(root.join(dir)).unwrap();
+        }
+        std::fs::write(
+            root.join("CODEOWNERS"),
+            "/payments/ @org/team-payments\n/search/ @org/team-search\n",
+        )
+        .unwrap();
+        // Two functions above the threshold for each team, one unowned.
+        std::fs::write(
+            root.join("payments/api.py"),
+            branchy("charge", 3) + &branchy("refund", 4),
+        )
+        .unwrap();
+        std::fs::write(
+            root.join("search/index.py"),
+            branchy("rank", 3) + &branchy("query", 5),
+        )
+        .unwrap();
+        std::fs::write(
+            root.join("scripts/tool.py"),
+            branchy("main", 3) + &branchy("simple", 1),
+        )
+        .unwrap();
+
+        let budget = |owner: &str, max_above_threshold| Budget {
+            owner: owner.to_string(),
+            max_above_threshold,
+        };
+        let budgets = Budgets {
+            codeowners: CodeOwners::find(root),
+            budgets: vec![
+                budget("team-payments", 2),
+                budget("team-search", 1),
+                budget(owners::UNOWNED, 1),
+            ],
+        };
+        let verdict = check(root, &threshold(3), 1, Some(&budgets));
+
+        assert_eq!(verdict.above, 5);
+        let statuses: Vec<(&str, usize, usize, bool)> = verdict
+            .budgets
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(|s| (s.owner.as_str(), s.above_threshold, s.budget, s.passed))
+            .collect();
+        assert_eq!(
+            statuses,
+            [
+                ("@org/team-payments", 2, 2, true),
+                ("@org/team-search", 2, 1, false),
+                (owners::UNOWNED, 1, 1, true),
+            ]
+        );
+        assert!(!verdict.passed());
+        assert!(verdict
+            .line()
+            .starts_with("FAIL: @org/team-search 2 of 1 over budget (5 functions exceed 3, "));
+
+        // The last budget of an owner applies.
+        let budgets = Budgets {
+            budgets: [budgets.budgets, vec![budget("team-search", 2)]].concat(),
+            ..budgets
+        };
+        let verdict = check(root, &threshold(3), 1, Some(&budgets));
+        assert!(verdict.passed());
+        assert!(verdict
+            .line()
+            .starts_with("PASS: every owner is within budget (5 functions exceed 3, "));
+    }
+}
@@ -0,0 +1,219 @@
+//! Cognitive complexity, after SonarSource's: how hard a function's control
+//! flow is to follow rather than how many paths it has. Each control
+//! structure adds one, plus one for every structure it is nested in, so that
+//! nested conditions weigh more than a flat sequence of them. `elif`,
+//! `else` and each sequence of like boolean operators add one regardless of
+//! nesting, and early returns add nothing.
+//!
+//! Like the cyclomatic complexity under the default preset, nested
+//! functions, classes and lambdas are scored on their own and add nothing
+//! to the function they are in.
+
+use tree_sitter::Node;
+
+/// Cognitive complexity of the code under `node`, not counting `node`
+/// itself.
+pub fn complexity(node: Node) -> u32 {
+    let mut total: u32 = 0;
+    // Nodes still to score, with their parent and the number of structures
+    // they are nested in: a stack of its own rather than recursion, so that
+    // no nesting overflows the thread's. Parents are kept rather than looked
+    // up, which takes a walk down from the root.
+    let mut stack = Vec::new();
+    push_children(&mut stack, node, 0);
+    while let Some((node, parent, nesting)) = stack.pop() {
+        total = total.saturating_add(visit(node, parent, nesting, &mut stack));
+    }
+    total
+}
+
+type Stack<'tree> = Vec<(Node<'tree>, Node<'tree>, u32)>;
+
+/// Pushes the named children of `node`, at `nesting` levels deep.
+fn push_children<'tree>(stack: &mut Stack<'tree>, node: Node<'tree>, nesting: u32) {
+    let mut cursor = node.walk();
+    stack.extend(node.named_children(&mut cursor).map(|child| (child, node, nesting)));
+}
+
+/// What `node`, a child of `parent`, itself adds, pushing the children to
+/// score with it.
+fn visit<'tree>(node: Node<'tree>, parent: Node, nesting: u32, stack: &mut Stack<'tree>) -> u32 {
+    match node.kind() {
+        kind if crate::language::is_definition(kind) => 0,
+        "if_statement" => {
+            let mut cursor = node.walk();
+            for child in node.named_children(&mut cursor) {
+                match child.kind() {
+                    "elif_clause" | "else_clause" => stack.push((child, node, nesting)),
+                    _ => stack.push((child, node, nesting + 1)),
+                }
+            }
+            // JavaScript's `else if` is an `if` in an `else`, as flat as an
+            // `elif`.
+            let else_if = parent.kind() == "else_clause";
+            if else_if {
+                1
+            } else {
+                1 + nesting
+            }
+        }
+        "elif_clause" => {
+            push_children(stack, node, nesting + 1);
+            1
+        }
+        "else_clause" if parent.kind() == "if_statement" => {
+            match node.named_child(0).filter(|child| child.kind() == "if_statement") {
+                Some(else_if) => {
+                    stack.push((else_if, node, nesting));
+                    0
+                }
+                None => {
+                    push_children(stack, node, nesting + 1);
+                    1
+                }
+            }
+        }
+        "for_statement" | "for_in_statement" | "while_statement" | "do_statement"
+        | "except_clause" | "except_group_clause" | "catch_clause" | "match_statement"
+        | "switch_statement" | "conditional_expression" | "ternary_expression" => {
+            push_children(stack, node, nesting + 1);
+            1 + nesting
+        }
+        "boolean_operator" | "binary_expression" => {
+            push_children(stack, node, nesting);
+            match boolean_operator(node) {
+                Some(operator) if boolean_operator(parent) != Some(operator) => 1,
+                _ => 0,
+            }
+        }
+        _ => {
+            push_children(stack, node, nesting);
+            0
+        }
+    }
+}
+
+/// The operator of `node` if it is a boolean one: `and` or `or` in Python,
+/// `&&`, `||` or `??` in JavaScript and TypeScript.
+fn boolean_operator(node: Node) -> Option<&'static str> {
+    let operator = node.child_by_field_name("operator")?.kind();
+    match node.kind() {
+        "boolean_operator" => ["and", "or"].into_iter().find(|&o| o == operator),
+        "binary_expression" => ["&&", "||", "??"].into_iter().find(|&o| o == operator),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::calculate_complexity;
+    use crate::weights::Weights;
+
+    fn scores(source: &str) -> Vec<(u32, u32)> {
+        calculate_complexity(source, &Weights::default())
+            .unwrap()
+            .iter()
+            .map(|f| (f.complexity, f.cognitive_complexity))
+            .collect()
+    }
+
+    #[test]
+    fn test_nesting_weighs_more_than_sequence() {
+        let sequential = "\
+def f(a, b, c):
+    if a:
+        pass
+    if b:
+        pass
+    if c:
+        pass
+";
+        let nested = "\
+def f(a, b, c):
+    if a:
+        if b:
+            if c:
+                pass
+";
+        // The same cyclomatic complexity, but 1 + 2 + 3 once nested.
+        assert_eq!(scores(sequential), [(4, 3)]);
+        assert_eq!(scores(nested), [(4, 6)]);
+    }
+
+    #[test]
+    fn test_sonar_examples() {
+        // The examples of SonarSource's white paper, in Python.
+        let sum_of_primes = "\
+def sum_of_primes(maximum):
+    total = 0
+    for i in range(1, maximum):
+        for j in range(2, i):
+            if i % j == 0:
+                break
+        total += i
+    return total
+";
+        assert_eq!(scores(sum_of_primes), [(4, 6)]);
+        let get_words = "\
+def get_words(number):
+    match number:
+        case 1:
+            return 'one'
+        case 2:
+            return 'a couple'
+        case _:
+            return 'lots'
+";
+        assert_eq!(scores(get_words), [(4, 1)]);
+    }
+
+    #[test]
+    fn test_flat_increments() {
+        let source = "\
+def f(a, b, c, d):
+    if a and b and c:
+        return 1
+    elif a or b and c:
+        return 2
+    else:
+        try:
+            return 3
+        except ValueError:
+            return 4 if d else 5
+";
+        // if 1, and 1, elif 1, or + and 2, else 1, except 2, ternary 3.
+        assert_eq!(scores(source)[0].1, 11);
+        let javascript = "\
+function f(a, b) {
+  if (a) {
+    return 1;
+  } else if (b) {
+    return a ?? b;
+  } else {
+    for (const x of a) { if (x) { return x; } }
+  }
+}
+";
+        let functions = crate::calculate_complexity_with(
+            javascript,
+            std::path::Path::new("f.js"),
+            &Weights::default(),
+            crate::depth::DEFAULT_MAX_DEPTH,
+        )
+        .unwrap();
+        // if 1, else if 1, ?? 1, else 1, for 2, if 3.
+        assert_eq!(functions[0].cognitive_complexity, 9);
+    }
+
+    #[test]
+    fn test_nested_functions_are_scored_on_their_own() {
+        let source = "\
+def outer(items):
+    def inner(x):
+        if x:
+            return x
+    return sorted(items, key=lambda x: x if x else 0)
+";
+        assert_eq!(scores(source), [(1, 0), (2, 1), (2, 1)]);
+    }
+}
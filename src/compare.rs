@@ -0,0 +1,155 @@
+//! Per-function comparison of two analysis results.
+
+use crate::output::text::{truncate_end, truncate_start};
+use crate::radon::rank;
+use crate::output::table::{NAME_WIDTH, PATH_WIDTH};
+use crate::{AnalysisResult, FunctionComplexity};
+use comfy_table::{Cell, Table};
+use serde::Serialize;
+use std::collections::HashMap;
+
+#[derive(Debug, Serialize)]
+pub struct Comparison {
+    /// Number of functions present in both results with the same score.
+    pub matching: usize,
+    pub mismatches: Vec<Mismatch>,
+    pub only_left: Vec<FunctionComplexity>,
+    pub only_right: Vec<FunctionComplexity>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Mismatch {
+    pub name: String,
+    pub file: String,
+    pub line: u32,
+    pub left: u32,
+    pub right: u32,
+}
+
+/// Matches functions of both results on their fingerprint.
+pub fn compare(left: &AnalysisResult, right: &AnalysisResult) -> Comparison {
+    let right_by_fingerprint: HashMap<&str, &FunctionComplexity> = right
+        .functions
+        .iter()
+        .map(|func| (func.fingerprint.as_str(), func))
+        .collect();
+    let left_fingerprints: HashMap<&str, &FunctionComplexity> = left
+        .functions
+        .iter()
+        .map(|func| (func.fingerprint.as_str(), func))
+        .collect();
+
+    let mut comparison = Comparison {
+        matching: 0,
+        mismatches: Vec::new(),
+        only_left: Vec::new(),
+        only_right: Vec::new(),
+    };
+
+    for func in &left.functions {
+        match right_by_fingerprint.get(func.fingerprint.as_str()) {
+            Some(other) if other.complexity == func.complexity => comparison.matching += 1,
+            Some(other) => comparison.mismatches.push(Mismatch {
+                name: other.name.clone(),
+                file: other.file.clone(),
+                line: other.line,
+                left: func.complexity,
+                right: other.complexity,
+            }),
+            None => comparison.only_left.push(func.clone()),
+        }
+    }
+    comparison.only_right = right
+        .functions
+        .iter()
+        .filter(|func| !left_fingerprints.contains_key(func.fingerprint.as_str()))
+        .cloned()
+        .collect();
+
+    comparison
+}
+
+pub fn print_comparison(comparison: &Comparison, left_label: &str, right_label: &str) {
+    println!(
+        "{} matching, {} mismatched, {} only in {}, {} only in {}",
+        comparison.matching,
+        comparison.mismatches.len(),
+        comparison.only_left.len(),
+        left_label,
+        comparison.only_right.len(),
+        right_label
+    );
+
+    if !comparison.mismatches.is_empty() {
+        let mut table = Table::new();
+        table.set_header(vec!["Function", "File", "Line", left_label, right_label]);
+        for mismatch in &comparison.mismatches {
+            table.add_row(vec![
+                Cell::new(truncate_end(&mismatch.name, NAME_WIDTH)),
+                Cell::new(truncate_start(&mismatch.file, PATH_WIDTH)),
+                Cell::new(mismatch.line.to_string()),
+                Cell::new(format!("{} ({})", mismatch.left, rank(mismatch.left))),
+                Cell::new(format!("{} ({})", mismatch.right, rank(mismatch.right))),
+            ]);
+        }
+        println!("\nScore mismatches:\n{}", table);
+    }
+
+    for (label, functions) in [
+        (left_label, &comparison.only_left),
+        (right_label, &comparison.only_right),
+    ] {
+        if functions.is_empty() {
+            continue;
+        }
+        let mut table = Table::new();
+        table.set_header(vec!["Function", "File", "Line", "Complexity"]);
+        for func in functions {
+            table.add_row(vec![
+                Cell::new(truncate_end(&func.name, NAME_WIDTH)),
+                Cell::new(truncate_start(&func.file, PATH_WIDTH)),
+                Cell::new(func.line.to_string()),
+                Cell::new(func.complexity.to_string()),
+            ]);
+        }
+        println!("\nOnly in {}:\n{}", label, table);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fingerprint::fingerprint;
+
+    fn result(functions: &[(&str, u32)]) -> AnalysisResult {
+        AnalysisResult {
+            functions: functions
+                .iter()
+                .map(|&(name, complexity)| FunctionComplexity {
+                    name: name.to_string(),
+                    file: "a.py".to_string(),
+                    line: 1,
+                    complexity,
+                    fingerprint: fingerprint("a.py", name),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_compare_separates_missing_from_mismatched() {
+        let left = result(&[("same", 2), ("differs", 3), ("gone", 1)]);
+        let right = result(&[("same", 2), ("differs", 5), ("added", 4)]);
+
+        let comparison = compare(&left, &right);
+
+        assert_eq!(comparison.matching, 1);
+        assert_eq!(comparison.mismatches.len(), 1);
+        assert_eq!(comparison.mismatches[0].name, "differs");
+        assert_eq!((comparison.mismatches[0].left, comparison.mismatches[0].right), (3, 5));
+        assert_eq!(comparison.only_left[0].name, "gone");
+        assert_eq!(comparison.only_right[0].name, "added");
+    }
+}
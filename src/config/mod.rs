@@ -0,0 +1,1122 @@
+//! Settings read from `analyzr.toml` and `ANALYZR_*` environment variables.
+//!
+//! Command-line flags take precedence over environment variables, which take
+//! precedence over the file.
+
+pub mod toml;
+
+use crate::categories::{self, Category};
+use crate::gates::{self, Gate};
+use crate::owners::Budget;
+use crate::risk::{self, Metric};
+use crate::rules::{Rule, Rules};
+use crate::severity::Severity;
+use crate::generated::GeneratedDetector;
+use crate::vendored::VendorDetector;
+use crate::weights::{Construct, Preset, Weights};
+use crate::AnalyzeOptions;
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+use comfy_table::{Cell, Table};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use toml::{Entry, Value};
+
+pub const FILE_NAME: &str = "analyzr.toml";
+
+/// Starter configuration written by `config init`, spelling out the defaults.
+pub const TEMPLATE: &str = r#"# Configuration for analyzr (complexity-audit), found by searching the
+# analyzed directory and its parents.
+#
+# Command-line flags take precedence over the ANALYZR_THRESHOLD,
+# ANALYZR_ERROR_THRESHOLD, ANALYZR_INFO_THRESHOLD and ANALYZR_PRESET
+# environment variables, which take precedence over this file.
+
+# Count complexity the way another tool does: analyzr, radon, mccabe or sonar.
+preset = "analyzr"
+
+# Skip third-party code copied into the repository. Directory names listed
+# in vendored-dirs are treated as vendored on top of the built-in ones.
+skip-vendored = false
+vendored-dirs = []
+
+# Files matching these patterns, such as "client/models/*.py", are treated as
+# generated on top of *_pb2.py, migrations and files marked as generated.
+generated-patterns = []
+
+[thresholds]
+# Functions above `warn` are reported as warnings, and above `error` (by
+# default twice `warn`) as errors.
+warn = 10
+# error = 20
+
+# Functions above `info` but not above `warn` are reported with severity info.
+# info = 5
+
+[weights]
+# How much a construct adds to a function's complexity, overriding the
+# preset. See the README for the list of constructs.
+# with = 0
+# ternary = 0
+
+[risk]
+# Coefficients of the risk score, a weighted sum of each function's metrics:
+# cyclomatic, locals, lines, cognitive, max_depth and params. No risk score
+# is computed unless a coefficient is set.
+# cyclomatic = 1.0
+# locals = 0.5
+
+# Thresholds for the files matching any of `paths`, relative to this file's
+# directory, replacing the ones above. When several rules match a file, the
+# last one applies. Functions above `max_cyclomatic` are reported with
+# `severity`: info, warning (the default) or error.
+# [[rules]]
+# paths = ["services/payments/**"]
+# max_cyclomatic = 8
+# severity = "error"
+
+# Categories of functions, such as framework entry points, recognized by
+# their decorators (globs of names without `@` or arguments) or by a base
+# class of their class (globs matching the base as written or its last
+# component). Functions no category matches are "uncategorized", and when
+# several match, the last one applies. A category's `max_cyclomatic` and
+# `severity` replace the thresholds above, and `[[rules]]` replace those.
+# Roll them up with --group-by category.
+# [[categories]]
+# name = "http-handler"
+# decorators = ["app.route", "*.get", "*.post", "router.*"]
+# bases = ["*View", "APIView"]
+# max_cyclomatic = 8
+#
+# [[categories]]
+# name = "task"
+# decorators = ["shared_task", "*.task"]
+
+[budgets]
+# Number of functions above the warn threshold each CODEOWNERS owner may
+# have before `check` fails, so that teams are not held to one global number.
+# Files no pattern matches belong to "unowned". Owners without a budget may
+# have none.
+# "team-payments" = { max_above_threshold = 5 }
+# unowned = { max_above_threshold = 20 }
+
+[summary_gates]
+# Ceilings on the summary statistics of a run, which fails when one is
+# exceeded: mean, p50, p90, p95, max, gini, top_decile_share,
+# above_threshold and above_threshold_ratio. Suffixed with `_increase`, a gate limits how much
+# the statistic may grow since the last run recorded with --trend, when run
+# with --compare-to-last.
+# mean = 4.5
+# p95 = 9
+# above_threshold_ratio = 0.03
+# p95_increase = 0.5
+"#;
+
+const ENV_THRESHOLD: &str = "ANALYZR_THRESHOLD";
+const ENV_ERROR_THRESHOLD: &str = "ANALYZR_ERROR_THRESHOLD";
+const ENV_INFO_THRESHOLD: &str = "ANALYZR_INFO_THRESHOLD";
+const ENV_PRESET: &str = "ANALYZR_PRESET";
+
+/// Where a setting's value comes from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Source {
+    Default,
+    File { path: PathBuf, line: usize },
+    Env(&'static str),
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Source::Default => write!(f, "default"),
+            Source::File { path, line } => write!(f, "{}:{}", path.display(), line),
+            Source::Env(name) => write!(f, "${}", name),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Setting<T> {
+    pub value: T,
+    pub source: Source,
+}
+
+impl<T> Setting<T> {
+    fn default(value: T) -> Self {
+        Setting {
+            value,
+            source: Source::Default,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Problem {
+    pub source: Source,
+    pub message: String,
+}
+
+/// The effective configuration, before command-line flags are applied.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    /// The file the configuration was read from, if one was found.
+    pub file: Option<PathBuf>,
+    pub threshold: Setting<u32>,
+    pub error_threshold: Setting<Option<u32>>,
+    pub info_threshold: Setting<Option<u32>>,
+    pub preset: Setting<Preset>,
+    pub weights: Vec<Setting<(Construct, u32)>>,
+    pub risk: Vec<Setting<(Metric, f64)>>,
+    pub skip_vendored: Setting<bool>,
+    pub vendored_dirs: Setting<Vec<String>>,
+    pub generated_patterns: Setting<Vec<String>>,
+    /// `[[rules]]` tables, each with the line of its first setting.
+    pub rules: Vec<Setting<Rule>>,
+    /// `[[categories]]` tables, each with the line of its first setting.
+    pub categories: Vec<Setting<Category>>,
+    /// `[budgets]` entries, one per owner.
+    pub budgets: Vec<Setting<Budget>>,
+    /// `[summary_gates]` entries, one per statistic and mode.
+    pub summary_gates: Vec<Setting<Gate>>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            file: None,
+            threshold: Setting::default(10),
+            error_threshold: Setting::default(None),
+            info_threshold: Setting::default(None),
+            preset: Setting::default(Preset::Analyzr),
+            weights: Vec::new(),
+            risk: Vec::new(),
+            skip_vendored: Setting::default(false),
+            vendored_dirs: Setting::default(Vec::new()),
+            generated_patterns: Setting::default(Vec::new()),
+            rules: Vec::new(),
+            categories: Vec::new(),
+            budgets: Vec::new(),
+            summary_gates: Vec::new(),
+        }
+    }
+}
+
+/// The `analyzr.toml` in `start` or the closest of its parents.
+pub fn find(start: &Path) -> Option<PathBuf> {
+    let start = start.canonicalize().ok()?;
+    let dir = if start.is_dir() { &start } else { start.parent()? };
+    dir.ancestors()
+        .map(|dir| dir.join(FILE_NAME))
+        .find(|path| path.is_file())
+}
+
+/// Loads the configuration for `start`, reading variables through `env`.
+/// Every problem found is returned rather than only the first; settings with
+/// a problem keep their default.
+pub fn load(start: &Path, env: impl Fn(&str) -> Option<String>) -> (Config, Vec<Problem>) {
+    let mut config = Config::default();
+    let mut problems = Vec::new();
+
+    if let Some(path) = find(start) {
+        match std::fs::read_to_string(&path) {
+            Ok(source) => {
+                let (entries, errors) = toml::parse(&source);
+                for error in errors {
+                    problems.push(Problem {
+                        source: Source::File {
+                            path: path.clone(),
+                            line: error.line,
+                        },
+                        message: error.message,
+                    });
+                }
+                let mut rules: BTreeMap<usize, Vec<&Entry>> = BTreeMap::new();
+                let mut categories: BTreeMap<usize, Vec<&Entry>> = BTreeMap::new();
+                for entry in &entries {
+                    let tables = match (entry.table.as_str(), entry.element) {
+                        ("rules", Some(_)) => Some(&mut rules),
+                        ("categories", Some(_)) => Some(&mut categories),
+                        _ => None,
+                    };
+                    if let (Some(tables), Some(element)) = (tables, entry.element) {
+                        tables.entry(element).or_default().push(entry);
+                        continue;
+                    }
+                    let source = Source::File {
+                        path: path.clone(),
+                        line: entry.line,
+                    };
+                    if let Err(message) = config.set(entry, source.clone()) {
+                        problems.push(Problem { source, message });
+                    }
+                }
+                for entries in rules.values() {
+                    let source = |line| Source::File {
+                        path: path.clone(),
+                        line,
+                    };
+                    match parse_rule(entries) {
+                        Ok(rule) => config.rules.push(Setting {
+                            value: rule,
+                            source: source(entries[0].line),
+                        }),
+                        Err((line, message)) => problems.push(Problem {
+                            source: source(line),
+                            message,
+                        }),
+                    }
+                }
+                for entries in categories.values() {
+                    let source = |line| Source::File {
+                        path: path.clone(),
+                        line,
+                    };
+                    let parsed = parse_category(entries).and_then(|category| {
+                        let defined = config.categories.iter().any(|c| c.value.name == category.name);
+                        if defined {
+                            let message = format!("category `{}` is defined twice", category.name);
+                            return Err((entries[0].line, message));
+                        }
+                        Ok(category)
+                    });
+                    match parsed {
+                        Ok(category) => config.categories.push(Setting {
+                            value: category,
+                            source: source(entries[0].line),
+                        }),
+                        Err((line, message)) => problems.push(Problem {
+                            source: source(line),
+                            message,
+                        }),
+                    }
+                }
+            }
+            Err(error) => problems.push(Problem {
+                source: Source::File { path: path.clone(), line: 0 },
+                message: format!("cannot read the file: {}", error),
+            }),
+        }
+        config.file = Some(path);
+    }
+
+    for name in [ENV_THRESHOLD, ENV_ERROR_THRESHOLD, ENV_INFO_THRESHOLD, ENV_PRESET] {
+        let Some(value) = env(name) else { continue };
+        let source = Source::Env(name);
+        if let Err(message) = config.set_env(name, &value, source.clone()) {
+            problems.push(Problem { source, message });
+        }
+    }
+
+    let warn = config.threshold.value;
+    if let Some(error) = config.error_threshold.value.filter(|&error| error < warn) {
+        problems.push(Problem {
+            source: config.error_threshold.source.clone(),
+            message: format!("error threshold {} is below the warn threshold {}", error, warn),
+        });
+    }
+    if let Some(info) = config.info_threshold.value.filter(|&info| info >= warn) {
+        problems.push(Problem {
+            source: config.info_threshold.source.clone(),
+            message: format!("info threshold {} is not below the warn threshold {}", info, warn),
+        });
+    }
+
+    (config, problems)
+}
+
+impl Config {
+    fn set(&mut self, entry: &Entry, source: Source) -> Result<(), String> {
+        let key = entry.key.as_str();
+        let value = &entry.value;
+        match (entry.table.as_str(), key) {
+            ("", "preset") => {
+                self.preset = Setting {
+                    value: parse_preset(string(key, value)?)?,
+                    source,
+                }
+            }
+            ("", "skip-vendored") => {
+                self.skip_vendored = Setting {
+                    value: boolean(key, value)?,
+                    source,
+                }
+            }
+            ("", "vendored-dirs") => {
+                self.vendored_dirs = Setting {
+                    value: strings(key, value)?,
+                    source,
+                }
+            }
+            ("", "generated-patterns") => {
+                self.generated_patterns = Setting {
+                    value: strings(key, value)?,
+                    source,
+                }
+            }
+            ("thresholds", "warn") => {
+                self.threshold = Setting {
+                    value: integer(key, value)?,
+                    source,
+                }
+            }
+            ("thresholds", "error") => {
+                self.error_threshold = Setting {
+                    value: Some(integer(key, value)?),
+                    source,
+                }
+            }
+            ("thresholds", "info") => {
+                self.info_threshold = Setting {
+                    value: Some(integer(key, value)?),
+                    source,
+                }
+            }
+            ("weights", name) => {
+                let construct = parse_construct(name)?;
+                self.weights.push(Setting {
+                    value: (construct, integer(key, value)?),
+                    source,
+                });
+            }
+            ("risk", name) => {
+                let metric = risk::parse_metric(name)?;
+                self.risk.push(Setting {
+                    value: (metric, number(key, value)?),
+                    source,
+                });
+            }
+            ("budgets", owner) => self.budgets.push(Setting {
+                value: parse_budget(owner, value)?,
+                source,
+            }),
+            ("summary_gates", name) => self.summary_gates.push(Setting {
+                value: gates::parse_gate(name, number(key, value)?)?,
+                source,
+            }),
+            ("" | "thresholds", _) => return Err(format!("unknown setting `{}`", key)),
+            (table, _) => return Err(format!("unknown table `[{}]`", table)),
+        }
+        Ok(())
+    }
+
+    fn set_env(&mut self, name: &'static str, value: &str, source: Source) -> Result<(), String> {
+        let integer = || {
+            value
+                .trim()
+                .parse::<u32>()
+                .map_err(|_| format!("`{}` is not a valid threshold", value))
+        };
+        match name {
+            ENV_THRESHOLD => {
+                self.threshold = Setting {
+                    value: integer()?,
+                    source,
+                }
+            }
+            ENV_ERROR_THRESHOLD => {
+                self.error_threshold = Setting {
+                    value: Some(integer()?),
+                    source,
+                }
+            }
+            ENV_INFO_THRESHOLD => {
+                self.info_threshold = Setting {
+                    value: Some(integer()?),
+                    source,
+                }
+            }
+            _ => {
+                self.preset = Setting {
+                    value: parse_preset(value)?,
+                    source,
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The analysis options the configuration sets, the others being the
+    /// defaults.
+    pub fn options(&self) -> AnalyzeOptions {
+        let mut weights = Weights::preset(self.preset.value);
+        for setting in &self.weights {
+            let (construct, weight) = setting.value;
+            weights.set(construct, weight);
+        }
+        let vendored_dirs = &self.vendored_dirs.value;
+        let skip_vendored = self.skip_vendored.value || !vendored_dirs.is_empty();
+        AnalyzeOptions {
+            threshold: self.threshold.value,
+            error_threshold: self.error_threshold.value,
+            info_threshold: self.info_threshold.value,
+            preset: self.preset.value,
+            weights,
+            vendored: skip_vendored.then(|| VendorDetector::with_components(vendored_dirs)),
+            generated: GeneratedDetector::with_patterns(&self.generated_patterns.value),
+            categories: self.categories.iter().map(|setting| setting.value.clone()).collect(),
+            risk: self.risk.iter().map(|setting| setting.value).collect(),
+            rules: self.rules(),
+            ..Default::default()
+        }
+    }
+
+    /// The `[[rules]]` of the file, if it has any.
+    pub fn rules(&self) -> Option<Rules> {
+        let base = self.file.as_ref()?.parent()?.to_path_buf();
+        let rules = self.rules.iter().map(|setting| setting.value.clone()).collect();
+        (!self.rules.is_empty()).then_some(Rules { base, rules })
+    }
+
+    /// Every setting with its value and where the value comes from.
+    pub fn table(&self) -> Table {
+        let mut table = Table::new();
+        table.set_header(vec!["Setting", "Value", "Source"]);
+        let mut row = |name: &str, value: String, source: &Source| {
+            table.add_row(vec![
+                Cell::new(name),
+                Cell::new(value),
+                Cell::new(source.to_string()),
+            ]);
+        };
+        let optional = |value: Option<u32>, default: &str| {
+            value.map_or(default.to_string(), |v| v.to_string())
+        };
+
+        row("preset", self.preset.value.name().to_string(), &self.preset.source);
+        row(
+            "thresholds.warn",
+            self.threshold.value.to_string(),
+            &self.threshold.source,
+        );
+        row(
+            "thresholds.error",
+            optional(self.error_threshold.value, "2 × warn"),
+            &self.error_threshold.source,
+        );
+        row(
+            "thresholds.info",
+            optional(self.info_threshold.value, "none"),
+            &self.info_threshold.source,
+        );
+        for setting in &self.weights {
+            let (construct, weight) = setting.value;
+            row(
+                &format!("weights.{}", construct.name()),
+                weight.to_string(),
+                &setting.source,
+            );
+        }
+        for setting in &self.risk {
+            let (metric, coefficient) = setting.value;
+            row(
+                &format!("risk.{}", metric.name()),
+                coefficient.to_string(),
+                &setting.source,
+            );
+        }
+        row(
+            "skip-vendored",
+            self.skip_vendored.value.to_string(),
+            &self.skip_vendored.source,
+        );
+        row(
+            "vendored-dirs",
+            self.vendored_dirs.value.join(", "),
+            &self.vendored_dirs.source,
+        );
+        row(
+            "generated-patterns",
+            self.generated_patterns.value.join(", "),
+            &self.generated_patterns.source,
+        );
+        for setting in &self.budgets {
+            let budget = &setting.value;
+            row(
+                &format!("budgets.{}", budget.owner),
+                format!("{} above the threshold", budget.max_above_threshold),
+                &setting.source,
+            );
+        }
+        for setting in &self.summary_gates {
+            let gate = &setting.value;
+            row(
+                &format!("summary_gates.{}", gate.name()),
+                gate.limit.to_string(),
+                &setting.source,
+            );
+        }
+        for (index, setting) in self.rules.iter().enumerate() {
+            let rule = &setting.value;
+            row(
+                &format!("rules[{}]", index),
+                format!(
+                    "{}: {} above {}",
+                    rule.paths.join(", "),
+                    severity_name(rule.severity),
+                    rule.max_cyclomatic
+                ),
+                &setting.source,
+            );
+        }
+        for setting in &self.categories {
+            let category = &setting.value;
+            let mut matchers: Vec<String> =
+                category.decorators.iter().map(|d| format!("@{}", d)).collect();
+            matchers.extend(category.bases.iter().map(|base| format!("({})", base)));
+            let mut value = matchers.join(", ");
+            if let Some(max) = category.max_cyclomatic {
+                value.push_str(&format!(
+                    ": {} above {}",
+                    severity_name(category.severity),
+                    max
+                ));
+            }
+            row(&format!("categories.{}", category.name), value, &setting.source);
+        }
+        table
+    }
+}
+
+/// Loads the configuration for `start`, failing with every problem found.
+pub fn load_checked(start: &Path) -> Result<Config> {
+    let (config, problems) = load(start, |name| std::env::var(name).ok());
+    if !problems.is_empty() {
+        bail!("{}", describe(&problems));
+    }
+    Ok(config)
+}
+
+pub fn describe(problems: &[Problem]) -> String {
+    let mut text = format!(
+        "{} problem{} in the configuration:",
+        problems.len(),
+        if problems.len() == 1 { "" } else { "s" }
+    );
+    for problem in problems {
+        text.push_str(&format!("\n  {}: {}", problem.source, problem.message));
+    }
+    text
+}
+
+/// Writes [`TEMPLATE`] to `dir`, refusing to replace an existing file unless
+/// `force` is set.
+pub fn init(dir: &Path, force: bool) -> Result<PathBuf> {
+    let path = dir.join(FILE_NAME);
+    if path.exists() && !force {
+        bail!("{} already exists (use --force to overwrite it)", path.display());
+    }
+    std::fs::write(&path, TEMPLATE)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(path)
+}
+
+fn type_error(key: &str, expected: &str, value: &Value) -> String {
+    format!("`{}` must be {}, not {}", key, expected, value.type_name())
+}
+
+fn string<'a>(key: &str, value: &'a Value) -> Result<&'a str, String> {
+    match value {
+        Value::String(s) => Ok(s),
+        _ => Err(type_error(key, "a string", value)),
+    }
+}
+
+fn strings(key: &str, value: &Value) -> Result<Vec<String>, String> {
+    let Value::Array(values) = value else {
+        return Err(type_error(key, "an array of strings", value));
+    };
+    values
+        .iter()
+        .map(|v| string(key, v).map(str::to_string))
+        .collect()
+}
+
+fn boolean(key: &str, value: &Value) -> Result<bool, String> {
+    match value {
+        Value::Boolean(b) => Ok(*b),
+        _ => Err(type_error(key, "a boolean", value)),
+    }
+}
+
+fn integer(key: &str, value: &Value) -> Result<u32, String> {
+    match value {
+        Value::Integer(i) => u32::try_from(*i)
+            .map_err(|_| format!("`{}` must be between 0 and {}, not {}", key, u32::MAX, i)),
+        _ => Err(type_error(key, "an integer", value)),
+    }
+}
+
+fn number(key: &str, value: &Value) -> Result<f64, String> {
+    match value {
+        Value::Integer(i) => Ok(*i as f64),
+        Value::Float(x) if x.is_finite() => Ok(*x),
+        _ => Err(type_error(key, "a number", value)),
+    }
+}
+
+/// A `[[rules]]` table from its entries, or the line and message of the
+/// first problem with it.
+fn parse_rule(entries: &[&Entry]) -> Result<Rule, (usize, String)> {
+    let mut paths = None;
+    let mut max_cyclomatic = None;
+    let mut severity = Severity::Warning;
+    for entry in entries {
+        let key = entry.key.as_str();
+        let value = &entry.value;
+        let at_line = |message| (entry.line, message);
+        match key {
+            "paths" => paths = Some(strings(key, value).map_err(at_line)?),
+            "max_cyclomatic" => max_cyclomatic = Some(integer(key, value).map_err(at_line)?),
+            "severity" => {
+                severity = parse_severity(string(key, value).map_err(at_line)?).map_err(at_line)?
+            }
+            _ => return Err(at_line(format!("unknown rule setting `{}`", key))),
+        }
+    }
+    let line = entries[0].line;
+    match (paths, max_cyclomatic) {
+        (Some(paths), _) if paths.is_empty() => Err((line, "rule with no `paths`".to_string())),
+        (Some(paths), Some(max_cyclomatic)) => Ok(Rule {
+            paths,
+            max_cyclomatic,
+            severity,
+        }),
+        (None, _) => Err((line, "rule with no `paths`".to_string())),
+        (_, None) => Err((line, "rule with no `max_cyclomatic`".to_string())),
+    }
+}
+
+/// A `[[categories]]` table from its entries, or the line and message of the
+/// first problem with it.
+fn parse_category(entries: &[&Entry]) -> Result<Category, (usize, String)> {
+    let mut name = None;
+    let mut decorators = Vec::new();
+    let mut bases = Vec::new();
+    let mut max_cyclomatic = None;
+    let mut severity = Severity::Warning;
+    for entry in entries {
+        let key = entry.key.as_str();
+        let value = &entry.value;
+        let at_line = |message| (entry.line, message);
+        match key {
+            "name" => name = Some(string(key, value).map_err(at_line)?.to_string()),
+            "decorators" | "bases" => {
+                let patterns = strings(key, value).map_err(at_line)?;
+                for pattern in &patterns {
+                    categories::check_pattern(pattern).map_err(at_line)?;
+                }
+                if key == "decorators" {
+                    decorators = patterns;
+                } else {
+                    bases = patterns;
+                }
+            }
+            "max_cyclomatic" => max_cyclomatic = Some(integer(key, value).map_err(at_line)?),
+            "severity" => severity = parse_severity(string(key, value).map_err(at_line)?).map_err(at_line)?,
+            _ => return Err(at_line(format!("unknown category setting `{}`", key))),
+        }
+    }
+    let line = entries[0].line;
+    let name = match name {
+        None => return Err((line, "category with no `name`".to_string())),
+        Some(name) if name == categories::UNCATEGORIZED => {
+            return Err((line, format!("`{}` is the category of unmatched functions", name)))
+        }
+        Some(name) => name,
+    };
+    if decorators.is_empty() && bases.is_empty() {
+        return Err((line, format!("category `{}` with no `decorators` or `bases`", name)));
+    }
+    Ok(Category {
+        name,
+        decorators,
+        bases,
+        max_cyclomatic,
+        severity,
+    })
+}
+
+/// The budget of `owner` from its inline table.
+fn parse_budget(owner: &str, value: &Value) -> Result<Budget, String> {
+    let Value::Table(entries) = value else {
+        return Err(type_error(owner, "a table such as `{ max_above_threshold = 5 }`", value));
+    };
+    let mut max_above_threshold = None;
+    for (key, value) in entries {
+        match key.as_str() {
+            "max_above_threshold" => max_above_threshold = Some(integer(key, value)?),
+            _ => return Err(format!("unknown budget setting `{}`", key)),
+        }
+    }
+    let max_above_threshold = max_above_threshold
+        .ok_or_else(|| format!("budget of `{}` with no `max_above_threshold`", owner))?;
+    Ok(Budget {
+        owner: owner.to_string(),
+        max_above_threshold: max_above_threshold as usize,
+    })
+}
+
+fn parse_severity(name: &str) -> Result<Severity, String> {
+    match name {
+        "info" => Ok(Severity::Info),
+        "warning" => Ok(Severity::Warning),
+        "error" => Ok(Severity::Error),
+        other => Err(format!(
+            "unknown severity `{}` (expected info, warning or error)",
+            other
+        )),
+    }
+}
+
+fn severity_name(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Info => "info",
+        Severity::Warning => "warning",
+        Severity::Error => "error",
+    }
+}
+
+fn parse_preset(name: &str) -> Result<Preset, String> {
+    Preset::from_str(name, false).map_err(|_| {
+        let names: Vec<&str> = Preset::value_variants().iter().map(|p| p.name()).collect();
+        format!("unknown preset `{}` (expected one of {})", name, names.join(", "))
+    })
+}
+
+fn parse_construct(name: &str) -> Result<Construct, String> {
+    Construct::ALL
+        .into_iter()
+        .find(|c| c.name() == name)
+        .ok_or_else(|| format!("unknown construct `{}`", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn no_env(_: &str) -> Option<String> {
+        None
+    }
+
+    fn write_config(source: &str) -> TempDir {
+        let temp_dir = TempDir::new().unwrap();
+        std::fs::write(temp_dir.path().join(FILE_NAME), source).unwrap();
+        temp_dir
+    }
+
+    #[test]
+    fn test_init_round_trips() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = init(temp_dir.path(), false).unwrap();
+        assert!(init(temp_dir.path(), false).is_err());
+        assert!(init(temp_dir.path(), true).is_ok());
+
+        let (config, problems) = load(temp_dir.path(), no_env);
+
+        assert_eq!(problems, []);
+        assert_eq!(config.file.as_deref(), Some(path.canonicalize().unwrap().as_path()));
+        let defaults = Config::default();
+        assert_eq!(config.threshold.value, defaults.threshold.value);
+        assert_eq!(config.preset.value, defaults.preset.value);
+        assert_eq!(config.error_threshold, defaults.error_threshold);
+        assert!(matches!(config.threshold.source, Source::File { line: 23, .. }));
+    }
+
+    #[test]
+    fn test_every_problem_is_reported() {
+        let temp_dir = write_config(
+            "preset = \"lizard\"\n\
+             skip-vendored = \"yes\"\n\
+             vendored-dirs = [\"ok\", 3]\n\
+             colour = true\n\
+             [thresholds]\n\
+             warn = -1\n\
+             error = 5\n\
+             [weights]\n\
+             goto = 1\n\
+             if = 2\n\
+             [rules]\n\
+             x = 1\n\
+             broken\n\
+             [risk]\n\
+             halstead = 1\n\
+             locals = \"high\"\n\
+             cyclomatic = 1.5\n",
+        );
+
+        let (config, problems) = load(temp_dir.path(), no_env);
+
+        let found: Vec<(usize, &str)> = problems
+            .iter()
+            .map(|problem| match problem.source {
+                Source::File { line, .. } => (line, problem.message.as_str()),
+                _ => panic!("unexpected source {}", problem.source),
+            })
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (13, "expected `key = value`, got `broken`"),
+                (1, "unknown preset `lizard` (expected one of analyzr, radon, mccabe, sonar)"),
+                (2, "`skip-vendored` must be a boolean, not a string"),
+                (3, "`vendored-dirs` must be a string, not an integer"),
+                (4, "unknown setting `colour`"),
+                (6, "`warn` must be between 0 and 4294967295, not -1"),
+                (9, "unknown construct `goto`"),
+                (12, "unknown table `[rules]`"),
+                (15, "unknown metric `halstead` (expected one of cyclomatic, locals, lines, cognitive, max_depth, params)"),
+                (16, "`locals` must be a number, not a string"),
+                (7, "error threshold 5 is below the warn threshold 10"),
+            ]
+        );
+        // Valid settings still apply.
+        assert_eq!(config.error_threshold.value, Some(5));
+        assert_eq!(config.weights.len(), 1);
+        assert_eq!(config.risk[0].value, (Metric::Cyclomatic, 1.5));
+    }
+
+    #[test]
+    fn test_rules() {
+        let temp_dir = write_config(
+            "[[rules]]\n\
+             paths = [\"services/payments/**\"]\n\
+             max_cyclomatic = 8\n\
+             severity = \"error\"\n\
+             [[rules]]\n\
+             paths = [\"legacy/**\"]\n\
+             max_cyclomatic = 30\n\
+             [[rules]]\n\
+             paths = [\"x/**\"]\n\
+             severity = \"fatal\"\n\
+             [[rules]]\n\
+             max_cyclomatic = 5\n",
+        );
+
+        let (config, problems) = load(temp_dir.path(), no_env);
+
+        let messages: Vec<&str> = problems.iter().map(|p| p.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "unknown severity `fatal` (expected info, warning or error)",
+                "rule with no `paths`",
+            ]
+        );
+        let rules = config.rules().unwrap();
+        assert_eq!(rules.base, temp_dir.path().canonicalize().unwrap());
+        assert_eq!(
+            rules.rules,
+            [
+                Rule {
+                    paths: vec!["services/payments/**".to_string()],
+                    max_cyclomatic: 8,
+                    severity: Severity::Error,
+                },
+                Rule {
+                    paths: vec!["legacy/**".to_string()],
+                    max_cyclomatic: 30,
+                    severity: Severity::Warning,
+                },
+            ]
+        );
+        assert!(matches!(config.rules[1].source, Source::File { line: 6, .. }));
+    }
+
+    #[test]
+    fn test_categories() {
+        let temp_dir = write_config(
+            "[[categories]]\n\
+             name = \"http-handler\"\n\
+             decorators = [\"app.route\", \"*.get\"]\n\
+             bases = [\"*View\"]\n\
+             max_cyclomatic = 8\n\
+             severity = \"error\"\n\
+             [[categories]]\n\
+             name = \"task\"\n\
+             decorators = [\"shared_task\"]\n\
+             [[categories]]\n\
+             name = \"cli\"\n\
+             decorators = [\"@click.command\"]\n\
+             [[categories]]\n\
+             name = \"route\"\n\
+             decorators = [\"app.route('/')\"]\n\
+             [[categories]]\n\
+             name = \"model\"\n\
+             bases = [\"models/Model\"]\n\
+             [[categories]]\n\
+             decorators = [\"x\"]\n\
+             [[categories]]\n\
+             name = \"empty\"\n\
+             [[categories]]\n\
+             name = \"uncategorized\"\n\
+             decorators = [\"x\"]\n\
+             [[categories]]\n\
+             name = \"task\"\n\
+             decorators = [\"celery.task\"]\n\
+             [[categories]]\n\
+             name = \"job\"\n\
+             decorators = [\"job\"]\n\
+             schedule = \"daily\"\n",
+        );
+
+        let (config, problems) = load(temp_dir.path(), no_env);
+
+        let found: Vec<(usize, &str)> = problems
+            .iter()
+            .map(|problem| match problem.source {
+                Source::File { line, .. } => (line, problem.message.as_str()),
+                _ => panic!("unexpected source {}", problem.source),
+            })
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (12, "`@click.command`: write decorators without `@`"),
+                (15, "`app.route('/')`: decorators are matched by name, without arguments"),
+                (18, "`models/Model` is not a dotted name"),
+                (20, "category with no `name`"),
+                (22, "category `empty` with no `decorators` or `bases`"),
+                (24, "`uncategorized` is the category of unmatched functions"),
+                (27, "category `task` is defined twice"),
+                (32, "unknown category setting `schedule`"),
+            ]
+        );
+        let categories: Vec<&Category> = config.categories.iter().map(|s| &s.value).collect();
+        assert_eq!(
+            categories,
+            [
+                &Category {
+                    name: "http-handler".to_string(),
+                    decorators: vec!["app.route".to_string(), "*.get".to_string()],
+                    bases: vec!["*View".to_string()],
+                    max_cyclomatic: Some(8),
+                    severity: Severity::Error,
+                },
+                &Category {
+                    name: "task".to_string(),
+                    decorators: vec!["shared_task".to_string()],
+                    bases: Vec::new(),
+                    max_cyclomatic: None,
+                    severity: Severity::Warning,
+                },
+            ]
+        );
+        assert!(matches!(config.categories[1].source, Source::File { line: 8, .. }));
+    }
+
+    #[test]
+    fn test_budgets() {
+        let temp_dir = write_config(
+            "[budgets]\n\
+             \"team-payments\" = { max_above_threshold = 5 }\n\
+             unowned = { max_above_threshold = 0 }\n\
+             search = 3\n\
+             ops = { max = 1 }\n\
+             web = {}\n",
+        );
+
+        let (config, problems) = load(temp_dir.path(), no_env);
+
+        let messages: Vec<&str> = problems.iter().map(|p| p.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "`search` must be a table such as `{ max_above_threshold = 5 }`, not an integer",
+                "unknown budget setting `max`",
+                "budget of `web` with no `max_above_threshold`",
+            ]
+        );
+        let budgets: Vec<(&str, usize)> = config
+            .budgets
+            .iter()
+            .map(|setting| (setting.value.owner.as_str(), setting.value.max_above_threshold))
+            .collect();
+        assert_eq!(budgets, [("team-payments", 5), ("unowned", 0)]);
+    }
+
+    #[test]
+    fn test_summary_gates() {
+        let temp_dir = write_config(
+            "[summary_gates]
+             mean = 4.5
+             p95 = 9
+             p95_increase = 0.5
+             p99 = 12
+             max = \"20\"
+",
+        );
+
+        let (config, problems) = load(temp_dir.path(), no_env);
+
+        let messages: Vec<&str> = problems.iter().map(|p| p.message.as_str()).collect();
+        assert_eq!(messages.len(), 2);
+        assert!(messages[0].starts_with("unknown summary gate `p99`"));
+        assert_eq!(messages[1], "`max` must be a number, not a string");
+        let gates: Vec<(String, f64)> = config
+            .summary_gates
+            .iter()
+            .map(|setting| (setting.value.name(), setting.value.limit))
+            .collect();
+        assert_eq!(
+            gates,
+            [
+                ("mean".to_string(), 4.5),
+                ("p95".to_string(), 9.0),
+                ("p95_increase".to_string(), 0.5),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cross_setting_ranges() {
+        let temp_dir = write_config("[thresholds]\nwarn = 10\nerror = 5\ninfo = 10\n");
+        let (_, problems) = load(temp_dir.path(), no_env);
+        let messages: Vec<&str> = problems.iter().map(|p| p.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "error threshold 5 is below the warn threshold 10",
+                "info threshold 10 is not below the warn threshold 10",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_env_overrides_file() {
+        let temp_dir = write_config("preset = \"radon\"\n[thresholds]\nwarn = 8\n");
+        let nested = temp_dir.path().join("src/pkg");
+        std::fs::create_dir_all(&nested).unwrap();
+        let env = |name: &str| match name {
+            ENV_THRESHOLD => Some("12".to_string()),
+            ENV_INFO_THRESHOLD => Some("many".to_string()),
+            _ => None,
+        };
+
+        let (config, problems) = load(&nested, env);
+
+        assert_eq!(config.threshold.value, 12);
+        assert_eq!(config.threshold.source, Source::Env(ENV_THRESHOLD));
+        assert_eq!(config.preset.value, Preset::Radon);
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].source, Source::Env(ENV_INFO_THRESHOLD));
+    }
+
+    #[test]
+    fn test_options() {
+        let temp_dir = write_config(
+            "preset = \"sonar\"\nvendored-dirs = [\"ext\"]\n\
+             [thresholds]\nwarn = 8\nerror = 30\n[weights]\nwith = 2\n[risk]\nlocals = 1\n",
+        );
+        let (config, _) = load(temp_dir.path(), no_env);
+        let options = config.options();
+        assert_eq!((options.threshold, options.error_threshold), (8, Some(30)));
+        assert_eq!(options.preset, Preset::Sonar);
+        assert_eq!(options.weights.get(Construct::With), 2);
+        assert_eq!(options.weights.get(Construct::If), 1);
+        assert_eq!(options.vendored.unwrap().extra(), ["ext"]);
+        assert_eq!(options.risk, [(Metric::Locals, 1.0)]);
+        assert!(options.rules.is_none());
+    }
+}
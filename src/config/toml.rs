@@ -0,0 +1,377 @@
+//! The subset of TOML used by `analyzr.toml`: `[table]` and `[[array]]`
+//! headers and single-line `key = value` pairs whose values are strings,
+//! integers, floats, booleans, or arrays or inline tables of those.
+
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    Array(Vec<Value>),
+    /// An inline table, `{ key = value, ... }`, with its keys in order.
+    Table(Vec<(String, Value)>),
+}
+
+impl Value {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::String(_) => "a string",
+            Value::Integer(_) => "an integer",
+            Value::Float(_) => "a float",
+            Value::Boolean(_) => "a boolean",
+            Value::Array(_) => "an array",
+            Value::Table(_) => "a table",
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::String(s) => write!(f, "{:?}", s),
+            Value::Integer(i) => write!(f, "{}", i),
+            Value::Float(x) => write!(f, "{}", x),
+            Value::Boolean(b) => write!(f, "{}", b),
+            Value::Array(values) => {
+                let values: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+                write!(f, "[{}]", values.join(", "))
+            }
+            Value::Table(entries) if entries.is_empty() => write!(f, "{{}}"),
+            Value::Table(entries) => {
+                let entries: Vec<String> =
+                    entries.iter().map(|(key, value)| format!("{} = {}", key, value)).collect();
+                write!(f, "{{ {} }}", entries.join(", "))
+            }
+        }
+    }
+}
+
+/// A `key = value` pair, with the table it belongs to (empty for the root
+/// table) and the line it is on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub table: String,
+    /// Position of the table in its array, for an `[[array]]` table.
+    pub element: Option<usize>,
+    pub key: String,
+    pub value: Value,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxError {
+    pub line: usize,
+    pub message: String,
+}
+
+/// Parses every line it can, returning the entries along with an error for
+/// each line it could not.
+pub fn parse(source: &str) -> (Vec<Entry>, Vec<SyntaxError>) {
+    let mut entries: Vec<Entry> = Vec::new();
+    let mut errors = Vec::new();
+    let mut table = String::new();
+    let mut element = None;
+    // Number of `[[array]]` tables seen so far under each name.
+    let mut arrays: Vec<(String, usize)> = Vec::new();
+
+    for (index, raw) in source.lines().enumerate() {
+        let line = index + 1;
+        let mut error = |message: String| errors.push(SyntaxError { line, message });
+        let text = strip_comment(raw).trim();
+        if text.is_empty() {
+            continue;
+        }
+
+        if let Some(header) = text.strip_prefix("[[") {
+            match header.strip_suffix("]]").map(str::trim) {
+                Some(name) if is_bare_key(name) => {
+                    let index = match arrays.iter_mut().find(|(array, _)| array == name) {
+                        Some((_, count)) => {
+                            *count += 1;
+                            *count - 1
+                        }
+                        None => {
+                            arrays.push((name.to_string(), 1));
+                            0
+                        }
+                    };
+                    table = name.to_string();
+                    element = Some(index);
+                }
+                _ => error(format!("invalid table header `{}`", text)),
+            }
+            continue;
+        }
+        if let Some(header) = text.strip_prefix('[') {
+            match header.strip_suffix(']').map(str::trim) {
+                Some(name) if is_bare_key(name) => {
+                    table = name.to_string();
+                    element = None;
+                }
+                _ => error(format!("invalid table header `{}`", text)),
+            }
+            continue;
+        }
+
+        let Some((key, value)) = text.split_once('=') else {
+            error(format!("expected `key = value`, got `{}`", text));
+            continue;
+        };
+        let key = match parse_key(key) {
+            Ok(key) => key,
+            Err(message) => {
+                error(message);
+                continue;
+            }
+        };
+        if entries
+            .iter()
+            .any(|e| e.table == table && e.element == element && e.key == key)
+        {
+            error(format!("duplicate key `{}`", key));
+            continue;
+        }
+        match parse_value(value.trim()) {
+            Ok(value) => entries.push(Entry {
+                table: table.clone(),
+                element,
+                key: key.to_string(),
+                value,
+                line,
+            }),
+            Err(message) => error(message),
+        }
+    }
+
+    (entries, errors)
+}
+
+fn is_bare_key(key: &str) -> bool {
+    !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+fn parse_key(key: &str) -> Result<&str, String> {
+    let key = key.trim();
+    match key.strip_prefix('"').and_then(|k| k.strip_suffix('"')) {
+        Some(quoted) => Ok(quoted),
+        None if is_bare_key(key) => Ok(key),
+        None => Err(format!("invalid key `{}`", key)),
+    }
+}
+
+/// `line` up to a `#` that is not inside a string.
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    for (index, c) in line.char_indices() {
+        match quote {
+            Some('"') if escaped => escaped = false,
+            Some('"') if c == '\\' => escaped = true,
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == '#' => return &line[..index],
+            None => {}
+        }
+    }
+    line
+}
+
+fn parse_value(text: &str) -> Result<Value, String> {
+    let (value, rest) = parse_prefix(text)?;
+    if !rest.trim().is_empty() {
+        return Err(format!("unexpected `{}` after value", rest.trim()));
+    }
+    Ok(value)
+}
+
+/// Parses the value at the start of `text`, returning it and what follows.
+fn parse_prefix(text: &str) -> Result<(Value, &str), String> {
+    if let Some(rest) = text.strip_prefix('"') {
+        let mut value = String::new();
+        let mut chars = rest.char_indices();
+        while let Some((index, c)) = chars.next() {
+            match c {
+                '"' => return Ok((Value::String(value), &rest[index + 1..])),
+                '\\' => match chars.next().map(|(_, c)| c) {
+                    Some('"') => value.push('"'),
+                    Some('\\') => value.push('\\'),
+                    Some('n') => value.push('\n'),
+                    Some('t') => value.push('\t'),
+                    other => {
+                        return Err(format!(
+                            "unsupported escape `\\{}`",
+                            other.map(String::from).unwrap_or_default()
+                        ))
+                    }
+                },
+                c => value.push(c),
+            }
+        }
+        return Err("unterminated string".to_string());
+    }
+    if let Some(rest) = text.strip_prefix('\'') {
+        let end = rest.find('\'').ok_or("unterminated string")?;
+        return Ok((Value::String(rest[..end].to_string()), &rest[end + 1..]));
+    }
+    if let Some(mut rest) = text.strip_prefix('[') {
+        let mut values = Vec::new();
+        loop {
+            rest = rest.trim_start();
+            if let Some(after) = rest.strip_prefix(']') {
+                return Ok((Value::Array(values), after));
+            }
+            let (value, after) = parse_prefix(rest)?;
+            values.push(value);
+            rest = after.trim_start();
+            if let Some(after) = rest.strip_prefix(',') {
+                rest = after;
+            } else if !rest.starts_with(']') {
+                return Err("expected `,` or `]` in array".to_string());
+            }
+        }
+    }
+
+    if let Some(mut rest) = text.strip_prefix('{') {
+        let mut entries: Vec<(String, Value)> = Vec::new();
+        loop {
+            rest = rest.trim_start();
+            if let Some(after) = rest.strip_prefix('}') {
+                return Ok((Value::Table(entries), after));
+            }
+            let (key, after) = rest.split_once('=').ok_or("expected `key = value` in table")?;
+            let key = parse_key(key)?;
+            if entries.iter().any(|(k, _)| k == key) {
+                return Err(format!("duplicate key `{}` in table", key));
+            }
+            let (value, after) = parse_prefix(after.trim_start())?;
+            entries.push((key.to_string(), value));
+            rest = after.trim_start();
+            if let Some(after) = rest.strip_prefix(',') {
+                rest = after;
+            } else if !rest.starts_with('}') {
+                return Err("expected `,` or `}` in table".to_string());
+            }
+        }
+    }
+
+    let end = text.find([',', ']', '}']).unwrap_or(text.len());
+    let (token, rest) = text.split_at(end);
+    let token = token.trim_end();
+    let value = match token {
+        "true" => Value::Boolean(true),
+        "false" => Value::Boolean(false),
+        _ => {
+            let digits = token.replace('_', "");
+            if let Ok(integer) = digits.parse() {
+                Value::Integer(integer)
+            } else if let (true, Ok(float)) = (
+                digits.contains(['.', 'e', 'E']) && !digits.contains("inf"),
+                digits.parse(),
+            ) {
+                Value::Float(float)
+            } else if token.is_empty() {
+                return Err("missing value".to_string());
+            } else {
+                return Err(format!("invalid value `{}`", token));
+            }
+        }
+    };
+    Ok((value, rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_values() {
+        let source = r#"
+# comment
+preset = "radon"   # trailing comment
+count = 1_000
+ratio = 0.5
+enabled = true
+names = ["a # b", 'c\d', ]
+nested = [[1, 2], []]
+
+[thresholds]
+"warn" = -3
+"#;
+        let (entries, errors) = parse(source);
+        assert_eq!(errors, []);
+        let values: Vec<(&str, &str, String)> = entries
+            .iter()
+            .map(|e| (e.table.as_str(), e.key.as_str(), e.value.to_string()))
+            .collect();
+        assert_eq!(
+            values,
+            [
+                ("", "preset", "\"radon\"".to_string()),
+                ("", "count", "1000".to_string()),
+                ("", "ratio", "0.5".to_string()),
+                ("", "enabled", "true".to_string()),
+                ("", "names", "[\"a # b\", \"c\\\\d\"]".to_string()),
+                ("", "nested", "[[1, 2], []]".to_string()),
+                ("thresholds", "warn", "-3".to_string()),
+            ]
+        );
+        assert_eq!(entries[6].line, 11);
+    }
+
+    #[test]
+    fn test_inline_tables() {
+        let source = "[budgets]\n\"team-payments\" = { max_above_threshold = 5 }\nunowned = {max_above_threshold=0,}\nempty = {}\nbad = { a = 1 b = 2 }\ndup = { a = 1, a = 2 }\n";
+        let (entries, errors) = parse(source);
+        let values: Vec<(&str, String)> =
+            entries.iter().map(|e| (e.key.as_str(), e.value.to_string())).collect();
+        assert_eq!(
+            values,
+            [
+                ("team-payments", "{ max_above_threshold = 5 }".to_string()),
+                ("unowned", "{ max_above_threshold = 0 }".to_string()),
+                ("empty", "{}".to_string()),
+            ]
+        );
+        let lines: Vec<usize> = errors.iter().map(|e| e.line).collect();
+        assert_eq!(lines, [5, 6]);
+    }
+
+    #[test]
+    fn test_array_tables() {
+        let source = "[[rules]]\nx = 1\n[[rules]]\nx = 2\n[other]\nx = 3\n[[rules]]\n[[ rules ]]\nx = 4\nx = 5\n";
+        let (entries, errors) = parse(source);
+        let values: Vec<(&str, Option<usize>, String)> = entries
+            .iter()
+            .map(|e| (e.table.as_str(), e.element, e.value.to_string()))
+            .collect();
+        assert_eq!(
+            values,
+            [
+                ("rules", Some(0), "1".to_string()),
+                ("rules", Some(1), "2".to_string()),
+                ("other", None, "3".to_string()),
+                ("rules", Some(3), "4".to_string()),
+            ]
+        );
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].line, 10);
+    }
+
+    #[test]
+    fn test_errors_are_collected_per_line() {
+        let source = "a = \nb = \"open\nc = 1 2\n[bad table]\nd = [1,\ne = yes\ne = 1\nf = 1\nf = 2\n";
+        let (entries, errors) = parse(source);
+        let lines: Vec<usize> = errors.iter().map(|e| e.line).collect();
+        assert_eq!(lines, [1, 2, 3, 4, 5, 6, 9]);
+        assert!(errors[6].message.contains("duplicate"));
+        let keys: Vec<&str> = entries.iter().map(|e| e.key.as_str()).collect();
+        assert_eq!(keys, ["e", "f"]);
+    }
+}
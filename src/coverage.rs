@@ -0,0 +1,410 @@
+//! Cross-reference of complexity with test coverage, to find the functions
+//! most likely to hide bugs: complex ones that tests barely run.
+//!
+//! Coverage is read from LCOV tracefiles or coverage.py's Cobertura XML. A
+//! function's coverage is that of its body, from the line after its
+//! signature to its last line, so that neither decorators nor the `def`
+//! line, which run on import, count as tested.
+
+use crate::output::relative_path;
+use crate::output::text::{truncate_end, truncate_start};
+use crate::severity::Metric;
+use crate::output::table::{NAME_WIDTH, PATH_WIDTH};
+use crate::{above, AnalysisResult};
+use anyhow::{bail, Context, Result};
+use comfy_table::{Cell, Table};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Line hits of each file of a coverage report.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Coverage {
+    /// Path components of each file, with the hits of each measured line.
+    files: Vec<(Vec<String>, BTreeMap<u32, u64>)>,
+}
+
+fn components(path: &str) -> Vec<String> {
+    path.split(['/', '\\'])
+        .filter(|c| !c.is_empty() && *c != ".")
+        .map(str::to_string)
+        .collect()
+}
+
+impl Coverage {
+    /// Reads `path` as coverage.py XML when it starts with an XML tag, as
+    /// LCOV otherwise.
+    pub fn load(path: &Path) -> Result<Self> {
+        let source = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let coverage = if source.trim_start().starts_with('<') {
+            Coverage::parse_xml(&source)
+        } else {
+            Coverage::parse_lcov(&source)
+        };
+        coverage.with_context(|| format!("Failed to parse {}", path.display()))
+    }
+
+    /// Parses an LCOV tracefile, keeping its `SF` and `DA` records.
+    pub fn parse_lcov(source: &str) -> Result<Self> {
+        let mut coverage = Coverage::default();
+        let mut current: Option<(Vec<String>, BTreeMap<u32, u64>)> = None;
+        for (index, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if let Some(file) = line.strip_prefix("SF:") {
+                current = Some((components(file), BTreeMap::new()));
+            } else if let Some(record) = line.strip_prefix("DA:") {
+                let Some((_, lines)) = current.as_mut() else {
+                    bail!("line {}: DA record outside of a file", index + 1);
+                };
+                let mut fields = record.split(',');
+                let (Some(number), Some(hits)) = (fields.next(), fields.next()) else {
+                    bail!("line {}: malformed DA record", index + 1);
+                };
+                let number = number
+                    .parse()
+                    .with_context(|| format!("line {}", index + 1))?;
+                // Some tools write negative or fractional hit counts.
+                let hits = hits.parse::<f64>().map_or(0, |hits| hits.max(0.0) as u64);
+                *lines.entry(number).or_default() += hits;
+            } else if line == "end_of_record" {
+                coverage.files.extend(current.take());
+            }
+        }
+        coverage.files.extend(current);
+        Ok(coverage)
+    }
+
+    /// Parses a Cobertura report as written by `coverage xml`, keeping the
+    /// `line` elements of each `class`.
+    pub fn parse_xml(source: &str) -> Result<Self> {
+        let mut coverage = Coverage::default();
+        let mut current: Option<(Vec<String>, BTreeMap<u32, u64>)> = None;
+        let mut rest = source;
+        while let Some(start) = rest.find('<') {
+            let Some(end) = rest[start..].find('>') else {
+                bail!("unterminated tag");
+            };
+            let tag = &rest[start + 1..start + end];
+            rest = &rest[start + end + 1..];
+            let name = tag.split_whitespace().next().unwrap_or_default();
+            match name {
+                "class" => {
+                    let filename =
+                        attribute(tag, "filename").context("class element without a filename")?;
+                    coverage.files.extend(current.take());
+                    current = Some((components(&filename), BTreeMap::new()));
+                }
+                "/class" => coverage.files.extend(current.take()),
+                "line" => {
+                    if let Some((_, lines)) = current.as_mut() {
+                        let number = attribute(tag, "number")
+                            .and_then(|number| number.parse().ok())
+                            .context("line element without a number")?;
+                        let hits = attribute(tag, "hits")
+                            .and_then(|hits| hits.parse().ok())
+                            .unwrap_or(0);
+                        *lines.entry(number).or_default() += hits;
+                    }
+                }
+                _ => {}
+            }
+        }
+        coverage.files.extend(current);
+        Ok(coverage)
+    }
+
+    /// Hits of each measured line of the file at `relative` (with `/`
+    /// separators): the file whose path ends with it or, for a report with
+    /// paths relative to a source directory, whose path it ends with. The
+    /// longest match wins.
+    fn lines(&self, relative: &str) -> Option<&BTreeMap<u32, u64>> {
+        let relative = components(relative);
+        self.files
+            .iter()
+            .filter(|(file, _)| file.ends_with(&relative) || relative.ends_with(file))
+            .max_by_key(|(file, _)| file.len().min(relative.len()))
+            .map(|(_, lines)| lines)
+    }
+}
+
+/// The value of `name="..."` in the inside of a tag.
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    let pattern = format!(" {}=\"", name);
+    let start = tag.find(&pattern)? + pattern.len();
+    let value = &tag[start..start + tag[start..].find('"')?];
+    Some(
+        value
+            .replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&quot;", "\"")
+            .replace("&apos;", "'")
+            .replace("&amp;", "&"),
+    )
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FunctionCoverage {
+    pub name: String,
+    pub file: String,
+    pub line: u32,
+    pub end_line: u32,
+    pub complexity: u32,
+    /// Lines of the body the report measures.
+    pub lines: u32,
+    pub covered: u32,
+    /// Percentage of the measured lines that ran, 0 when none are measured.
+    pub percent: f64,
+    /// Whether the report measures any line of the body. A function whose
+    /// file is missing from the report is not in it either.
+    pub in_report: bool,
+    /// Above the threshold and under the minimum coverage.
+    pub risky: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    pub threshold: u32,
+    pub min_coverage: f64,
+    /// Number of functions above the threshold and under the minimum
+    /// coverage.
+    pub risky: usize,
+    /// Functions ranked by complexity weighted by the share of their body
+    /// that is not covered.
+    pub functions: Vec<FunctionCoverage>,
+}
+
+/// Parses a `--min-coverage` argument, a percentage.
+pub fn parse_percent(spec: &str) -> Result<f64, String> {
+    spec.parse::<f64>()
+        .ok()
+        .filter(|percent| (0.0..=100.0).contains(percent))
+        .ok_or_else(|| format!("`{}` is not a percentage between 0 and 100", spec))
+}
+
+/// Joins the functions of `result`, analyzed under `root`, with `coverage`.
+pub fn cross_reference(
+    result: &AnalysisResult,
+    root: &Path,
+    coverage: &Coverage,
+    threshold: u32,
+    min_coverage: f64,
+) -> Report {
+    let mut functions: Vec<FunctionCoverage> = result
+        .functions
+        .iter()
+        .map(|func| {
+            let body = func.signature_end_line.max(func.line) + 1..=func.end_line;
+            let (lines, covered) =
+                coverage
+                    .lines(&relative_path(&func.file, root))
+                    .map_or((0, 0), |hits| {
+                        hits.range(body)
+                            .fold((0, 0), |(lines, covered), (_, &hits)| {
+                                (lines + 1, covered + u32::from(hits > 0))
+                            })
+                    });
+            let percent = if lines == 0 {
+                0.0
+            } else {
+                100.0 * covered as f64 / lines as f64
+            };
+            FunctionCoverage {
+                name: func.name.clone(),
+                file: func.file.clone(),
+                line: func.line,
+                end_line: func.end_line,
+                complexity: func.complexity,
+                lines,
+                covered,
+                percent,
+                in_report: lines > 0,
+                risky: above(func, threshold, Metric::Cyclomatic) && percent < min_coverage,
+            }
+        })
+        .collect();
+    let priority = |f: &FunctionCoverage| f.complexity as f64 * (100.0 - f.percent);
+    functions.sort_by(|a, b| {
+        priority(b)
+            .total_cmp(&priority(a))
+            .then(b.complexity.cmp(&a.complexity))
+            .then_with(|| (&a.file, a.line).cmp(&(&b.file, b.line)))
+    });
+    Report {
+        threshold,
+        min_coverage,
+        risky: functions.iter().filter(|f| f.risky).count(),
+        functions,
+    }
+}
+
+impl Report {
+    pub fn summary_line(&self) -> String {
+        format!(
+            "{} {} with complexity above {} and under {}% coverage",
+            self.risky,
+            if self.risky == 1 {
+                "function"
+            } else {
+                "functions"
+            },
+            self.threshold,
+            self.min_coverage
+        )
+    }
+}
+
+/// The coverage of `function`, marked when the report does not measure it.
+fn describe(function: &FunctionCoverage) -> String {
+    if function.in_report {
+        format!(
+            "{:.0}% ({}/{})",
+            function.percent, function.covered, function.lines
+        )
+    } else {
+        "0% (not in report)".to_string()
+    }
+}
+
+pub fn print_table(report: &Report) {
+    let mut table = Table::new();
+    table.set_header(vec![
+        "Function",
+        "File",
+        "Line",
+        "Complexity",
+        "Coverage",
+        "Risky",
+    ]);
+    for function in &report.functions {
+        table.add_row(vec![
+            Cell::new(truncate_end(&function.name, NAME_WIDTH)),
+            Cell::new(truncate_start(&function.file, PATH_WIDTH)),
+            Cell::new(function.line.to_string()),
+            Cell::new(function.complexity.to_string()),
+            Cell::new(describe(function)),
+            Cell::new(if function.risky { "yes" } else { "" }),
+        ]);
+    }
+    println!("{}", table);
+    println!("\n{}", report.summary_line());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse_lcov() {
+        let coverage = Coverage::parse_lcov(
+            "TN:\nSF:/repo/src/pkg/a.py\nDA:1,1\nDA:2,0\nDA:2,3\nend_of_record\n\
+             SF:src/b.py\nDA:4,0\nend_of_record\n",
+        )
+        .unwrap();
+        let a = coverage.lines("pkg/a.py").unwrap();
+        assert_eq!(a.iter().collect::<Vec<_>>(), [(&1, &1), (&2, &3)]);
+        assert!(coverage.lines("b.py").is_some());
+        assert!(coverage.lines("c.py").is_none());
+        assert!(Coverage::parse_lcov("DA:1,1\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_xml() {
+        let coverage = Coverage::parse_xml(
+            r#"<?xml version="1.0" ?>
+<coverage version="7.4.0">
+  <sources><source>/repo/src</source></sources>
+  <packages><package name="pkg"><classes>
+    <class name="a.py" filename="pkg/a.py" line-rate="0.5">
+      <methods/>
+      <lines>
+        <line number="3" hits="1"/>
+        <line number="4" hits="0" branch="true" condition-coverage="0% (0/2)"/>
+      </lines>
+    </class>
+    <class name="b&amp;c.py" filename="pkg/b&amp;c.py"><lines/></class>
+  </classes></package></packages>
+</coverage>"#,
+        )
+        .unwrap();
+        // Paths relative to a source directory match the longer ones of the
+        // analysis.
+        let a = coverage.lines("src/pkg/a.py").unwrap();
+        assert_eq!(a.iter().collect::<Vec<_>>(), [(&3, &1), (&4, &0)]);
+        assert!(coverage.lines("pkg/b&c.py").is_some());
+    }
+
+    #[test]
+    fn test_cross_reference() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        std::fs::create_dir(root.join("pkg")).unwrap();
+        std::fs::write(
+            root.join("pkg/a.py"),
+            "import functools\n\
+             \n\
+             @functools.cache\n\
+             def tested(x):\n\
+             \x20   if x:\n\
+             \x20       return 1\n\
+             \x20   return 2\n\
+             \n\
+             def untested(x, y):\n\
+             \x20   if x:\n\
+             \x20       return 1\n\
+             \x20   if y:\n\
+             \x20       return 2\n\
+             \x20   return 3\n",
+        )
+        .unwrap();
+        std::fs::write(
+            root.join("b.py"),
+            "def absent(x, y):\n    return x or y or 1\n",
+        )
+        .unwrap();
+        // Decorators and `def` lines ran on import, which does not make the
+        // function tested.
+        let coverage = Coverage::parse_lcov(
+            "SF:pkg/a.py\nDA:1,1\nDA:3,1\nDA:4,1\nDA:5,4\nDA:6,2\nDA:7,2\n\
+             DA:9,1\nDA:10,1\nDA:11,0\nDA:12,1\nDA:13,0\nDA:14,0\nend_of_record\n",
+        )
+        .unwrap();
+        let result = crate::analyze_directory(root, 2).unwrap();
+        let report = cross_reference(&result, root, &coverage, 2, 50.0);
+
+        let rows: Vec<(&str, u32, u32, u32, bool, bool)> = report
+            .functions
+            .iter()
+            .map(|f| {
+                let name = f.name.as_str();
+                (name, f.complexity, f.lines, f.covered, f.in_report, f.risky)
+            })
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                ("absent", 3, 0, 0, false, true),
+                ("untested", 3, 5, 2, true, true),
+                ("tested", 2, 3, 3, true, false),
+            ]
+        );
+        assert_eq!(report.functions[1].percent, 40.0);
+        assert_eq!(describe(&report.functions[0]), "0% (not in report)");
+        assert_eq!(describe(&report.functions[1]), "40% (2/5)");
+        assert_eq!(report.risky, 2);
+        assert_eq!(
+            report.summary_line(),
+            "2 functions with complexity above 2 and under 50% coverage"
+        );
+    }
+
+    #[test]
+    fn test_parse_percent() {
+        assert_eq!(parse_percent("75"), Ok(75.0));
+        assert_eq!(parse_percent("0"), Ok(0.0));
+        assert!(parse_percent("101").is_err());
+        assert!(parse_percent("NaN").is_err());
+        assert!(parse_percent("-inf").is_err());
+    }
+}
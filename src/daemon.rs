@@ -0,0 +1,603 @@
+//! A resident analyzer for editor integrations that would otherwise pay
+//! process startup on every keystroke, answering requests over a Unix
+//! socket.
+//!
+//! Each message, in both directions, is a 4-byte big-endian length followed
+//! by that many bytes of JSON. Requests are `analyze_file` (a path, whose
+//! result is cached until the file's modification time or size changes),
+//! `analyze_source` and `shutdown`, each optionally with the weights and
+//! the maximum nesting depth to use.
+
+use crate::depth::TooDeep;
+use crate::weights::Weights;
+use crate::{calculate_complexity_with, FunctionComplexity};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Largest message accepted, far above any source file.
+const MAX_MESSAGE: u32 = 64 * 1024 * 1024;
+
+/// Socket used when none is given, shared by the daemon and `--use-daemon`.
+pub fn default_socket() -> PathBuf {
+    std::env::temp_dir().join("analyzr.sock")
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "request", rename_all = "snake_case")]
+pub enum Request {
+    AnalyzeFile {
+        path: PathBuf,
+        #[serde(default)]
+        weights: Weights,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        max_depth: Option<usize>,
+    },
+    AnalyzeSource {
+        source: String,
+        /// Name of a file the source is parsed as, for the language of its
+        /// extension; Python without.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        file_name: Option<PathBuf>,
+        #[serde(default)]
+        weights: Weights,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        max_depth: Option<usize>,
+    },
+    Shutdown,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Response {
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub functions: Vec<FunctionComplexity>,
+    /// Whether the functions of an `analyze_file` request came from the
+    /// cache.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub cached: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Set when the error is a tree nested deeper than allowed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub too_deep: Option<TooDeep>,
+}
+
+impl Response {
+    fn functions(functions: Vec<FunctionComplexity>, cached: bool) -> Self {
+        Response {
+            ok: true,
+            functions,
+            cached,
+            error: None,
+            too_deep: None,
+        }
+    }
+
+    fn error(error: impl ToString) -> Self {
+        Response {
+            ok: false,
+            error: Some(error.to_string()),
+            ..Default::default()
+        }
+    }
+
+    /// The response to a failed analysis, with the error prefixed by
+    /// `context` if any.
+    fn failure(error: anyhow::Error, context: Option<&Path>) -> Self {
+        let message = match context {
+            Some(path) => format!("{}: {}", path.display(), error),
+            None => error.to_string(),
+        };
+        Response {
+            too_deep: error.downcast_ref::<TooDeep>().cloned(),
+            ..Response::error(message)
+        }
+    }
+}
+
+pub fn write_message<T: Serialize>(writer: &mut impl Write, message: &T) -> Result<()> {
+    let bytes = serde_json::to_vec(message)?;
+    writer.write_all(&(bytes.len() as u32).to_be_bytes())?;
+    writer.write_all(&bytes)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// The next message, or `None` when the other side closed the connection.
+pub fn read_message<T: for<'de> Deserialize<'de>>(reader: &mut impl Read) -> Result<Option<T>> {
+    let mut length = [0; 4];
+    match reader.read_exact(&mut length) {
+        Ok(()) => {}
+        Err(error) if error.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(error) => return Err(error.into()),
+    }
+    let length = u32::from_be_bytes(length);
+    anyhow::ensure!(
+        length <= MAX_MESSAGE,
+        "message of {} bytes is too large",
+        length
+    );
+    let mut bytes = vec![0; length as usize];
+    reader.read_exact(&mut bytes)?;
+    Ok(Some(serde_json::from_slice(&bytes)?))
+}
+
+#[cfg(unix)]
+pub use unix::{serve, Client};
+
+#[cfg(unix)]
+mod unix {
+    use super::*;
+    use crate::fingerprint::fingerprint;
+    use crate::output::relative_path;
+    use std::collections::HashMap;
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant, SystemTime};
+
+    /// How often the listener checks for shutdown and idleness.
+    const POLL: Duration = Duration::from_millis(20);
+
+    /// The nesting depth a request allows.
+    fn limit(max_depth: Option<usize>) -> usize {
+        max_depth.unwrap_or(crate::depth::DEFAULT_MAX_DEPTH)
+    }
+
+    struct Cached {
+        modified: Option<SystemTime>,
+        size: u64,
+        weights: Weights,
+        max_depth: Option<usize>,
+        functions: Vec<FunctionComplexity>,
+    }
+
+    struct State {
+        cache: Mutex<HashMap<PathBuf, Cached>>,
+        last_request: Mutex<Instant>,
+        shutdown: AtomicBool,
+    }
+
+    impl State {
+        fn handle(&self, request: Request) -> Response {
+            *self.last_request.lock().unwrap() = Instant::now();
+            match request {
+                Request::AnalyzeSource {
+                    source,
+                    file_name,
+                    weights,
+                    max_depth,
+                } => match calculate_complexity_with(
+                    &source,
+                    file_name.as_deref().unwrap_or(Path::new("source.py")),
+                    &weights,
+                    limit(max_depth),
+                ) {
+                    Ok(functions) => Response::functions(functions, false),
+                    Err(error) => Response::failure(error, None),
+                },
+                Request::AnalyzeFile {
+                    path,
+                    weights,
+                    max_depth,
+                } => self.analyze_file(path, weights, max_depth),
+                Request::Shutdown => {
+                    self.shutdown.store(true, Ordering::SeqCst);
+                    Response::functions(Vec::new(), false)
+                }
+            }
+        }
+
+        fn analyze_file(
+            &self,
+            path: PathBuf,
+            weights: Weights,
+            max_depth: Option<usize>,
+        ) -> Response {
+            let metadata = match std::fs::metadata(&path) {
+                Ok(metadata) => metadata,
+                Err(error) => return Response::error(format!("{}: {}", path.display(), error)),
+            };
+            let (modified, size) = (metadata.modified().ok(), metadata.len());
+            if let Some(cached) = self.cache.lock().unwrap().get(&path) {
+                if cached.modified == modified
+                    && cached.size == size
+                    && cached.weights == weights
+                    && cached.max_depth == max_depth
+                {
+                    return Response::functions(cached.functions.clone(), true);
+                }
+            }
+            // Parsed without holding the lock, so that clients do not wait
+            // on each other.
+            let functions = match std::fs::read_to_string(&path)
+                .map_err(anyhow::Error::from)
+                .and_then(|source| {
+                    calculate_complexity_with(&source, &path, &weights, limit(max_depth))
+                })
+            {
+                Ok(functions) => functions,
+                Err(error) => return Response::failure(error, Some(&path)),
+            };
+            let cached = Cached {
+                modified,
+                size,
+                weights,
+                max_depth,
+                functions: functions.clone(),
+            };
+            self.cache.lock().unwrap().insert(path, cached);
+            Response::functions(functions, false)
+        }
+
+        fn serve_client(&self, mut stream: UnixStream) {
+            while let Ok(Some(request)) = read_message::<Request>(&mut stream) {
+                let response = self.handle(request);
+                if write_message(&mut stream, &response).is_err()
+                    || self.shutdown.load(Ordering::SeqCst)
+                {
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Answers requests on `socket`, each client on its own thread, until a
+    /// `shutdown` request or, with `idle`, until no request came for that
+    /// long. A socket file left by a daemon that is gone is replaced.
+    pub fn serve(socket: &Path, idle: Option<Duration>) -> Result<()> {
+        if socket.exists() {
+            if UnixStream::connect(socket).is_ok() {
+                anyhow::bail!("a daemon is already listening on {}", socket.display());
+            }
+            std::fs::remove_file(socket)?;
+        }
+        let listener = UnixListener::bind(socket)?;
+        listener.set_nonblocking(true)?;
+        let state = Arc::new(State {
+            cache: Mutex::default(),
+            last_request: Mutex::new(Instant::now()),
+            shutdown: AtomicBool::new(false),
+        });
+        let result = loop {
+            if state.shutdown.load(Ordering::SeqCst) {
+                break Ok(());
+            }
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    if let Err(error) = stream.set_nonblocking(false) {
+                        break Err(error.into());
+                    }
+                    let state = Arc::clone(&state);
+                    std::thread::spawn(move || state.serve_client(stream));
+                }
+                Err(error) if error.kind() == std::io::ErrorKind::WouldBlock => {
+                    let idle_for = state.last_request.lock().unwrap().elapsed();
+                    if idle.is_some_and(|idle| idle_for >= idle) {
+                        break Ok(());
+                    }
+                    std::thread::sleep(POLL);
+                }
+                Err(error) => break Err(error.into()),
+            }
+        };
+        let _ = std::fs::remove_file(socket);
+        result
+    }
+
+    /// A connection to a running daemon, shared by the clones of the client.
+    /// Once a request fails the client stops trying, and the caller analyzes
+    /// in-process instead.
+    #[derive(Debug, Clone)]
+    pub struct Client {
+        stream: Arc<Mutex<Option<UnixStream>>>,
+    }
+
+    impl Client {
+        /// Connects to the daemon on `socket`, if one is listening.
+        pub fn connect(socket: &Path) -> Option<Self> {
+            let stream = UnixStream::connect(socket).ok()?;
+            Some(Client {
+                stream: Arc::new(Mutex::new(Some(stream))),
+            })
+        }
+
+        /// The daemon's response, or `None` when it could not be reached.
+        pub fn request(&self, request: &Request) -> Option<Response> {
+            let mut guard = self.stream.lock().unwrap();
+            let stream = guard.as_mut()?;
+            let response = write_message(stream, request)
+                .and_then(|()| read_message::<Response>(stream))
+                .ok()
+                .flatten();
+            if response.is_none() {
+                *guard = None;
+            }
+            response
+        }
+
+        /// The functions of the file at `path` as the daemon scores them,
+        /// fingerprinted relative to `root` and marked `generated` as given,
+        /// or `None` when the daemon could not be reached.
+        pub fn analyze_file(
+            &self,
+            path: &Path,
+            root: &Path,
+            generated: bool,
+            weights: &Weights,
+            max_depth: Option<usize>,
+        ) -> Option<Result<Vec<FunctionComplexity>>> {
+            let response = self.request(&Request::AnalyzeFile {
+                path: path.canonicalize().ok()?,
+                weights: weights.clone(),
+                max_depth,
+            })?;
+            Some(match (response.too_deep, response.error) {
+                (Some(too_deep), _) if !response.ok => Err(too_deep.into()),
+                (_, Some(error)) if !response.ok => Err(anyhow::anyhow!(error)),
+                // The daemon read the canonical path; functions are in the
+                // file as the caller named it, under the caller's root.
+                _ => {
+                    let file = path.to_string_lossy().to_string();
+                    let relative = relative_path(&file, root);
+                    Ok(response
+                        .functions
+                        .into_iter()
+                        .map(|func| FunctionComplexity {
+                            file: file.clone(),
+                            fingerprint: fingerprint(&relative, &func.name),
+                            generated,
+                            ..func
+                        })
+                        .collect())
+                }
+            })
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use tempfile::TempDir;
+
+        fn start(socket: &Path, idle: Option<Duration>) -> std::thread::JoinHandle<Result<()>> {
+            let listening = socket.to_path_buf();
+            let handle = std::thread::spawn(move || serve(&listening, idle));
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while !socket_ready(socket) && Instant::now() < deadline {
+                std::thread::sleep(POLL);
+            }
+            handle
+        }
+
+        fn socket_ready(socket: &Path) -> bool {
+            UnixStream::connect(socket).is_ok()
+        }
+
+        #[test]
+        fn test_requests_and_cache_invalidation() {
+            let temp_dir = TempDir::new().unwrap();
+            let socket = temp_dir.path().join("analyzr.sock");
+            let file = temp_dir.path().join("app.py");
+            std::fs::write(&file, "def f(x):\n    if x:\n        return 1\n").unwrap();
+            let daemon = start(&socket, None);
+            let client = Client::connect(&socket).unwrap();
+
+            let source = Request::AnalyzeSource {
+                source: "def g(a, b):\n    return a and b\n".to_string(),
+                file_name: None,
+                weights: Weights::default(),
+                max_depth: None,
+            };
+            let response = client.request(&source).unwrap();
+            assert_eq!(
+                (
+                    response.functions[0].name.as_str(),
+                    response.functions[0].complexity
+                ),
+                ("g", 2)
+            );
+
+            let analyze = Request::AnalyzeFile {
+                path: file.clone(),
+                weights: Weights::default(),
+                max_depth: None,
+            };
+            let first = client.request(&analyze).unwrap();
+            assert!(first.ok && !first.cached);
+            assert_eq!(first.functions[0].complexity, 2);
+            assert!(client.request(&analyze).unwrap().cached);
+
+            // A change of size invalidates the cached result.
+            std::fs::write(
+                &file,
+                "def f(x):\n    if x:\n        return 1\n    while x:\n        pass\n",
+            )
+            .unwrap();
+            let changed = client.request(&analyze).unwrap();
+            assert!(!changed.cached);
+            assert_eq!(changed.functions[0].complexity, 3);
+
+            // So do other weights.
+            let radon = Request::AnalyzeFile {
+                path: file.clone(),
+                weights: Weights::preset(crate::weights::Preset::Mccabe),
+                max_depth: None,
+            };
+            assert!(!client.request(&radon).unwrap().cached);
+            // And another depth limit, which the file exceeds.
+            let shallow = Request::AnalyzeFile {
+                path: file.clone(),
+                weights: Weights::default(),
+                max_depth: Some(3),
+            };
+            let response = client.request(&shallow).unwrap();
+            assert!(!response.ok && !response.cached);
+            assert_eq!(response.too_deep.map(|too_deep| too_deep.line), Some(2));
+
+            let missing = Request::AnalyzeFile {
+                path: temp_dir.path().join("missing.py"),
+                weights: Weights::default(),
+                max_depth: None,
+            };
+            let response = client.request(&missing).unwrap();
+            assert!(!response.ok && response.error.unwrap().contains("missing.py"));
+
+            assert!(client.request(&Request::Shutdown).unwrap().ok);
+            daemon.join().unwrap().unwrap();
+            assert!(!socket.exists());
+            // The client gives up once the daemon is gone.
+            assert!(client.request(&source).is_none());
+            assert!(Client::connect(&socket).is_none());
+        }
+
+        #[test]
+        fn test_concurrent_clients() {
+            let temp_dir = TempDir::new().unwrap();
+            let socket = temp_dir.path().join("analyzr.sock");
+            let daemon = start(&socket, None);
+
+            let clients: Vec<_> = (0..8)
+                .map(|branches| {
+                    let socket = socket.clone();
+                    std::thread::spawn(move || {
+                        let client = Client::connect(&socket).unwrap();
+                        let mut source = "def f(x):\n".to_string();
+                        for i in 0..branches {
+                            source.push_str(&format!("    if x == {}:\n        return {}\n", i, i));
+                        }
+                        source.push_str("    return x\n");
+                        for _ in 0..20 {
+                            let response = client
+                                .request(&Request::AnalyzeSource {
+                                    source: source.clone(),
+                                    file_name: None,
+                                    weights: Weights::default(),
+                                    max_depth: None,
+                                })
+                                .unwrap();
+                            assert_eq!(response.functions[0].complexity, branches + 1);
+                        }
+                    })
+                })
+                .collect();
+            for client in clients {
+                client.join().unwrap();
+            }
+
+            Client::connect(&socket)
+                .unwrap()
+                .request(&Request::Shutdown)
+                .unwrap();
+            daemon.join().unwrap().unwrap();
+        }
+
+        #[test]
+        fn test_analysis_through_daemon() {
+            let temp_dir = TempDir::new().unwrap();
+            let socket = temp_dir.path().join("analyzr.sock");
+            let root = temp_dir.path().join("src");
+            std::fs::create_dir(&root).unwrap();
+            std::fs::write(root.join("a.py"), "def f(x):\n    return x or 1\n").unwrap();
+            std::fs::write(root.join("b.py"), "def g(:\n").unwrap();
+            let expected = crate::analyze_directory(&root, 10).unwrap();
+
+            let daemon = start(&socket, None);
+            let options = crate::AnalyzeOptions {
+                daemon: Client::connect(&socket),
+                ..Default::default()
+            };
+            let through_daemon = crate::analyze_path(&root, &options).unwrap();
+            assert_eq!(through_daemon.functions, expected.functions);
+            assert_eq!(through_daemon.errors.len(), expected.errors.len());
+
+            // Once the daemon is gone, files are analyzed in-process.
+            options.daemon.as_ref().unwrap().request(&Request::Shutdown);
+            daemon.join().unwrap().unwrap();
+            let in_process = crate::analyze_path(&root, &options).unwrap();
+            assert_eq!(in_process.functions, expected.functions);
+        }
+
+        #[test]
+        fn test_idle_shutdown_and_stale_socket() {
+            let temp_dir = TempDir::new().unwrap();
+            let socket = temp_dir.path().join("analyzr.sock");
+            // A socket file left behind by a daemon that is gone.
+            drop(UnixListener::bind(&socket).unwrap());
+            assert!(socket.exists());
+
+            let started = Instant::now();
+            let daemon = start(&socket, Some(Duration::from_millis(200)));
+            assert!(serve(&socket, None)
+                .unwrap_err()
+                .to_string()
+                .contains("already listening"));
+            daemon.join().unwrap().unwrap();
+            assert!(started.elapsed() >= Duration::from_millis(200));
+            assert!(!socket.exists());
+        }
+    }
+}
+
+/// Stand-in on platforms without Unix sockets: there is never a daemon to
+/// reach, so `--use-daemon` always analyzes in-process.
+#[cfg(not(unix))]
+mod fallback {
+    use super::*;
+
+    pub fn serve(_socket: &Path, _idle: Option<std::time::Duration>) -> Result<()> {
+        anyhow::bail!("the daemon needs Unix domain sockets, which this platform lacks")
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct Client;
+
+    impl Client {
+        pub fn connect(_socket: &Path) -> Option<Self> {
+            None
+        }
+
+        pub fn analyze_file(
+            &self,
+            _path: &Path,
+            _root: &Path,
+            _generated: bool,
+            _weights: &Weights,
+            _max_depth: Option<usize>,
+        ) -> Option<Result<Vec<FunctionComplexity>>> {
+            None
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub use fallback::{serve, Client};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_framing() {
+        let mut buffer = Vec::new();
+        write_message(&mut buffer, &Request::Shutdown).unwrap();
+        assert_eq!(&buffer[..4], &22u32.to_be_bytes());
+        assert_eq!(&buffer[4..], br#"{"request":"shutdown"}"#.as_slice());
+
+        let mut reader = buffer.as_slice();
+        let request: Request = read_message(&mut reader).unwrap().unwrap();
+        assert!(matches!(request, Request::Shutdown));
+        assert!(read_message::<Request>(&mut reader).unwrap().is_none());
+
+        let oversized = (MAX_MESSAGE + 1).to_be_bytes();
+        assert!(read_message::<Request>(&mut oversized.as_slice()).is_err());
+
+        let request: Request =
+            serde_json::from_str(r#"{"request":"analyze_source","source":"x = 1"}"#).unwrap();
+        assert!(
+            matches!(request, Request::AnalyzeSource { weights, .. } if weights == Weights::default())
+        );
+    }
+}
@@ -0,0 +1,137 @@
+//! Limit on the nesting depth of syntax trees. A generated file with
+//! thousands of nested parentheses or a long chain of boolean operators is
+//! rejected before any walk, by a check that itself uses no recursion. The
+//! walks that score a function keep their own stacks, so that raising the
+//! limit never overflows the thread's; the limit keeps such files, which no
+//! one reads, out of reports, and out of the walks of other commands that do
+//! recurse.
+
+use serde::{Deserialize, Serialize};
+use tree_sitter::Node;
+
+/// Nesting depth allowed by default, far beyond hand-written code and well
+/// within the stack of a thread, while CPython itself stops at 200 nested
+/// parentheses.
+pub const DEFAULT_MAX_DEPTH: usize = 1000;
+
+/// A tree nested deeper than allowed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TooDeep {
+    /// Line of the first node beyond the limit.
+    pub line: u32,
+    pub limit: usize,
+}
+
+impl std::fmt::Display for TooDeep {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "syntax nested more than {} levels deep at line {} (raise --max-depth to analyze it)",
+            self.limit, self.line
+        )
+    }
+}
+
+impl std::error::Error for TooDeep {}
+
+/// Fails when a node under `root` is more than `limit` levels below it.
+pub fn check(root: Node, limit: usize) -> Result<(), TooDeep> {
+    let mut cursor = root.walk();
+    let mut depth = 0;
+    loop {
+        if depth > limit {
+            return Err(TooDeep {
+                line: cursor.node().start_position().row as u32 + 1,
+                limit,
+            });
+        }
+        if cursor.goto_first_child() {
+            depth += 1;
+            continue;
+        }
+        while !cursor.goto_next_sibling() {
+            if depth == 0 || !cursor.goto_parent() {
+                return Ok(());
+            }
+            depth -= 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tree_sitter::Parser as TSParser;
+
+    fn depth_check(source: &str, limit: usize) -> Result<(), TooDeep> {
+        let mut parser = TSParser::new();
+        parser.set_language(tree_sitter_python::language()).unwrap();
+        let tree = parser.parse(source, None).unwrap();
+        check(tree.root_node(), limit)
+    }
+
+    #[test]
+    fn test_check() {
+        // module > expression_statement > parenthesized_expression >
+        // parenthesized_expression > identifier
+        let source = "x = 1\n((y))\n";
+        assert_eq!(depth_check(source, 4), Ok(()));
+        assert_eq!(depth_check(source, 3), Err(TooDeep { line: 2, limit: 3 }));
+        assert_eq!(depth_check("", 0), Ok(()));
+    }
+
+    #[test]
+    fn test_deep_nesting() {
+        // Deep enough to overflow the stack of a test thread in a recursive
+        // walk.
+        let depth = 20_000;
+        let parentheses = format!(
+            "def f(x):\n    return {}x{}\n",
+            "(".repeat(depth),
+            ")".repeat(depth)
+        );
+        let operators = |depth| {
+            format!(
+                "def g(x):\n    if x{}:\n        return 1\n",
+                " and x".repeat(depth)
+            )
+        };
+        for source in [parentheses, operators(depth)] {
+            let error = crate::calculate_complexity(&source, &Default::default()).unwrap_err();
+            assert_eq!(
+                error.downcast_ref::<TooDeep>(),
+                Some(&TooDeep {
+                    line: 2,
+                    limit: DEFAULT_MAX_DEPTH
+                })
+            );
+        }
+        // Below the limit, the file is scored as usual.
+        let functions = crate::calculate_complexity(&operators(300), &Default::default()).unwrap();
+        assert_eq!(functions[0].complexity, 302);
+        // Without a limit, the walks that score functions still fit in the
+        // stack.
+        let javascript = format!(
+            "function h(x) {{\n  if (x{}) {{\n    return 1;\n  }}\n}}\n",
+            " && x".repeat(depth)
+        );
+        for (source, file) in [(operators(depth), "deep.py"), (javascript, "deep.js")] {
+            let functions = crate::calculate_complexity_with(
+                &source,
+                std::path::Path::new(file),
+                &Default::default(),
+                usize::MAX,
+            )
+            .unwrap();
+            assert_eq!(functions[0].complexity, depth as u32 + 2);
+        }
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(temp_dir.path().join("deep.py"), operators(depth)).unwrap();
+        std::fs::write(temp_dir.path().join("ok.py"), "def h():\n    pass\n").unwrap();
+        let result = crate::analyze_directory(temp_dir.path(), 10).unwrap();
+        assert_eq!(result.functions.len(), 1);
+        assert_eq!(result.errors.len(), 1);
+        assert_eq!(result.errors[0].category, crate::IssueCategory::TooDeep);
+    }
+}
@@ -0,0 +1,507 @@
+//! Functions that regressed, improved, appeared or disappeared between two
+//! analysis results, for review bots as much as for people.
+
+use crate::output::text::{truncate_end, truncate_start};
+use crate::{AnalysisResult, FunctionComplexity};
+use crate::output::table::{NAME_WIDTH, PATH_WIDTH};
+use comfy_table::{Cell, Table};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+/// Version of the diff JSON, independent of the result schema. Bumped when a
+/// field is renamed or removed.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// A function as it is in one of the two results.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Side {
+    pub name: String,
+    pub file: String,
+    pub line: u32,
+    pub complexity: u32,
+    pub fingerprint: String,
+}
+
+impl Side {
+    fn of(func: &FunctionComplexity) -> Self {
+        Side {
+            name: func.name.clone(),
+            file: func.file.clone(),
+            line: func.line,
+            complexity: func.complexity,
+            fingerprint: func.fingerprint.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Change {
+    pub before: Option<Side>,
+    pub after: Option<Side>,
+}
+
+impl Change {
+    /// The function as it is now, or as it was when it was removed.
+    fn current(&self) -> &Side {
+        self.after.as_ref().or(self.before.as_ref()).unwrap()
+    }
+
+    /// The name and file the function had, when it was renamed or moved.
+    fn previous(&self) -> Option<&Side> {
+        let before = self.before.as_ref()?;
+        let after = self.after.as_ref()?;
+        (before.fingerprint != after.fingerprint).then_some(before)
+    }
+
+    fn delta(&self) -> i64 {
+        let complexity = |side: &Option<Side>| side.as_ref().map_or(0, |s| s.complexity as i64);
+        complexity(&self.after) - complexity(&self.before)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Diff {
+    pub schema_version: u32,
+    /// Functions whose complexity went up, the biggest increase first.
+    pub regressed: Vec<Change>,
+    /// Functions whose complexity went down, the biggest decrease first.
+    pub improved: Vec<Change>,
+    pub added: Vec<Change>,
+    pub removed: Vec<Change>,
+    /// Functions renamed or moved to another file without any change to
+    /// their code.
+    pub moved: Vec<Change>,
+    /// Functions moved to another file under the same name and edited on the
+    /// way, the biggest increase first.
+    pub moved_changed: Vec<Change>,
+    /// Number of functions found in both results with the same score.
+    pub unchanged: usize,
+}
+
+/// Matches the functions of both results on their fingerprint, then pairs
+/// the remaining ones that have the same body hash, so that a function that
+/// is only renamed or moved is not reported as removed and added. Of the
+/// rest, a function removed from one file and added to another under the
+/// same name, which no other remaining function has, was moved and changed.
+pub fn diff(before: &AnalysisResult, after: &AnalysisResult) -> Diff {
+    let mut by_fingerprint: HashMap<&str, &FunctionComplexity> = HashMap::new();
+    for func in &before.functions {
+        by_fingerprint.entry(&func.fingerprint).or_insert(func);
+    }
+    let mut matched = HashSet::new();
+    let mut pairs = Vec::new();
+    let mut added = Vec::new();
+    for func in &after.functions {
+        match by_fingerprint.get(func.fingerprint.as_str()) {
+            Some(&old) if matched.insert(&old.fingerprint) => pairs.push((old, func)),
+            _ => added.push(func),
+        }
+    }
+    let mut removed: Vec<&FunctionComplexity> = before
+        .functions
+        .iter()
+        .filter(|func| !matched.contains(&func.fingerprint))
+        .collect();
+
+    let mut diff = Diff {
+        schema_version: SCHEMA_VERSION,
+        regressed: Vec::new(),
+        improved: Vec::new(),
+        added: Vec::new(),
+        removed: Vec::new(),
+        moved: Vec::new(),
+        moved_changed: Vec::new(),
+        unchanged: 0,
+    };
+    let mut relocated = Vec::new();
+    added.retain(|func| {
+        let position = removed
+            .iter()
+            .position(|old| !func.body_hash.is_empty() && old.body_hash == func.body_hash);
+        match position {
+            Some(index) => {
+                relocated.push((removed.remove(index), *func));
+                false
+            }
+            None => true,
+        }
+    });
+
+    let unique = |functions: &[&FunctionComplexity], name: &str| {
+        functions.iter().filter(|func| func.name == name).count() == 1
+    };
+    let mut edited = Vec::new();
+    let candidates = added.clone();
+    added.retain(|func| {
+        let position = removed.iter().position(|old| {
+            old.name == func.name
+                && old.file != func.file
+                && unique(&removed, &func.name)
+                && unique(&candidates, &func.name)
+        });
+        match position {
+            Some(index) => {
+                edited.push((removed.remove(index), *func));
+                false
+            }
+            None => true,
+        }
+    });
+    diff.moved_changed = edited
+        .into_iter()
+        .map(|(old, new)| Change {
+            before: Some(Side::of(old)),
+            after: Some(Side::of(new)),
+        })
+        .collect();
+
+    for (old, new) in pairs.into_iter().chain(relocated.iter().copied()) {
+        let change = Change {
+            before: Some(Side::of(old)),
+            after: Some(Side::of(new)),
+        };
+        match new.complexity.cmp(&old.complexity) {
+            std::cmp::Ordering::Greater => diff.regressed.push(change),
+            std::cmp::Ordering::Less => diff.improved.push(change),
+            std::cmp::Ordering::Equal if old.fingerprint != new.fingerprint => {
+                diff.moved.push(change)
+            }
+            std::cmp::Ordering::Equal => diff.unchanged += 1,
+        }
+    }
+    diff.added = added
+        .into_iter()
+        .map(|func| Change {
+            before: None,
+            after: Some(Side::of(func)),
+        })
+        .collect();
+    diff.removed = removed
+        .into_iter()
+        .map(|func| Change {
+            before: Some(Side::of(func)),
+            after: None,
+        })
+        .collect();
+
+    diff.regressed
+        .sort_by_key(|change| std::cmp::Reverse(change.delta()));
+    diff.improved.sort_by_key(|change| change.delta());
+    diff.added
+        .sort_by_key(|change| std::cmp::Reverse(change.delta()));
+    diff.moved_changed
+        .sort_by_key(|change| std::cmp::Reverse(change.delta()));
+    diff
+}
+
+fn summary(diff: &Diff) -> String {
+    format!(
+        "{} regressed, {} improved, {} added, {} removed, {} moved, {} moved and changed, {} unchanged",
+        diff.regressed.len(),
+        diff.improved.len(),
+        diff.added.len(),
+        diff.removed.len(),
+        diff.moved.len(),
+        diff.moved_changed.len(),
+        diff.unchanged
+    )
+}
+
+fn sections(diff: &Diff) -> [(&'static str, &[Change]); 6] {
+    [
+        ("Regressed", &diff.regressed),
+        ("Added", &diff.added),
+        ("Improved", &diff.improved),
+        ("Removed", &diff.removed),
+        ("Moved", &diff.moved),
+        ("Moved and changed", &diff.moved_changed),
+    ]
+}
+
+fn score(side: &Option<Side>) -> String {
+    side.as_ref()
+        .map_or_else(String::new, |s| s.complexity.to_string())
+}
+
+fn signed(delta: i64) -> String {
+    if delta > 0 {
+        format!("+{}", delta)
+    } else {
+        delta.to_string()
+    }
+}
+
+pub fn print_diff(diff: &Diff) {
+    println!("{}", summary(diff));
+    for (title, changes) in sections(diff) {
+        if changes.is_empty() {
+            continue;
+        }
+        let mut table = Table::new();
+        table.set_header(vec![
+            "Function", "File", "Line", "Before", "After", "Change",
+        ]);
+        for change in changes {
+            let current = change.current();
+            table.add_row(vec![
+                Cell::new(truncate_end(&current.name, NAME_WIDTH)),
+                Cell::new(truncate_start(&current.file, PATH_WIDTH)),
+                Cell::new(current.line.to_string()),
+                Cell::new(score(&change.before)),
+                Cell::new(score(&change.after)),
+                Cell::new(match change.previous() {
+                    Some(before) if change.delta() != 0 => format!(
+                        "{}, was {} in {}",
+                        signed(change.delta()),
+                        before.name,
+                        before.file
+                    ),
+                    Some(before) => format!("was {} in {}", before.name, before.file),
+                    None => signed(change.delta()),
+                }),
+            ]);
+        }
+        println!("\n{}:\n{}", title, table);
+    }
+}
+
+pub fn render_markdown(diff: &Diff) -> String {
+    let mut out = String::from("## Complexity diff\n\n");
+    out.push_str(&summary(diff));
+    out.push_str(".\n");
+    for (title, changes) in sections(diff) {
+        if changes.is_empty() {
+            continue;
+        }
+        out.push_str(&format!("\n### {}\n\n", title));
+        out.push_str("| Function | File | Before | After | Change |\n");
+        out.push_str("|---|---|---:|---:|---:|\n");
+        for change in changes {
+            let current = change.current();
+            let moved_from = change.previous().map_or_else(String::new, |before| {
+                format!(" (was `{}` in `{}`)", before.name, before.file)
+            });
+            out.push_str(&format!(
+                "| `{}`{} | `{}:{}` | {} | {} | {} |\n",
+                current.name.replace('|', "\\|"),
+                moved_from,
+                current.file,
+                current.line,
+                score(&change.before),
+                score(&change.after),
+                signed(change.delta())
+            ));
+        }
+    }
+    out
+}
+
+/// GitHub Actions workflow commands annotating the regressed and added
+/// functions, and the moved ones that got more complex, on their new line.
+///
+/// See <https://docs.github.com/en/actions/using-workflows/workflow-commands-for-github-actions#setting-a-warning-message>.
+pub fn render_github(diff: &Diff) -> String {
+    let mut out = String::new();
+    let moved_up = diff.moved_changed.iter().filter(|change| change.delta() > 0);
+    for change in diff.regressed.iter().chain(&diff.added).chain(moved_up) {
+        let Some(after) = &change.after else {
+            continue;
+        };
+        let message = match &change.before {
+            Some(before) if before.file != after.file => format!(
+                "Complexity of '{}' went from {} to {} as it moved from {}",
+                after.name, before.complexity, after.complexity, before.file
+            ),
+            Some(before) => format!(
+                "Complexity of '{}' went from {} to {}",
+                after.name, before.complexity, after.complexity
+            ),
+            None => format!(
+                "New function '{}' has a complexity of {}",
+                after.name, after.complexity
+            ),
+        };
+        out.push_str(&format!(
+            "::warning file={},line={},title={}::{}\n",
+            escape_property(&after.file),
+            after.line,
+            escape_property("Complexity diff"),
+            escape_data(&message)
+        ));
+    }
+    out
+}
+
+fn escape_data(value: &str) -> String {
+    value
+        .replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+fn escape_property(value: &str) -> String {
+    escape_data(value).replace(':', "%3A").replace(',', "%2C")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fingerprint::fingerprint;
+
+    fn function(
+        name: &str,
+        file: &str,
+        line: u32,
+        complexity: u32,
+        body: &str,
+    ) -> FunctionComplexity {
+        FunctionComplexity {
+            name: name.to_string(),
+            file: file.to_string(),
+            line,
+            complexity,
+            fingerprint: fingerprint(file, name),
+            body_hash: body.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn result(functions: Vec<FunctionComplexity>) -> AnalysisResult {
+        AnalysisResult {
+            functions,
+            ..Default::default()
+        }
+    }
+
+    fn golden(before: Vec<FunctionComplexity>, after: Vec<FunctionComplexity>, expected: &str) {
+        let diff = diff(&result(before), &result(after));
+        assert_eq!(
+            serde_json::to_string_pretty(&diff).unwrap(),
+            expected.trim_end()
+        );
+    }
+
+    #[test]
+    fn test_rename_matches_golden_file() {
+        golden(
+            vec![
+                function("load", "app/io.py", 3, 4, "h1"),
+                function("parse", "app/io.py", 12, 7, "h2"),
+            ],
+            vec![
+                function("load", "app/io.py", 3, 4, "h1"),
+                function("parse_config", "app/io.py", 12, 7, "h2"),
+            ],
+            include_str!("../testdata/diff/rename.json"),
+        );
+    }
+
+    #[test]
+    fn test_move_matches_golden_file() {
+        golden(
+            vec![
+                function("parse", "app/io.py", 12, 7, "h2"),
+                function("helper", "app/io.py", 30, 2, "h3"),
+            ],
+            vec![
+                function("parse", "app/parsing.py", 1, 7, "h2"),
+                function("helper", "app/io.py", 14, 2, "h3"),
+            ],
+            include_str!("../testdata/diff/move.json"),
+        );
+    }
+
+    #[test]
+    fn test_move_with_edit_matches_golden_file() {
+        golden(
+            vec![
+                function("parse", "app/io.py", 12, 7, "h2"),
+                function("__init__", "app/io.py", 40, 1, "h3"),
+                function("close", "app/io.py", 50, 2, "h5"),
+            ],
+            vec![
+                function("parse", "app/parsing.py", 1, 9, "h4"),
+                // Ambiguous names are not paired.
+                function("__init__", "app/parsing.py", 20, 2, "h6"),
+                function("__init__", "app/loading.py", 5, 1, "h7"),
+                function("close", "app/io.py", 50, 3, "h8"),
+            ],
+            include_str!("../testdata/diff/move_with_edit.json"),
+        );
+    }
+
+    #[test]
+    fn test_regression_matches_golden_file() {
+        golden(
+            vec![
+                function("handle", "app/api.py", 10, 8, "h1"),
+                function("render", "app/api.py", 40, 12, "h2"),
+                function("legacy", "app/old.py", 1, 5, "h3"),
+            ],
+            vec![
+                function("handle", "app/api.py", 10, 13, "h4"),
+                function("render", "app/api.py", 45, 9, "h5"),
+                function("validate", "app/api.py", 70, 6, "h6"),
+            ],
+            include_str!("../testdata/diff/regression.json"),
+        );
+    }
+
+    #[test]
+    fn test_github_annotations() {
+        let diff = diff(
+            &result(vec![
+                function("handle", "app/api.py", 10, 8, "h1"),
+                function("render", "app/api.py", 40, 12, "h2"),
+            ]),
+            &result(vec![
+                function("handle", "app/api.py", 11, 13, "h4"),
+                function("render", "app/api.py", 45, 9, "h5"),
+                function("validate", "app/a,b.py", 70, 6, "h6"),
+            ]),
+        );
+        assert_eq!(
+            render_github(&diff),
+            "::warning file=app/api.py,line=11,title=Complexity diff::Complexity of 'handle' went from 8 to 13\n\
+             ::warning file=app/a%2Cb.py,line=70,title=Complexity diff::New function 'validate' has a complexity of 6\n"
+        );
+    }
+
+    #[test]
+    fn test_github_annotates_moved_regressions() {
+        let diff = diff(
+            &result(vec![
+                function("parse", "app/io.py", 12, 7, "h1"),
+                function("load", "app/io.py", 30, 5, "h2"),
+            ]),
+            &result(vec![
+                function("parse", "app/parsing.py", 1, 9, "h3"),
+                function("load", "app/loading.py", 1, 4, "h4"),
+            ]),
+        );
+        assert_eq!(diff.moved_changed.len(), 2);
+        assert_eq!(
+            render_github(&diff),
+            "::warning file=app/parsing.py,line=1,title=Complexity diff::Complexity of 'parse' went from 7 to 9 as it moved from app/io.py\n"
+        );
+    }
+
+    #[test]
+    fn test_markdown() {
+        let diff = diff(
+            &result(vec![function("parse", "app/io.py", 12, 7, "h2")]),
+            &result(vec![
+                function("parse_config", "app/io.py", 12, 7, "h2"),
+                function("new", "app/io.py", 30, 3, "h3"),
+            ]),
+        );
+        assert_eq!(
+            render_markdown(&diff),
+            "## Complexity diff\n\n0 regressed, 0 improved, 1 added, 0 removed, 1 moved, 0 moved and changed, 0 unchanged.\n\
+             \n### Added\n\n| Function | File | Before | After | Change |\n|---|---|---:|---:|---:|\n\
+             | `new` | `app/io.py:30` |  | 3 | +3 |\n\
+             \n### Moved\n\n| Function | File | Before | After | Change |\n|---|---|---:|---:|---:|\n\
+             | `parse_config` (was `parse` in `app/io.py`) | `app/io.py:12` | 7 | 7 | 0 |\n"
+        );
+    }
+}
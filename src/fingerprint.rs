@@ -0,0 +1,32 @@
+/// Stable identifier for a function, derived from its repo-relative path and
+/// name so that it survives line drift between runs.
+///
+/// Uses 64-bit FNV-1a rather than `DefaultHasher`, whose output is not
+/// guaranteed to be stable across Rust releases.
+pub fn fingerprint(relative_path: &str, name: &str) -> String {
+    let mut hash = FNV_OFFSET_BASIS;
+    for byte in relative_path
+        .bytes()
+        .chain(std::iter::once(0))
+        .chain(name.bytes())
+    {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    format!("{:016x}", hash)
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fingerprint_is_stable() {
+        assert_eq!(fingerprint("", ""), "af63bd4c8601b7df");
+        assert_eq!(fingerprint("a.py", "f"), fingerprint("a.py", "f"));
+        assert_ne!(fingerprint("a.py", "f"), fingerprint("a.pyf", ""));
+    }
+}
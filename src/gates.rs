@@ -0,0 +1,436 @@
+//! Gates on the summary statistics of a run, set with `[summary_gates]` in
+//! `analyzr.toml`, so that CI fails when the distribution of complexity
+//! drifts even though no single function crosses the threshold.
+//!
+//! A gate is either a ceiling on a statistic (`p95 = 9`) or, with
+//! `--compare-to-last`, on how much the statistic may increase since the
+//! last run recorded in the `--trend` file (`p95_increase = 0.5`).
+
+use crate::meta::{git_info, now};
+use crate::Summary;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::Write;
+use std::path::Path;
+
+/// Suffix of the keys that gate the increase of a statistic.
+const INCREASE: &str = "_increase";
+
+/// A summary statistic a gate can apply to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Statistic {
+    Mean,
+    P50,
+    P90,
+    P95,
+    Max,
+    Gini,
+    TopDecileShare,
+    /// Share of the functions above the threshold.
+    AboveThresholdRatio,
+    /// Number of functions above the threshold.
+    AboveThreshold,
+}
+
+impl Statistic {
+    pub const ALL: [Statistic; 9] = [
+        Statistic::Mean,
+        Statistic::P50,
+        Statistic::P90,
+        Statistic::P95,
+        Statistic::Max,
+        Statistic::Gini,
+        Statistic::TopDecileShare,
+        Statistic::AboveThresholdRatio,
+        Statistic::AboveThreshold,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Statistic::Mean => "mean",
+            Statistic::P50 => "p50",
+            Statistic::P90 => "p90",
+            Statistic::P95 => "p95",
+            Statistic::Max => "max",
+            Statistic::Gini => "gini",
+            Statistic::TopDecileShare => "top_decile_share",
+            Statistic::AboveThresholdRatio => "above_threshold_ratio",
+            Statistic::AboveThreshold => "above_threshold",
+        }
+    }
+
+    fn value(self, summary: &Summary) -> f64 {
+        match self {
+            Statistic::Mean => summary.mean_complexity,
+            Statistic::P50 => summary.p50_complexity as f64,
+            Statistic::P90 => summary.p90_complexity as f64,
+            Statistic::P95 => summary.p95_complexity as f64,
+            Statistic::Max => summary.max_complexity as f64,
+            Statistic::Gini => summary.gini_coefficient,
+            Statistic::TopDecileShare => summary.top_decile_share,
+            Statistic::AboveThresholdRatio => {
+                summary.functions_above_threshold as f64 / summary.total_functions.max(1) as f64
+            }
+            Statistic::AboveThreshold => summary.functions_above_threshold as f64,
+        }
+    }
+}
+
+/// What a gate limits: the statistic itself, or its increase since the
+/// last recorded run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    Absolute,
+    Delta,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Gate {
+    pub statistic: Statistic,
+    pub mode: Mode,
+    pub limit: f64,
+}
+
+impl Gate {
+    /// The gate's key in `[summary_gates]`.
+    pub fn name(&self) -> String {
+        match self.mode {
+            Mode::Absolute => self.statistic.name().to_string(),
+            Mode::Delta => format!("{}{}", self.statistic.name(), INCREASE),
+        }
+    }
+}
+
+/// The gate set by `key = limit` in `[summary_gates]`.
+pub fn parse_gate(key: &str, limit: f64) -> Result<Gate, String> {
+    let (name, mode) = match key.strip_suffix(INCREASE) {
+        Some(name) => (name, Mode::Delta),
+        None => (key, Mode::Absolute),
+    };
+    let statistic = Statistic::ALL
+        .into_iter()
+        .find(|statistic| statistic.name() == name)
+        .ok_or_else(|| {
+            let names: Vec<&str> = Statistic::ALL.iter().map(|s| s.name()).collect();
+            format!(
+                "unknown summary gate `{}` (expected one of {}, optionally suffixed with `{}`)",
+                key,
+                names.join(", "),
+                INCREASE
+            )
+        })?;
+    if !limit.is_finite() {
+        return Err(format!("summary gate `{}` must be a finite number", key));
+    }
+    if limit < 0.0 && mode == Mode::Absolute {
+        return Err(format!("summary gate `{}` cannot be negative", key));
+    }
+    Ok(Gate {
+        statistic,
+        mode,
+        limit,
+    })
+}
+
+/// One gate of a run: its target and the actual value, the statistic or its
+/// increase.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GateResult {
+    pub gate: String,
+    pub mode: Mode,
+    pub target: f64,
+    pub actual: f64,
+    /// The statistic in the last recorded run, for delta gates.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous: Option<f64>,
+    pub passed: bool,
+}
+
+impl fmt::Display for GateResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let verdict = if self.passed { "passed" } else { "FAILED" };
+        match self.previous {
+            Some(previous) => write!(
+                f,
+                "{}: {} increased by {} ({} to {}), allowed {}",
+                verdict,
+                self.gate.trim_end_matches(INCREASE),
+                round(self.actual),
+                round(previous),
+                round(previous + self.actual),
+                round(self.target)
+            ),
+            None => write!(
+                f,
+                "{}: {} is {}, gate {}",
+                verdict,
+                self.gate,
+                round(self.actual),
+                round(self.target)
+            ),
+        }
+    }
+}
+
+impl GateResult {
+    /// Why the gate failed, naming it and by how much.
+    pub fn failure(&self) -> String {
+        let over = round(self.actual - self.target);
+        match self.previous {
+            Some(previous) => format!(
+                "summary gate `{}` failed: {} increased by {} since the last recorded run ({} to {}), {} more than the {} allowed",
+                self.gate,
+                self.gate.trim_end_matches(INCREASE),
+                round(self.actual),
+                round(previous),
+                round(previous + self.actual),
+                over,
+                round(self.target)
+            ),
+            None => format!(
+                "summary gate `{}` failed: {} is {}, {} above the gate of {}",
+                self.gate,
+                self.gate,
+                round(self.actual),
+                over,
+                round(self.target)
+            ),
+        }
+    }
+}
+
+/// `value` to four decimals, without trailing zeros.
+fn round(value: f64) -> f64 {
+    (value * 10_000.0).round() / 10_000.0
+}
+
+/// The statistics of `summary`, by name, as recorded in the trend file.
+pub fn statistics(summary: &Summary) -> BTreeMap<String, f64> {
+    Statistic::ALL
+        .into_iter()
+        .map(|statistic| (statistic.name().to_string(), statistic.value(summary)))
+        .collect()
+}
+
+/// Evaluates `gates` against `summary`: the absolute ones always, the delta
+/// ones against `previous` when there is a recorded run to compare to.
+pub fn evaluate(gates: &[Gate], summary: &Summary, previous: Option<&Entry>) -> Vec<GateResult> {
+    gates
+        .iter()
+        .filter_map(|gate| {
+            let value = gate.statistic.value(summary);
+            let (actual, previous) = match gate.mode {
+                Mode::Absolute => (value, None),
+                Mode::Delta => {
+                    let before = *previous?.statistics.get(gate.statistic.name())?;
+                    (value - before, Some(before))
+                }
+            };
+            Some(GateResult {
+                gate: gate.name(),
+                mode: gate.mode,
+                target: gate.limit,
+                actual,
+                previous,
+                // Compared after rounding so that float noise in the mean
+                // never fails a gate it meets.
+                passed: round(actual) <= gate.limit,
+            })
+        })
+        .collect()
+}
+
+/// One recorded run of the trend file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    pub timestamp: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    pub statistics: BTreeMap<String, f64>,
+}
+
+impl Entry {
+    /// An entry for `summary`, at the commit `root` is checked out at.
+    pub fn new(summary: &Summary, root: &Path) -> Self {
+        Entry {
+            timestamp: now(),
+            commit: git_info(root).map(|info| info.sha),
+            statistics: statistics(summary),
+        }
+    }
+}
+
+/// The last run recorded in the trend file at `path`, if it exists and has
+/// any.
+pub fn last(path: &Path) -> Result<Option<Entry>> {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(error) => {
+            return Err(error).with_context(|| format!("Failed to read {}", path.display()))
+        }
+    };
+    let Some((index, line)) = text
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .last()
+    else {
+        return Ok(None);
+    };
+    let entry = serde_json::from_str(line)
+        .with_context(|| format!("Invalid trend entry at {}:{}", path.display(), index + 1))?;
+    Ok(Some(entry))
+}
+
+/// Appends `entry` to the trend file at `path`, creating it if needed.
+pub fn record(path: &Path, entry: &Entry) -> Result<()> {
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    writeln!(file, "{}", serde_json::to_string(entry)?)
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FunctionComplexity;
+
+    fn summary(complexities: &[u32]) -> Summary {
+        let functions: Vec<FunctionComplexity> = complexities
+            .iter()
+            .map(|&complexity| FunctionComplexity {
+                complexity,
+                ..Default::default()
+            })
+            .collect();
+        crate::summarize(&functions, 10, crate::severity::Metric::Cyclomatic).unwrap()
+    }
+
+    fn gates(settings: &[(&str, f64)]) -> Vec<Gate> {
+        settings
+            .iter()
+            .map(|&(key, limit)| parse_gate(key, limit).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_absolute_gates() {
+        // mean 5.4, p95 12, one function of five above the threshold.
+        let summary = summary(&[1, 2, 4, 8, 12]);
+        let gates = gates(&[
+            ("mean", 4.5),
+            ("p95", 12.0),
+            ("above_threshold_ratio", 0.03),
+            ("above_threshold", 1.0),
+        ]);
+        let results = evaluate(&gates, &summary, None);
+        let verdicts: Vec<(&str, f64, bool)> = results
+            .iter()
+            .map(|r| (r.gate.as_str(), round(r.actual), r.passed))
+            .collect();
+        assert_eq!(
+            verdicts,
+            [
+                ("mean", 5.4, false),
+                ("p95", 12.0, true),
+                ("above_threshold_ratio", 0.2, false),
+                ("above_threshold", 1.0, true),
+            ]
+        );
+        assert_eq!(
+            results[0].failure(),
+            "summary gate `mean` failed: mean is 5.4, 0.9 above the gate of 4.5"
+        );
+        assert_eq!(results[1].to_string(), "passed: p95 is 12, gate 12");
+
+        let json = serde_json::to_value(&results[2]).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "gate": "above_threshold_ratio",
+                "mode": "absolute",
+                "target": 0.03,
+                "actual": 0.2,
+                "passed": false,
+            })
+        );
+    }
+
+    #[test]
+    fn test_delta_gates() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let trend = temp_dir.path().join("trend.jsonl");
+        let gates = gates(&[("p95_increase", 0.5), ("mean_increase", 1.0), ("max", 20.0)]);
+
+        // Nothing recorded yet: only the absolute gate applies.
+        assert_eq!(last(&trend).unwrap(), None);
+        let before = summary(&[1, 2, 4, 8]);
+        assert_eq!(evaluate(&gates, &before, None).len(), 1);
+        record(&trend, &Entry::new(&before, temp_dir.path())).unwrap();
+        record(&trend, &Entry::new(&summary(&[1, 2, 4, 9]), temp_dir.path())).unwrap();
+
+        let previous = last(&trend).unwrap().unwrap();
+        assert_eq!(previous.statistics["p95"], 9.0);
+        let after = summary(&[1, 2, 4, 10]);
+        let results = evaluate(&gates, &after, Some(&previous));
+        let verdicts: Vec<(&str, f64, Option<f64>, bool)> = results
+            .iter()
+            .map(|r| (r.gate.as_str(), round(r.actual), r.previous, r.passed))
+            .collect();
+        assert_eq!(
+            verdicts,
+            [
+                ("p95_increase", 1.0, Some(9.0), false),
+                ("mean_increase", 0.25, Some(4.0), true),
+                ("max", 10.0, None, true),
+            ]
+        );
+        assert_eq!(
+            results[0].failure(),
+            "summary gate `p95_increase` failed: p95 increased by 1 since the last recorded run (9 to 10), 0.5 more than the 0.5 allowed"
+        );
+        assert_eq!(
+            results[1].to_string(),
+            "passed: mean increased by 0.25 (4 to 4.25), allowed 1"
+        );
+        // A decrease always passes.
+        let results = evaluate(&gates, &before, Some(&previous));
+        assert!(results.iter().all(|r| r.passed));
+    }
+
+    #[test]
+    fn test_parse_gate() {
+        assert_eq!(
+            parse_gate("p95_increase", 0.5),
+            Ok(Gate {
+                statistic: Statistic::P95,
+                mode: Mode::Delta,
+                limit: 0.5
+            })
+        );
+        assert!(parse_gate("p99", 3.0).unwrap_err().starts_with("unknown summary gate `p99`"));
+        assert_eq!(
+            parse_gate("mean", -1.0).unwrap_err(),
+            "summary gate `mean` cannot be negative"
+        );
+        // NaN would fail every run, and infinity pass every one.
+        for (key, limit) in [
+            ("mean", f64::NAN),
+            ("p95", f64::INFINITY),
+            ("mean_increase", f64::NAN),
+            ("p95_increase", f64::NEG_INFINITY),
+        ] {
+            assert_eq!(
+                parse_gate(key, limit).unwrap_err(),
+                format!("summary gate `{}` must be a finite number", key)
+            );
+        }
+    }
+}
@@ -0,0 +1,177 @@
+//! Heuristics recognizing generated code, such as protobuf modules and ORM
+//! migrations.
+
+use std::path::Path;
+
+/// File name patterns of generated modules. A pattern containing `/` matches
+/// the last components of the path, so `migrations/*.py` matches
+/// `shop/migrations/0001_initial.py`.
+pub const DEFAULT_PATTERNS: &[&str] = &["*_pb2.py", "*_pb2_grpc.py", "migrations/*.py"];
+
+/// Phrases that mark a file as generated when they appear in its first
+/// [`MARKER_LINES`] lines.
+const MARKERS: &[&str] = &[
+    "@generated",
+    "do not edit",
+    "auto-generated",
+    "autogenerated",
+    "code generated by",
+    "generated by the protocol buffer compiler",
+    "generated by django",
+    "openapi-generator",
+];
+
+const MARKER_LINES: usize = 10;
+
+/// What to do with the functions of generated files.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GeneratedMode {
+    /// List them like any other function, tagged `"generated": true`
+    #[default]
+    Include,
+    /// Leave them out of the listing and statistics
+    Exclude,
+    /// Replace them with one row per file with aggregate statistics
+    Collapse,
+}
+
+#[derive(Debug, Clone)]
+pub struct GeneratedDetector {
+    patterns: Vec<String>,
+}
+
+impl Default for GeneratedDetector {
+    fn default() -> Self {
+        GeneratedDetector {
+            patterns: DEFAULT_PATTERNS.iter().map(|p| p.to_string()).collect(),
+        }
+    }
+}
+
+impl GeneratedDetector {
+    /// The default patterns plus `extra` ones.
+    pub fn with_patterns(extra: &[String]) -> Self {
+        let mut detector = GeneratedDetector::default();
+        detector.patterns.extend(extra.iter().cloned());
+        detector
+    }
+
+    /// The patterns added to the built-in ones.
+    pub fn extra(&self) -> &[String] {
+        &self.patterns[DEFAULT_PATTERNS.len()..]
+    }
+
+    /// Why `relative` (a path relative to the analyzed root) looks generated.
+    pub fn check_path(&self, relative: &Path) -> Option<String> {
+        let components: Vec<String> = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy().into_owned())
+            .collect();
+        self.patterns
+            .iter()
+            .find(|pattern| {
+                let parts: Vec<&str> = pattern.split('/').collect();
+                parts.len() <= components.len()
+                    && parts
+                        .iter()
+                        .zip(&components[components.len() - parts.len()..])
+                        .all(|(part, component)| wildcard(part, component))
+            })
+            .map(|pattern| format!("pattern `{}`", pattern))
+    }
+
+    /// Why `source` looks generated, judging from its header.
+    pub fn check_source(&self, source: &str) -> Option<String> {
+        source.lines().take(MARKER_LINES).find_map(|line| {
+            let line = line.to_lowercase();
+            MARKERS
+                .iter()
+                .find(|marker| line.contains(*marker))
+                .map(|marker| format!("marker \"{}\"", marker))
+        })
+    }
+}
+
+/// Whether `text` matches `pattern`, where `*` stands for any run of
+/// characters and `?` for any one character.
+pub fn wildcard(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    // Positions to resume from when the last `*` needs to match more.
+    let (mut p, mut t) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((star_p, star_t)) => {
+                    p = star_p + 1;
+                    t = star_t + 1;
+                    star = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wildcard() {
+        assert!(wildcard("*_pb2.py", "user_pb2.py"));
+        assert!(wildcard("*_pb2.py", "_pb2.py"));
+        assert!(!wildcard("*_pb2.py", "user_pb2.pyi"));
+        assert!(wildcard("a*b*c", "aXbYbZc"));
+        assert!(wildcard("v?.py", "v1.py"));
+        assert!(!wildcard("v?.py", "v10.py"));
+        assert!(wildcard("*", ""));
+    }
+
+    #[test]
+    fn test_generated_paths() {
+        let detector = GeneratedDetector::default();
+        assert!(detector.check_path(Path::new("api/user_pb2.py")).is_some());
+        assert!(detector.check_path(Path::new("api/user_pb2_grpc.py")).is_some());
+        assert!(detector
+            .check_path(Path::new("shop/migrations/0001_initial.py"))
+            .is_some());
+        assert!(detector.check_path(Path::new("migrations/0002_auto.py")).is_some());
+        assert_eq!(detector.check_path(Path::new("shop/migrations.py")), None);
+        assert_eq!(detector.check_path(Path::new("shop/migrations/data/load.json")), None);
+        assert_eq!(detector.check_path(Path::new("api/pb2_helpers.py")), None);
+    }
+
+    #[test]
+    fn test_extra_patterns() {
+        let detector = GeneratedDetector::with_patterns(&["client/*/*.py".to_string()]);
+        assert!(detector.check_path(Path::new("sdk/client/models/pet.py")).is_some());
+        assert_eq!(detector.check_path(Path::new("sdk/client/pet.py")), None);
+    }
+
+    #[test]
+    fn test_markers() {
+        let detector = GeneratedDetector::default();
+        let protobuf = "# -*- coding: utf-8 -*-\n# Generated by the protocol buffer compiler.  DO NOT EDIT!\n";
+        assert!(detector.check_source(protobuf).is_some());
+        assert!(detector
+            .check_source("# Generated by Django 4.2 on 2024-01-01 12:00\n")
+            .is_some());
+        assert_eq!(
+            detector.check_source("\"\"\"Reports generated by the billing job.\"\"\"\n"),
+            None
+        );
+        let late = format!("{}# @generated\n", "\n".repeat(MARKER_LINES));
+        assert_eq!(detector.check_source(&late), None);
+    }
+}
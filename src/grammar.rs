@@ -0,0 +1,459 @@
+//! Checks that the grammars still have what the analysis relies on.
+//! Upgrading tree-sitter-python has renamed nodes before, and nothing fails
+//! when it does: a renamed kind is simply never matched again, and functions
+//! quietly score lower. Every run checks the node kinds, fields and queries
+//! the analysis refers to, and counts each construct in a snippet where its
+//! number is known, failing with what is missing before analyzing anything.
+
+use crate::language::{Language, JS_FUNCTIONS_QUERY};
+use crate::weights::{Construct, Weights};
+use anyhow::{bail, Result};
+use std::path::Path;
+use serde::Serialize;
+use tree_sitter::{Parser as TSParser, Query, QueryCursor, QueryErrorKind};
+
+/// Grammars the analysis is written against, as required in `Cargo.toml`.
+pub const GRAMMARS: [(Language, &str); 3] = [
+    (Language::Python, "tree-sitter-python 0.20"),
+    (Language::JavaScript, "tree-sitter-javascript 0.20"),
+    (Language::TypeScript, "tree-sitter-typescript 0.20"),
+];
+
+/// Functions with their name and body, the unit of the analysis.
+pub const FUNCTIONS_QUERY: &str = "[
+            (function_definition body: (block) @body)
+            (lambda body: (_) @body)
+        ] @function";
+
+/// Call sites of a name, as `f(...)` or `obj.f(...)`.
+pub const CALLS_QUERY: &str = "(call function: (identifier) @callee)
+         (call function: (attribute attribute: (identifier) @callee))";
+
+/// The built-in queries, with the file a snippet is parsed as, the snippet
+/// and the number of matches it has.
+const QUERIES: [(&str, &str, &str, &str, usize); 4] = [
+    (
+        "functions",
+        FUNCTIONS_QUERY,
+        "a.py",
+        "def f():\n    pass\n\n@d\ndef g():\n    def h():\n        return lambda x: x\n",
+        4,
+    ),
+    ("calls", CALLS_QUERY, "a.py", "f(x)\nobj.g(y)\nh\n", 2),
+    (
+        "javascript-functions",
+        JS_FUNCTIONS_QUERY,
+        "a.js",
+        "function f() {}\nconst g = () => 1;\nclass A { m() {} }\nh(function* () {});\n",
+        4,
+    ),
+    (
+        "typescript-functions",
+        JS_FUNCTIONS_QUERY,
+        "a.tsx",
+        "function f(): void {}\nconst g = (x: number) => <b>{x}</b>;\nclass A { m<T>() {} }\n",
+        3,
+    ),
+];
+
+/// Node kinds matched by name in the walks over the tree.
+const NODE_KINDS: &[&str] = &[
+    "aliased_import",
+    "as_pattern",
+    "as_pattern_target",
+    "assert_statement",
+    "assignment",
+    "attribute",
+    "augmented_assignment",
+    "block",
+    "boolean_operator",
+    "break_statement",
+    "call",
+    "case_clause",
+    "class_definition",
+    "comment",
+    "conditional_expression",
+    "continue_statement",
+    "decorated_definition",
+    "decorator",
+    "elif_clause",
+    "else_clause",
+    "except_clause",
+    "except_group_clause",
+    "finally_clause",
+    "for_in_clause",
+    "for_statement",
+    "function_definition",
+    "global_statement",
+    "identifier",
+    "if_clause",
+    "if_statement",
+    "import_from_statement",
+    "import_statement",
+    "keyword_argument",
+    "lambda",
+    "line_continuation",
+    "named_expression",
+    "nonlocal_statement",
+    "return_statement",
+    "string",
+    "subscript",
+    "try_statement",
+    "while_statement",
+    "with_statement",
+    "yield",
+];
+
+/// Node kinds matched by name in JavaScript and TypeScript trees.
+const JS_NODE_KINDS: &[&str] = &[
+    "arrow_function",
+    "assignment_expression",
+    "binary_expression",
+    "catch_clause",
+    "class",
+    "class_declaration",
+    "do_statement",
+    "finally_clause",
+    "for_in_statement",
+    "for_statement",
+    "function_declaration",
+    "function_expression",
+    "generator_function",
+    "generator_function_declaration",
+    "if_statement",
+    "method_definition",
+    "pair",
+    "statement_block",
+    "switch_case",
+    "ternary_expression",
+    "try_statement",
+    "variable_declarator",
+    "while_statement",
+];
+
+/// Fields looked up by name in JavaScript and TypeScript trees.
+const JS_FIELDS: &[&str] = &["body", "condition", "key", "left", "name", "operator", "parameter"];
+
+/// Fields looked up by name in the walks over the tree.
+const FIELDS: &[&str] = &[
+    "alias",
+    "alternative",
+    "body",
+    "condition",
+    "definition",
+    "function",
+    "left",
+    "module_name",
+    "name",
+    "object",
+    "parameters",
+    "right",
+    "superclasses",
+    "value",
+];
+
+/// Module-level snippets with a known number of one construct.
+const CANARIES: [(Construct, &str, u32); 17] = [
+    (Construct::If, "if a:\n    pass\n", 1),
+    (
+        Construct::Elif,
+        "if a:\n    pass\nelif b:\n    pass\nelif c:\n    pass\n",
+        2,
+    ),
+    (Construct::For, "for a in b:\n    pass\n", 1),
+    (Construct::While, "while a:\n    pass\n", 1),
+    (Construct::Try, "try:\n    pass\nexcept E:\n    pass\n", 1),
+    (
+        Construct::Except,
+        "try:\n    pass\nexcept A:\n    pass\nexcept B:\n    pass\n",
+        2,
+    ),
+    (Construct::With, "with a as b:\n    pass\n", 1),
+    (Construct::BoolOp, "x = a and b or not c\n", 2),
+    (Construct::Ternary, "x = a if b else c\n", 1),
+    (Construct::ComprehensionFor, "x = [a for b in c for a in b]\n", 2),
+    (Construct::ComprehensionIf, "x = {a for a in b if a}\n", 1),
+    (Construct::Assert, "assert a, b\n", 1),
+    (Construct::LoopElse, "for a in b:\n    pass\nelse:\n    pass\n", 1),
+    (
+        Construct::TryElse,
+        "try:\n    pass\nexcept E:\n    pass\nelse:\n    pass\n",
+        1,
+    ),
+    (Construct::Finally, "try:\n    pass\nfinally:\n    pass\n", 1),
+    (
+        Construct::Case,
+        "match a:\n    case 1:\n        pass\n    case _:\n        pass\n",
+        2,
+    ),
+    (Construct::Walrus, "if (x := a):\n    pass\n", 1),
+];
+
+/// JavaScript snippets with a known number of one construct, parsed as
+/// JavaScript and as TypeScript.
+const JS_CANARIES: [(Construct, &str, u32); 8] = [
+    (Construct::If, "if (a) {} else if (b) {} else {}\n", 2),
+    (Construct::For, "for (;;) {}\nfor (a of b) {}\nfor (a in b) {}\n", 3),
+    (Construct::While, "while (a) {}\ndo {} while (a);\n", 2),
+    (Construct::Except, "try {} catch (e) {}\n", 1),
+    (Construct::Finally, "try {} finally {}\n", 1),
+    (Construct::BoolOp, "x = a && b || c;\ny = a ?? b + c;\n", 3),
+    (Construct::Ternary, "x = a ? b : c;\n", 1),
+    (Construct::Case, "switch (a) { case 1: break; case 2: default: }\n", 2),
+];
+
+/// A grammar that was checked.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Grammar {
+    pub language: Language,
+    pub version: String,
+    /// Version of the grammar's language ABI.
+    pub abi: usize,
+}
+
+/// What the grammars lack, if anything.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Report {
+    pub grammars: Vec<Grammar>,
+    pub node_kinds: usize,
+    pub fields: usize,
+    pub queries: usize,
+    pub canaries: usize,
+    pub missing_node_kinds: Vec<String>,
+    pub missing_fields: Vec<String>,
+    /// Queries that do not compile, with why.
+    pub query_errors: Vec<String>,
+    /// Snippets in which a query or construct is not found as often as it
+    /// occurs.
+    pub canary_failures: Vec<String>,
+}
+
+impl Report {
+    pub fn is_ok(&self) -> bool {
+        self.missing_node_kinds.is_empty()
+            && self.missing_fields.is_empty()
+            && self.query_errors.is_empty()
+            && self.canary_failures.is_empty()
+    }
+
+    /// The grammars with their versions, e.g. `tree-sitter-python 0.20 (ABI
+    /// 14), ...`.
+    pub fn versions(&self) -> String {
+        let versions: Vec<String> = self
+            .grammars
+            .iter()
+            .map(|grammar| format!("{} (ABI {})", grammar.version, grammar.abi))
+            .collect();
+        versions.join(", ")
+    }
+
+    /// One line per problem.
+    pub fn problems(&self) -> Vec<String> {
+        let kinds = self
+            .missing_node_kinds
+            .iter()
+            .map(|kind| format!("missing node kind `{}`", kind));
+        let fields = self
+            .missing_fields
+            .iter()
+            .map(|field| format!("missing field `{}`", field));
+        kinds
+            .chain(fields)
+            .chain(self.query_errors.iter().cloned())
+            .chain(self.canary_failures.iter().cloned())
+            .collect()
+    }
+}
+
+/// Checks the grammars against the built-in queries, node kinds and
+/// fields, and the grammar of `custom_language` against the `custom`
+/// queries, given with a name each.
+pub fn check(custom: &[(String, String)], custom_language: Language) -> Report {
+    let language = tree_sitter_python::language();
+    let scripts = [
+        (Language::JavaScript, tree_sitter_javascript::language()),
+        (Language::TypeScript, tree_sitter_typescript::language_typescript()),
+    ];
+    let mut report = Report {
+        grammars: GRAMMARS
+            .iter()
+            .map(|&(language, version)| Grammar {
+                language,
+                version: version.to_string(),
+                abi: language.grammar(Path::new("")).version(),
+            })
+            .collect(),
+        node_kinds: NODE_KINDS.len() + scripts.len() * JS_NODE_KINDS.len(),
+        fields: FIELDS.len() + scripts.len() * JS_FIELDS.len(),
+        queries: QUERIES.len() + custom.len(),
+        canaries: QUERIES.len() + CANARIES.len() + scripts.len() * JS_CANARIES.len(),
+        ..Default::default()
+    };
+    report.missing_node_kinds = NODE_KINDS
+        .iter()
+        .filter(|kind| language.id_for_node_kind(kind, true) == 0)
+        .map(|kind| kind.to_string())
+        .collect();
+    report.missing_fields = FIELDS
+        .iter()
+        .filter(|field| language.field_id_for_name(field).is_none())
+        .map(|field| field.to_string())
+        .collect();
+    for (script, grammar) in scripts {
+        let kinds = JS_NODE_KINDS
+            .iter()
+            .filter(|kind| grammar.id_for_node_kind(kind, true) == 0)
+            .map(|kind| format!("{} ({})", kind, script.name()));
+        report.missing_node_kinds.extend(kinds);
+        let fields = JS_FIELDS
+            .iter()
+            .filter(|field| grammar.field_id_for_name(field).is_none())
+            .map(|field| format!("{} ({})", field, script.name()));
+        report.missing_fields.extend(fields);
+    }
+
+    let mut parser = TSParser::new();
+    for (name, source, file, canary, expected) in QUERIES {
+        let path = Path::new(file);
+        let grammar = Language::of(path).unwrap().grammar(path);
+        let query = match compile(grammar, name, source) {
+            Ok(query) => query,
+            Err(error) => {
+                report.query_errors.push(error);
+                continue;
+            }
+        };
+        parser.set_language(grammar).unwrap();
+        let Some(tree) = parser.parse(canary, None) else {
+            continue;
+        };
+        let found = QueryCursor::new()
+            .matches(&query, tree.root_node(), canary.as_bytes())
+            .count();
+        if found != expected {
+            report.canary_failures.push(format!(
+                "query `{}` matches {} times instead of {} in {:?}",
+                name, found, expected, canary
+            ));
+        }
+    }
+    for (name, source) in custom {
+        if let Err(error) = compile(custom_language.grammar(Path::new("")), name, source) {
+            report.query_errors.push(error);
+        }
+    }
+
+    let canaries = CANARIES
+        .iter()
+        .map(|canary| (language, canary))
+        .chain(
+            scripts
+                .iter()
+                .flat_map(|&(_, grammar)| JS_CANARIES.iter().map(move |canary| (grammar, canary))),
+        );
+    let weights = Weights::default();
+    for (grammar, &(construct, canary, expected)) in canaries {
+        parser.set_language(grammar).unwrap();
+        let Some(tree) = parser.parse(canary, None) else {
+            continue;
+        };
+        let found = weights.counts(tree.root_node())[construct as usize];
+        if found != expected {
+            report.canary_failures.push(format!(
+                "construct `{}` counted {} times instead of {} in {:?}",
+                construct.name(),
+                found,
+                expected,
+                canary
+            ));
+        }
+    }
+    report
+}
+
+/// The query `source`, or why it does not compile against `language`.
+fn compile(language: tree_sitter::Language, name: &str, source: &str) -> Result<Query, String> {
+    Query::new(language, source).map_err(|error| {
+        let problem = match error.kind {
+            QueryErrorKind::NodeType => format!("unknown node kind `{}`", error.message),
+            QueryErrorKind::Field => format!("unknown field `{}`", error.message),
+            QueryErrorKind::Capture => format!("unknown capture `{}`", error.message),
+            _ => format!("invalid query ({})", error.message.trim()),
+        };
+        format!(
+            "query `{}`: {} at line {}, column {}",
+            name,
+            problem,
+            error.row + 1,
+            error.column + 1
+        )
+    })
+}
+
+/// Fails, naming the grammar and everything it lacks, unless it has all
+/// the analysis relies on.
+pub fn validate() -> Result<()> {
+    let report = check(&[], Language::Python);
+    if !report.is_ok() {
+        bail!(
+            "{} do not have what the analysis relies on, which would silently under-count:\n  {}\n(run `complexity-audit doctor` for details)",
+            report.versions(),
+            report.problems().join("\n  ")
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grammar_has_everything() {
+        let report = check(&[], Language::Python);
+        assert_eq!(report.problems(), Vec::<String>::new());
+        assert_eq!(report.canaries, 37);
+        validate().unwrap();
+    }
+
+    #[test]
+    fn test_custom_queries() {
+        let custom = [
+            (
+                "ok".to_string(),
+                "(if_statement condition: (_) @condition)".to_string(),
+            ),
+            // As if a grammar upgrade had renamed the clause.
+            (
+                "renamed.scm".to_string(),
+                "(if_statement (else_if_clause) @elif)".to_string(),
+            ),
+            (
+                "field.scm".to_string(),
+                "(if_statement test: (_) @test)".to_string(),
+            ),
+        ];
+        let report = check(&custom, Language::Python);
+        assert!(!report.is_ok());
+        assert_eq!(report.queries, 7);
+        assert_eq!(
+            report.grammars.iter().map(|g| g.language).collect::<Vec<_>>(),
+            Language::ALL
+        );
+        assert_eq!(
+            report.query_errors,
+            [
+                "query `renamed.scm`: unknown node kind `else_if_clause` at line 1, column 16",
+                "query `field.scm`: unknown field `test` at line 1, column 15",
+            ]
+        );
+
+        // Queries are checked against the grammar they are written for.
+        let javascript = [(
+            "ok.scm".to_string(),
+            "(arrow_function body: (_) @body)".to_string(),
+        )];
+        assert!(check(&javascript, Language::JavaScript).is_ok());
+        assert!(!check(&javascript, Language::Python).is_ok());
+    }
+}
@@ -0,0 +1,365 @@
+//! Complexity of one function across the commits that touched its file.
+
+use crate::fingerprint::fingerprint;
+use crate::locate::designates;
+use crate::meta::git;
+use crate::weights::{Preset, Weights};
+use crate::depth::DEFAULT_MAX_DEPTH;
+use crate::{calculate_complexity_with, FunctionComplexity};
+use anyhow::{bail, Context, Result};
+use comfy_table::{Cell, Table};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// The function's score at one commit, or a gap when it was absent.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Point {
+    pub sha: String,
+    pub date: String,
+    pub author: String,
+    /// Name of the function at this commit, which differs from the queried
+    /// one before a rename.
+    pub name: Option<String>,
+    pub complexity: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Revision {
+    sha: String,
+    date: String,
+    author: String,
+    /// Path of the file at this commit, relative to the top of the repository.
+    path: String,
+}
+
+/// A function as scored from one blob, which is what gets cached.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Scored {
+    name: String,
+    complexity: u32,
+    body_hash: String,
+}
+
+/// Splits a `path/to/file.py::function` locator.
+pub fn parse_locator(spec: &str) -> Result<(String, String), String> {
+    match spec.rsplit_once("::") {
+        Some((path, name)) if !path.is_empty() && !name.is_empty() => {
+            Ok((path.to_string(), name.to_string()))
+        }
+        _ => Err(format!("expected PATH::FUNCTION, got `{}`", spec)),
+    }
+}
+
+/// Version of the scores kept by [`BlobCache`], part of their file names so
+/// that scores computed by an older analysis are not read back.
+const CACHE_FORMAT: u32 = 3;
+
+/// Scores of blobs, kept in the repository's git directory so that repeated
+/// queries only parse blobs they have not seen before.
+pub struct BlobCache {
+    dir: Option<PathBuf>,
+    preset: Preset,
+}
+
+impl BlobCache {
+    pub fn new(repo: &Path, preset: Preset, enabled: bool) -> Self {
+        let dir = enabled
+            .then(|| git(repo, &["rev-parse", "--path-format=absolute", "--git-common-dir"]))
+            .flatten()
+            .map(|git_dir| PathBuf::from(git_dir).join("analyzr-history"));
+        BlobCache { dir, preset }
+    }
+
+    fn functions(&self, repo: &Path, revision: &Revision) -> Result<Vec<Scored>> {
+        let spec = format!("{}:{}", revision.sha, revision.path);
+        // The file may have been deleted in this commit.
+        let Some(blob) = git(repo, &["rev-parse", "--verify", "--quiet", &spec]) else {
+            return Ok(Vec::new());
+        };
+        let cached = self
+            .dir
+            .as_ref()
+            .map(|dir| {
+                dir.join(format!("{}-{}-v{}.json", blob, self.preset.name(), CACHE_FORMAT))
+            });
+        if let Some(json) = cached.as_ref().and_then(|path| std::fs::read(path).ok()) {
+            if let Ok(functions) = serde_json::from_slice(&json) {
+                return Ok(functions);
+            }
+        }
+
+        let source = cat_blob(repo, &blob)?;
+        // A revision that does not parse simply has no functions.
+        let weights = Weights::preset(self.preset);
+        let path = Path::new(&revision.path);
+        let functions: Vec<Scored> = calculate_complexity_with(&source, path, &weights, DEFAULT_MAX_DEPTH)
+            .unwrap_or_default()
+            .iter()
+            .map(|func| Scored {
+                name: func.name.clone(),
+                complexity: func.complexity,
+                body_hash: body_hash(&source, func),
+            })
+            .collect();
+        if let Some(path) = cached {
+            // Failing to cache only costs speed.
+            let _ = std::fs::create_dir_all(path.parent().unwrap())
+                .and_then(|_| std::fs::write(&path, serde_json::to_vec(&functions)?));
+        }
+        Ok(functions)
+    }
+}
+
+fn cat_blob(repo: &Path, blob: &str) -> Result<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(["cat-file", "blob", blob])
+        .output()
+        .context("Failed to run git")?;
+    if !output.status.success() {
+        bail!("git could not read blob {}", blob);
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Hash of a function's body with indentation ignored, which survives
+/// renames and moves within the file.
+fn body_hash(source: &str, func: &FunctionComplexity) -> String {
+    let first = func.signature_end_line.max(func.line) as usize;
+    let body: Vec<&str> = source
+        .lines()
+        .skip(first)
+        .take((func.end_line as usize).saturating_sub(first))
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect();
+    fingerprint("", &body.join("\n"))
+}
+
+/// The last `last` commits that touched `path` (relative to `repo`), newest
+/// first, following renames of the file.
+fn revisions(repo: &Path, path: &str, last: usize) -> Result<Vec<Revision>> {
+    let log = git(
+        repo,
+        &[
+            "log",
+            "--follow",
+            "-n",
+            &last.to_string(),
+            "--format=%x00%H%x09%aI%x09%an",
+            "--name-only",
+            "--",
+            path,
+        ],
+    )
+    .with_context(|| format!("git log found no commits touching {}", path))?;
+
+    let mut revisions = Vec::new();
+    for record in log.split('\0').filter(|record| !record.trim().is_empty()) {
+        let mut lines = record.lines().filter(|line| !line.is_empty());
+        let header = lines.next().unwrap_or_default();
+        let mut fields = header.splitn(3, '\t');
+        let (Some(sha), Some(date), Some(author), Some(path)) =
+            (fields.next(), fields.next(), fields.next(), lines.next())
+        else {
+            bail!("unexpected git log output: {:?}", record);
+        };
+        revisions.push(Revision {
+            sha: sha.to_string(),
+            date: date.to_string(),
+            author: author.to_string(),
+            path: path.to_string(),
+        });
+    }
+    Ok(revisions)
+}
+
+/// Scores `name` in `path` at each of the last `last` commits touching the
+/// file, oldest first. `name` is a qualified name or an end of one, as in
+/// [`crate::locate`], and the first function it designates is taken. When the function is missing under its name, a
+/// function with the same body as in the next newer commit is taken to be it
+/// under its earlier name.
+pub fn history(
+    repo: &Path,
+    path: &str,
+    name: &str,
+    last: usize,
+    cache: &BlobCache,
+) -> Result<Vec<Point>> {
+    let mut points = Vec::new();
+    let mut current = name.to_string();
+    let mut current_body: Option<String> = None;
+
+    for revision in revisions(repo, path, last)? {
+        let functions = cache.functions(repo, &revision)?;
+        let found = functions.iter().find(|f| designates(&current, &f.name)).or_else(|| {
+            let body = current_body.as_ref()?;
+            functions.iter().find(|f| &f.body_hash == body)
+        });
+        if let Some(func) = found {
+            current = func.name.clone();
+            current_body = Some(func.body_hash.clone());
+        }
+        points.push(Point {
+            sha: revision.sha,
+            date: revision.date,
+            author: revision.author,
+            name: found.map(|func| func.name.clone()),
+            complexity: found.map(|func| func.complexity),
+        });
+    }
+
+    points.reverse();
+    Ok(points)
+}
+
+/// One block character per point, scaled between the lowest and highest
+/// score, with a space for each gap.
+pub fn sparkline(points: &[Point]) -> String {
+    let scores = points.iter().filter_map(|p| p.complexity);
+    let (Some(min), Some(max)) = (scores.clone().min(), scores.max()) else {
+        return String::new();
+    };
+    points
+        .iter()
+        .map(|point| match point.complexity {
+            Some(c) if max > min => {
+                SPARKS[((c - min) as usize * (SPARKS.len() - 1)) / (max - min) as usize]
+            }
+            Some(_) => SPARKS[0],
+            None => ' ',
+        })
+        .collect()
+}
+
+pub fn print_table(points: &[Point], name: &str) {
+    let mut table = Table::new();
+    table.set_header(vec!["Commit", "Date", "Author", "Function", "Complexity"]);
+    for point in points {
+        table.add_row(vec![
+            Cell::new(&point.sha[..point.sha.len().min(10)]),
+            Cell::new(&point.date),
+            Cell::new(&point.author),
+            Cell::new(point.name.as_deref().unwrap_or("(absent)")),
+            Cell::new(point.complexity.map_or("-".to_string(), |c| c.to_string())),
+        ]);
+    }
+    println!("{}", table);
+    let gaps = points.iter().filter(|p| p.complexity.is_none()).count();
+    if gaps > 0 {
+        println!("{} absent from {} of {} commits", name, gaps, points.len());
+    }
+    println!("Trend (oldest to newest): {}", sparkline(points));
+}
+
+pub fn render_csv(points: &[Point]) -> String {
+    let mut csv = String::from("commit,date,author,function,complexity\n");
+    for point in points {
+        let fields = [
+            point.sha.clone(),
+            point.date.clone(),
+            point.author.clone(),
+            point.name.clone().unwrap_or_default(),
+            point.complexity.map(|c| c.to_string()).unwrap_or_default(),
+        ];
+        let fields: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+        csv.push_str(&fields.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn run_git(dir: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(["-c", "user.name=Ada, QA", "-c", "user.email=ada@example.com"])
+            .args(args)
+            .output()
+            .unwrap()
+            .status;
+        assert!(status.success(), "git {:?} failed", args);
+    }
+
+    fn commit(dir: &Path, source: &str) {
+        std::fs::write(dir.join("billing.py"), source).unwrap();
+        run_git(dir, &["add", "-A"]);
+        run_git(dir, &["commit", "-q", "-m", "change"]);
+    }
+
+    #[test]
+    fn test_history_follows_renames_and_reports_gaps() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = temp_dir.path();
+        run_git(repo, &["init", "-q"]);
+        commit(repo, "def compute_totals(x):\n    return x\n");
+        commit(repo, "def compute_totals(x):\n    if x:\n        return x\n    return 0\n");
+        commit(repo, "def unrelated():\n    pass\n");
+        commit(repo, "def compute_total(x):\n        if x:\n            return x\n        return 0\n");
+
+        let cache = BlobCache::new(repo, Preset::Analyzr, true);
+        let points = history(repo, "billing.py", "compute_total", 10, &cache).unwrap();
+
+        let names: Vec<Option<&str>> = points.iter().map(|p| p.name.as_deref()).collect();
+        assert_eq!(
+            names,
+            [Some("compute_totals"), Some("compute_totals"), None, Some("compute_total")]
+        );
+        let scores: Vec<Option<u32>> = points.iter().map(|p| p.complexity).collect();
+        assert_eq!(scores, [Some(1), Some(2), None, Some(2)]);
+        assert_eq!(points[0].author, "Ada, QA");
+        assert_eq!(sparkline(&points), "▁█ █");
+
+        // A second query is answered from the cache.
+        let cached = history(repo, "billing.py", "compute_total", 10, &cache).unwrap();
+        assert_eq!(cached, points);
+        let cache_dir = repo.join(".git/analyzr-history");
+        assert_eq!(std::fs::read_dir(cache_dir).unwrap().count(), 4);
+
+        let last_two = history(repo, "billing.py", "compute_total", 2, &cache).unwrap();
+        assert_eq!(last_two.len(), 2);
+    }
+
+    #[test]
+    fn test_parse_locator() {
+        assert_eq!(
+            parse_locator("billing/invoice.py::compute_totals").unwrap(),
+            ("billing/invoice.py".to_string(), "compute_totals".to_string())
+        );
+        assert!(parse_locator("billing/invoice.py").is_err());
+        assert!(parse_locator("::f").is_err());
+    }
+
+    #[test]
+    fn test_render_csv() {
+        let point = Point {
+            sha: "abc".to_string(),
+            date: "2024-01-02T03:04:05+00:00".to_string(),
+            author: "Ada \"the\" Dev, QA".to_string(),
+            name: None,
+            complexity: None,
+        };
+        assert_eq!(
+            render_csv(&[point]),
+            "commit,date,author,function,complexity\n\
+             abc,2024-01-02T03:04:05+00:00,\"Ada \"\"the\"\" Dev, QA\",,\n"
+        );
+    }
+}
@@ -0,0 +1,506 @@
+//! Complexity of classes including the methods they inherit, with bases
+//! resolved across the analyzed files.
+//!
+//! Resolution is best-effort and never guesses: a base is found when it is
+//! a class of the same file, or is imported by name (`from pkg.models import
+//! Base`, relative imports included) from an analyzed module of the same
+//! root. Every other base is recorded as unknown.
+
+use crate::depth;
+use crate::output::relative_path;
+use crate::{python_files, AnalysisResult};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use tree_sitter::{Node, Parser as TSParser};
+
+/// An ancestor of a class, in JSON.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Ancestor {
+    pub name: String,
+    pub file: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClassReport {
+    pub name: String,
+    pub file: String,
+    pub line: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project: Option<String>,
+    /// Weighted methods per class: the total complexity of the methods the
+    /// class defines.
+    pub wmc: u32,
+    /// `wmc` plus the complexity of the inherited methods it does not
+    /// override.
+    pub effective_wmc: u32,
+    /// Resolved ancestors in method resolution order, each listed once.
+    pub ancestors: Vec<Ancestor>,
+    /// Bases of the class or its ancestors that could not be resolved, whose
+    /// methods `effective_wmc` leaves out.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unknown_bases: Vec<String>,
+}
+
+enum Base {
+    Class(usize),
+    Unknown(String),
+}
+
+struct Class {
+    name: String,
+    file: usize,
+    line: u32,
+    bases: Vec<Base>,
+    /// Names and first lines of the methods the class defines.
+    methods: Vec<(String, u32)>,
+}
+
+struct File {
+    project: Option<String>,
+    /// The file as the functions of the result name it.
+    name: String,
+    /// Path relative to its root, with `/` separators.
+    relative: String,
+}
+
+/// Where a name imported into a file comes from.
+struct Import {
+    /// Candidate module paths relative to the root, such as `pkg/models.py`.
+    modules: Vec<String>,
+    name: String,
+}
+
+/// Reports every class under `roots`, each an optional project label with
+/// its directory, using the complexity of the methods found in `result`.
+/// Methods missing from it, such as vendored ones, count zero.
+pub fn analyze(result: &AnalysisResult, roots: &[(Option<String>, PathBuf)]) -> Vec<ClassReport> {
+    let mut parser = TSParser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+
+    let mut files = Vec::new();
+    let mut classes = Vec::new();
+    // Per class, its base expressions and its file.
+    let mut pending: Vec<(Vec<String>, usize)> = Vec::new();
+    let mut imports: Vec<HashMap<String, Import>> = Vec::new();
+    for (project, root) in roots {
+        let mut paths: Vec<PathBuf> = python_files(root).collect();
+        paths.sort();
+        for path in paths {
+            let Ok(source) = std::fs::read_to_string(&path) else {
+                continue;
+            };
+            let Some(tree) = parser.parse(&source, None) else {
+                continue;
+            };
+            if depth::check(tree.root_node(), depth::DEFAULT_MAX_DEPTH).is_err() {
+                continue;
+            }
+            let relative = relative_path(&path.to_string_lossy(), root);
+            let name = match project {
+                Some(_) => relative.clone(),
+                None => path.to_string_lossy().to_string(),
+            };
+            let file = files.len();
+            imports.push(collect_imports(tree.root_node(), &source, &relative));
+            collect_classes(tree.root_node(), &source, file, &mut classes, &mut pending);
+            files.push(File {
+                project: project.clone(),
+                name,
+                relative,
+            });
+        }
+    }
+
+    for (index, (bases, file)) in pending.into_iter().enumerate() {
+        classes[index].bases = bases
+            .into_iter()
+            .filter(|base| base != "object")
+            .map(|base| resolve(&base, file, &files, &classes, &imports[file]))
+            .collect();
+    }
+
+    let complexity: HashMap<(Option<&str>, &str, u32), u32> = result
+        .functions
+        .iter()
+        .map(|f| {
+            (
+                (f.project.as_deref(), f.file.as_str(), f.line),
+                f.complexity,
+            )
+        })
+        .collect();
+    let method_complexity = |class: &Class, line: u32| {
+        let file = &files[class.file];
+        let key = (file.project.as_deref(), file.name.as_str(), line);
+        complexity.get(&key).copied().unwrap_or(0)
+    };
+
+    let mut orders = HashMap::new();
+    (0..classes.len())
+        .map(|index| {
+            let class = &classes[index];
+            let order = linearize(index, &classes, &mut orders, &mut HashSet::new());
+            let wmc = class
+                .methods
+                .iter()
+                .map(|&(_, line)| method_complexity(class, line))
+                .sum();
+            let mut defined: HashSet<&str> =
+                class.methods.iter().map(|(n, _)| n.as_str()).collect();
+            let mut effective_wmc = wmc;
+            let mut unknown_bases = Vec::new();
+            for &ancestor in &order {
+                let ancestor = &classes[ancestor];
+                for (name, line) in &ancestor.methods {
+                    if defined.insert(name) {
+                        effective_wmc += method_complexity(ancestor, *line);
+                    }
+                }
+            }
+            for &owner in std::iter::once(&index).chain(&order) {
+                for base in &classes[owner].bases {
+                    if let Base::Unknown(name) = base {
+                        if !unknown_bases.contains(name) {
+                            unknown_bases.push(name.clone());
+                        }
+                    }
+                }
+            }
+            ClassReport {
+                name: class.name.clone(),
+                file: files[class.file].name.clone(),
+                line: class.line,
+                project: files[class.file].project.clone(),
+                wmc,
+                effective_wmc,
+                ancestors: order
+                    .iter()
+                    .map(|&ancestor| Ancestor {
+                        name: classes[ancestor].name.clone(),
+                        file: files[classes[ancestor].file].name.clone(),
+                    })
+                    .collect(),
+                unknown_bases,
+            }
+        })
+        .collect()
+}
+
+fn collect_classes(
+    node: Node,
+    source: &str,
+    file: usize,
+    classes: &mut Vec<Class>,
+    pending: &mut Vec<(Vec<String>, usize)>,
+) {
+    if node.kind() == "class_definition" {
+        let text = |node: Node| source[node.byte_range()].to_string();
+        let bases = node
+            .child_by_field_name("superclasses")
+            .map(|list| {
+                let mut cursor = list.walk();
+                list.named_children(&mut cursor)
+                    .filter(|base| base.kind() != "keyword_argument")
+                    .map(text)
+                    .collect()
+            })
+            .unwrap_or_default();
+        let mut methods = Vec::new();
+        if let Some(body) = node.child_by_field_name("body") {
+            let mut cursor = body.walk();
+            for child in body.named_children(&mut cursor) {
+                let function = match child.kind() {
+                    "decorated_definition" => child.child_by_field_name("definition"),
+                    _ => Some(child),
+                };
+                let Some(function) = function.filter(|f| f.kind() == "function_definition") else {
+                    continue;
+                };
+                if let Some(name) = function.child_by_field_name("name") {
+                    methods.push((text(name), function.start_position().row as u32 + 1));
+                }
+            }
+        }
+        if let Some(name) = node.child_by_field_name("name") {
+            classes.push(Class {
+                name: text(name),
+                file,
+                line: node.start_position().row as u32 + 1,
+                bases: Vec::new(),
+                methods,
+            });
+            pending.push((bases, file));
+        }
+    }
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        collect_classes(child, source, file, classes, pending);
+    }
+}
+
+/// Names imported with `from ... import ...` at the top level of a file at
+/// `relative`.
+fn collect_imports(root: Node, source: &str, relative: &str) -> HashMap<String, Import> {
+    let text = |node: Node| source[node.byte_range()].to_string();
+    let mut imports = HashMap::new();
+    let mut cursor = root.walk();
+    for statement in root.named_children(&mut cursor) {
+        if statement.kind() != "import_from_statement" {
+            continue;
+        }
+        let Some(module) = statement.child_by_field_name("module_name") else {
+            continue;
+        };
+        let modules = module_paths(&text(module), relative);
+        let mut names = statement.walk();
+        for name in statement.children_by_field_name("name", &mut names) {
+            let (imported, alias) = match name.kind() {
+                "aliased_import" => match (
+                    name.child_by_field_name("name"),
+                    name.child_by_field_name("alias"),
+                ) {
+                    (Some(imported), Some(alias)) => (text(imported), text(alias)),
+                    _ => continue,
+                },
+                _ => (text(name), text(name)),
+            };
+            imports.insert(
+                alias,
+                Import {
+                    modules: modules.clone(),
+                    name: imported,
+                },
+            );
+        }
+    }
+    imports
+}
+
+/// Candidate paths, relative to the root, of `module` imported from the file
+/// at `relative`: `a.b` is `a/b.py` or `a/b/__init__.py`, and `..a` is
+/// resolved against the file's package.
+fn module_paths(module: &str, relative: &str) -> Vec<String> {
+    let dots = module.len() - module.trim_start_matches('.').len();
+    let mut parts: Vec<&str> = Vec::new();
+    if dots > 0 {
+        parts = relative.split('/').collect();
+        parts.pop();
+        for _ in 1..dots {
+            if parts.pop().is_none() {
+                return Vec::new();
+            }
+        }
+    }
+    parts.extend(module[dots..].split('.').filter(|part| !part.is_empty()));
+    let base = parts.join("/");
+    if base.is_empty() {
+        return vec!["__init__.py".to_string()];
+    }
+    vec![format!("{}.py", base), format!("{}/__init__.py", base)]
+}
+
+fn resolve(
+    base: &str,
+    file: usize,
+    files: &[File],
+    classes: &[Class],
+    imports: &HashMap<String, Import>,
+) -> Base {
+    if let Some(index) = classes
+        .iter()
+        .position(|c| c.file == file && c.name == base)
+    {
+        return Base::Class(index);
+    }
+    if let Some(import) = imports.get(base) {
+        let project = &files[file].project;
+        let found = classes.iter().position(|class| {
+            let source = &files[class.file];
+            class.name == import.name
+                && &source.project == project
+                && import.modules.contains(&source.relative)
+        });
+        if let Some(index) = found {
+            return Base::Class(index);
+        }
+    }
+    Base::Unknown(base.to_string())
+}
+
+/// The ancestors of `class` in method resolution order (C3, as Python
+/// computes it), falling back to a depth-first order when the hierarchy has
+/// no consistent order. Cycles, which only arise from unresolvable code, are
+/// cut.
+fn linearize(
+    class: usize,
+    classes: &[Class],
+    orders: &mut HashMap<usize, Vec<usize>>,
+    visiting: &mut HashSet<usize>,
+) -> Vec<usize> {
+    if let Some(order) = orders.get(&class) {
+        return order.clone();
+    }
+    if !visiting.insert(class) {
+        return Vec::new();
+    }
+    let bases: Vec<usize> = classes[class]
+        .bases
+        .iter()
+        .filter_map(|base| match base {
+            Base::Class(index) if *index != class => Some(*index),
+            _ => None,
+        })
+        .collect();
+    let mut sequences: Vec<Vec<usize>> = bases
+        .iter()
+        .map(|&base| {
+            let mut sequence = vec![base];
+            sequence.extend(linearize(base, classes, orders, visiting));
+            sequence
+        })
+        .collect();
+    sequences.push(bases.clone());
+
+    let mut order = Vec::new();
+    loop {
+        sequences.retain(|sequence| !sequence.is_empty());
+        if sequences.is_empty() {
+            break;
+        }
+        let head = sequences
+            .iter()
+            .map(|sequence| sequence[0])
+            .find(|&candidate| {
+                sequences
+                    .iter()
+                    .all(|sequence| !sequence[1..].contains(&candidate))
+            });
+        let Some(head) = head else {
+            // Inconsistent hierarchy: take the rest depth-first.
+            for sequence in &sequences {
+                for &ancestor in sequence {
+                    if !order.contains(&ancestor) && ancestor != class {
+                        order.push(ancestor);
+                    }
+                }
+            }
+            break;
+        };
+        if head != class && !order.contains(&head) {
+            order.push(head);
+        }
+        for sequence in &mut sequences {
+            if sequence[0] == head {
+                sequence.remove(0);
+            }
+        }
+    }
+    visiting.remove(&class);
+    orders.insert(class, order.clone());
+    order
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+    use tempfile::TempDir;
+
+    fn write(root: &Path, name: &str, source: &str) {
+        let path = root.join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, source).unwrap();
+    }
+
+    fn report<'a>(reports: &'a [ClassReport], name: &str) -> &'a ClassReport {
+        reports.iter().find(|r| r.name == name).unwrap()
+    }
+
+    #[test]
+    fn test_module_paths() {
+        assert_eq!(
+            module_paths("pkg.models", "app.py"),
+            ["pkg/models.py", "pkg/models/__init__.py"]
+        );
+        assert_eq!(
+            module_paths(".models", "pkg/views.py"),
+            ["pkg/models.py", "pkg/models/__init__.py"]
+        );
+        assert_eq!(
+            module_paths("..base", "pkg/sub/views.py"),
+            ["pkg/base.py", "pkg/base/__init__.py"]
+        );
+        assert_eq!(
+            module_paths(".", "pkg/views.py"),
+            ["pkg.py", "pkg/__init__.py"]
+        );
+        assert!(module_paths("...", "views.py").is_empty());
+    }
+
+    #[test]
+    fn test_inherited_methods_across_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let branchy = "        if a:\n            return 1\n        if b:\n            return 2\n        return 3\n";
+        write(
+            root,
+            "pkg/base.py",
+            &format!(
+                "class Base:\n    def big(self, a, b):\n{}\n    def small(self):\n        pass\n",
+                branchy
+            ),
+        );
+        write(
+            root,
+            "pkg/mixins.py",
+            "from .base import Base\n\nclass Left(Base):\n    def small(self):\n        pass\n\nclass Right(Base):\n    def extra(self):\n        pass\n",
+        );
+        write(
+            root,
+            "app.py",
+            &format!(
+                "from pkg.mixins import Left, Right as R\nfrom django.views import View\n\n\
+                 class Diamond(Left, R):\n    def own(self, a, b):\n{}\n\
+                 class Framework(View, Diamond):\n    pass\n",
+                branchy
+            ),
+        );
+        let result = crate::analyze_directory(root, 10).unwrap();
+
+        let reports = analyze(&result, &[(None, root.to_path_buf())]);
+
+        let base = report(&reports, "Base");
+        assert_eq!((base.wmc, base.effective_wmc), (4, 4));
+        assert!(base.ancestors.is_empty());
+
+        let diamond = report(&reports, "Diamond");
+        let ancestors: Vec<&str> = diamond.ancestors.iter().map(|a| a.name.as_str()).collect();
+        // Python's method resolution order: Base, shared by both parents,
+        // comes once and last.
+        assert_eq!(ancestors, ["Left", "Right", "Base"]);
+        // own 3, Left.small 1, Right.extra 1, Base.big 3 (Base.small is
+        // overridden by Left).
+        assert_eq!((diamond.wmc, diamond.effective_wmc), (3, 8));
+        assert!(diamond.ancestors[2].file.ends_with("pkg/base.py"));
+        assert!(diamond.unknown_bases.is_empty());
+
+        let framework = report(&reports, "Framework");
+        assert_eq!(framework.unknown_bases, ["View"]);
+        assert_eq!((framework.wmc, framework.effective_wmc), (0, 8));
+    }
+
+    #[test]
+    fn test_cycles_are_cut() {
+        let temp_dir = TempDir::new().unwrap();
+        write(
+            temp_dir.path(),
+            "loop.py",
+            "class A(B):\n    def a(self):\n        pass\n\nclass B(A):\n    def b(self):\n        pass\n",
+        );
+        let result = crate::analyze_directory(temp_dir.path(), 10).unwrap();
+        let reports = analyze(&result, &[(None, temp_dir.path().to_path_buf())]);
+        assert_eq!(report(&reports, "A").effective_wmc, 2);
+        assert_eq!(report(&reports, "B").effective_wmc, 2);
+    }
+}
@@ -0,0 +1,326 @@
+//! Languages the analysis covers, each with its tree-sitter grammar, the
+//! file extensions it owns and the query finding its functions.
+//!
+//! Control flow is counted by node kind in [`crate::weights`], where the
+//! kinds of every language sit side by side: each grammar only ever
+//! produces its own.
+
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::OnceLock;
+use tree_sitter::{Node, Query};
+
+/// Functions of JavaScript and TypeScript, with their body: declarations,
+/// expressions, arrow functions and methods, generators included.
+pub const JS_FUNCTIONS_QUERY: &str = "[
+            (function_declaration body: (_) @body)
+            (generator_function_declaration body: (_) @body)
+            (function_expression body: (_) @body)
+            (generator_function body: (_) @body)
+            (arrow_function body: (_) @body)
+            (method_definition body: (_) @body)
+        ] @function";
+
+/// Node kinds of functions and classes, whose bodies are scopes of their
+/// own, in every grammar.
+const DEFINITIONS: &[&str] = &[
+    "function_definition",
+    "lambda",
+    "class_definition",
+    "function_declaration",
+    "generator_function_declaration",
+    "function_expression",
+    "generator_function",
+    "arrow_function",
+    "method_definition",
+    "class_declaration",
+    "abstract_class_declaration",
+    "class",
+];
+
+#[derive(
+    clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    #[default]
+    Python,
+    /// JavaScript, with JSX (.js, .jsx, .mjs, .cjs)
+    #[value(name = "javascript")]
+    JavaScript,
+    /// TypeScript, with TSX (.ts, .tsx, .mts, .cts)
+    #[value(name = "typescript")]
+    TypeScript,
+}
+
+impl Language {
+    pub const ALL: [Language; 3] = [Language::Python, Language::JavaScript, Language::TypeScript];
+
+    pub fn is_python(&self) -> bool {
+        *self == Language::Python
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Language::Python => "Python",
+            Language::JavaScript => "JavaScript",
+            Language::TypeScript => "TypeScript",
+        }
+    }
+
+    /// Extensions of the files in the language, without the dot.
+    pub fn extensions(self) -> &'static [&'static str] {
+        match self {
+            Language::Python => &["py"],
+            Language::JavaScript => &["js", "jsx", "mjs", "cjs"],
+            Language::TypeScript => &["ts", "tsx", "mts", "cts"],
+        }
+    }
+
+    /// The language of the file at `path`, by its extension.
+    pub fn of(path: &Path) -> Option<Language> {
+        let extension = path.extension()?.to_str()?;
+        Language::ALL
+            .into_iter()
+            .find(|language| language.extensions().contains(&extension))
+    }
+
+    /// The grammar the file at `path` is parsed with: TypeScript files with
+    /// JSX have one of their own, since `<T>value` casts and JSX elements
+    /// cannot be told apart.
+    pub fn grammar(self, path: &Path) -> tree_sitter::Language {
+        match self {
+            Language::Python => tree_sitter_python::language(),
+            Language::JavaScript => tree_sitter_javascript::language(),
+            Language::TypeScript if path.extension().is_some_and(|ext| ext == "tsx") => {
+                tree_sitter_typescript::language_tsx()
+            }
+            Language::TypeScript => tree_sitter_typescript::language_typescript(),
+        }
+    }
+
+    /// Query capturing each `@function` with its `@body`.
+    pub fn functions_query(self) -> &'static str {
+        match self {
+            Language::Python => crate::grammar::FUNCTIONS_QUERY,
+            Language::JavaScript | Language::TypeScript => JS_FUNCTIONS_QUERY,
+        }
+    }
+
+    /// [`Language::functions_query`] compiled for the grammar of the file at
+    /// `path`, once per grammar and shared by every file and thread.
+    pub fn compiled_functions_query(self, path: &Path) -> &'static Query {
+        static QUERIES: [OnceLock<Query>; 4] = [const { OnceLock::new() }; 4];
+        let index = match self {
+            Language::Python => 0,
+            Language::JavaScript => 1,
+            Language::TypeScript if path.extension().is_some_and(|ext| ext == "tsx") => 3,
+            Language::TypeScript => 2,
+        };
+        QUERIES[index].get_or_init(|| {
+            Query::new(self.grammar(path), self.functions_query())
+                .expect("the functions query compiles against its grammar")
+        })
+    }
+}
+
+/// Whether `kind` is a function or class, in any grammar.
+pub fn is_definition(kind: &str) -> bool {
+    DEFINITIONS.contains(&kind)
+}
+
+/// Node kinds of classes, in every grammar.
+const CLASSES: &[&str] = &[
+    "class_definition",
+    "class_declaration",
+    "abstract_class_declaration",
+    "class",
+];
+
+/// Name of `function`: its own, `<lambda>` for a Python lambda as Python
+/// names it, or for an anonymous JavaScript function the name it is
+/// assigned to (`const handler = () => ...`, `{ handler: function () {} }`),
+/// or `<anonymous>`.
+pub fn function_name(function: Node, source: &str) -> String {
+    let text = |node: Node| source[node.byte_range()].to_string();
+    if function.kind() == "lambda" {
+        return "<lambda>".to_string();
+    }
+    if let Some(name) = function.child_by_field_name("name") {
+        return text(name);
+    }
+    let named = function.parent().and_then(|parent| match parent.kind() {
+        "variable_declarator" | "public_field_definition" => parent.child_by_field_name("name"),
+        "field_definition" => parent.child_by_field_name("property"),
+        "assignment_expression" => parent.child_by_field_name("left"),
+        "pair" => parent.child_by_field_name("key"),
+        _ => None,
+    });
+    named.map_or_else(|| "<anonymous>".to_string(), text)
+}
+
+/// The dotted names of the classes and functions `function` is defined in,
+/// and its own: Python's `__qualname__` without `<locals>`
+/// (`Invoice.compute_totals`, `outer.inner`, `outer.<lambda>`).
+pub fn qualified_name(function: Node, source: &str) -> String {
+    let mut names = Vec::new();
+    let mut node = Some(function);
+    while let Some(current) = node {
+        if CLASSES.contains(&current.kind()) {
+            if let Some(name) = current.child_by_field_name("name") {
+                names.push(source[name.byte_range()].to_string());
+            }
+        } else if is_definition(current.kind()) {
+            names.push(function_name(current, source));
+        }
+        node = current.parent();
+    }
+    names.reverse();
+    names.join(".")
+}
+
+/// The last component of a Python qualified name, the function's own name.
+pub fn own_name(qualified: &str) -> &str {
+    qualified.rsplit('.').next().unwrap_or(qualified)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::weights::Weights;
+
+    #[test]
+    fn test_of() {
+        let languages: Vec<Option<Language>> = ["a.py", "b.jsx", "c.mjs", "d.ts", "e.tsx", "f.rs", "g"]
+            .iter()
+            .map(|path| Language::of(Path::new(path)))
+            .collect();
+        assert_eq!(
+            languages,
+            [
+                Some(Language::Python),
+                Some(Language::JavaScript),
+                Some(Language::JavaScript),
+                Some(Language::TypeScript),
+                Some(Language::TypeScript),
+                None,
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_javascript_functions() {
+        let source = "\
+function plain(a) {
+  if (a && b || c) {
+    return 1;
+  } else if (a ?? b) {
+    return 2;
+  }
+  return a ? 3 : 4;
+}
+
+const arrow = (items) => items.map(x => x > 0 ? x : -x);
+
+class Cart {
+  total() {
+    let sum = 0;
+    for (const item of this.items) {
+      try {
+        sum += item.price;
+      } catch (e) {
+        continue;
+      }
+    }
+    return sum;
+  }
+  handler = function () {
+    do { x-- } while (x > 0);
+  };
+}
+
+module.exports.route = function* (req) {
+  switch (req.method) {
+    case 'GET': yield 1; break;
+    case 'POST': yield 2; break;
+    default: yield 3;
+  }
+};
+";
+        let functions = crate::calculate_complexity_with(
+            source,
+            Path::new("cart.js"),
+            &Weights::default(),
+            crate::depth::DEFAULT_MAX_DEPTH,
+        )
+        .unwrap();
+        let scores: Vec<(&str, u32, u32)> = functions
+            .iter()
+            .map(|f| (f.name.as_str(), f.line, f.complexity))
+            .collect();
+        // A switch's `default` is not a case of its own.
+        assert_eq!(
+            scores,
+            [
+                ("plain", 1, 7),
+                ("arrow", 10, 1),
+                ("arrow.<anonymous>", 10, 2),
+                ("Cart.total", 13, 4),
+                ("Cart.handler", 24, 2),
+                ("module.exports.route", 29, 3),
+            ]
+        );
+        assert_eq!(functions[3].locals, 3);
+    }
+
+    #[test]
+    fn test_typescript_functions() {
+        let source = "\
+export class Api {
+  private cache = new Map<string, number>();
+
+  async get<T>(key: string): Promise<T | undefined> {
+    const hit = <number>this.cache.get(key);
+    if (hit !== undefined) {
+      return hit as T;
+    }
+    return undefined;
+  }
+}
+
+export const useThing = (id?: number): string => {
+  while (id) {
+    id--;
+  }
+  return `${id}`;
+};
+";
+        let tsx = "\
+export function Row({ items }: Props) {
+  return <ul>{items.map((item) => item.visible && <li>{item.name}</li>)}</ul>;
+}
+";
+        let score = |source: &str, path: &str| {
+            let functions = crate::calculate_complexity_with(
+                source,
+                Path::new(path),
+                &Weights::default(),
+                crate::depth::DEFAULT_MAX_DEPTH,
+            )
+            .unwrap();
+            functions
+                .iter()
+                .map(|f| (f.name.clone(), f.complexity))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            score(source, "api.ts"),
+            [("Api.get".to_string(), 2), ("useThing".to_string(), 2)]
+        );
+        assert_eq!(
+            score(tsx, "row.tsx"),
+            [("Row".to_string(), 1), ("Row.<anonymous>".to_string(), 2)]
+        );
+    }
+}
@@ -0,0 +1,2150 @@
+//! Cyclomatic and cognitive complexity of Python, JavaScript and TypeScript
+//! functions, the library behind the `complexity-audit` command.
+//!
+//! [`analyze_source`] scores a string of code, and [`analyze_path`] every
+//! file under a directory, with the walk and scoring set by
+//! [`AnalyzeOptions`]:
+//!
+//! ```
+//! use complexity_audit::language::Language;
+//! use complexity_audit::{analyze_path, analyze_source, AnalyzeOptions};
+//!
+//! let functions = analyze_source("const f = (a) => a ?? 0;", Language::JavaScript).unwrap();
+//! assert_eq!((functions[0].name.as_str(), functions[0].complexity), ("f", 2));
+//!
+//! let dir = tempfile::tempdir().unwrap();
+//! std::fs::write(dir.path().join("app.py"), "def main():\n    pass\n").unwrap();
+//! let options = AnalyzeOptions {
+//!     walk: complexity_audit::walk::Filter {
+//!         exclude: vec!["tests/**".to_string()],
+//!         ..Default::default()
+//!     },
+//!     ..Default::default()
+//! };
+//! let result = analyze_path(dir.path(), &options).unwrap();
+//! assert_eq!(result.summary.unwrap().total_functions, 1);
+//! ```
+//!
+//! Results serialize to the JSON that `--output json` prints, given the
+//! options its flags and [`config`] set.
+
+pub mod annotate;
+pub mod baseline;
+pub mod body_hash;
+pub mod categories;
+pub mod check;
+pub mod cognitive;
+pub mod compare;
+pub mod config;
+pub mod coverage;
+pub mod daemon;
+pub mod depth;
+pub mod diff;
+pub mod fingerprint;
+pub mod gates;
+pub mod generated;
+pub mod grammar;
+pub mod history;
+pub mod inheritance;
+pub mod language;
+pub mod links;
+pub mod locate;
+pub mod locals;
+pub mod merge;
+pub mod meta;
+pub mod output;
+pub mod owners;
+pub mod progress;
+pub mod radon;
+pub mod risk;
+pub mod rollup;
+pub mod rules;
+pub mod run_cache;
+pub mod severity;
+pub mod snapshot;
+pub mod suggest;
+pub mod trace;
+pub mod vendored;
+pub mod verify;
+pub mod walk;
+pub mod weights;
+pub mod workspace;
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use comfy_table::{Cell, Table};
+use fingerprint::fingerprint;
+use meta::Meta;
+use output::text;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use severity::{Severity, Thresholds};
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use tree_sitter::QueryCursor;
+use weights::Weights;
+use tree_sitter::Parser as TSParser;
+use walkdir::WalkDir;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FunctionComplexity {
+    pub name: String,
+    /// Path of the function's file as the analysis was given it.
+    pub file: String,
+    pub line: u32,
+    #[serde(default)]
+    pub column: u32,
+    pub end_line: u32,
+    /// Last line of the `def` signature, which differs from `line` when the
+    /// parameter list spans several lines.
+    #[serde(default)]
+    pub signature_end_line: u32,
+    pub complexity: u32,
+    /// Cognitive complexity, which weighs nesting (see [`cognitive`]).
+    #[serde(default)]
+    pub cognitive_complexity: u32,
+    /// Number of local variables the function binds, parameters excluded.
+    #[serde(default)]
+    pub locals: u32,
+    pub fingerprint: String,
+    /// Hash of the function's normalized source, which changes with its code
+    /// but not with its formatting.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub body_hash: String,
+    /// Label of the root the function was found under, when several are
+    /// analyzed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project: Option<String>,
+    /// Link to the function's first line, when requested with `--links`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// How far the function exceeds the thresholds, if it does.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub severity: Option<Severity>,
+    /// The `[[rules]]` entry of the configuration that set the severity,
+    /// when one applies to the function's file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rule: Option<rules::AppliedRule>,
+    /// Names of the function's decorators, without `@` or arguments.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub decorators: Vec<String>,
+    /// Base classes of the class the function is a method of.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub bases: Vec<String>,
+    /// Category from the `[[categories]]` of the configuration, when it has
+    /// any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    /// What the complexity is made of, kept with `--explain`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub breakdown: Vec<weights::Term>,
+    /// Whether this is a dunder method set apart with `--dunder separate`,
+    /// which does not count towards the functions above the threshold.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub dunder: bool,
+    /// Whether the function is in a generated module.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub generated: bool,
+    /// Change since the baseline given with `--baseline`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delta: Option<baseline::Delta>,
+    /// Risk score, when risk coefficients are configured.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub risk: Option<f64>,
+    /// Language of the function's file, left out for Python.
+    #[serde(default, skip_serializing_if = "language::Language::is_python")]
+    pub language: language::Language,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AnalysisResult {
+    pub functions: Vec<FunctionComplexity>,
+    /// Generated modules whose functions were collapsed with `--generated
+    /// collapse`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub generated_files: Vec<GeneratedFile>,
+    /// Functions of the baseline that no longer exist, with `--show-removed`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub removed: Vec<FunctionComplexity>,
+    /// Functions rolled up by file, with `--group-by file`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<rollup::FileComplexity>,
+    /// Functions rolled up by directory, with `--group-by directory`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub directories: Vec<rollup::DirectoryComplexity>,
+    /// Classes with the complexity of their own and inherited methods, with
+    /// `--inheritance`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub classes: Vec<inheritance::ClassReport>,
+    pub summary: Option<Summary>,
+    /// Summary gates with their target and actual values, when configured.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub gates: Vec<gates::GateResult>,
+    /// Files that were skipped, so readers know what the report does not cover.
+    #[serde(default)]
+    pub errors: Vec<FileIssue>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Meta>,
+}
+
+/// The functions of one generated module, summed up in a single row.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GeneratedFile {
+    pub file: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project: Option<String>,
+    pub functions: usize,
+    pub total_complexity: u64,
+    pub max_complexity: u32,
+    pub mean_complexity: f64,
+}
+
+impl GeneratedFile {
+    fn collapse(file: &str, functions: &[FunctionComplexity]) -> Self {
+        let total_complexity: u64 = functions.iter().map(|f| f.complexity as u64).sum();
+        GeneratedFile {
+            file: file.to_string(),
+            project: None,
+            functions: functions.len(),
+            total_complexity,
+            max_complexity: functions.iter().map(|f| f.complexity).max().unwrap_or(0),
+            mean_complexity: total_complexity as f64 / functions.len().max(1) as f64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileIssue {
+    pub path: String,
+    pub category: IssueCategory,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum IssueCategory {
+    Io,
+    Encoding,
+    Parse,
+    TooLarge,
+    Timeout,
+    TooDeep,
+}
+
+impl IssueCategory {
+    pub fn label(self) -> &'static str {
+        match self {
+            IssueCategory::Io => "io",
+            IssueCategory::Encoding => "encoding",
+            IssueCategory::Parse => "parse",
+            IssueCategory::TooLarge => "too-large",
+            IssueCategory::Timeout => "timeout",
+            IssueCategory::TooDeep => "too-deep",
+        }
+    }
+}
+
+impl FileIssue {
+    fn io(path: &Path, error: &std::io::Error) -> Self {
+        let category = match error.kind() {
+            std::io::ErrorKind::InvalidData => IssueCategory::Encoding,
+            _ => IssueCategory::Io,
+        };
+        FileIssue {
+            path: path.to_string_lossy().to_string(),
+            category,
+            message: error.to_string(),
+        }
+    }
+
+    /// A directory or file the walk could not enter or list.
+    fn walk(root: &Path, error: &walkdir::Error) -> Self {
+        let path = error.path().unwrap_or(root);
+        match error.io_error() {
+            Some(io) => FileIssue::io(path, io),
+            None => FileIssue {
+                path: path.to_string_lossy().to_string(),
+                category: IssueCategory::Io,
+                message: error.to_string(),
+            },
+        }
+    }
+}
+
+/// One-line account of skipped files, e.g. "4 files skipped (3 io, 1 parse)".
+pub fn skipped_line(errors: &[FileIssue]) -> Option<String> {
+    if errors.is_empty() {
+        return None;
+    }
+    let mut counts: Vec<(IssueCategory, usize)> = Vec::new();
+    for error in errors {
+        match counts.iter_mut().find(|(category, _)| *category == error.category) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((error.category, 1)),
+        }
+    }
+    counts.sort();
+    let breakdown: Vec<String> = counts
+        .iter()
+        .map(|(category, count)| format!("{} {}", count, category.label()))
+        .collect();
+    Some(format!(
+        "{} file{} skipped ({})",
+        errors.len(),
+        if errors.len() == 1 { "" } else { "s" },
+        breakdown.join(", ")
+    ))
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Summary {
+    pub mean_complexity: f64,
+    pub max_complexity: u32,
+    pub p50_complexity: u32,
+    pub p90_complexity: u32,
+    pub p95_complexity: u32,
+    /// Gini coefficient of the complexities: 0 when complexity is spread
+    /// evenly, approaching 1 when a few functions hold all of it.
+    #[serde(default)]
+    pub gini_coefficient: f64,
+    /// Share of the total complexity held by the most complex 10% of
+    /// functions (at least one function).
+    #[serde(default)]
+    pub top_decile_share: f64,
+    pub total_functions: usize,
+    pub functions_above_threshold: usize,
+    /// Functions found in generated modules, whether they are listed,
+    /// excluded or collapsed.
+    #[serde(default)]
+    pub generated_functions: usize,
+    #[serde(default)]
+    pub worst_files: Vec<WorstFile>,
+    /// Functions with the highest risk score, when risk coefficients are
+    /// configured.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub top_risk: Vec<risk::RiskyFunction>,
+    /// Functions whose risk exceeds `--risk-threshold`, when given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub functions_above_risk_threshold: Option<usize>,
+    /// Per-project breakdown when several labelled roots are analyzed.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub projects: Vec<ProjectSummary>,
+    /// Per-category breakdown, with `--group-by category`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub categories: Vec<categories::CategorySummary>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProjectSummary {
+    pub project: String,
+    pub threshold: u32,
+    pub total_functions: usize,
+    pub mean_complexity: f64,
+    pub max_complexity: u32,
+    pub functions_above_threshold: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WorstFile {
+    pub file: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project: Option<String>,
+    pub max_complexity: u32,
+    pub total_complexity: u64,
+    pub functions_above_threshold: usize,
+    /// Most complex function of the file.
+    pub worst_function: String,
+    pub worst_function_line: u32,
+}
+
+/// Criterion the worst files are ranked by.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FileRanking {
+    /// Highest function complexity
+    #[default]
+    Max,
+    /// Sum of the complexities of all functions
+    Total,
+    /// Number of functions above the threshold
+    AboveThreshold,
+}
+
+/// The functions of the Python `source`.
+#[cfg(test)]
+fn calculate_complexity(source: &str, weights: &Weights) -> Result<Vec<FunctionComplexity>> {
+    calculate_complexity_with(source, Path::new("source.py"), weights, depth::DEFAULT_MAX_DEPTH)
+}
+
+/// The functions of `source`, Python, JavaScript or TypeScript code in
+/// `language`, scored with the default weights. Their `file` is empty and
+/// they have no fingerprint, which is relative to an analyzed directory.
+///
+/// ```
+/// use complexity_audit::analyze_source;
+/// use complexity_audit::language::Language;
+///
+/// let source = "def check(x):\n    if x > 0:\n        return 1\n    return 0\n";
+/// let functions = analyze_source(source, Language::Python).unwrap();
+/// assert_eq!(functions[0].name, "check");
+/// assert_eq!(functions[0].line, 1);
+/// assert_eq!(functions[0].complexity, 2);
+/// ```
+pub fn analyze_source(
+    source: &str,
+    language: language::Language,
+) -> Result<Vec<FunctionComplexity>> {
+    score(
+        source,
+        Path::new(""),
+        None,
+        false,
+        language,
+        &Weights::default(),
+        depth::DEFAULT_MAX_DEPTH,
+    )
+}
+
+/// The functions of `source`, the content of the file at `path`, in the
+/// language of its extension (Python for any other), failing with
+/// [`depth::TooDeep`] when the syntax tree is nested more than `max_depth`
+/// levels deep. Fingerprints are of `path` as given.
+pub fn calculate_complexity_with(
+    source: &str,
+    path: &Path,
+    weights: &Weights,
+    max_depth: usize,
+) -> Result<Vec<FunctionComplexity>> {
+    let language = language::Language::of(path).unwrap_or_default();
+    let file = path.to_string_lossy();
+    score(source, path, Some(&file), false, language, weights, max_depth)
+}
+
+/// The functions of `source` in `language`, each with `path` as its file,
+/// fingerprinted by `relative`, the path of the file relative to the
+/// analyzed root (without a fingerprint when there is no file), and marked
+/// `generated` as given.
+fn score(
+    source: &str,
+    path: &Path,
+    relative: Option<&str>,
+    generated: bool,
+    language: language::Language,
+    weights: &Weights,
+    max_depth: usize,
+) -> Result<Vec<FunctionComplexity>> {
+    let grammar = language.grammar(path);
+    let mut parser = TSParser::new();
+    parser.set_language(grammar).unwrap();
+
+    let tree = parser
+        .parse(source, None)
+        .with_context(|| format!("Failed to parse {} code", language.name()))?;
+    depth::check(tree.root_node(), max_depth)?;
+    // Functions of a tree with errors would be scored on what tree-sitter
+    // made of the broken code, so the file is reported instead.
+    if let Some(error) = syntax_error(tree.root_node()) {
+        anyhow::bail!(
+            "syntax error at line {}, column {}",
+            error.start_position().row + 1,
+            error.start_position().column + 1
+        );
+    }
+    let mut results = Vec::new();
+    let file = path.to_string_lossy().to_string();
+
+    let query = language.compiled_functions_query(path);
+    let function_index = query.capture_index_for_name("function").unwrap();
+    let body_index = query.capture_index_for_name("body").unwrap();
+
+    let mut query_cursor = QueryCursor::new();
+    let matches = query_cursor.matches(query, tree.root_node(), source.as_bytes());
+
+    for m in matches {
+        let capture = |index| m.captures.iter().find(|c| c.index == index).unwrap().node;
+        let function_node = capture(function_index);
+        let body_node = capture(body_index);
+
+        let own_name = language::function_name(function_node, source);
+        // An arrow function's or lambda's body may be a bare expression,
+        // counted whole.
+        let scope = match body_node.kind() {
+            "block" | "statement_block" => body_node,
+            _ => function_node,
+        };
+        let counts = weights.counts(scope);
+        let complexity = weights.total(&counts).saturating_add(1); // Base complexity plus weighted constructs
+
+        let name = language::qualified_name(function_node, source);
+        results.push(FunctionComplexity {
+            dunder: is_dunder(&own_name) && is_method(function_node),
+            fingerprint: relative.map_or_else(String::new, |relative| fingerprint(relative, &name)),
+            name,
+            file: file.clone(),
+            line: function_node.start_position().row as u32 + 1,
+            column: function_node.start_position().column as u32 + 1,
+            end_line: function_node.end_position().row as u32 + 1,
+            signature_end_line: body_node
+                .prev_sibling()
+                .unwrap_or(function_node)
+                .end_position()
+                .row as u32
+                + 1,
+            complexity,
+            cognitive_complexity: cognitive::complexity(scope),
+            locals: locals::count(body_node, source),
+            body_hash: body_hash::hash(function_node, source),
+            project: None,
+            url: None,
+            severity: None,
+            rule: None,
+            decorators: decorators(function_node, source),
+            bases: class_bases(function_node, source),
+            category: None,
+            breakdown: weights.explain(&counts),
+            generated,
+            delta: None,
+            risk: None,
+            language,
+        });
+    }
+
+    Ok(results)
+}
+
+/// The first node under `root` that tree-sitter could not parse, or had to
+/// make up, if any.
+fn syntax_error(root: tree_sitter::Node) -> Option<tree_sitter::Node> {
+    let mut node = root.has_error().then_some(root)?;
+    loop {
+        if node.is_error() || node.is_missing() {
+            return Some(node);
+        }
+        let mut cursor = node.walk();
+        let Some(child) = node.children(&mut cursor).find(|child| child.has_error()) else {
+            return Some(node);
+        };
+        node = child;
+    }
+}
+
+/// `__name__`, the naming scheme Python reserves for special methods.
+fn is_dunder(name: &str) -> bool {
+    name.len() > 4 && name.starts_with("__") && name.ends_with("__")
+}
+
+/// Whether `function` is defined directly in a class body, possibly behind
+/// decorators.
+fn is_method(function: tree_sitter::Node) -> bool {
+    let mut node = function;
+    if let Some(parent) = node.parent().filter(|p| p.kind() == "decorated_definition") {
+        node = parent;
+    }
+    node.parent()
+        .filter(|block| block.kind() == "block")
+        .and_then(|block| block.parent())
+        .is_some_and(|owner| owner.kind() == "class_definition")
+}
+
+/// Names of the decorators of `function`, without arguments and with
+/// whitespace removed: `app.route` for `@app.route("/")`.
+fn decorators(function: tree_sitter::Node, source: &str) -> Vec<String> {
+    let Some(decorated) = function.parent().filter(|p| p.kind() == "decorated_definition") else {
+        return Vec::new();
+    };
+    let mut cursor = decorated.walk();
+    let names = decorated
+        .children(&mut cursor)
+        .filter(|child| child.kind() == "decorator")
+        .filter_map(|decorator| {
+            let mut expression = decorator.named_child(0)?;
+            if expression.kind() == "call" {
+                expression = expression.child_by_field_name("function")?;
+            }
+            expression.utf8_text(source.as_bytes()).ok()
+        })
+        .map(|name| name.split_whitespace().collect())
+        .collect();
+    names
+}
+
+/// Base classes of the class `function` is a method of, as written, without
+/// keyword arguments such as `metaclass=`.
+fn class_bases(function: tree_sitter::Node, source: &str) -> Vec<String> {
+    if !is_method(function) {
+        return Vec::new();
+    }
+    let mut node = function;
+    while node.kind() != "class_definition" {
+        let Some(parent) = node.parent() else {
+            return Vec::new();
+        };
+        node = parent;
+    }
+    let Some(superclasses) = node.child_by_field_name("superclasses") else {
+        return Vec::new();
+    };
+    let mut cursor = superclasses.walk();
+    let bases = superclasses
+        .named_children(&mut cursor)
+        .filter(|base| base.kind() != "keyword_argument" && base.kind() != "comment")
+        .filter_map(|base| base.utf8_text(source.as_bytes()).ok())
+        .map(|base| base.split_whitespace().collect())
+        .collect();
+    bases
+}
+
+/// What to do with dunder methods such as `__repr__` or `__eq__`.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DunderMode {
+    /// Treat them like any other method
+    #[default]
+    Include,
+    /// Leave them out of the listing and the summary
+    Exclude,
+    /// List them, tagged as dunder, but never count them as above the
+    /// threshold
+    Separate,
+}
+
+/// Threshold of an analysis by default.
+pub const DEFAULT_THRESHOLD: u32 = 10;
+
+/// How an analysis scores functions, which files it skips beyond the fixed
+/// cache and virtualenv directories, and what it reports about them.
+#[derive(Debug, Clone)]
+pub struct AnalyzeOptions {
+    /// Complexity above which a function counts as above the threshold in
+    /// the summary, and is reported as a warning.
+    pub threshold: u32,
+    /// Complexity above which a function is reported as an error, instead
+    /// of twice `threshold`.
+    pub error_threshold: Option<u32>,
+    /// Complexity above which a function below `threshold` is reported as
+    /// info.
+    pub info_threshold: Option<u32>,
+    /// Preset `weights` started from, as the run metadata names it.
+    pub preset: weights::Preset,
+    pub weights: Weights,
+    pub vendored: Option<vendored::VendorDetector>,
+    /// Report every skipped file on stderr.
+    pub verbose: bool,
+    /// Keep each function's breakdown by construct.
+    pub explain: bool,
+    pub dunder: DunderMode,
+    /// Dunder methods exempt from `dunder`, such as `__init__`.
+    pub keep_dunder: Vec<String>,
+    pub generated: generated::GeneratedDetector,
+    pub generated_mode: generated::GeneratedMode,
+    pub progress: Option<progress::Reporter>,
+    /// Records how one file is treated, and skips all others.
+    pub trace: Option<trace::Trace>,
+    /// Daemon that scores the files instead of this process.
+    pub daemon: Option<daemon::Client>,
+    /// Nesting depth above which a file is skipped, instead of
+    /// [`depth::DEFAULT_MAX_DEPTH`].
+    pub max_depth: Option<usize>,
+    /// The only language analyzed, instead of all of them.
+    pub language: Option<language::Language>,
+    /// Paths the walk leaves out besides those of other languages.
+    pub walk: walk::Filter,
+    /// Complexity the thresholds apply to.
+    pub metric: severity::Metric,
+    /// Threads scoring files, instead of one per core.
+    pub jobs: Option<NonZeroUsize>,
+    /// What ranks the worst files of the summary.
+    pub rank_files_by: FileRanking,
+    /// Number of worst files in the summary.
+    pub top_files: usize,
+    /// Categories the functions are sorted into, whose thresholds replace
+    /// the others.
+    pub categories: Vec<categories::Category>,
+    /// Roll the summary up by category.
+    pub category_summary: bool,
+    /// Coefficients of the risk score, which is not computed without any.
+    pub risk: Vec<(risk::Metric, f64)>,
+    /// Risk above which a function counts in the summary.
+    pub risk_threshold: Option<f64>,
+    /// Report the classes under the analyzed directories.
+    pub inheritance: bool,
+    /// Thresholds for the functions under given paths, which replace those
+    /// of the categories.
+    pub rules: Option<rules::Rules>,
+    /// Link each function to the line it starts on.
+    pub links: Option<links::LinkOptions>,
+    /// Describe the run in the `meta` of the result.
+    pub meta: bool,
+}
+
+impl Default for AnalyzeOptions {
+    fn default() -> Self {
+        AnalyzeOptions {
+            threshold: DEFAULT_THRESHOLD,
+            error_threshold: None,
+            info_threshold: None,
+            preset: weights::Preset::default(),
+            weights: Weights::default(),
+            vendored: None,
+            verbose: false,
+            explain: false,
+            dunder: DunderMode::default(),
+            keep_dunder: Vec::new(),
+            generated: generated::GeneratedDetector::default(),
+            generated_mode: generated::GeneratedMode::default(),
+            progress: None,
+            trace: None,
+            daemon: None,
+            max_depth: None,
+            language: None,
+            walk: walk::Filter::default(),
+            metric: severity::Metric::default(),
+            jobs: None,
+            rank_files_by: FileRanking::default(),
+            top_files: DEFAULT_TOP_FILES,
+            categories: Vec::new(),
+            category_summary: false,
+            risk: Vec::new(),
+            risk_threshold: None,
+            inheritance: false,
+            rules: None,
+            links: None,
+            meta: false,
+        }
+    }
+}
+
+/// Stack of the threads analyzing files: that of a main thread rather than
+/// rayon's default, for the walks over a file that still recurse.
+const THREAD_STACK_SIZE: usize = 8 * 1024 * 1024;
+
+#[cfg(test)]
+fn analyze_directory(path: &Path, threshold: u32) -> Result<AnalysisResult> {
+    analyze_path(
+        path,
+        &AnalyzeOptions {
+            threshold,
+            ..Default::default()
+        },
+    )
+}
+
+/// The functions of the files under the directory `path`, or of the file
+/// `path`, with their summary, the files skipped and why, and whatever else
+/// `options` asks for: this is the result `--output json` prints. A file
+/// named directly is analyzed even when the walk of its directory would
+/// leave it out, and in `options.language` when it has one.
+///
+/// ```
+/// use complexity_audit::{analyze_path, AnalyzeOptions};
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// std::fs::write(dir.path().join("app.py"), "def main():\n    pass\n").unwrap();
+///
+/// let options = AnalyzeOptions {
+///     threshold: 5,
+///     ..Default::default()
+/// };
+/// let result = analyze_path(dir.path(), &options).unwrap();
+/// assert_eq!(result.functions[0].name, "main");
+/// assert!(result.functions[0].file.ends_with("app.py"));
+/// assert_eq!(result.summary.unwrap().functions_above_threshold, 0);
+/// ```
+pub fn analyze_path(path: &Path, options: &AnalyzeOptions) -> Result<AnalysisResult> {
+    let mut result = scan(path, options)?;
+    let root = match path.parent() {
+        Some(parent) if path.is_file() => directory(parent),
+        _ => path.to_path_buf(),
+    };
+    if let Some(links) = &options.links {
+        links::Linker::detect(&root, links.base.clone(), links.template.clone())
+            .annotate(&mut result);
+    }
+    finish(&mut result, &[(None, root)], &[], options);
+    Ok(result)
+}
+
+/// The functions of the files under `path` and their summary, as scored,
+/// before [`finish`] adds to them.
+pub(crate) fn scan(path: &Path, options: &AnalyzeOptions) -> Result<AnalysisResult> {
+    // Paths of a file are relative to its directory.
+    let mut walk_errors = Vec::new();
+    let (root, files): (&Path, Vec<PathBuf>) = match path.parent() {
+        Some(parent) if path.is_file() => (parent, vec![path.to_path_buf()]),
+        _ => (
+            path,
+            discover(
+                path,
+                options.trace.as_ref(),
+                options.language,
+                &options.walk,
+            )
+            .filter_map(|entry| entry.map_err(|issue| walk_errors.push(issue)).ok())
+            .collect(),
+        ),
+    };
+    if let Some(progress) = &options.progress {
+        progress.emit(&progress::Event::Discovered { files: files.len() });
+    }
+    // Files are scored in parallel, and collected in the order the walk
+    // found them so that results do not depend on the number of threads.
+    let score = || {
+        files
+            .par_iter()
+            .filter(|file_path| options.trace.as_ref().is_none_or(|trace| trace.concerns(file_path)))
+            .map(|file_path| {
+                let started = Instant::now();
+                let mut errors = Vec::new();
+                let functions = analyze_file(root, file_path, None, options, &mut errors);
+                if let Some(progress) = &options.progress {
+                    progress.emit(&progress::Event::FileDone {
+                        path: file_path.to_string_lossy().to_string(),
+                        functions: functions.len(),
+                        elapsed_ms: started.elapsed().as_millis() as u64,
+                    });
+                }
+                (file_path, functions, errors)
+            })
+            .collect::<Vec<_>>()
+    };
+    let scored = rayon::ThreadPoolBuilder::new()
+        .num_threads(options.jobs.map_or(0, NonZeroUsize::get))
+        .stack_size(THREAD_STACK_SIZE)
+        .build()?
+        .install(score);
+    let mut result = collect(scored, options);
+    result.errors.splice(0..0, walk_errors);
+    Ok(result)
+}
+
+/// The functions of `source`, the content of the file at `path`, which may
+/// differ from what is on disk or not be saved at all: what
+/// [`analyze_path`] reports for the file with that content. The language
+/// is that of `options.language`, or else of the extension of `path`.
+///
+/// ```
+/// use complexity_audit::{analyze_buffer, AnalyzeOptions};
+/// use std::path::Path;
+///
+/// let source = "def unsaved(x):\n    return x or 0\n";
+/// let result = analyze_buffer(source, Path::new("src/app.py"), &AnalyzeOptions::default());
+/// assert_eq!(result.functions[0].file, "src/app.py");
+/// assert_eq!(result.functions[0].complexity, 2);
+/// ```
+pub fn analyze_buffer(source: &str, path: &Path, options: &AnalyzeOptions) -> AnalysisResult {
+    let root = path.parent().unwrap_or(path);
+    let mut errors = Vec::new();
+    let functions = analyze_file(root, path, Some(source), options, &mut errors);
+    let mut result = collect(vec![(path, functions, errors)], options);
+    let root = directory(root);
+    if let Some(links) = &options.links {
+        links::Linker::detect(&root, links.base.clone(), links.template.clone())
+            .annotate(&mut result);
+    }
+    finish(&mut result, &[(None, root)], &[], options);
+    result
+}
+
+/// `dir`, or the current directory when it is the empty parent of a bare
+/// file name.
+fn directory(dir: &Path) -> PathBuf {
+    if dir.as_os_str().is_empty() {
+        PathBuf::from(".")
+    } else {
+        dir.to_path_buf()
+    }
+}
+
+/// Adds to `result`, the functions found under `roots` (each an optional
+/// project label with its directory), what `options` asks for beyond their
+/// scores: the worst files, categories, risk, classes, severities, rules
+/// and the run metadata. The functions of `projects` are held to their own
+/// thresholds.
+pub(crate) fn finish(
+    result: &mut AnalysisResult,
+    roots: &[(Option<String>, PathBuf)],
+    projects: &[workspace::Project],
+    options: &AnalyzeOptions,
+) {
+    if let Some(summary) = &mut result.summary {
+        summary.worst_files = worst_files(
+            &result.functions,
+            options.threshold,
+            options.metric,
+            options.rank_files_by,
+            options.top_files,
+        );
+    }
+    if !options.categories.is_empty() {
+        categories::classify(&options.categories, &mut result.functions);
+        if let (Some(summary), true) = (&mut result.summary, options.category_summary) {
+            summary.categories =
+                categories::summarize(&options.categories, &result.functions, options.threshold);
+        }
+    }
+    let risk_model = risk::RiskModel::new(&options.risk);
+    if !risk_model.is_empty() {
+        for func in &mut result.functions {
+            func.risk = risk_model.score(func);
+        }
+        if let Some(summary) = &mut result.summary {
+            summary.top_risk = risk::top(&result.functions, risk::TOP_FUNCTIONS);
+            summary.functions_above_risk_threshold = options.risk_threshold.map(|threshold| {
+                let risks = result.functions.iter().filter_map(|f| f.risk);
+                risks.filter(|&risk| risk > threshold).count()
+            });
+        }
+    }
+    if options.inheritance {
+        result.classes = inheritance::analyze(result, roots);
+    }
+    let thresholds = |warn| Thresholds {
+        metric: options.metric,
+        ..Thresholds::new(warn, options.error_threshold, options.info_threshold)
+    };
+    severity::annotate(result, |label| {
+        let project = projects.iter().find(|project| Some(project.label.as_str()) == label);
+        thresholds(project.map_or(options.threshold, |project| project.options.threshold))
+    });
+    categories::apply_thresholds(&options.categories, &mut result.functions);
+    if let Some(rules) = &options.rules {
+        let base = rules.base.canonicalize().unwrap_or_else(|_| rules.base.clone());
+        let mut relative_paths: HashMap<PathBuf, Option<String>> = HashMap::new();
+        rules.apply(result, |func| {
+            let source = match &func.project {
+                Some(project) => {
+                    let (_, root) =
+                        roots.iter().find(|(label, _)| label.as_ref() == Some(project))?;
+                    root.join(&func.file)
+                }
+                None => PathBuf::from(&func.file),
+            };
+            relative_paths
+                .entry(source)
+                .or_insert_with_key(|source| {
+                    let source = source.canonicalize().ok()?;
+                    let relative = source.strip_prefix(&base).ok()?;
+                    Some(output::relative_path(&relative.to_string_lossy(), Path::new("")))
+                })
+                .clone()
+        });
+    }
+    if options.meta {
+        let paths: Vec<PathBuf> = roots.iter().map(|(_, path)| path.clone()).collect();
+        let filters = filters(options, projects);
+        let mut meta = Meta::collect(&paths, &thresholds(options.threshold), filters);
+        meta.preset = Some(options.preset.name().to_string());
+        meta.weights = options.weights.overrides(options.preset);
+        meta.risk = risk_model.describe();
+        result.meta = Some(meta);
+    }
+}
+
+/// The options that leave functions or files out, spelled as the flags
+/// that set them.
+fn filters(options: &AnalyzeOptions, projects: &[workspace::Project]) -> Vec<String> {
+    let mut filters = Vec::new();
+    if let Some(vendored) = &options.vendored {
+        filters.push("--skip-vendored".to_string());
+        for dir in vendored.extra() {
+            filters.push(format!("--vendored-dir={}", dir));
+        }
+    }
+    if options.dunder != DunderMode::Include {
+        let mode = options.dunder.to_possible_value().unwrap();
+        filters.push(format!("--dunder={}", mode.get_name()));
+    }
+    for name in &options.keep_dunder {
+        filters.push(format!("--keep-dunder={}", name));
+    }
+    if options.generated_mode != generated::GeneratedMode::Include {
+        let mode = options.generated_mode.to_possible_value().unwrap();
+        filters.push(format!("--generated={}", mode.get_name()));
+    }
+    for pattern in options.generated.extra() {
+        filters.push(format!("--generated-pattern={}", pattern));
+    }
+    for project in projects {
+        if options.vendored.is_none() && project.options.vendored.is_some() {
+            filters.push(format!("--project-skip-vendored={}", project.label));
+        }
+    }
+    for project in projects {
+        if project.options.threshold != options.threshold {
+            let threshold = project.options.threshold;
+            filters.push(format!("--project-threshold={}={}", project.label, threshold));
+        }
+    }
+    if let Some(max_depth) = options.max_depth.filter(|&depth| depth != depth::DEFAULT_MAX_DEPTH) {
+        filters.push(format!("--max-depth={}", max_depth));
+    }
+    filters
+}
+
+/// The result of an analysis from the functions and skipped files of each
+/// file scored, in order.
+fn collect<P: AsRef<Path>>(
+    scored: Vec<(P, Vec<FunctionComplexity>, Vec<FileIssue>)>,
+    options: &AnalyzeOptions,
+) -> AnalysisResult {
+    let mut all_functions = Vec::new();
+    let mut errors = Vec::new();
+    let mut generated_files = Vec::new();
+    // Functions of generated modules left out of `all_functions`.
+    let mut hidden_generated = 0;
+    for (file_path, functions, file_errors) in scored {
+        errors.extend(file_errors);
+        let mode = options.generated_mode;
+        if mode != generated::GeneratedMode::Include && functions.iter().any(|f| f.generated) {
+            hidden_generated += functions.len();
+            if mode == generated::GeneratedMode::Collapse {
+                let file = file_path.as_ref().to_string_lossy();
+                generated_files.push(GeneratedFile::collapse(&file, &functions));
+            }
+            continue;
+        }
+        all_functions.extend(functions);
+    }
+
+    let mut summary = summarize(&all_functions, options.threshold, options.metric);
+    if let Some(summary) = &mut summary {
+        summary.generated_functions += hidden_generated;
+    }
+
+    AnalysisResult {
+        functions: all_functions,
+        generated_files,
+        summary,
+        errors,
+        ..Default::default()
+    }
+}
+
+/// Functions of the file at `file_path` under the analyzed root `path`, with
+/// `source` as its content instead of what is on disk when given, or none
+/// when it is skipped.
+fn analyze_file(
+    path: &Path,
+    file_path: &Path,
+    source: Option<&str>,
+    options: &AnalyzeOptions,
+    errors: &mut Vec<FileIssue>,
+) -> Vec<FunctionComplexity> {
+    let trace = options.trace.as_ref();
+    let skip = |reason: String| {
+        if options.verbose {
+            eprintln!("skipping vendored {}: {}", file_path.display(), reason);
+        }
+        trace::record(trace, file_path, || trace::Step::Vendored { reason });
+    };
+    if let Some(detector) = &options.vendored {
+        let relative = file_path.strip_prefix(path).unwrap_or(file_path);
+        if let Some(reason) = detector.check_path(relative) {
+            skip(reason);
+            return Vec::new();
+        }
+    }
+    let on_disk = source.is_none();
+    let read;
+    let source = match source {
+        Some(source) => source,
+        None => match std::fs::read_to_string(file_path) {
+            Ok(content) => {
+                read = content;
+                &read
+            }
+            Err(error) => {
+                errors.push(FileIssue::io(file_path, &error));
+                trace::record(trace, file_path, || trace::Step::Unreadable {
+                    error: error.to_string(),
+                });
+                return Vec::new();
+            }
+        },
+    };
+    if let Some(reason) = options
+        .vendored
+        .as_ref()
+        .and_then(|detector| detector.check_source(source))
+    {
+        skip(reason);
+        return Vec::new();
+    }
+    let relative = file_path.strip_prefix(path).unwrap_or(file_path);
+    let generated = options
+        .generated
+        .check_path(relative)
+        .or_else(|| options.generated.check_source(source));
+    // Walked files are in the language of their extension, which
+    // `options.language` only ever agrees with.
+    let extension_language = language::Language::of(file_path);
+    let language = options.language.or(extension_language).unwrap_or_default();
+    // A daemon that cannot be reached leaves the file to this process. It
+    // reads files from disk, and tells their language by their extension.
+    let computed = options
+        .daemon
+        .as_ref()
+        .filter(|_| on_disk && extension_language == Some(language))
+        .and_then(|daemon| {
+            daemon.analyze_file(
+                file_path,
+                path,
+                generated.is_some(),
+                &options.weights,
+                options.max_depth,
+            )
+        })
+        .unwrap_or_else(|| {
+            let max_depth = options.max_depth.unwrap_or(depth::DEFAULT_MAX_DEPTH);
+            let relative = output::relative_path(&file_path.to_string_lossy(), path);
+            score(
+                source,
+                file_path,
+                Some(&relative),
+                generated.is_some(),
+                language,
+                &options.weights,
+                max_depth,
+            )
+        });
+    let mut functions = match computed {
+        Ok(functions) => functions,
+        Err(error) => {
+            let category = if error.is::<depth::TooDeep>() {
+                IssueCategory::TooDeep
+            } else {
+                IssueCategory::Parse
+            };
+            errors.push(FileIssue {
+                path: file_path.to_string_lossy().to_string(),
+                category,
+                message: error.to_string(),
+            });
+            trace::record(trace, file_path, || trace::Step::ParseError {
+                message: error.to_string(),
+            });
+            return Vec::new();
+        }
+    };
+
+    if let Some(reason) = &generated {
+        trace::record(trace, file_path, || trace::Step::Generated {
+            reason: reason.clone(),
+            mode: options.generated_mode.to_possible_value().unwrap().get_name().to_string(),
+        });
+    }
+    for func in &mut functions {
+        if !options.explain {
+            func.breakdown.clear();
+        }
+        func.dunder &= options.dunder != DunderMode::Include
+            && !options
+                .keep_dunder
+                .iter()
+                .any(|name| name == language::own_name(&func.name));
+    }
+    if options.dunder == DunderMode::Exclude {
+        let before = functions.len();
+        functions.retain(|func| !func.dunder);
+        if functions.len() < before {
+            trace::record(trace, file_path, || trace::Step::DunderExcluded {
+                functions: before - functions.len(),
+            });
+        }
+    }
+    trace::record(trace, file_path, || trace::Step::Analyzed {
+        functions: functions.len(),
+    });
+    functions
+}
+
+/// Python files under `path`, skipping bytecode caches, virtualenvs and
+/// what git ignores, and what cannot be walked.
+pub fn python_files(path: &Path) -> impl Iterator<Item = PathBuf> {
+    discover(path, None, Some(language::Language::Python), &walk::Filter::default())
+        .filter_map(Result::ok)
+}
+
+/// Files of `only` that language, or of any, under `path` that `filter`
+/// keeps, recording what is decided about the traced file, and the
+/// directories and files that could not be walked.
+pub fn discover<'a>(
+    path: &Path,
+    trace: Option<&'a trace::Trace>,
+    only: Option<language::Language>,
+    filter: &walk::Filter,
+) -> impl Iterator<Item = Result<PathBuf, FileIssue>> + 'a {
+    let root = path.to_path_buf();
+    let walked = path.to_path_buf();
+    let filter = filter.clone();
+    let mut ignores = walk::Ignores::new(path);
+    WalkDir::new(path)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(move |entry| {
+            let path = entry.path();
+            if entry.file_type().is_dir() {
+                let step = filter.exclusion(&root, path, true, &mut ignores);
+                if let (Some(trace), Some(step)) = (trace, &step) {
+                    trace.record_within(path, step.clone());
+                }
+                return step.is_none();
+            }
+            let step = exclusion(path, only)
+                .or_else(|| filter.exclusion(&root, path, false, &mut ignores));
+            trace::record(trace, path, || step.clone().unwrap_or(trace::Step::Discovered));
+            step.is_none()
+        })
+        .filter_map(move |entry| match entry {
+            Ok(entry) if entry.file_type().is_dir() => None,
+            Ok(entry) => Some(Ok(entry.into_path())),
+            Err(error) => Some(Err(FileIssue::walk(&walked, &error))),
+        })
+}
+
+/// Why the directory walk leaves out `path`, a file, for its language.
+fn exclusion(path: &Path, only: Option<language::Language>) -> Option<trace::Step> {
+    let Some(language) = language::Language::of(path) else {
+        return Some(trace::Step::Unsupported {
+            extension: path.extension().map(|ext| ext.to_string_lossy().into_owned()),
+        });
+    };
+    only.filter(|&only| only != language)
+        .map(|only| trace::Step::OtherLanguage {
+            language: language.name().to_string(),
+            only: only.name().to_string(),
+        })
+}
+
+/// Number of worst files listed in the summary by default.
+pub const DEFAULT_TOP_FILES: usize = 5;
+
+/// Summary statistics over `functions`, or `None` when there are none.
+pub fn summarize(
+    functions: &[FunctionComplexity],
+    threshold: u32,
+    metric: severity::Metric,
+) -> Option<Summary> {
+    if functions.is_empty() {
+        return None;
+    }
+
+    let mut complexities: Vec<u32> = functions.iter().map(|f| f.complexity).collect();
+    complexities.sort_unstable();
+    let total_complexity: u64 = complexities.iter().map(|&c| c as u64).sum();
+
+    Some(Summary {
+        mean_complexity: total_complexity as f64 / complexities.len() as f64,
+        max_complexity: complexities[complexities.len() - 1],
+        p50_complexity: percentile(&complexities, 50),
+        p90_complexity: percentile(&complexities, 90),
+        p95_complexity: percentile(&complexities, 95),
+        gini_coefficient: gini(&complexities, total_complexity),
+        top_decile_share: top_decile_share(&complexities, total_complexity),
+        total_functions: functions.len(),
+        functions_above_threshold: functions
+            .iter()
+            .filter(|f| above(f, threshold, metric))
+            .count(),
+        generated_functions: functions.iter().filter(|f| f.generated).count(),
+        worst_files: worst_files(
+            functions,
+            threshold,
+            metric,
+            FileRanking::default(),
+            DEFAULT_TOP_FILES,
+        ),
+        top_risk: Vec::new(),
+        functions_above_risk_threshold: None,
+        projects: Vec::new(),
+        categories: Vec::new(),
+    })
+}
+
+/// Whether the complexity `metric` applies to of `func` is above the
+/// threshold; set-apart dunder methods never are.
+pub fn above(func: &FunctionComplexity, threshold: u32, metric: severity::Metric) -> bool {
+    !func.dunder && metric.value(func) > threshold
+}
+
+/// The `top` files ranking highest by `ranking`, each with its most complex
+/// function.
+pub fn worst_files(
+    functions: &[FunctionComplexity],
+    threshold: u32,
+    metric: severity::Metric,
+    ranking: FileRanking,
+    top: usize,
+) -> Vec<WorstFile> {
+    let mut files: Vec<WorstFile> = Vec::new();
+    for func in functions {
+        let index = match files
+            .iter()
+            .position(|f| f.file == func.file && f.project == func.project)
+        {
+            Some(index) => index,
+            None => {
+                files.push(WorstFile {
+                    file: func.file.clone(),
+                    project: func.project.clone(),
+                    max_complexity: func.complexity,
+                    total_complexity: 0,
+                    functions_above_threshold: 0,
+                    worst_function: func.name.clone(),
+                    worst_function_line: func.line,
+                });
+                files.len() - 1
+            }
+        };
+        let file = &mut files[index];
+        file.total_complexity += func.complexity as u64;
+        if above(func, threshold, metric) {
+            file.functions_above_threshold += 1;
+        }
+        if func.complexity > file.max_complexity {
+            file.max_complexity = func.complexity;
+            file.worst_function = func.name.clone();
+            file.worst_function_line = func.line;
+        }
+    }
+
+    let key = |file: &WorstFile| match ranking {
+        FileRanking::Max => file.max_complexity as u64,
+        FileRanking::Total => file.total_complexity,
+        FileRanking::AboveThreshold => file.functions_above_threshold as u64,
+    };
+    files.sort_by(|a, b| {
+        key(b)
+            .cmp(&key(a))
+            .then(b.max_complexity.cmp(&a.max_complexity))
+            .then_with(|| a.file.cmp(&b.file))
+    });
+    files.truncate(top);
+    files
+}
+
+/// Mean of `count` values summing to `total`, `None` when there are none.
+pub fn mean(total: u64, count: usize) -> Option<f64> {
+    (count > 0).then(|| total as f64 / count as f64)
+}
+
+/// Gini coefficient of an ascending, non-empty slice summing to `total`.
+fn gini(sorted: &[u32], total: u64) -> f64 {
+    if total == 0 {
+        return 0.0;
+    }
+    let n = sorted.len() as f64;
+    let weighted: f64 = sorted
+        .iter()
+        .enumerate()
+        .map(|(i, &c)| (i + 1) as f64 * c as f64)
+        .sum();
+    (2.0 * weighted / (n * total as f64) - (n + 1.0) / n).max(0.0)
+}
+
+/// Share of `total` held by the top tenth (rounded up) of an ascending,
+/// non-empty slice.
+fn top_decile_share(sorted: &[u32], total: u64) -> f64 {
+    if total == 0 {
+        return 0.0;
+    }
+    let top = sorted.len().div_ceil(10);
+    let top_total: u64 = sorted.iter().rev().take(top).map(|&c| c as u64).sum();
+    top_total as f64 / total as f64
+}
+
+/// Nearest-rank percentile of an ascending, non-empty slice.
+fn percentile(sorted: &[u32], p: usize) -> u32 {
+    let rank = (p * sorted.len()).div_ceil(100).max(1);
+    sorted[rank - 1]
+}
+
+/// The steps the analysis of `root` takes about `file`, found by running it
+/// with a trace.
+pub fn why(root: &Path, file: &Path, mut options: AnalyzeOptions) -> Result<Vec<trace::Step>> {
+    let canonical_root = root
+        .canonicalize()
+        .with_context(|| format!("Failed to read {}", root.display()))?;
+    let canonical_file = file
+        .canonicalize()
+        .with_context(|| format!("Failed to read {}", file.display()))?;
+    let relative = canonical_file.strip_prefix(&canonical_root).with_context(|| {
+        format!("{} is not under the analyzed directory {}", file.display(), root.display())
+    })?;
+    // The walk reaches the file through the root as spelled.
+    let trace = trace::Trace::new(root.join(relative));
+    options.trace = Some(trace.clone());
+    scan(root, &options)?;
+    Ok(trace.steps())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::table::{function_table, print_table, summary_text};
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn create_test_python_file(dir: &TempDir, name: &str, content: &str) -> PathBuf {
+        let file_path = dir.path().join(name);
+        // Ensure parent directory exists
+        if let Some(parent) = file_path.parent() {
+            fs::create_dir_all(parent).unwrap();
+        }
+        fs::write(&file_path, content).unwrap();
+        file_path
+    }
+
+
+    #[test]
+    fn test_calculate_complexity_simple() {
+        let source = r#"
+def simple_function():
+    return True
+
+def complex_function():
+    if True:
+        for i in range(10):
+            while i > 0:
+                try:
+                    with open('file.txt') as f:
+                        if i % 2 == 0 and i > 5:
+                            pass
+                except Exception:
+                    pass
+"#;
+        let results = calculate_complexity(source, &Weights::default()).unwrap();
+        assert_eq!(results.len(), 2);
+        
+        let simple = results.iter().find(|f| f.name == "simple_function").unwrap();
+        assert_eq!(simple.complexity, 1);
+        
+        let complex = results.iter().find(|f| f.name == "complex_function").unwrap();
+        assert_eq!(complex.complexity, 9); // 1 base + 1 if + 1 for + 1 while + 1 try + 1 with + 1 if + 1 and + 1 except
+    }
+
+    #[test]
+    fn test_nested_scopes() {
+        let source = r#"
+def factory(options):
+    def decorator(func):
+        def wrapper(*args):
+            if args and options:
+                return func(*args)
+        for option in options:
+            pass
+        return wrapper
+    if options:
+        return decorator
+
+class Repository:
+    def find(self, items, key):
+        matches = filter(lambda item: item.key == key or item.alias == key, items)
+        while items:
+            items.pop()
+        return sorted(matches, key=lambda item: item.rank and item.score)
+
+    def save(self, item):
+        def validate(value):
+            if not value:
+                raise ValueError(value)
+        validate(item)
+
+ordering = lambda a, b: a < b or a == b
+"#;
+        let results = calculate_complexity(source, &Weights::default()).unwrap();
+        let scores: Vec<(&str, u32)> = results
+            .iter()
+            .map(|f| (f.name.as_str(), f.complexity))
+            .collect();
+        // Each branch counts once, in the innermost function or lambda.
+        assert_eq!(
+            scores,
+            [
+                ("factory", 2),
+                ("factory.decorator", 2),
+                ("factory.decorator.wrapper", 3),
+                ("Repository.find", 2),
+                ("Repository.find.<lambda>", 2),
+                ("Repository.find.<lambda>", 2),
+                ("Repository.save", 1),
+                ("Repository.save.validate", 2),
+                ("<lambda>", 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_analyze_directory() {
+        let temp_dir = TempDir::new().unwrap();
+        
+        create_test_python_file(
+            &temp_dir,
+            "simple.py",
+            r#"
+def simple():
+    return True
+"#,
+        );
+        
+        create_test_python_file(
+            &temp_dir,
+            "complex.py",
+            r#"
+def complex():
+    if True:
+        for i in range(10):
+            while i > 0:
+                try:
+                    pass
+                except:
+                    pass
+"#,
+        );
+        
+        // Create a file in a subdirectory
+        create_test_python_file(
+            &temp_dir,
+            "subdir/nested.py",
+            r#"
+def nested():
+    if True and False:
+        pass
+"#,
+        );
+        
+        // Create a file that should be ignored
+        create_test_python_file(
+            &temp_dir,
+            "venv/ignored.py",
+            r#"
+def ignored():
+    pass
+"#,
+        );
+        
+        let result = analyze_directory(temp_dir.path(), 5).unwrap();
+        
+        assert_eq!(result.functions.len(), 3);
+        assert!(result.summary.is_some());
+        
+        let summary = result.summary.unwrap();
+        assert_eq!(summary.total_functions, 3);
+        assert_eq!(summary.functions_above_threshold, 1);
+        
+        // Verify the complex function is above threshold
+        let complex = result.functions.iter().find(|f| f.name == "complex").unwrap();
+        assert!(complex.complexity > 5);
+    }
+
+    #[test]
+    fn test_mixed_languages() {
+        let temp_dir = TempDir::new().unwrap();
+        create_test_python_file(
+            &temp_dir,
+            "api/views.py",
+            "def index(request):\n    if request.user:\n        return 1\n",
+        );
+        create_test_python_file(
+            &temp_dir,
+            "web/src/cart.ts",
+            "export const total = (items: Item[]): number => {\n  let sum = 0;\n  for (const item of items) {\n    sum += item.price ?? 0;\n  }\n  return sum;\n};\n",
+        );
+        create_test_python_file(
+            &temp_dir,
+            "web/src/Row.tsx",
+            "export function Row({ item }: Props) {\n  return item.visible && <li>{item.name}</li>;\n}\n",
+        );
+        create_test_python_file(&temp_dir, "web/legacy.js", "function old(a) {\n  return a || 1;\n}\n");
+        create_test_python_file(&temp_dir, "README.md", "# Shop\n");
+
+        let result = analyze_directory(temp_dir.path(), 10).unwrap();
+        let functions: Vec<(&str, language::Language, u32)> = result
+            .functions
+            .iter()
+            .map(|f| (f.name.as_str(), f.language, f.complexity))
+            .collect();
+        assert_eq!(
+            functions,
+            [
+                ("index", language::Language::Python, 2),
+                ("old", language::Language::JavaScript, 2),
+                ("Row", language::Language::TypeScript, 2),
+                ("total", language::Language::TypeScript, 3),
+            ]
+        );
+        assert_eq!(result.summary.unwrap().total_functions, 4);
+        assert!(result.errors.is_empty());
+        let json = serde_json::to_value(&result.functions).unwrap();
+        assert_eq!(json[0].get("language"), None);
+        assert_eq!(json[3]["language"], "typescript");
+
+        let options = AnalyzeOptions {
+            language: Some(language::Language::TypeScript),
+            ..Default::default()
+        };
+        let result = analyze_path(temp_dir.path(), &options).unwrap();
+        let names: Vec<&str> = result.functions.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, ["Row", "total"]);
+        let steps = why(
+            temp_dir.path(),
+            &temp_dir.path().join("api/views.py"),
+            options.clone(),
+        )
+        .unwrap();
+        assert_eq!(
+            steps,
+            [trace::Step::OtherLanguage {
+                language: "Python".to_string(),
+                only: "TypeScript".to_string()
+            }]
+        );
+    }
+
+    #[test]
+    fn test_output_formats() {
+        let temp_dir = TempDir::new().unwrap();
+        create_test_python_file(
+            &temp_dir,
+            "test.py",
+            r#"
+def test():
+    if True:
+        pass
+"#,
+        );
+        
+        let result = analyze_directory(temp_dir.path(), 1).unwrap();
+        
+        // Test JSON serialization
+        let json = serde_json::to_string_pretty(&result).unwrap();
+        assert!(json.contains("test"));
+        assert!(json.contains("complexity"));
+        
+        // Test table output (we can't easily test the actual output, but we can verify it doesn't panic)
+        print_table(&result, &Thresholds::new(1, None, None), None);
+    }
+
+    #[test]
+    fn test_skip_vendored() {
+        let temp_dir = TempDir::new().unwrap();
+        create_test_python_file(&temp_dir, "app/main.py", "def main():\n    pass\n");
+        create_test_python_file(&temp_dir, "app/vendors/models.py", "def order():\n    pass\n");
+        create_test_python_file(&temp_dir, "third_party/six.py", "def six():\n    pass\n");
+        create_test_python_file(
+            &temp_dir,
+            "app/compat.py",
+            "# Vendored from https://github.com/x/compat\ndef compat():\n    pass\n",
+        );
+        let options = AnalyzeOptions {
+            vendored: Some(vendored::VendorDetector::default()),
+            ..Default::default()
+        };
+
+        let result = analyze_path(temp_dir.path(), &options).unwrap();
+
+        let mut names: Vec<&str> = result.functions.iter().map(|f| f.name.as_str()).collect();
+        names.sort_unstable();
+        assert_eq!(names, ["main", "order"]);
+        assert_eq!(analyze_directory(temp_dir.path(), 10).unwrap().functions.len(), 4);
+    }
+
+    #[test]
+    fn test_dunder_modes() {
+        let temp_dir = TempDir::new().unwrap();
+        let busy = "        if x:\n            pass\n        if y:\n            pass\n";
+        create_test_python_file(
+            &temp_dir,
+            "models.py",
+            &format!(
+                "class Order:\n    def __init__(self, x, y):\n{busy}\n    @staticmethod\n    def __eq__(x, y):\n{busy}\n    def total(self, x, y):\n{busy}\n\ndef __getattr__(x, y):\n{busy}",
+                busy = busy
+            ),
+        );
+        let analyze = |dunder, keep: &[&str]| {
+            let options = AnalyzeOptions {
+                threshold: 2,
+                dunder,
+                keep_dunder: keep.iter().map(|name| name.to_string()).collect(),
+                ..Default::default()
+            };
+            analyze_path(temp_dir.path(), &options).unwrap()
+        };
+        let names = |result: &AnalysisResult| -> Vec<String> {
+            result.functions.iter().map(|f| f.name.clone()).collect()
+        };
+
+        let included = analyze(DunderMode::Include, &[]);
+        assert_eq!(names(&included).len(), 4);
+        assert!(included.functions.iter().all(|f| !f.dunder));
+        assert_eq!(included.summary.unwrap().functions_above_threshold, 4);
+
+        // The module-level `__getattr__` is not a method, so it stays.
+        let excluded = analyze(DunderMode::Exclude, &["__init__"]);
+        assert_eq!(names(&excluded), ["Order.__init__", "Order.total", "__getattr__"]);
+        assert_eq!(excluded.summary.unwrap().total_functions, 3);
+
+        let separate = analyze(DunderMode::Separate, &[]);
+        let dunders: Vec<&str> = separate
+            .functions
+            .iter()
+            .filter(|f| f.dunder)
+            .map(|f| f.name.as_str())
+            .collect();
+        assert_eq!(dunders, ["Order.__init__", "Order.__eq__"]);
+        let summary = separate.summary.as_ref().unwrap();
+        assert_eq!(summary.total_functions, 4);
+        assert_eq!(summary.functions_above_threshold, 2);
+        let json = serde_json::to_value(&separate).unwrap();
+        assert_eq!(json["functions"][0]["dunder"], true);
+        assert!(json["functions"][2].get("dunder").is_none());
+    }
+
+    #[test]
+    fn test_parallel_results_match_sequential() {
+        let temp_dir = TempDir::new().unwrap();
+        for i in 0..40 {
+            let branches = "    if x:\n        x -= 1\n".repeat(i % 7);
+            create_test_python_file(
+                &temp_dir,
+                &format!("pkg{}/module_{}.py", i % 4, i),
+                &format!("def f{}(x):\n{}    return x\n\ndef g{}():\n    pass\n", i, branches, i),
+            );
+        }
+        create_test_python_file(&temp_dir, "web/app.js", "function h(a) { return a ?? 0; }\n");
+        fs::write(temp_dir.path().join("pkg1/latin1.py"), b"# caf\xe9\n").unwrap();
+
+        let analyze = |jobs: usize| {
+            let options = AnalyzeOptions {
+                threshold: 3,
+                jobs: NonZeroUsize::new(jobs),
+                ..Default::default()
+            };
+            let result = analyze_path(temp_dir.path(), &options).unwrap();
+            serde_json::to_value(&result).unwrap()
+        };
+        let sequential = analyze(1);
+        assert_eq!(sequential["functions"].as_array().unwrap().len(), 81);
+        assert_eq!(sequential["errors"].as_array().unwrap().len(), 1);
+        assert_eq!(analyze(4), sequential);
+        assert_eq!(analyze(0), sequential);
+    }
+
+    #[test]
+    fn test_why_each_reason() {
+        use trace::Step;
+        let temp_dir = TempDir::new().unwrap();
+        let files = [
+            ("notes.txt", "def f():\n    pass\n"),
+            ("venv/lib/site.py", "def f():\n    pass\n"),
+            ("build/lib/app.py", "def f():\n    pass\n"),
+            ("vendor/six.py", "def f():\n    pass\n"),
+            ("copied.py", "# Vendored from https://example.com/lib\ndef f():\n    pass\n"),
+            ("api/user_pb2.py", "def f():\n    pass\n"),
+            (
+                "model.py",
+                "class A:\n    def __eq__(self, o):\n        pass\n    def save(self):\n        pass\n",
+            ),
+        ];
+        for (name, content) in files {
+            create_test_python_file(&temp_dir, name, content);
+        }
+        fs::write(temp_dir.path().join("latin1.py"), b"# caf\xe9\n").unwrap();
+        fs::write(temp_dir.path().join(".gitignore"), "build/\n").unwrap();
+        let options = AnalyzeOptions {
+            vendored: Some(vendored::VendorDetector::with_components(&[])),
+            dunder: DunderMode::Exclude,
+            generated_mode: generated::GeneratedMode::Collapse,
+            ..Default::default()
+        };
+        let why = |name: &str| {
+            why(temp_dir.path(), &temp_dir.path().join(name), options.clone()).unwrap()
+        };
+
+        assert_eq!(
+            why("notes.txt"),
+            [Step::Unsupported {
+                extension: Some("txt".to_string())
+            }]
+        );
+        assert_eq!(
+            why("venv/lib/site.py"),
+            [Step::ExcludedDirectory {
+                pattern: "venv".to_string()
+            }]
+        );
+        let ignored = why("build/lib/app.py");
+        assert!(matches!(
+            &ignored[..],
+            [Step::Ignored { pattern, file: Some(file) }] if pattern == "build/" && file.ends_with(".gitignore")
+        ));
+        let vendored = why("vendor/six.py");
+        assert!(matches!(
+            &vendored[..],
+            [Step::Discovered, Step::Vendored { reason }] if reason.contains("vendor")
+        ));
+        let marked = why("copied.py");
+        assert!(matches!(
+            &marked[..],
+            [Step::Discovered, Step::Vendored { reason }] if reason.contains("marker")
+        ));
+        assert!(matches!(&why("latin1.py")[..], [Step::Discovered, Step::Unreadable { .. }]));
+        assert_eq!(
+            why("api/user_pb2.py"),
+            [
+                Step::Discovered,
+                Step::Generated {
+                    reason: "pattern `*_pb2.py`".to_string(),
+                    mode: "collapse".to_string()
+                },
+                Step::Analyzed { functions: 1 },
+            ]
+        );
+        assert_eq!(
+            why("model.py"),
+            [
+                Step::Discovered,
+                Step::DunderExcluded { functions: 1 },
+                Step::Analyzed { functions: 1 },
+            ]
+        );
+
+        let elsewhere = TempDir::new().unwrap();
+        let outside = create_test_python_file(&elsewhere, "app.py", "");
+        let error = super::why(temp_dir.path(), &outside, options.clone()).unwrap_err();
+        assert!(error.to_string().contains("is not under the analyzed directory"));
+    }
+
+    #[test]
+    fn test_generated_modes() {
+        let temp_dir = TempDir::new().unwrap();
+        create_test_python_file(&temp_dir, "app.py", "def handle(x):\n    if x:\n        pass\n");
+        std::fs::create_dir(temp_dir.path().join("api")).unwrap();
+        create_test_python_file(
+            &temp_dir,
+            "api/user_pb2.py",
+            "def a():\n    pass\n\ndef b(x):\n    if x:\n        pass\n",
+        );
+        create_test_python_file(
+            &temp_dir,
+            "client.py",
+            "# Code generated by openapi-generator. DO NOT EDIT.\ndef c():\n    pass\n",
+        );
+        let analyze = |generated_mode| {
+            let options = AnalyzeOptions {
+                threshold: 1,
+                generated_mode,
+                ..Default::default()
+            };
+            let mut result = analyze_path(temp_dir.path(), &options).unwrap();
+            result.functions.sort_by(|a, b| a.name.cmp(&b.name));
+            result.generated_files.sort_by(|a, b| a.file.cmp(&b.file));
+            result
+        };
+
+        let included = analyze(generated::GeneratedMode::Include);
+        let flags: Vec<(&str, bool)> = included
+            .functions
+            .iter()
+            .map(|f| (f.name.as_str(), f.generated))
+            .collect();
+        assert_eq!(flags, [("a", true), ("b", true), ("c", true), ("handle", false)]);
+        let summary = included.summary.as_ref().unwrap();
+        assert_eq!((summary.total_functions, summary.generated_functions), (4, 3));
+        assert!(included.generated_files.is_empty());
+
+        let excluded = analyze(generated::GeneratedMode::Exclude);
+        assert_eq!(excluded.functions.len(), 1);
+        assert!(excluded.generated_files.is_empty());
+        let summary = excluded.summary.as_ref().unwrap();
+        assert_eq!((summary.total_functions, summary.generated_functions), (1, 3));
+
+        let collapsed = analyze(generated::GeneratedMode::Collapse);
+        assert_eq!(collapsed.functions.len(), 1);
+        assert_eq!(collapsed.summary.as_ref().unwrap().generated_functions, 3);
+        let json = serde_json::to_value(&collapsed).unwrap();
+        let pb2 = &json["generated_files"][0];
+        assert!(pb2["file"].as_str().unwrap().ends_with("user_pb2.py"));
+        assert_eq!(pb2["functions"], 2);
+        assert_eq!(pb2["total_complexity"], 3);
+        assert_eq!(pb2["max_complexity"], 2);
+        assert_eq!(pb2["mean_complexity"], 1.5);
+        assert_eq!(json["generated_files"][1]["functions"], 1);
+        let table = function_table(&collapsed, None, severity::Metric::Cyclomatic).to_string();
+        assert!(table.contains("(2 generated functions)"));
+        assert!(table.contains("max 2"));
+    }
+
+    #[test]
+    fn test_progress_events() {
+        let temp_dir = TempDir::new().unwrap();
+        create_test_python_file(&temp_dir, "a.py", "def f():\n    pass\n\ndef g():\n    pass\n");
+        create_test_python_file(&temp_dir, "b.py", "VERSION = 1\n");
+        let buffer = progress::tests::Buffer::default();
+        let options = AnalyzeOptions {
+            progress: Some(progress::Reporter::new(Box::new(buffer.clone()))),
+            ..Default::default()
+        };
+        analyze_path(temp_dir.path(), &options).unwrap();
+
+        let events = buffer.events();
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0], serde_json::json!({"event": "discovered", "files": 2}));
+        let mut done: Vec<(String, u64)> = events[1..]
+            .iter()
+            .map(|event| {
+                assert_eq!(event["event"], "file_done");
+                assert!(event["elapsed_ms"].is_u64());
+                let path = event["path"].as_str().unwrap();
+                let name = Path::new(path).file_name().unwrap().to_string_lossy();
+                (name.to_string(), event["functions"].as_u64().unwrap())
+            })
+            .collect();
+        done.sort();
+        // Files without functions are reported too.
+        assert_eq!(done, [("a.py".to_string(), 2), ("b.py".to_string(), 0)]);
+    }
+
+    #[test]
+    fn test_worst_files() {
+        let function = |name: &str, file: &str, complexity| FunctionComplexity {
+            name: name.to_string(),
+            file: file.to_string(),
+            line: complexity,
+            complexity,
+            ..Default::default()
+        };
+        let functions = vec![
+            function("a1", "a.py", 30),
+            function("b1", "b.py", 12),
+            function("b2", "b.py", 15),
+            function("b3", "b.py", 11),
+            function("c1", "c.py", 2),
+        ];
+
+        let by_max = worst_files(&functions, 10, severity::Metric::Cyclomatic, FileRanking::Max, 2);
+        let files: Vec<&str> = by_max.iter().map(|f| f.file.as_str()).collect();
+        assert_eq!(files, ["a.py", "b.py"]);
+        assert_eq!(by_max[1].worst_function, "b2");
+        assert_eq!(by_max[1].total_complexity, 38);
+        assert_eq!(by_max[1].functions_above_threshold, 3);
+
+        let by_total = worst_files(&functions, 10, severity::Metric::Cyclomatic, FileRanking::Total, 5);
+        let files: Vec<&str> = by_total.iter().map(|f| f.file.as_str()).collect();
+        assert_eq!(files, ["b.py", "a.py", "c.py"]);
+
+        let by_count = worst_files(&functions, 10, severity::Metric::Cyclomatic, FileRanking::AboveThreshold, 1);
+        assert_eq!(by_count[0].file, "b.py");
+    }
+
+    #[test]
+    fn test_concentration() {
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-9;
+
+        assert!(close(gini(&[3, 3, 3, 3], 12), 0.0));
+        assert!(close(gini(&[1, 2, 3, 4], 10), 0.25));
+        assert!(close(gini(&[0, 0, 0, 10], 10), 0.75));
+        assert!(close(gini(&[7], 7), 0.0));
+        assert!(close(gini(&[0, 0], 0), 0.0));
+
+        let twenty: Vec<u32> = (1..=20).collect();
+        assert!(close(top_decile_share(&twenty, 210), 39.0 / 210.0));
+        assert!(close(top_decile_share(&[1, 1, 2], 4), 0.5));
+        assert!(close(top_decile_share(&[0, 0], 0), 0.0));
+    }
+
+    #[test]
+    fn test_derived_statistics() {
+        assert_eq!(mean(0, 0), None);
+        assert_eq!(mean(7, 2), Some(3.5));
+
+        // No functions, whether there are no files or only empty ones.
+        let temp_dir = TempDir::new().unwrap();
+        let empty = analyze_directory(temp_dir.path(), 10).unwrap();
+        create_test_python_file(&temp_dir, "empty.py", "");
+        create_test_python_file(&temp_dir, "constants.py", "X = 1\n");
+        let without_functions = analyze_directory(temp_dir.path(), 10).unwrap();
+        for result in [empty, without_functions] {
+            assert_eq!(result.summary, None);
+            assert_eq!(summary_text(&result, 10, None), "");
+            let json = serde_json::to_string(&result).unwrap();
+            assert_eq!(serde_json::from_str::<AnalysisResult>(&json).unwrap(), result);
+        }
+
+        create_test_python_file(&temp_dir, "one.py", "def f(x):\n    if x:\n        pass\n");
+        let single = analyze_directory(temp_dir.path(), 10).unwrap();
+        let summary = single.summary.clone().unwrap();
+        assert_eq!(summary.mean_complexity, 2.0);
+        assert_eq!(
+            (summary.p50_complexity, summary.p90_complexity, summary.p95_complexity),
+            (2, 2, 2)
+        );
+        assert_eq!(summary.gini_coefficient, 0.0);
+        assert_eq!(summary.top_decile_share, 1.0);
+        let json = serde_json::to_string(&single).unwrap();
+        assert_eq!(serde_json::from_str::<AnalysisResult>(&json).unwrap(), single);
+        // Fixed precision whatever the values.
+        assert!(summary_text(&single, 10, None).contains("Mean Complexity: 2.00\n"));
+
+        // Huge weights saturate instead of overflowing.
+        let mut weights = Weights::default();
+        weights.set(weights::Construct::If, u32::MAX);
+        let source = "def f(x):\n    if x:\n        pass\n    if x:\n        pass\n";
+        let functions = calculate_complexity(source, &weights).unwrap();
+        assert_eq!(functions[0].complexity, u32::MAX);
+        let summary = summarize(&functions, 10, severity::Metric::Cyclomatic).unwrap();
+        assert_eq!(summary.mean_complexity, u32::MAX as f64);
+    }
+
+    #[test]
+    fn test_unreadable_files_are_reported() {
+        let temp_dir = TempDir::new().unwrap();
+        create_test_python_file(&temp_dir, "good.py", "def good():\n    pass\n");
+        create_test_python_file(&temp_dir, "pkg/other.py", "def other():\n    pass\n");
+        fs::write(temp_dir.path().join("latin1.py"), b"# caf\xe9\ndef bad():\n    pass\n").unwrap();
+        // A link to nowhere cannot be read, whoever runs the tests.
+        #[cfg(unix)]
+        std::os::unix::fs::symlink("missing.py", temp_dir.path().join("dangling.py")).unwrap();
+
+        let result = analyze_directory(temp_dir.path(), 10).unwrap();
+
+        assert_eq!(result.functions.len(), 2);
+        let errors: Vec<(&str, IssueCategory)> = result
+            .errors
+            .iter()
+            .map(|error| (error.path.rsplit('/').next().unwrap(), error.category))
+            .collect();
+        #[cfg(unix)]
+        assert_eq!(
+            errors,
+            [
+                ("dangling.py", IssueCategory::Io),
+                ("latin1.py", IssueCategory::Encoding)
+            ]
+        );
+        #[cfg(not(unix))]
+        assert_eq!(errors, [("latin1.py", IssueCategory::Encoding)]);
+
+        let json: serde_json::Value = serde_json::to_value(&result).unwrap();
+        assert_eq!(json["errors"].as_array().unwrap().last().unwrap()["category"], "encoding");
+    }
+
+    #[test]
+    fn test_syntax_errors_are_reported() {
+        let temp_dir = TempDir::new().unwrap();
+        create_test_python_file(&temp_dir, "good.py", "def good():\n    pass\n");
+        create_test_python_file(
+            &temp_dir,
+            "broken.py",
+            "def ok():\n    pass\n\ndef bad(:\n    pass\n",
+        );
+
+        let result = analyze_directory(temp_dir.path(), 10).unwrap();
+
+        let names: Vec<&str> = result.functions.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, ["good"]);
+        assert_eq!(result.errors.len(), 1);
+        assert!(result.errors[0].path.ends_with("broken.py"));
+        assert_eq!(result.errors[0].category, IssueCategory::Parse);
+        assert_eq!(result.errors[0].message, "syntax error at line 4, column 9");
+
+        let error =
+            analyze_source("function f( {\n}\n", language::Language::JavaScript).unwrap_err();
+        assert!(error.to_string().starts_with("syntax error at line 1"));
+    }
+
+    #[test]
+    fn test_walk_errors_are_reported() {
+        let temp_dir = TempDir::new().unwrap();
+        let missing = temp_dir.path().join("missing");
+        let result = analyze_directory(&missing, 10).unwrap();
+        assert_eq!(result.errors.len(), 1);
+        assert_eq!(result.errors[0].path, missing.to_string_lossy());
+        assert_eq!(result.errors[0].category, IssueCategory::Io);
+
+        // A directory that cannot be listed, unless the tests run as root.
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            create_test_python_file(&temp_dir, "good.py", "def good():\n    pass\n");
+            create_test_python_file(&temp_dir, "locked/hidden.py", "def hidden():\n    pass\n");
+            let locked = temp_dir.path().join("locked");
+            fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+            let unreadable = fs::read_dir(&locked).is_err();
+            let result = analyze_directory(temp_dir.path(), 10);
+            fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+            if unreadable {
+                let result = result.unwrap();
+                assert_eq!(result.functions.len(), 1);
+                assert_eq!(result.errors.len(), 1);
+                assert_eq!(result.errors[0].path, locked.to_string_lossy());
+                assert_eq!(result.errors[0].category, IssueCategory::Io);
+            }
+        }
+    }
+
+    #[test]
+    fn test_skipped_line() {
+        let issue = |category| FileIssue {
+            path: "a.py".to_string(),
+            category,
+            message: String::new(),
+        };
+        assert_eq!(skipped_line(&[]), None);
+        assert_eq!(
+            skipped_line(&[
+                issue(IssueCategory::Parse),
+                issue(IssueCategory::Io),
+                issue(IssueCategory::Io),
+                issue(IssueCategory::Io),
+            ]),
+            Some("4 files skipped (3 io, 1 parse)".to_string())
+        );
+    }
+
+    #[test]
+    fn test_non_ascii_names_and_paths() {
+        let temp_dir = TempDir::new().unwrap();
+        let long_name = format!("処理_{}", "長い名前".repeat(10));
+        create_test_python_file(
+            &temp_dir,
+            "📁 データ/モジュール.py",
+            &format!(
+                "def 計算する(値):\n    if 値:\n        pass\n\ndef {}():\n    pass\n",
+                long_name
+            ),
+        );
+
+        let result = analyze_directory(temp_dir.path(), 10).unwrap();
+
+        let json = serde_json::to_string(&result).unwrap();
+        let parsed: AnalysisResult = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, result);
+        let names: Vec<&str> = parsed.functions.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, ["計算する", long_name.as_str()]);
+        assert!(parsed.functions[0].file.ends_with("📁 データ/モジュール.py"));
+
+        let rendered = function_table(&result, None, severity::Metric::Cyclomatic).to_string();
+        let widths: Vec<usize> = rendered.lines().map(text::display_width).collect();
+        assert!(widths.iter().all(|&w| w == widths[0]), "misaligned table:\n{}", rendered);
+        assert!(rendered.contains("計算する"));
+        assert!(rendered.contains('…'));
+    }
+
+    #[test]
+    fn test_meta_is_optional_in_json() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut result = analyze_directory(temp_dir.path(), 10).unwrap();
+
+        let json = serde_json::to_string(&result).unwrap();
+        assert!(!json.contains("\"meta\""));
+
+        let thresholds = Thresholds::new(10, Some(20), None);
+        result.meta = Some(Meta::collect(&[temp_dir.path().to_path_buf()], &thresholds, Vec::new()));
+        let json: serde_json::Value = serde_json::to_value(&result).unwrap();
+        assert_eq!(json["meta"]["schema_version"], meta::SCHEMA_VERSION);
+        assert_eq!(json["meta"]["threshold"], 10);
+    }
+}
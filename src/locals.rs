@@ -0,0 +1,178 @@
+//! Number of local variables a function binds.
+
+use std::collections::HashSet;
+use tree_sitter::Node;
+
+/// Distinct names bound in `body`, not counting parameters or names declared
+/// `global` or `nonlocal`. Names bound by an assignment expression count even
+/// inside a comprehension, since they leak into the function, but the
+/// comprehension's own loop variables do not.
+pub fn count(body: Node, source: &str) -> u32 {
+    let mut locals = Locals::default();
+    locals.walk(body, source);
+    locals.bound.difference(&locals.declared).count() as u32
+}
+
+#[derive(Default)]
+struct Locals<'a> {
+    bound: HashSet<&'a str>,
+    /// Names declared `global` or `nonlocal`.
+    declared: HashSet<&'a str>,
+}
+
+impl<'a> Locals<'a> {
+    /// Records the names bound under `body`. Nodes still to visit are kept on
+    /// a stack of their own rather than recursion, so that no nesting
+    /// overflows the thread's.
+    fn walk(&mut self, body: Node, source: &'a str) {
+        let mut stack = vec![body];
+        while let Some(node) = stack.pop() {
+            if self.visit(node, source) {
+                let mut cursor = node.walk();
+                stack.extend(node.named_children(&mut cursor));
+            }
+        }
+    }
+
+    /// Records the names `node` itself binds or declares, returning whether
+    /// its children are in the same scope and should be visited too.
+    fn visit(&mut self, node: Node, source: &'a str) -> bool {
+        let field = |name| node.child_by_field_name(name);
+        match node.kind() {
+            "assignment" | "augmented_assignment" | "for_statement" | "for_in_statement" => {
+                if let Some(left) = field("left") {
+                    self.bind(left, source);
+                }
+            }
+            "named_expression" | "variable_declarator" => {
+                if let Some(name) = field("name") {
+                    self.bind(name, source);
+                }
+            }
+            "as_pattern_target" => self.bind(node, source),
+            "catch_clause" => {
+                if let Some(parameter) = field("parameter") {
+                    self.bind(parameter, source);
+                }
+            }
+            "except_clause" => {
+                let mut cursor = node.walk();
+                for child in node.named_children(&mut cursor) {
+                    if child.prev_sibling().is_some_and(|prev| prev.kind() == "as") {
+                        self.bind(child, source);
+                    }
+                }
+            }
+            "import_statement" | "import_from_statement" => {
+                let mut cursor = node.walk();
+                for name in node.children_by_field_name("name", &mut cursor) {
+                    let bound = match name.kind() {
+                        "aliased_import" => name.child_by_field_name("alias"),
+                        // `import a.b` binds `a`.
+                        _ => name.named_child(0),
+                    };
+                    if let Some(bound) = bound {
+                        self.bind(bound, source);
+                    }
+                }
+                return false;
+            }
+            "global_statement" | "nonlocal_statement" => {
+                let mut cursor = node.walk();
+                for name in node.named_children(&mut cursor) {
+                    self.declared.insert(text(name, source));
+                }
+                return false;
+            }
+            // Nested scopes bind their name here and everything else there.
+            "function_definition"
+            | "class_definition"
+            | "function_declaration"
+            | "generator_function_declaration"
+            | "class_declaration" => {
+                if let Some(name) = field("name") {
+                    self.bind(name, source);
+                }
+                return false;
+            }
+            kind if crate::language::is_definition(kind) => return false,
+            _ => {}
+        }
+        true
+    }
+
+    /// Binds the names of an assignment target. Attribute and subscript
+    /// targets bind nothing.
+    fn bind(&mut self, target: Node, source: &'a str) {
+        let mut stack = vec![target];
+        while let Some(target) = stack.pop() {
+            match target.kind() {
+                "identifier" | "shorthand_property_identifier_pattern" => {
+                    self.bound.insert(text(target, source));
+                }
+                "attribute" | "subscript" | "member_expression" | "subscript_expression" => {}
+                // Default values are not bound: `{ a = fallback }` binds `a`.
+                "assignment_pattern" | "object_assignment_pattern" => {
+                    stack.extend(target.child_by_field_name("left"));
+                }
+                _ => {
+                    let mut cursor = target.walk();
+                    stack.extend(target.named_children(&mut cursor));
+                }
+            }
+        }
+    }
+}
+
+fn text<'a>(node: Node, source: &'a str) -> &'a str {
+    node.utf8_text(source.as_bytes()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::calculate_complexity;
+    use crate::weights::{Construct, Weights};
+
+    /// Every function of the fixture is preceded by an
+    /// `# expected: complexity=N walrus=N locals=N` comment, where `walrus`
+    /// is the complexity with `--weight walrus=1`.
+    #[test]
+    fn test_walrus_fixture() {
+        let source = include_str!("../testdata/walrus.py");
+        let mut walrus = Weights::default();
+        walrus.set(Construct::Walrus, 1);
+        let default_scores = calculate_complexity(source, &Weights::default()).unwrap();
+        let walrus_scores = calculate_complexity(source, &walrus).unwrap();
+
+        let mut checked = 0;
+        let mut lines = source.lines().peekable();
+        while let Some(line) = lines.next() {
+            let Some(expected) = line.strip_prefix("# expected: ") else {
+                continue;
+            };
+            let name = lines.peek().unwrap()["def ".len()..].split('(').next().unwrap();
+            let func = default_scores.iter().find(|f| f.name == name).unwrap();
+            let with_walrus = walrus_scores.iter().find(|f| f.name == name).unwrap();
+            for field in expected.split_whitespace() {
+                let (metric, value) = field.split_once('=').unwrap();
+                let actual = match metric {
+                    "complexity" => func.complexity,
+                    "walrus" => with_walrus.complexity,
+                    _ => func.locals,
+                };
+                assert_eq!(actual, value.parse::<u32>().unwrap(), "{} of {}", metric, name);
+            }
+            checked += 1;
+        }
+        assert_eq!(checked, default_scores.len());
+    }
+
+    #[test]
+    fn test_scopes() {
+        let source = "def f(param):\n    global g\n    g = 1\n    import os.path, json as j\n    from x import y, z as w\n    def inner():\n        hidden = 1\n    squares = [n * n for n in range(3)]\n    obj.attr = obj[0] = 2\n    with open(param) as (a, b):\n        pass\n    try:\n        pass\n    except E as error:\n        pass\n    first, *rest = param\n";
+        let functions = calculate_complexity(source, &Weights::default()).unwrap();
+        // os, j, y, w, inner, squares, a, b, error, first, rest
+        assert_eq!(functions[0].locals, 11);
+        assert_eq!(functions[1].locals, 1);
+    }
+}
@@ -0,0 +1,307 @@
+//! One function of one file, found by a `path::name` locator without
+//! walking any directory, for review bots and editor hovers.
+//!
+//! Functions are located by the qualified name the analysis gives them (see
+//! [`crate::language::qualified_name`]). A locator matches the functions
+//! whose qualified name it is, or ends, so that `compute_totals` finds
+//! `Invoice.compute_totals` as long as no other function has that name.
+
+use crate::depth;
+use crate::weights::Weights;
+use crate::{calculate_complexity_with, FunctionComplexity};
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use std::path::Path;
+
+/// Near-matches suggested for a function that does not exist.
+const SUGGESTIONS: usize = 3;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FunctionReport {
+    pub qualified_name: String,
+    #[serde(flatten)]
+    pub function: FunctionComplexity,
+    /// The lines of the function, from its `def` line.
+    pub source: String,
+}
+
+/// Whether `locator` designates the function named `qualified`.
+pub fn designates(locator: &str, qualified: &str) -> bool {
+    qualified == locator
+        || qualified
+            .strip_suffix(locator)
+            .is_some_and(|prefix| prefix.ends_with('.'))
+}
+
+/// Levenshtein distance between `a` and `b`, by characters.
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, &b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != b);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// The functions of `candidates` whose name is close to `locator`, closest
+/// first.
+fn near_matches<'a>(locator: &str, candidates: &'a [(u32, String)]) -> Vec<&'a (u32, String)> {
+    let tolerance = (locator.chars().count() / 3).max(1);
+    let mut near: Vec<(usize, &(u32, String))> = candidates
+        .iter()
+        .map(|candidate| {
+            let qualified = &candidate.1;
+            let last = qualified.rsplit('.').next().unwrap_or(qualified);
+            (distance(locator, qualified).min(distance(locator, last)), candidate)
+        })
+        .filter(|&(distance, _)| distance <= tolerance)
+        .collect();
+    near.sort_by_key(|&(distance, (line, _))| (distance, *line));
+    near.into_iter()
+        .take(SUGGESTIONS)
+        .map(|(_, candidate)| candidate)
+        .collect()
+}
+
+/// The function of the file at `path` that `locator` designates, scored
+/// with `weights`. Fails listing the candidates when several functions
+/// match, and suggesting near-matches when none does.
+pub fn locate(path: &Path, locator: &str, weights: &Weights) -> Result<FunctionReport> {
+    let source = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    // `outer.<locals>.inner`, as Python spells it, is `outer.inner` here.
+    let locator = locator.replace(".<locals>", "");
+    let functions = calculate_complexity_with(&source, path, weights, depth::DEFAULT_MAX_DEPTH)?;
+
+    let mut matching: Vec<&FunctionComplexity> = functions
+        .iter()
+        .filter(|func| designates(&locator, &func.name))
+        .collect();
+    // An exact qualified name wins over functions it is only the end of.
+    if let Some(exact) = matching.iter().find(|func| func.name == locator) {
+        matching = vec![*exact];
+    }
+    let listing = |functions: &[&(u32, String)]| {
+        let lines: Vec<String> = functions
+            .iter()
+            .map(|(line, qualified)| format!("  {} (line {})", qualified, line))
+            .collect();
+        lines.join("\n")
+    };
+    match matching.as_slice() {
+        [func] => {
+            let function = (*func).clone();
+            let lines: Vec<&str> = source
+                .lines()
+                .skip(function.line as usize - 1)
+                .take((function.end_line - function.line + 1) as usize)
+                .collect();
+            Ok(FunctionReport {
+                qualified_name: function.name.clone(),
+                function,
+                source: lines.join("\n"),
+            })
+        }
+        [] => {
+            let candidates: Vec<(u32, String)> = functions
+                .iter()
+                .map(|func| (func.line, func.name.clone()))
+                .collect();
+            let near = near_matches(&locator, &candidates);
+            if near.is_empty() {
+                bail!("no function `{}` in {}", locator, path.display());
+            }
+            bail!(
+                "no function `{}` in {}; did you mean:\n{}",
+                locator,
+                path.display(),
+                listing(&near)
+            )
+        }
+        several => {
+            let candidates: Vec<(u32, String)> = several
+                .iter()
+                .map(|func| (func.line, func.name.clone()))
+                .collect();
+            let references: Vec<&(u32, String)> = candidates.iter().collect();
+            bail!(
+                "`{}` matches {} functions in {}; give one of their qualified names:\n{}",
+                locator,
+                several.len(),
+                path.display(),
+                listing(&references)
+            )
+        }
+    }
+}
+
+/// The report as text: location, metrics with the breakdown of the
+/// complexity, and the source.
+pub fn render_text(report: &FunctionReport) -> String {
+    let func = &report.function;
+    let mut out = format!(
+        "{} ({}:{}-{})\n",
+        report.qualified_name, func.file, func.line, func.end_line
+    );
+    out.push_str(&format!("Complexity: {}\n", func.complexity));
+    out.push_str("  1 base\n");
+    for term in &func.breakdown {
+        out.push_str(&format!(
+            "  + {} {}×{}\n",
+            term.construct, term.count, term.weight
+        ));
+    }
+    out.push_str(&format!("Locals: {}\n", func.locals));
+    if !func.decorators.is_empty() {
+        out.push_str(&format!("Decorators: {}\n", func.decorators.join(", ")));
+    }
+    if !func.bases.is_empty() {
+        out.push_str(&format!("Class bases: {}\n", func.bases.join(", ")));
+    }
+    out.push_str(&format!("Body hash: {}\n\n", func.body_hash));
+    let width = func.end_line.to_string().len();
+    for (line, text) in (func.line..).zip(report.source.lines()) {
+        out.push_str(&format!("{:>width$} | {}\n", line, text, width = width));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const SOURCE: &str = "\
+class Invoice:
+    def compute_totals(self, items):
+        total = 0
+        for item in items:
+            if item.taxed:
+                total += item.price * 1.2
+            else:
+                total += item.price
+        return total
+
+    def render(self):
+        def line(item):
+            if item:
+                return item.name
+            return ''
+        return line
+
+
+def render():
+    pass
+";
+
+    fn write(source: &str) -> (TempDir, std::path::PathBuf) {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("billing.py");
+        std::fs::write(&path, source).unwrap();
+        (temp_dir, path)
+    }
+
+    #[test]
+    fn test_method_locator() {
+        let (_temp_dir, path) = write(SOURCE);
+        let report = locate(&path, "Invoice.compute_totals", &Weights::default()).unwrap();
+        assert_eq!(report.qualified_name, "Invoice.compute_totals");
+        assert_eq!(
+            (report.function.line, report.function.end_line, report.function.complexity),
+            (2, 9, 3)
+        );
+        let constructs: Vec<&str> = report
+            .function
+            .breakdown
+            .iter()
+            .map(|term| term.construct.as_str())
+            .collect();
+        assert_eq!(constructs, ["if", "for"]);
+        assert!(report.source.starts_with("    def compute_totals(self, items):\n"));
+        assert!(report.source.ends_with("        return total"));
+        // The bare name is enough when it is unique.
+        let bare = locate(&path, "compute_totals", &Weights::default()).unwrap();
+        assert_eq!(bare.qualified_name, "Invoice.compute_totals");
+
+        let text = render_text(&report);
+        assert!(text.starts_with(&format!(
+            "Invoice.compute_totals ({}:2-9)\nComplexity: 3\n  1 base\n  + if 1×1\n  + for 1×1\n",
+            path.display()
+        )));
+        assert!(text.ends_with("\n9 |         return total\n"));
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["qualified_name"], "Invoice.compute_totals");
+        assert_eq!(json["complexity"], 3);
+    }
+
+    #[test]
+    fn test_nested_locator() {
+        let (_temp_dir, path) = write(SOURCE);
+        for locator in ["Invoice.render.line", "render.line", "Invoice.render.<locals>.line"] {
+            let report = locate(&path, locator, &Weights::default()).unwrap();
+            assert_eq!(report.qualified_name, "Invoice.render.line");
+            assert_eq!(report.function.complexity, 2);
+        }
+        // The module-level `render` is the only one named exactly so.
+        let report = locate(&path, "render", &Weights::default()).unwrap();
+        assert_eq!(report.function.line, 19);
+    }
+
+    #[test]
+    fn test_ambiguous_and_missing() {
+        let (_temp_dir, path) = write(
+            "class A:\n    def save(self):\n        pass\n\n\
+             class B:\n    def save(self):\n        pass\n",
+        );
+        let error = locate(&path, "save", &Weights::default())
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("`save` matches 2 functions"));
+        assert!(error.ends_with("\n  A.save (line 2)\n  B.save (line 6)"));
+
+        let error = locate(&path, "A.sav", &Weights::default())
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("no function `A.sav`"));
+        assert!(error.ends_with("did you mean:\n  A.save (line 2)"));
+        let error = locate(&path, "unrelated", &Weights::default())
+            .unwrap_err()
+            .to_string();
+        assert_eq!(
+            error,
+            format!("no function `unrelated` in {}", path.display())
+        );
+    }
+
+    #[test]
+    fn test_typescript_locator() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("cart.ts");
+        std::fs::write(
+            &path,
+            "export class Cart {\n  total(): number {\n    const price = (item: Item) => item.price ?? 0;\n    return this.items.reduce((sum, item) => sum + price(item), 0);\n  }\n}\n",
+        )
+        .unwrap();
+        let report = locate(&path, "Cart.total.price", &Weights::default()).unwrap();
+        assert_eq!(report.qualified_name, "Cart.total.price");
+        assert_eq!((report.function.line, report.function.complexity), (3, 2));
+        // Anonymous callbacks are found by their placeholder name.
+        let callback = locate(&path, "<anonymous>", &Weights::default()).unwrap();
+        assert_eq!(callback.qualified_name, "Cart.total.<anonymous>");
+        assert_eq!(callback.function.line, 4);
+    }
+
+    #[test]
+    fn test_distance() {
+        assert_eq!(distance("kitten", "sitting"), 3);
+        assert_eq!(distance("", "abc"), 3);
+        assert_eq!(distance("save", "save"), 0);
+        assert!(designates("save", "A.save"));
+        assert!(!designates("ave", "A.save"));
+    }
+}
@@ -1,194 +1,1720 @@
 use anyhow::{Context, Result};
-use clap::Parser;
-use comfy_table::{Cell, Table};
-use serde::Serialize;
-use std::path::PathBuf;
-use tree_sitter::{Query, QueryCursor};
-use tree_sitter::Parser as TSParser;
-use walkdir::WalkDir;
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
+use complexity_audit::output::table::print_table;
+use complexity_audit::*;
+use severity::Thresholds;
+use std::io::Read as _;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use weights::Weights;
 
 #[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[command(
+    author,
+    version,
+    about,
+    long_about = None,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    args: Args,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Merge JSON results from several runs into one report
+    Merge(MergeArgs),
+    /// Convert another tool's output into a JSON result
+    Import(ImportArgs),
+    /// Report per-function differences between two JSON results
+    Compare(CompareArgs),
+    /// Report functions that regressed, improved, were added or removed
+    /// between two JSON results
+    Diff(DiffArgs),
+    /// Rank functions above the threshold by refactoring priority
+    Suggest(SuggestArgs),
+    /// Check functions against the threshold and print a one-line verdict,
+    /// exiting with code 2 when any exceeds it
+    Check(CheckArgs),
+    /// Report each function's test coverage next to its complexity, ranking
+    /// complex functions with little coverage first
+    Coverage(CoverageArgs),
+    /// Stay resident and answer analysis requests over a Unix socket, for
+    /// editors and `--use-daemon`
+    Daemon(DaemonArgs),
+    /// Report functions whose score differs from another tool's (developer
+    /// command)
+    Verify(VerifyArgs),
+    /// Score one function at each recent commit that touched its file
+    History(HistoryArgs),
+    /// Explain how the analysis treats one file: whether it is found,
+    /// skipped, and why
+    Why(WhyArgs),
+    /// Print the body hash of a function and the normalized source it is
+    /// computed from
+    Hash {
+        /// Function to hash, as PATH::NAME
+        #[arg(value_name = "PATH::NAME", value_parser = history::parse_locator)]
+        function: (String, String),
+    },
+    /// Report the metrics, breakdown and source of one function, without
+    /// walking any directory
+    Function(FunctionArgs),
+    /// Inspect or create the `analyzr.toml` configuration file
+    #[command(subcommand)]
+    Config(ConfigCommand),
+    /// Check that the Python, JavaScript and TypeScript grammars have every
+    /// node kind, field and query the analysis relies on
+    Doctor(DoctorArgs),
+}
+
+#[derive(clap::Args, Debug)]
+struct FunctionArgs {
+    /// Function to report, as PATH::NAME with NAME its name or its qualified
+    /// name, e.g. src/billing.py::Invoice.compute_totals
+    #[arg(value_name = "PATH::NAME", value_parser = history::parse_locator)]
+    function: (String, String),
+
+    /// Count complexity the way another tool does (default: the configured
+    /// preset)
+    #[arg(long, value_enum)]
+    preset: Option<weights::Preset>,
+
+    /// Output format (text, json)
+    #[arg(short, long, default_value = "text")]
+    output: String,
+}
+
+#[derive(clap::Args, Debug)]
+struct DoctorArgs {
+    /// Tree-sitter query file to check against the grammar as well
+    /// (repeatable)
+    #[arg(long = "query", value_name = "FILE")]
+    queries: Vec<PathBuf>,
+
+    /// Grammar the query files are checked against
+    #[arg(long, visible_alias = "lang", value_enum, default_value_t = language::Language::Python)]
+    language: language::Language,
+
+    /// Output format (text, json)
+    #[arg(short, long, default_value = "text")]
+    output: String,
+}
+
+#[derive(clap::Args, Debug)]
+struct HistoryArgs {
+    /// Function to follow, as PATH::NAME with PATH relative to the repository
+    /// directory
+    #[arg(long, value_name = "PATH::NAME", value_parser = history::parse_locator)]
+    function: (String, String),
+
+    /// Number of most recent commits touching the file to score
+    #[arg(long, default_value_t = 50)]
+    last: usize,
+
+    /// Directory inside the git repository
+    #[arg(long, default_value = ".")]
+    repo: PathBuf,
+
+    /// Count complexity the way another tool does
+    #[arg(long, value_enum, default_value_t = weights::Preset::Analyzr)]
+    preset: weights::Preset,
+
+    /// Output format (table, csv, json)
+    #[arg(short, long, default_value = "table")]
+    output: String,
+
+    /// Score every revision again instead of reusing scores cached in the git
+    /// directory
+    #[arg(long)]
+    no_cache: bool,
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigCommand {
+    /// Validate the configuration for a directory and print the effective
+    /// settings with the source of each
+    Check {
+        /// Directory whose configuration to check
+        #[arg(default_value = ".")]
+        path: PathBuf,
+    },
+    /// Write a commented starter `analyzr.toml`
+    Init {
+        /// Directory to write the file to
+        #[arg(default_value = ".")]
+        path: PathBuf,
+
+        /// Overwrite an existing file
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+#[derive(clap::Args, Debug)]
 struct Args {
-    /// Path to analyze
-    path: PathBuf,
+    /// Directory or file to analyze, or `-` to read the source of one file
+    /// from stdin
+    #[arg(required_unless_present = "roots", conflicts_with = "roots")]
+    path: Option<PathBuf>,
+
+    /// Path of the file whose source is read from stdin with `-`, reported
+    /// as its file and telling its language by its extension
+    #[arg(long, value_name = "PATH")]
+    stdin_filename: Option<PathBuf>,
+
+    /// Labelled root to analyze instead of a single path (repeatable), e.g.
+    /// --root api=repos/api --root web=repos/web
+    #[arg(long = "root", value_name = "LABEL=PATH", value_parser = workspace::parse_root)]
+    roots: Vec<(String, PathBuf)>,
+
+    /// Threshold for the project with the given label (repeatable)
+    #[arg(long = "project-threshold", value_name = "LABEL=N", value_parser = workspace::parse_threshold)]
+    project_thresholds: Vec<(String, u32)>,
+
+    /// Skip vendored code in the project with the given label only
+    /// (repeatable)
+    #[arg(long = "project-skip-vendored", value_name = "LABEL")]
+    project_skip_vendored: Vec<String>,
 
     /// Complexity threshold to highlight
     #[arg(short, long, default_value_t = 10)]
     threshold: u32,
 
-    /// Output format
+    /// Complexity above which a function is reported as an error rather than
+    /// a warning (default: twice the threshold)
+    #[arg(long)]
+    error_threshold: Option<u32>,
+
+    /// Complexity above which a function below the threshold is still
+    /// reported, with severity info (default: none)
+    #[arg(long)]
+    info_threshold: Option<u32>,
+
+    /// Output format (table, json, warnings-ng, rdjson, sarif, step-summary)
     #[arg(short, long, default_value = "table")]
     output: String,
 
+    /// Print `--output json` on a single line
+    #[arg(long)]
+    compact: bool,
+
+    /// Directory the file paths of warnings-ng, rdjson, sarif and
+    /// step-summary output are relative to (default: the analyzed path)
+    #[arg(long, value_name = "DIR")]
+    base_path: Option<PathBuf>,
+
     /// Display summary statistics
     #[arg(short, long)]
     summary: bool,
+
+    /// JSON result of an earlier run to show each function's change against
+    #[arg(long, value_name = "FILE")]
+    baseline: Option<PathBuf>,
+
+    /// List the functions of the baseline that no longer exist
+    #[arg(long, requires = "baseline")]
+    show_removed: bool,
+
+    /// Write the JSON result of this run to FILE, to compare later runs with
+    /// through --baseline
+    #[arg(long, value_name = "FILE")]
+    write_baseline: Option<PathBuf>,
+
+    /// List only the functions that regressed against the baseline, new or
+    /// grown above the threshold, and fail when there are any
+    #[arg(long, requires = "baseline")]
+    regressions_only: bool,
+
+    /// Coefficient of a metric in the risk score (repeatable), e.g.
+    /// --risk cyclomatic=1 --risk locals=0.5
+    #[arg(long = "risk", value_name = "METRIC=COEFFICIENT", value_parser = risk::parse_coefficient)]
+    risk: Vec<(risk::Metric, f64)>,
+
+    /// Risk score above which a function's risk is highlighted
+    #[arg(long, value_name = "SCORE", value_parser = risk::parse_threshold)]
+    risk_threshold: Option<f64>,
+
+    /// Report each class's complexity including the methods it inherits
+    /// from classes in other analyzed files
+    #[arg(long)]
+    inheritance: bool,
+
+    /// Order in which functions are listed
+    #[arg(long, visible_alias = "sort", value_enum, default_value_t = SortBy::Complexity)]
+    sort_by: SortBy,
+
+    /// List only the N most complex functions
+    #[arg(long, value_name = "N")]
+    top: Option<usize>,
+
+    /// List only the functions with a complexity of at least M
+    #[arg(long, value_name = "M")]
+    min_complexity: Option<u32>,
+
+    /// Omit run metadata (version, timestamp, git revision) so that output is
+    /// byte-for-byte reproducible
+    #[arg(long)]
+    no_meta: bool,
+
+    /// Write the canonical outputs of the run to DIR as a snapshot to check
+    /// in, instead of printing them
+    #[arg(long, value_name = "DIR", conflicts_with = "check_snapshot")]
+    accept_snapshot: Option<PathBuf>,
+
+    /// Compare the canonical outputs of the run with the snapshot in DIR,
+    /// failing with a diff when they differ
+    #[arg(long, value_name = "DIR")]
+    check_snapshot: Option<PathBuf>,
+
+    /// Exit with code 3, after printing the output, when any file could not
+    /// be analyzed
+    #[arg(long)]
+    fail_on_skips: bool,
+
+    /// Exit with code 2 when any function is above the threshold
+    #[arg(long)]
+    fail_on_threshold: bool,
+
+    /// Exit with code 2 when the mean complexity is above AVG, like
+    /// `mean = AVG` in `[summary_gates]`
+    #[arg(long, value_name = "AVG", value_parser = parse_max_average)]
+    max_average: Option<f64>,
+
+    /// Exit with code 2 when more than N functions are above the threshold,
+    /// like `above_threshold = N` in `[summary_gates]`
+    #[arg(long, value_name = "N")]
+    max_total_functions_above: Option<usize>,
+
+    /// Fail without any output when a file cannot be analyzed, instead of
+    /// skipping it. Checked once the whole tree has been analyzed, naming
+    /// the first such file the walk found
+    #[arg(long)]
+    strict: bool,
+
+    /// Analyze only the files of one language (default: every supported
+    /// language, by file extension); the language of a file given as the
+    /// path or read from stdin
+    #[arg(long, visible_alias = "lang", value_enum)]
+    language: Option<language::Language>,
+
+    /// Glob of paths to leave out, relative to the analyzed path (repeatable),
+    /// e.g. --exclude '**/migrations/*.py'
+    #[arg(long, value_name = "GLOB")]
+    exclude: Vec<String>,
+
+    /// Glob of the only files to analyze, relative to the analyzed path
+    /// (repeatable)
+    #[arg(long, value_name = "GLOB")]
+    include: Vec<String>,
+
+    /// Analyze the files .gitignore files ignore too
+    #[arg(long)]
+    no_ignore: bool,
+
+    /// Complexity shown in the table and compared with the thresholds
+    #[arg(long, value_enum, default_value_t = severity::Metric::Cyclomatic)]
+    metric: severity::Metric,
+
+    /// Count complexity the way another tool does
+    #[arg(long, value_enum, default_value_t = weights::Preset::Analyzr)]
+    preset: weights::Preset,
+
+    /// Override the weight of a construct on top of the preset (repeatable),
+    /// e.g. --weight with=0 --weight ternary=1
+    #[arg(long = "weight", value_name = "CONSTRUCT=N", value_parser = weights::parse_override)]
+    weights: Vec<(weights::Construct, u32)>,
+
+    /// Where boolean operators count (default: all, or conditions-only with
+    /// the mccabe preset)
+    #[arg(long, value_enum)]
+    bool_ops: Option<weights::BoolOps>,
+
+    /// What to do with dunder methods such as `__repr__` and `__eq__`
+    #[arg(long, value_enum, default_value_t = DunderMode::Include)]
+    dunder: DunderMode,
+
+    /// Dunder method that `--dunder` leaves alone (repeatable), e.g.
+    /// --keep-dunder __init__ --keep-dunder __new__
+    #[arg(long, value_name = "NAME")]
+    keep_dunder: Vec<String>,
+
+    /// Show what each function's complexity is made of, including constructs
+    /// that weigh nothing
+    #[arg(long)]
+    explain: bool,
+
+    /// Skip third-party code: paths with a component such as vendor,
+    /// third_party or site-packages, and files marked as vendored copies
+    #[arg(long)]
+    skip_vendored: bool,
+
+    /// Additional path component marking vendored code (implies
+    /// --skip-vendored; repeatable)
+    #[arg(long = "vendored-dir", value_name = "NAME")]
+    vendored_dirs: Vec<String>,
+
+    /// What to do with functions in generated modules such as *_pb2.py and
+    /// migrations
+    #[arg(long, value_enum, default_value_t = generated::GeneratedMode::Include)]
+    generated: generated::GeneratedMode,
+
+    /// Additional file name pattern marking generated modules (repeatable),
+    /// e.g. --generated-pattern 'client/models/*.py'
+    #[arg(long = "generated-pattern", value_name = "GLOB")]
+    generated_patterns: Vec<String>,
+
+    /// Report every file skipped by a filter on stderr
+    #[arg(short, long)]
+    verbose: bool,
+
+    /// Write copies of the analyzed files to DIR with the complexity of each
+    /// function in a comment at the end of its `def` line
+    #[arg(long, value_name = "DIR")]
+    annotate_source: Option<PathBuf>,
+
+    /// Complexity above which functions are annotated (default: the
+    /// threshold)
+    #[arg(long, value_name = "N", requires = "annotate_source")]
+    annotate_floor: Option<u32>,
+
+    /// Remove the annotations from the copies written by --annotate-source
+    /// instead of adding them
+    #[arg(long, requires = "annotate_source", conflicts_with = "annotate_floor")]
+    strip_annotations: bool,
+
+    /// Replay the previous result when no file or setting has changed since,
+    /// keeping it in DIR (default: the git directory)
+    #[arg(long, value_name = "DIR")]
+    run_cache: Option<Option<PathBuf>>,
+
+    /// Nesting depth of the syntax tree above which a file is skipped
+    /// rather than analyzed
+    #[arg(long, value_name = "N", default_value_t = depth::DEFAULT_MAX_DEPTH)]
+    max_depth: usize,
+
+    /// Number of threads analyzing files (default: one per core)
+    #[arg(short, long, value_name = "N")]
+    jobs: Option<NonZeroUsize>,
+
+    /// Analyze files through the daemon listening on SOCKET (default: the
+    /// daemon's default socket), analyzing in-process when none answers
+    #[arg(long, value_name = "SOCKET")]
+    use_daemon: Option<Option<PathBuf>>,
+
+    /// Write progress events to stderr for tools wrapping the CLI
+    #[arg(long, value_enum)]
+    progress: Option<progress::ProgressFormat>,
+
+    /// Number of worst files listed in the summary
+    #[arg(long, default_value_t = DEFAULT_TOP_FILES)]
+    top_files: usize,
+
+    /// Thresholds by path from the `[[rules]]` of the configuration file
+    #[arg(skip)]
+    rules: Option<rules::Rules>,
+
+    /// Categories of functions from the `[[categories]]` of the
+    /// configuration file
+    #[arg(skip)]
+    categories: Vec<categories::Category>,
+
+    /// Gates on the summary statistics from the `[summary_gates]` of the
+    /// configuration file
+    #[arg(skip)]
+    gates: Vec<gates::Gate>,
+
+    /// Append the summary statistics of the run to the JSON-lines FILE when
+    /// it passes its summary gates
+    #[arg(long, value_name = "FILE")]
+    trend: Option<PathBuf>,
+
+    /// Check the `*_increase` summary gates against the last run recorded
+    /// in the --trend file
+    #[arg(long, requires = "trend")]
+    compare_to_last: bool,
+
+    /// List the functions one by one, roll them up by file or directory, or
+    /// add a breakdown of them by category to the summary
+    #[arg(long, value_enum)]
+    group_by: Option<GroupBy>,
+
+    /// Number of path components directories are rolled up to, with
+    /// --group-by directory
+    #[arg(long, value_name = "N", default_value_t = 1)]
+    depth: usize,
+
+    /// Criterion the worst files are ranked by
+    #[arg(long, value_enum, default_value_t = FileRanking::Max)]
+    rank_files_by: FileRanking,
+
+    /// Add a link to each function: a permalink when the git remote is on
+    /// GitHub, GitLab or Bitbucket, a file:// URI otherwise
+    #[arg(long)]
+    links: bool,
+
+    /// Base URL for function links, e.g. https://github.com/org/repo/blob/<sha>
+    /// (implies --links)
+    #[arg(long)]
+    link_base: Option<String>,
+
+    /// Template for function links with {base}, {sha}, {path} and {line}
+    /// placeholders (default: {base}/{path}#L{line}; implies --links)
+    #[arg(long)]
+    link_template: Option<String>,
+}
+
+#[derive(clap::Args, Debug)]
+struct ImportArgs {
+    /// Tool that produced the input
+    #[arg(long, value_enum)]
+    from: ImportFormat,
+
+    /// Output of the tool (for radon: `radon cc -j`)
+    input: PathBuf,
+
+    /// File to write the converted result to (default: stdout)
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Directory the tool was run against; paths are made relative to it so
+    /// fingerprints line up with an analysis of the same directory
+    #[arg(long, default_value = ".")]
+    root: PathBuf,
+
+    /// Threshold used to compute the summary
+    #[arg(short, long, default_value_t = 10)]
+    threshold: u32,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum ImportFormat {
+    Radon,
+}
+
+#[derive(clap::Args, Debug)]
+struct CompareArgs {
+    /// First JSON result
+    left: PathBuf,
+
+    /// Second JSON result
+    right: PathBuf,
+
+    /// Output format (table, json)
+    #[arg(short, long, default_value = "table")]
+    output: String,
+}
+
+#[derive(clap::Args, Debug)]
+struct DiffArgs {
+    /// JSON result before the change
+    before: PathBuf,
+
+    /// JSON result after the change
+    after: PathBuf,
+
+    /// Output format (table, json, markdown, github)
+    #[arg(short, long, default_value = "table")]
+    output: String,
+}
+
+#[derive(clap::Args, Debug)]
+struct SuggestArgs {
+    /// Path to analyze
+    path: PathBuf,
+
+    /// Complexity threshold above which functions are ranked
+    #[arg(short, long, default_value_t = 10)]
+    threshold: u32,
+
+    /// Output format (table, json, markdown)
+    #[arg(short, long, default_value = "table")]
+    output: String,
+
+    /// Weigh the priority by the number of call sites of each function
+    #[arg(long)]
+    fan_in: bool,
+}
+
+#[derive(clap::Args, Debug)]
+struct CheckArgs {
+    /// Directory to check
+    #[arg(default_value = ".")]
+    path: PathBuf,
+
+    /// Complexity above which a function fails the check (default: from
+    /// analyzr.toml, else 10)
+    #[arg(short, long)]
+    threshold: Option<u32>,
+
+    /// Complexity compared with the threshold, as with the analysis flag
+    #[arg(long, value_enum, default_value_t = severity::Metric::Cyclomatic)]
+    metric: severity::Metric,
+
+    /// Number of worst offenders listed after the verdict
+    #[arg(long, default_value_t = 0)]
+    list: usize,
+
+    /// Output format (text, json)
+    #[arg(short, long, default_value = "text")]
+    output: String,
+
+    /// Skip third-party code, as with the analysis flag
+    #[arg(long)]
+    skip_vendored: bool,
+
+    /// What to do with functions in generated modules
+    #[arg(long, value_enum, default_value_t = generated::GeneratedMode::Include)]
+    generated: generated::GeneratedMode,
+
+    /// What to do with dunder methods
+    #[arg(long, value_enum, default_value_t = DunderMode::Include)]
+    dunder: DunderMode,
 }
 
-#[derive(Debug, Serialize)]
-struct FunctionComplexity {
-    name: String,
-    file: String,
-    line: u32,
-    complexity: u32,
+#[derive(clap::Args, Debug)]
+struct CoverageArgs {
+    /// Directory to analyze
+    #[arg(default_value = ".")]
+    path: PathBuf,
+
+    /// Coverage report, as an LCOV tracefile or coverage.py XML
+    #[arg(long, value_name = "FILE", alias = "xml")]
+    lcov: PathBuf,
+
+    /// Complexity above which a function counts as complex (default: from
+    /// analyzr.toml, else 10)
+    #[arg(short, long)]
+    threshold: Option<u32>,
+
+    /// Percentage of covered lines under which a complex function is risky
+    #[arg(long, value_name = "PERCENT", default_value_t = 50.0, value_parser = coverage::parse_percent)]
+    min_coverage: f64,
+
+    /// Exit with code 2 when more than N functions are risky
+    #[arg(long, value_name = "N")]
+    max_risky: Option<usize>,
+
+    /// Output format (table, json)
+    #[arg(short, long, default_value = "table")]
+    output: String,
 }
 
-#[derive(Debug, Serialize)]
-struct AnalysisResult {
-    functions: Vec<FunctionComplexity>,
-    summary: Option<Summary>,
+#[derive(clap::Args, Debug)]
+struct DaemonArgs {
+    /// Socket to listen on (default: analyzr.sock in the temporary directory)
+    #[arg(long)]
+    socket: Option<PathBuf>,
+
+    /// Exit after this many seconds without a request (default: never)
+    #[arg(long, value_name = "SECONDS")]
+    idle_timeout: Option<u64>,
 }
 
-#[derive(Debug, Serialize)]
-struct Summary {
-    mean_complexity: f64,
-    max_complexity: u32,
-    total_functions: usize,
-    functions_above_threshold: usize,
+#[derive(clap::Args, Debug)]
+struct WhyArgs {
+    /// File to explain
+    file: PathBuf,
+
+    /// Directory the analysis would be run on
+    #[arg(long, default_value = ".")]
+    root: PathBuf,
+
+    /// Output format (text, json)
+    #[arg(short, long, default_value = "text")]
+    output: String,
+
+    /// Skip third-party code, as with the analysis flag
+    #[arg(long)]
+    skip_vendored: bool,
+
+    /// Additional path component marking vendored code (implies
+    /// --skip-vendored; repeatable)
+    #[arg(long = "vendored-dir", value_name = "NAME")]
+    vendored_dirs: Vec<String>,
+
+    /// What to do with functions in generated modules
+    #[arg(long, value_enum, default_value_t = generated::GeneratedMode::Include)]
+    generated: generated::GeneratedMode,
+
+    /// Additional file name pattern marking generated modules (repeatable)
+    #[arg(long = "generated-pattern", value_name = "GLOB")]
+    generated_patterns: Vec<String>,
+
+    /// What to do with dunder methods
+    #[arg(long, value_enum, default_value_t = DunderMode::Include)]
+    dunder: DunderMode,
+
+    /// Dunder method that `--dunder` leaves alone (repeatable)
+    #[arg(long, value_name = "NAME")]
+    keep_dunder: Vec<String>,
+
+    /// Glob of paths to leave out (repeatable)
+    #[arg(long, value_name = "GLOB")]
+    exclude: Vec<String>,
+
+    /// Glob of the only files to analyze (repeatable)
+    #[arg(long, value_name = "GLOB")]
+    include: Vec<String>,
+
+    /// Analyze the files .gitignore files ignore too
+    #[arg(long)]
+    no_ignore: bool,
 }
 
-fn calculate_complexity(source: &str) -> Result<Vec<FunctionComplexity>> {
-    let mut parser = TSParser::new();
-    let language = tree_sitter_python::language();
-    parser.set_language(language).unwrap();
+#[derive(clap::Args, Debug)]
+struct VerifyArgs {
+    /// Directory to analyze with both tools
+    path: PathBuf,
 
-    let tree = parser.parse(source, None).context("Failed to parse Python code")?;
-    let mut results = Vec::new();
+    /// Tool to verify against, which must be installed
+    #[arg(long, value_enum, default_value_t = ImportFormat::Radon)]
+    against: ImportFormat,
 
-    let query = Query::new(
-        language,
-        "(function_definition
-            name: (identifier) @name
-            body: (block) @body) @function",
-    )?;
+    /// JSON list of known divergences, each a `function` (`path::name`) and a
+    /// `reason`
+    #[arg(long)]
+    allowlist: Option<PathBuf>,
 
-    let mut query_cursor = QueryCursor::new();
-    let matches = query_cursor.matches(&query, tree.root_node(), source.as_bytes());
+    /// Output format (table, json)
+    #[arg(short, long, default_value = "table")]
+    output: String,
+}
+
+#[derive(clap::Args, Debug)]
+struct MergeArgs {
+    /// JSON result files to merge
+    #[arg(required = true, num_args = 2..)]
+    inputs: Vec<PathBuf>,
+
+    /// File to write the merged result to (default: stdout)
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Threshold used to recompute the summary (default: the inputs' threshold)
+    #[arg(short, long)]
+    threshold: Option<u32>,
 
-    for m in matches {
-        let function_node = m.captures[0].node;
-        let name_node = m.captures[1].node;
-        let body_node = m.captures[2].node;
+    /// When the same function has different scores in several inputs, keep
+    /// the one from the most recent run instead of failing
+    #[arg(long)]
+    keep_newest: bool,
 
-        let name = name_node.utf8_text(source.as_bytes())?;
-        let mut complexity = 1; // Base complexity
+    /// Omit run metadata from the merged result
+    #[arg(long)]
+    no_meta: bool,
+}
 
-        let control_flow_query = Query::new(
-            language,
-            "(if_statement) @if
-             (elif_clause) @elif
-             (for_statement) @for
-             (while_statement) @while
-             (try_statement) @try
-             (except_clause) @except
-             (with_statement) @with
-             (boolean_operator) @bool_op",
-        )?;
+/// Order of the listed functions.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum SortBy {
+    /// Most complex first
+    #[default]
+    Complexity,
+    /// By name
+    Name,
+    /// By file, and by line within a file
+    File,
+    /// By line, across files
+    Line,
+    /// Biggest regression against the baseline first
+    Delta,
+    /// Highest risk score first
+    Risk,
+}
 
-        let mut control_cursor = QueryCursor::new();
-        let control_matches = control_cursor.matches(&control_flow_query, body_node, source.as_bytes());
+/// How functions are grouped in the output.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum GroupBy {
+    /// One row per function, as without --group-by
+    Function,
+    /// One row per file, most complex in total first
+    File,
+    /// One row per directory, down to --depth components, most complex in
+    /// total first
+    Directory,
+    /// A breakdown by the categories of `[[categories]]` in analyzr.toml,
+    /// in the summary
+    Category,
+}
 
-        for _ in control_matches {
-            complexity += 1;
+fn main() -> Result<()> {
+    let matches = Cli::command()
+        .try_get_matches()
+        .unwrap_or_else(|error| exit_usage(error));
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|error| exit_usage(error));
+    if !matches!(cli.command, Some(Command::Doctor(_))) {
+        grammar::validate()?;
+    }
+    let result = match cli.command {
+        Some(Command::Merge(args)) => run_merge(args),
+        Some(Command::Import(args)) => run_import(args),
+        Some(Command::Compare(args)) => run_compare(args),
+        Some(Command::Diff(args)) => run_diff(args),
+        Some(Command::Suggest(args)) => run_suggest(args),
+        Some(Command::Check(args)) => run_check(args),
+        Some(Command::Coverage(args)) => run_coverage(args),
+        Some(Command::Daemon(args)) => daemon::serve(
+            &args.socket.unwrap_or_else(daemon::default_socket),
+            args.idle_timeout.map(std::time::Duration::from_secs),
+        ),
+        Some(Command::Verify(args)) => run_verify(args),
+        Some(Command::History(args)) => run_history(args),
+        Some(Command::Why(args)) => run_why(args),
+        Some(Command::Hash { function }) => run_hash(&function.0, &function.1),
+        Some(Command::Function(args)) => run_function(args),
+        Some(Command::Config(command)) => run_config(command),
+        Some(Command::Doctor(args)) => run_doctor(args),
+        None => {
+            let progress = cli.args.progress.map(|_| progress::Reporter::stderr());
+            let result = run_analyze(cli.args, &matches, progress.clone());
+            if let Some(progress) = progress {
+                progress.emit(&progress::Event::Finished {
+                    exit_code: exit_code(&result),
+                });
+            }
+            result
         }
+    };
+    if let Some(failed) = result.as_ref().err().and_then(|e| e.downcast_ref::<Failed>()) {
+        eprintln!("Error: {}", failed);
+        std::process::exit(failed.code);
+    }
+    result
+}
 
-        results.push(FunctionComplexity {
-            name: name.to_string(),
-            file: "".to_string(), // Will be set by caller
-            line: function_node.start_position().row as u32 + 1,
-            complexity,
-        });
+/// Path argument that reads the source to analyze from stdin.
+const STDIN: &str = "-";
+
+/// Exit code of a run that reported its results but failed a check on
+/// them: a function above the threshold with `--fail-on-threshold`, a
+/// summary gate, or a regression with `--regressions-only`.
+const EXIT_CHECK_FAILED: i32 = 2;
+
+/// Exit code of a run that reported its results but skipped files it could
+/// not analyze, with `--fail-on-skips`.
+const EXIT_SKIPPED: i32 = 3;
+
+/// A run that printed its output but fails, with the exit code telling why,
+/// as opposed to an error that stopped it, which exits with 1.
+#[derive(Debug)]
+struct Failed {
+    code: i32,
+    message: String,
+}
+
+impl std::fmt::Display for Failed {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Failed {}
+
+/// Parses `--max-average`, which must be a valid `mean` summary gate.
+fn parse_max_average(value: &str) -> Result<f64, String> {
+    let limit: f64 = value
+        .parse()
+        .map_err(|_| format!("`{}` is not a number", value))?;
+    gates::parse_gate("mean", limit)?;
+    Ok(limit)
+}
+
+/// Exits on a command-line error, with 1 rather than clap's 2, which is
+/// [`EXIT_CHECK_FAILED`]; `--help` and `--version` still exit with 0.
+fn exit_usage(error: clap::Error) -> ! {
+    if error.use_stderr() {
+        let _ = error.print();
+        std::process::exit(1);
+    }
+    error.exit()
+}
+
+fn exit_code(result: &Result<()>) -> i32 {
+    match result {
+        Ok(()) => 0,
+        Err(error) => error.downcast_ref::<Failed>().map_or(1, |failed| failed.code),
+    }
+}
+
+fn run_analyze(
+    mut args: Args,
+    matches: &clap::ArgMatches,
+    progress: Option<progress::Reporter>,
+) -> Result<()> {
+    if reads_stdin(&args) {
+        if args.stdin_filename.is_none() && args.language.is_none() {
+            anyhow::bail!(
+                "reading the source from stdin needs --stdin-filename or --lang to tell its language"
+            );
+        }
+    } else if args.stdin_filename.is_some() {
+        anyhow::bail!("--stdin-filename only applies when reading the source from stdin (`-`)");
+    }
+    // Renderers make paths relative to `path`; in a workspace they already are.
+    let path = report_root(&args);
+    let config_dir = args.path.as_ref().map_or_else(|| PathBuf::from("."), |_| path.clone());
+    apply_config(&config::load_checked(&config_dir)?, &mut args, matches);
+    let flag_gates = [
+        ("mean", args.max_average),
+        ("above_threshold", args.max_total_functions_above.map(|n| n as f64)),
+    ];
+    for (key, limit) in flag_gates {
+        if let Some(limit) = limit {
+            args.gates.push(gates::parse_gate(key, limit).map_err(anyhow::Error::msg)?);
+        }
+    }
+    if args.group_by == Some(GroupBy::Category) && args.categories.is_empty() {
+        anyhow::bail!("--group-by category needs [[categories]] in analyzr.toml");
+    }
+    let thresholds = Thresholds {
+        metric: args.metric,
+        ..Thresholds::new(args.threshold, args.error_threshold, args.info_threshold)
+    };
+    for warning in risk::RiskModel::new(&args.risk).warnings() {
+        eprintln!("warning: {}", warning);
+    }
+    let mut result = replay_or_analyze(&args, progress)?;
+    if let Some(error) = result.errors.first().filter(|_| args.strict) {
+        anyhow::bail!("Failed to analyze {}: {}", error.path, error.message);
+    }
+    if let Some(out) = &args.write_baseline {
+        write_result(&result, Some(out))?;
+        eprintln!("wrote baseline to {}", out.display());
+    }
+    if let Some(baseline) = &args.baseline {
+        let removed = baseline::annotate(&mut result, &read_result(baseline)?);
+        if args.show_removed {
+            result.removed = removed;
+        }
+        if args.regressions_only {
+            result.functions.retain(baseline::is_regression);
+        }
     }
+    let regressions = result.functions.len();
+    match args.group_by {
+        Some(GroupBy::File) => {
+            result.files = rollup::files(&result.functions, args.threshold, args.metric)
+        }
+        Some(GroupBy::Directory) => {
+            result.directories = rollup::directories(
+                &result.functions,
+                &path,
+                args.depth,
+                args.threshold,
+                args.metric,
+            )
+        }
+        _ => {}
+    }
+    // The summary was computed over every function already, so that what
+    // is listed does not change it.
+    select(&mut result.functions, args.metric, args.min_complexity, args.top);
+    match args.sort_by {
+        SortBy::Complexity => result.functions.sort_by(most_complex(args.metric)),
+        SortBy::Name => result
+            .functions
+            .sort_by(|a, b| a.name.cmp(&b.name).then_with(|| by_location(a, b))),
+        SortBy::File => result.functions.sort_by(by_location),
+        SortBy::Line => result
+            .functions
+            .sort_by(|a, b| a.line.cmp(&b.line).then_with(|| by_location(a, b))),
+        SortBy::Delta if args.baseline.is_none() => {
+            anyhow::bail!("--sort-by delta needs a --baseline to compare with")
+        }
+        SortBy::Delta => baseline::sort_by_delta(&mut result.functions),
+        SortBy::Risk if args.risk.is_empty() => {
+            anyhow::bail!("--sort-by risk needs risk coefficients (--risk or [risk] in analyzr.toml)")
+        }
+        SortBy::Risk => result.functions.sort_by(|a, b| {
+            b.risk.unwrap_or_default().total_cmp(&a.risk.unwrap_or_default())
+        }),
+    }
+    if args.accept_snapshot.is_some() || args.check_snapshot.is_some() {
+        let canonical = snapshot::canonical(&result, &path);
+        if let Some(dir) = &args.accept_snapshot {
+            snapshot::accept(dir, &canonical, args.threshold, args.risk_threshold)?;
+            eprintln!("wrote snapshot to {}", dir.display());
+        }
+        if let Some(dir) = &args.check_snapshot {
+            snapshot::check(dir, &canonical, args.threshold, args.risk_threshold)?;
+            eprintln!("matches the snapshot in {}", dir.display());
+        }
+        return Ok(());
+    }
+    if let Some(out) = &args.annotate_source {
+        let roots: Vec<annotate::Root> = match &args.path {
+            Some(_) => vec![annotate::Root {
+                label: None,
+                path: &path,
+            }],
+            None => args
+                .roots
+                .iter()
+                .map(|(label, path)| annotate::Root {
+                    label: Some(label),
+                    path,
+                })
+                .collect(),
+        };
+        let floor = (!args.strip_annotations).then(|| args.annotate_floor.unwrap_or(args.threshold));
+        let written = annotate::write_copies(&result, &roots, out, floor)?;
+        let files = if written == 1 { "file" } else { "files" };
+        eprintln!("wrote {} {} to {}", written, files, out.display());
+    }
+
+    let previous = match &args.trend {
+        Some(trend) if args.compare_to_last => {
+            let previous = gates::last(trend)?;
+            if previous.is_none() {
+                eprintln!(
+                    "warning: no run recorded in {} yet; skipping the *_increase summary gates",
+                    trend.display()
+                );
+            }
+            previous
+        }
+        _ => None,
+    };
+    if let Some(summary) = &result.summary {
+        result.gates = gates::evaluate(&args.gates, summary, previous.as_ref());
+    }
+
+    let base = args.base_path.as_ref().unwrap_or(&path);
+    match args.output.as_str() {
+        "table" => print_table(&result, &thresholds, args.risk_threshold),
+        "json" if args.compact => println!("{}", serde_json::to_string(&result)?),
+        "json" => println!("{}", serde_json::to_string_pretty(&result)?),
+        "warnings-ng" => println!(
+            "{}",
+            output::warnings_ng::render(&result, base, &thresholds)?
+        ),
+        "rdjson" => println!("{}", output::rdjson::render(&result, base, &thresholds)?),
+        "sarif" => println!("{}", output::sarif::render(&result, base, &thresholds)?),
+        "step-summary" => {
+            let markdown = output::step_summary::render(&result, base, &thresholds);
+            match std::env::var_os("GITHUB_STEP_SUMMARY") {
+                Some(file) => output::step_summary::append(Path::new(&file), &markdown)?,
+                None => print!("{}", markdown),
+            }
+        }
+        _ => anyhow::bail!("Invalid output format"),
+    }
+    for error in &result.errors {
+        eprintln!(
+            "warning: skipped {} ({}): {}",
+            error.path,
+            error.category.label(),
+            error.message
+        );
+    }
+
+    let mut failures = Vec::new();
+    let above = result.summary.as_ref().map_or(0, |s| s.functions_above_threshold);
+    if args.fail_on_threshold && above > 0 {
+        let functions = if above == 1 { "function is" } else { "functions are" };
+        failures.push(format!(
+            "{} {} above the threshold of {}",
+            above, functions, args.threshold
+        ));
+    }
+    if args.regressions_only && regressions > 0 {
+        let functions = if regressions == 1 { "function" } else { "functions" };
+        failures.push(format!(
+            "{} {} regressed against the baseline",
+            regressions, functions
+        ));
+    }
+    failures.extend(
+        result
+            .gates
+            .iter()
+            .filter(|gate| !gate.passed)
+            .map(|gate| gate.failure()),
+    );
+    if !failures.is_empty() {
+        return Err(Failed {
+            code: EXIT_CHECK_FAILED,
+            message: failures.join("\n"),
+        }
+        .into());
+    }
+    if args.fail_on_skips {
+        if let Some(skipped) = skipped_line(&result.errors) {
+            return Err(Failed {
+                code: EXIT_SKIPPED,
+                message: skipped,
+            }
+            .into());
+        }
+    }
+    // Failing runs are not recorded, so that rerunning one fails again.
+    if let (Some(trend), Some(summary)) = (&args.trend, &result.summary) {
+        let root = args.path.clone().unwrap_or_else(|| PathBuf::from("."));
+        gates::record(trend, &gates::Entry::new(summary, &root))?;
+    }
+
+    Ok(())
+}
+
+/// Fills in the analysis arguments that were not given on the command line
+/// from `config`. Weights, vendored directories and generated patterns add
+/// to the flags.
+fn apply_config(config: &config::Config, args: &mut Args, matches: &clap::ArgMatches) {
+    let from_cli = |id| matches.value_source(id) == Some(clap::parser::ValueSource::CommandLine);
+    if !from_cli("threshold") {
+        args.threshold = config.threshold.value;
+    }
+    if !from_cli("preset") {
+        args.preset = config.preset.value;
+    }
+    args.error_threshold = args.error_threshold.or(config.error_threshold.value);
+    args.info_threshold = args.info_threshold.or(config.info_threshold.value);
+    args.skip_vendored |= config.skip_vendored.value;
+    let weights = config.weights.iter().map(|setting| setting.value);
+    args.weights = weights.chain(args.weights.drain(..)).collect();
+    let risk = config.risk.iter().map(|setting| setting.value);
+    args.risk = risk.chain(args.risk.drain(..)).collect();
+    let dirs = config.vendored_dirs.value.iter().cloned();
+    args.vendored_dirs = dirs.chain(args.vendored_dirs.drain(..)).collect();
+    let patterns = config.generated_patterns.value.iter().cloned();
+    args.generated_patterns = patterns.chain(args.generated_patterns.drain(..)).collect();
+    args.rules = config.rules();
+    args.categories = config.categories.iter().map(|setting| setting.value.clone()).collect();
+    args.gates = config.summary_gates.iter().map(|setting| setting.value).collect();
+}
 
-    Ok(results)
+/// Whether `args` read the source to analyze from stdin.
+fn reads_stdin(args: &Args) -> bool {
+    args.path.as_ref().is_some_and(|path| path.as_os_str() == STDIN)
 }
 
-fn analyze_directory(path: &PathBuf, threshold: u32) -> Result<AnalysisResult> {
-    let mut all_functions = Vec::new();
-    let mut total_complexity = 0u64;
-    let mut max_complexity = 0u32;
+/// Directory the paths of the report are relative to: the analyzed one, or
+/// that of the analyzed file or of `--stdin-filename`. Empty in a
+/// workspace, whose paths already are relative to their root.
+fn report_root(args: &Args) -> PathBuf {
+    let file = match &args.path {
+        Some(_) if reads_stdin(args) => args.stdin_filename.as_deref(),
+        Some(path) if path.is_file() => Some(path.as_path()),
+        path => return path.clone().unwrap_or_default(),
+    };
+    file.and_then(Path::parent)
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .map_or_else(|| PathBuf::from("."), Path::to_path_buf)
+}
+
+/// Order of functions by project, file and line.
+fn by_location(a: &FunctionComplexity, b: &FunctionComplexity) -> std::cmp::Ordering {
+    (&a.project, &a.file, a.line).cmp(&(&b.project, &b.file, b.line))
+}
+
+/// Order of functions by the complexity `metric` applies to, most complex
+/// first, and then by location so that ties are always listed alike.
+fn most_complex(
+    metric: severity::Metric,
+) -> impl Fn(&FunctionComplexity, &FunctionComplexity) -> std::cmp::Ordering {
+    move |a, b| {
+        metric
+            .value(b)
+            .cmp(&metric.value(a))
+            .then_with(|| by_location(a, b))
+    }
+}
 
-    for entry in WalkDir::new(path)
-        .into_iter()
-        .filter_map(|e| e.ok())
-        .filter(|e| e.path().extension().map_or(false, |ext| ext == "py"))
-    {
-        let file_path = entry.path();
-        if file_path.to_string_lossy().contains("__pycache__")
-            || file_path.to_string_lossy().contains("venv")
-        {
-            continue;
+/// Keeps the functions with a complexity of at least `min_complexity`, and
+/// of those the `top` most complex, in their order.
+fn select(
+    functions: &mut Vec<FunctionComplexity>,
+    metric: severity::Metric,
+    min_complexity: Option<u32>,
+    top: Option<usize>,
+) {
+    if let Some(min) = min_complexity {
+        functions.retain(|func| metric.value(func) >= min);
+    }
+    if let Some(top) = top.filter(|&top| top < functions.len()) {
+        let mut ranked: Vec<usize> = (0..functions.len()).collect();
+        ranked.sort_by(|&a, &b| most_complex(metric)(&functions[a], &functions[b]));
+        let mut kept = vec![false; functions.len()];
+        for &index in &ranked[..top] {
+            kept[index] = true;
         }
+        let mut index = 0;
+        functions.retain(|_| {
+            index += 1;
+            kept[index - 1]
+        });
+    }
+}
 
-        let source = std::fs::read_to_string(file_path)?;
-        let mut functions = calculate_complexity(&source)?;
+/// What the walk of `args` leaves out besides files of other languages.
+fn walk_filter(args: &Args) -> walk::Filter {
+    walk::Filter {
+        exclude: args.exclude.clone(),
+        include: args.include.clone(),
+        no_ignore: args.no_ignore,
+    }
+}
 
-        for func in &mut functions {
-            func.file = file_path.to_string_lossy().to_string();
-            total_complexity += func.complexity as u64;
-            max_complexity = max_complexity.max(func.complexity);
+/// The result of the previous run when `--run-cache` is set and nothing it
+/// depends on has changed, and otherwise a fresh analysis.
+fn replay_or_analyze(args: &Args, progress: Option<progress::Reporter>) -> Result<AnalysisResult> {
+    // Source read from stdin changes without its file, if it has one.
+    let Some(dir) = args.run_cache.as_ref().filter(|_| !reads_stdin(args)) else {
+        return analyze(args, progress);
+    };
+    let roots: Vec<PathBuf> = match &args.path {
+        Some(path) => vec![path.clone()],
+        None => args.roots.iter().map(|(_, path)| path.clone()).collect(),
+    };
+    let cache = run_cache::RunCache::new(dir.clone(), roots.first().map_or(Path::new("."), |r| r));
+    let digest = run_cache::digest(&roots, &walk_filter(args), &format!("{:?}", args));
+    if let Some(mut result) = cache.load(&digest) {
+        if let Some(meta) = &mut result.meta {
+            meta.replayed = true;
         }
+        return Ok(result);
+    }
+    let result = analyze(args, progress)?;
+    cache.store(&digest, &result);
+    Ok(result)
+}
 
-        all_functions.extend(functions);
+/// Analyzes the path or workspace roots of `args`.
+fn analyze(args: &Args, progress: Option<progress::Reporter>) -> Result<AnalysisResult> {
+    let skip_vendored = args.skip_vendored || !args.vendored_dirs.is_empty();
+    let mut weights = Weights::preset(args.preset);
+    for &(construct, weight) in &args.weights {
+        weights.set(construct, weight);
     }
+    if let Some(bool_ops) = args.bool_ops {
+        weights.bool_ops = bool_ops;
+    }
+    let want_links = args.links || args.link_base.is_some() || args.link_template.is_some();
+    let options = AnalyzeOptions {
+        threshold: args.threshold,
+        error_threshold: args.error_threshold,
+        info_threshold: args.info_threshold,
+        preset: args.preset,
+        weights,
+        vendored: skip_vendored
+            .then(|| vendored::VendorDetector::with_components(&args.vendored_dirs)),
+        verbose: args.verbose,
+        explain: args.explain,
+        dunder: args.dunder,
+        keep_dunder: args.keep_dunder.clone(),
+        generated: generated::GeneratedDetector::with_patterns(&args.generated_patterns),
+        generated_mode: args.generated,
+        progress,
+        trace: None,
+        daemon: args.use_daemon.as_ref().and_then(|socket| {
+            let socket = socket.clone().unwrap_or_else(daemon::default_socket);
+            daemon::Client::connect(&socket)
+        }),
+        max_depth: Some(args.max_depth),
+        language: args.language,
+        walk: walk_filter(args),
+        metric: args.metric,
+        jobs: args.jobs,
+        rank_files_by: args.rank_files_by,
+        top_files: args.top_files,
+        categories: args.categories.clone(),
+        category_summary: args.group_by == Some(GroupBy::Category),
+        risk: args.risk.clone(),
+        risk_threshold: args.risk_threshold,
+        inheritance: args.inheritance,
+        rules: args.rules.clone(),
+        links: want_links.then(|| links::LinkOptions {
+            base: args.link_base.clone(),
+            template: args.link_template.clone(),
+        }),
+        meta: !args.no_meta,
+    };
 
-    let summary = if !all_functions.is_empty() {
-        Some(Summary {
-            mean_complexity: total_complexity as f64 / all_functions.len() as f64,
-            max_complexity,
-            total_functions: all_functions.len(),
-            functions_above_threshold: all_functions
+    match &args.path {
+        Some(_) if reads_stdin(args) => {
+            let mut source = String::new();
+            std::io::stdin()
+                .read_to_string(&mut source)
+                .context("Failed to read the source from stdin")?;
+            let file = args.stdin_filename.clone().unwrap_or_else(|| PathBuf::from(STDIN));
+            Ok(analyze_buffer(&source, &file, &options))
+        }
+        Some(path) => analyze_path(path, &options),
+        None => {
+            workspace::check_labels(
+                &args.roots,
+                args.project_thresholds
+                    .iter()
+                    .map(|(label, _)| label)
+                    .chain(&args.project_skip_vendored),
+            )?;
+            let projects: Vec<workspace::Project> = args
+                .roots
                 .iter()
-                .filter(|f| f.complexity > threshold)
-                .count(),
-        })
+                .map(|(label, path)| {
+                    let mut options = options.clone();
+                    options.threshold = args
+                        .project_thresholds
+                        .iter()
+                        .rev()
+                        .find(|(l, _)| l == label)
+                        .map_or(args.threshold, |(_, threshold)| *threshold);
+                    if options.vendored.is_none() && args.project_skip_vendored.contains(label) {
+                        options.vendored =
+                            Some(vendored::VendorDetector::with_components(&args.vendored_dirs));
+                    }
+                    workspace::Project {
+                        label: label.clone(),
+                        path: path.clone(),
+                        options,
+                    }
+                })
+                .collect();
+            workspace::analyze(&projects, &options)
+        }
+    }
+}
+
+fn run_merge(args: MergeArgs) -> Result<()> {
+    let mut inputs = Vec::new();
+    for path in &args.inputs {
+        inputs.push((path.clone(), read_result(path)?));
+    }
+
+    let policy = if args.keep_newest {
+        merge::ConflictPolicy::KeepNewest
     } else {
-        None
+        merge::ConflictPolicy::Fail
     };
+    let merged = merge::merge(inputs, args.threshold, policy, !args.no_meta)?;
+    for conflict in &merged.conflicts {
+        eprintln!("warning: {}", conflict);
+    }
 
-    Ok(AnalysisResult {
-        functions: all_functions,
-        summary,
-    })
+    write_result(&merged.result, args.output.as_deref())
 }
 
-fn print_table(result: &AnalysisResult, threshold: u32) {
-    let mut table = Table::new();
-    table.set_header(vec!["Function", "File", "Line", "Complexity"]);
+fn run_import(args: ImportArgs) -> Result<()> {
+    let json = std::fs::read_to_string(&args.input)
+        .with_context(|| format!("Failed to read {}", args.input.display()))?;
+    let result = match args.from {
+        ImportFormat::Radon => radon::import(&json, &args.root, args.threshold)?,
+    };
+    for error in &result.errors {
+        eprintln!("warning: radon could not analyze {}: {}", error.path, error.message);
+    }
 
-    for func in &result.functions {
-        let mut row = vec![
-            Cell::new(&func.name),
-            Cell::new(&func.file),
-            Cell::new(func.line.to_string()),
-            Cell::new(func.complexity.to_string()),
-        ];
+    write_result(&result, args.output.as_deref())
+}
+
+fn run_compare(args: CompareArgs) -> Result<()> {
+    let left = read_result(&args.left)?;
+    let right = read_result(&args.right)?;
+    let comparison = compare::compare(&left, &right);
+
+    match args.output.as_str() {
+        "table" => compare::print_comparison(
+            &comparison,
+            &args.left.to_string_lossy(),
+            &args.right.to_string_lossy(),
+        ),
+        "json" => println!("{}", serde_json::to_string_pretty(&comparison)?),
+        _ => anyhow::bail!("Invalid output format"),
+    }
+
+    Ok(())
+}
 
-        if func.complexity > threshold {
-            row[3] = Cell::new(func.complexity.to_string()).fg(comfy_table::Color::Red);
+fn run_diff(args: DiffArgs) -> Result<()> {
+    let before = read_result(&args.before)?;
+    let after = read_result(&args.after)?;
+    let diff = diff::diff(&before, &after);
+
+    match args.output.as_str() {
+        "table" => diff::print_diff(&diff),
+        "json" => println!("{}", serde_json::to_string_pretty(&diff)?),
+        "markdown" => print!("{}", diff::render_markdown(&diff)),
+        "github" => print!("{}", diff::render_github(&diff)),
+        _ => anyhow::bail!("Invalid output format"),
+    }
+
+    Ok(())
+}
+
+fn run_suggest(args: SuggestArgs) -> Result<()> {
+    let options = AnalyzeOptions {
+        threshold: args.threshold,
+        ..Default::default()
+    };
+    let result = analyze_path(&args.path, &options)?;
+    let suggestions = suggest::suggest(&result, &args.path, args.threshold, args.fan_in)?;
+
+    match args.output.as_str() {
+        "table" => suggest::print_table(&suggestions),
+        "json" => println!("{}", serde_json::to_string_pretty(&suggestions)?),
+        "markdown" => print!("{}", suggest::render_markdown(&suggestions)),
+        _ => anyhow::bail!("Invalid output format"),
+    }
+
+    Ok(())
+}
+
+/// Prints the coverage of each function, failing when more functions are
+/// risky than `--max-risky` allows.
+fn run_coverage(args: CoverageArgs) -> Result<()> {
+    let config = config::load_checked(&args.path)?;
+    let threshold = args.threshold.unwrap_or(config.threshold.value);
+    let coverage = coverage::Coverage::load(&args.lcov)?;
+    let options = AnalyzeOptions {
+        threshold,
+        weights: config.options().weights,
+        ..Default::default()
+    };
+    let result = analyze_path(&args.path, &options)?;
+    let report =
+        coverage::cross_reference(&result, &args.path, &coverage, threshold, args.min_coverage);
+
+    match args.output.as_str() {
+        "table" => coverage::print_table(&report),
+        "json" => println!("{}", serde_json::to_string_pretty(&report)?),
+        _ => anyhow::bail!("Invalid output format"),
+    }
+    match args.max_risky {
+        Some(max) if report.risky > max => Err(Failed {
+            code: EXIT_CHECK_FAILED,
+            message: format!(
+                "{} risky {}, more than the {} allowed",
+                report.risky,
+                if report.risky == 1 { "function" } else { "functions" },
+                max
+            ),
         }
+        .into()),
+        _ => Ok(()),
+    }
+}
 
-        table.add_row(row);
+/// Prints the verdict of `check`, failing when it did not pass.
+fn run_check(args: CheckArgs) -> Result<()> {
+    let config = config::load_checked(&args.path)?;
+    let defaults = config.options();
+    let options = AnalyzeOptions {
+        threshold: args.threshold.unwrap_or(defaults.threshold),
+        metric: args.metric,
+        vendored: defaults.vendored.or_else(|| args.skip_vendored.then(Default::default)),
+        dunder: args.dunder,
+        generated_mode: args.generated,
+        ..defaults
+    };
+    let budgets = (!config.budgets.is_empty()).then(|| check::Budgets {
+        codeowners: owners::CodeOwners::find(&args.path),
+        budgets: config.budgets.iter().map(|setting| setting.value.clone()).collect(),
+    });
+    // The worst offender is kept for the verdict line even when none are listed.
+    let keep = args.list.max(1);
+    let verdict = check::check(&args.path, &options, keep, budgets.as_ref());
+    match args.output.as_str() {
+        "text" => {
+            println!("{}", verdict.line());
+            for budget in verdict.budgets.iter().flatten() {
+                println!(
+                    "  {}: {} of {} {}",
+                    budget.owner,
+                    budget.above_threshold,
+                    budget.budget,
+                    if budget.passed { "ok" } else { "over budget" }
+                );
+            }
+            for offender in verdict.worst.iter().take(args.list) {
+                println!(
+                    "  {} ({}) at {}:{}",
+                    offender.name, offender.complexity, offender.file, offender.line
+                );
+            }
+        }
+        "json" => println!(
+            "{}",
+            serde_json::to_string_pretty(&serde_json::json!({
+                "passed": verdict.passed(),
+                "threshold": verdict.threshold,
+                "files": verdict.files,
+                "functions": verdict.functions,
+                "above_threshold": verdict.above,
+                "worst": &verdict.worst[..verdict.worst.len().min(args.list)],
+                "budgets": verdict.budgets,
+                "skipped": verdict.skipped,
+                "elapsed_ms": verdict.elapsed.as_millis() as u64,
+            }))?
+        ),
+        _ => anyhow::bail!("Invalid output format"),
     }
+    if verdict.skipped > 0 {
+        let files = if verdict.skipped == 1 { "file" } else { "files" };
+        eprintln!("warning: {} {} could not be read or parsed", verdict.skipped, files);
+    }
+    if !verdict.passed() {
+        return Err(Failed {
+            code: EXIT_CHECK_FAILED,
+            message: "the check failed".to_string(),
+        }
+        .into());
+    }
+    Ok(())
+}
 
-    println!("{}", table);
+fn run_verify(args: VerifyArgs) -> Result<()> {
+    let allowlist = match &args.allowlist {
+        Some(path) => verify::read_allowlist(path)?,
+        None => Vec::new(),
+    };
+    let (reference, preset) = match args.against {
+        ImportFormat::Radon => {
+            let json = verify::run_radon(&args.path)?;
+            (radon::import(&json, &args.path, 10)?, weights::Preset::Radon)
+        }
+    };
+    let options = AnalyzeOptions {
+        weights: Weights::preset(preset),
+        ..AnalyzeOptions::default()
+    };
+    let result = analyze_path(&args.path, &options)?;
+    let report = verify::verify(&reference, &result, &args.path, &allowlist);
 
-    if let Some(summary) = &result.summary {
-        println!("\nSummary:");
-        println!("Mean Complexity: {:.2}", summary.mean_complexity);
-        println!("Max Complexity: {}", summary.max_complexity);
-        println!("Total Functions: {}", summary.total_functions);
-        println!(
-            "Functions above threshold ({}): {}",
-            threshold, summary.functions_above_threshold
+    match args.output.as_str() {
+        "table" => verify::print_report(&report),
+        "json" => println!("{}", serde_json::to_string_pretty(&report)?),
+        _ => anyhow::bail!("Invalid output format"),
+    }
+
+    if !report.discrepancies.is_empty() {
+        anyhow::bail!(
+            "{} functions score differently from {}",
+            report.discrepancies.len(),
+            preset.name()
         );
     }
+    Ok(())
+}
+
+fn run_history(args: HistoryArgs) -> Result<()> {
+    let (path, name) = &args.function;
+    let cache = history::BlobCache::new(&args.repo, args.preset, !args.no_cache);
+    let points = history::history(&args.repo, path, name, args.last, &cache)?;
+
+    match args.output.as_str() {
+        "table" => history::print_table(&points, name),
+        "csv" => print!("{}", history::render_csv(&points)),
+        "json" => println!("{}", serde_json::to_string_pretty(&points)?),
+        _ => anyhow::bail!("Invalid output format"),
+    }
+    Ok(())
+}
+
+fn run_why(args: WhyArgs) -> Result<()> {
+    let config = config::load_checked(&args.root)?;
+    let vendored_dirs: Vec<String> = config
+        .vendored_dirs
+        .value
+        .iter()
+        .chain(&args.vendored_dirs)
+        .cloned()
+        .collect();
+    let generated_patterns: Vec<String> = config
+        .generated_patterns
+        .value
+        .iter()
+        .chain(&args.generated_patterns)
+        .cloned()
+        .collect();
+    let skip_vendored =
+        args.skip_vendored || config.skip_vendored.value || !vendored_dirs.is_empty();
+    let options = AnalyzeOptions {
+        vendored: skip_vendored.then(|| vendored::VendorDetector::with_components(&vendored_dirs)),
+        dunder: args.dunder,
+        keep_dunder: args.keep_dunder.clone(),
+        generated: generated::GeneratedDetector::with_patterns(&generated_patterns),
+        generated_mode: args.generated,
+        walk: walk::Filter {
+            exclude: args.exclude.clone(),
+            include: args.include.clone(),
+            no_ignore: args.no_ignore,
+        },
+        ..Default::default()
+    };
+    let steps = why(&args.root, &args.file, options)?;
+    let excluded = steps.iter().any(|step| {
+        matches!(step, trace::Step::Generated { mode, .. } if mode == "exclude")
+    });
+    let analyzed = !excluded && steps.last().is_some_and(|step| !step.is_skip());
+
+    match args.output.as_str() {
+        "text" => {
+            println!("{} (analyzing {}):", args.file.display(), args.root.display());
+            if steps.is_empty() {
+                println!("  not found by the directory walk, which does not follow symlinks");
+            }
+            for step in &steps {
+                println!("  {}", step.describe());
+            }
+        }
+        "json" => println!(
+            "{}",
+            serde_json::to_string_pretty(&serde_json::json!({
+                "file": args.file,
+                "root": args.root,
+                "analyzed": analyzed,
+                "steps": steps,
+            }))?
+        ),
+        _ => anyhow::bail!("Invalid output format"),
+    }
+    Ok(())
+}
+
+fn run_hash(path: &str, name: &str) -> Result<()> {
+    let source =
+        std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path))?;
+    let functions = body_hash::find(&source, Path::new(path), name)?;
+    if functions.is_empty() {
+        anyhow::bail!("no function named {} in {}", name, path);
+    }
+    for func in functions {
+        println!("{}  {}:{}", func.hash, func.name, func.line);
+        println!("  {}", func.normalized);
+    }
+    Ok(())
+}
+
+fn run_function(args: FunctionArgs) -> Result<()> {
+    let (path, locator) = &args.function;
+    let path = Path::new(path);
+    let directory = path.parent().filter(|dir| !dir.as_os_str().is_empty());
+    let config = config::load_checked(directory.unwrap_or(Path::new(".")))?;
+    let mut weights = Weights::preset(args.preset.unwrap_or(config.preset.value));
+    for setting in &config.weights {
+        let (construct, weight) = setting.value;
+        weights.set(construct, weight);
+    }
+    let report = locate::locate(path, locator, &weights)?;
+    match args.output.as_str() {
+        "text" => print!("{}", locate::render_text(&report)),
+        "json" => println!("{}", serde_json::to_string_pretty(&report)?),
+        _ => anyhow::bail!("Invalid output format"),
+    }
+    Ok(())
+}
+
+/// Prints the grammar checks, failing when any does not pass.
+fn run_doctor(args: DoctorArgs) -> Result<()> {
+    let mut custom = Vec::new();
+    for path in &args.queries {
+        let source = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        custom.push((path.display().to_string(), source));
+    }
+    let report = grammar::check(&custom, args.language);
+    match args.output.as_str() {
+        "text" => {
+            for grammar in &report.grammars {
+                println!(
+                    "{} grammar: {} (ABI {})",
+                    grammar.language.name(),
+                    grammar.version,
+                    grammar.abi
+                );
+            }
+            println!(
+                "Checked {} node kinds, {} fields, {} queries and {} canary snippets",
+                report.node_kinds, report.fields, report.queries, report.canaries
+            );
+            if report.is_ok() {
+                println!("OK: the grammars have everything the analysis relies on");
+            }
+            for problem in report.problems() {
+                println!("error: {}", problem);
+            }
+        }
+        "json" => println!("{}", serde_json::to_string_pretty(&report)?),
+        _ => anyhow::bail!("Invalid output format"),
+    }
+    if !report.is_ok() {
+        return Err(Failed {
+            code: EXIT_CHECK_FAILED,
+            message: "the grammar checks failed".to_string(),
+        }
+        .into());
+    }
+    Ok(())
+}
+
+fn run_config(command: ConfigCommand) -> Result<()> {
+    match command {
+        ConfigCommand::Check { path } => {
+            let (config, problems) = config::load(&path, |name| std::env::var(name).ok());
+            match &config.file {
+                Some(file) => println!("Configuration file: {}", file.display()),
+                None => println!(
+                    "No {} in {} or its parents",
+                    config::FILE_NAME,
+                    path.display()
+                ),
+            }
+            println!("{}", config.table());
+            if let Some(rules) = config.rules() {
+                let files: Vec<String> =
+                    discover(&rules.base, None, None, &walk::Filter::default())
+                        .filter_map(Result::ok)
+                        .map(|file| output::relative_path(&file.to_string_lossy(), &rules.base))
+                        .collect();
+                for (index, path) in rules.unmatched(&files) {
+                    println!(
+                        "warning: `{}` in rules[{}] ({}) matches no analyzed file; is it a typo?",
+                        path, index, config.rules[index].source
+                    );
+                }
+            }
+            if !problems.is_empty() {
+                anyhow::bail!("{}", config::describe(&problems));
+            }
+        }
+        ConfigCommand::Init { path, force } => {
+            let file = config::init(&path, force)?;
+            println!("Wrote {}", file.display());
+        }
+    }
+    Ok(())
+}
+
+fn read_result(path: &Path) -> Result<AnalysisResult> {
+    let file =
+        std::fs::File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    serde_json::from_reader(std::io::BufReader::new(file))
+        .with_context(|| format!("{} is not a JSON analysis result", path.display()))
+}
+
+/// Writes `result` as JSON to `path`, or to stdout when no path is given.
+fn write_result(result: &AnalysisResult, path: Option<&Path>) -> Result<()> {
+    let json = serde_json::to_string_pretty(result)?;
+    match path {
+        Some(path) => std::fs::write(path, json + "\n")
+            .with_context(|| format!("Failed to write {}", path.display()))?,
+        None => println!("{}", json),
+    }
+    Ok(())
 }
 
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use complexity_audit::output::table::summary_text;
+    use severity::Severity;
     use std::fs;
     use tempfile::TempDir;
 
@@ -203,129 +1729,200 @@ mod tests {
     }
 
     #[test]
-    fn test_calculate_complexity_simple() {
-        let source = r#"
-def simple_function():
-    return True
-
-def complex_function():
-    if True:
-        for i in range(10):
-            while i > 0:
-                try:
-                    with open('file.txt') as f:
-                        if i % 2 == 0 and i > 5:
-                            pass
-                except Exception:
-                    pass
-"#;
-        let results = calculate_complexity(source).unwrap();
-        assert_eq!(results.len(), 2);
-        
-        let simple = results.iter().find(|f| f.name == "simple_function").unwrap();
-        assert_eq!(simple.complexity, 1);
-        
-        let complex = results.iter().find(|f| f.name == "complex_function").unwrap();
-        assert_eq!(complex.complexity, 9); // 1 base + 1 if + 1 for + 1 while + 1 try + 1 with + 1 if + 1 and + 1 except
+    fn test_command_line_takes_precedence() {
+        let temp_dir = TempDir::new().unwrap();
+        let config =
+            "preset = \"sonar\"\nvendored-dirs = [\"ext\"]\ngenerated-patterns = [\"gen/*.py\"]\n\
+             [thresholds]\nwarn = 8\nerror = 30\n[weights]\nwith = 0\n[risk]\nlocals = 1\n";
+        fs::write(temp_dir.path().join(config::FILE_NAME), config).unwrap();
+        let config = config::load_checked(temp_dir.path()).unwrap();
+        let parse = |argv: &[&str]| {
+            let matches = Cli::command().get_matches_from(argv);
+            let mut args = Cli::from_arg_matches(&matches).unwrap().args;
+            apply_config(&config, &mut args, &matches);
+            args
+        };
+
+        let args = parse(&["analyzr", "src"]);
+        assert_eq!(args.threshold, 8);
+        assert_eq!(args.error_threshold, Some(30));
+        assert_eq!(args.preset, weights::Preset::Sonar);
+        assert_eq!(args.vendored_dirs, ["ext"]);
+        assert_eq!(args.generated_patterns, ["gen/*.py"]);
+
+        let args = parse(&["analyzr", "src", "-t", "10", "--preset", "analyzr", "--weight", "with=2"]);
+        assert_eq!(args.threshold, 10);
+        assert_eq!(args.preset, weights::Preset::Analyzr);
+        assert_eq!(args.weights, [(weights::Construct::With, 0), (weights::Construct::With, 2)]);
+
+        let args = parse(&["analyzr", "src", "--risk", "locals=2"]);
+        assert_eq!(args.risk, [(risk::Metric::Locals, 1.0), (risk::Metric::Locals, 2.0)]);
     }
 
     #[test]
-    fn test_analyze_directory() {
+    fn test_run_cache_replays_until_a_file_changes() {
         let temp_dir = TempDir::new().unwrap();
-        
-        create_test_python_file(
-            &temp_dir,
-            "simple.py",
-            r#"
-def simple():
-    return True
-"#,
-        );
-        
-        create_test_python_file(
-            &temp_dir,
-            "complex.py",
-            r#"
-def complex():
-    if True:
-        for i in range(10):
-            while i > 0:
-                try:
-                    pass
-                except:
-                    pass
-"#,
-        );
-        
-        // Create a file in a subdirectory
-        create_test_python_file(
-            &temp_dir,
-            "subdir/nested.py",
-            r#"
-def nested():
-    if True and False:
-        pass
-"#,
-        );
-        
-        // Create a file that should be ignored
-        create_test_python_file(
-            &temp_dir,
-            "venv/ignored.py",
-            r#"
-def ignored():
-    pass
-"#,
-        );
-        
-        let result = analyze_directory(&temp_dir.path().to_path_buf(), 5).unwrap();
-        
-        assert_eq!(result.functions.len(), 3);
-        assert!(result.summary.is_some());
-        
-        let summary = result.summary.unwrap();
-        assert_eq!(summary.total_functions, 3);
-        assert_eq!(summary.functions_above_threshold, 1);
-        
-        // Verify the complex function is above threshold
-        let complex = result.functions.iter().find(|f| f.name == "complex").unwrap();
-        assert!(complex.complexity > 5);
+        let cache_dir = TempDir::new().unwrap();
+        create_test_python_file(&temp_dir, "a.py", "def f(x):\n    if x:\n        pass\n");
+        create_test_python_file(&temp_dir, "b.py", "def g():\n    pass\n");
+        let root = temp_dir.path().to_str().unwrap();
+        let run = |extra: &[&str]| {
+            let mut argv = vec!["analyzr", root, "--run-cache", cache_dir.path().to_str().unwrap()];
+            argv.extend(extra);
+            let args = Cli::try_parse_from(argv).unwrap().args;
+            replay_or_analyze(&args, None).unwrap()
+        };
+        let replayed = |result: &AnalysisResult| result.meta.as_ref().unwrap().replayed;
+        let complexity = |result: &AnalysisResult, name: &str| {
+            result.functions.iter().find(|f| f.name == name).unwrap().complexity
+        };
+
+        let first = run(&[]);
+        assert!(!replayed(&first));
+        let second = run(&[]);
+        assert!(replayed(&second));
+        assert_eq!(second.meta.as_ref().unwrap().timestamp, first.meta.as_ref().unwrap().timestamp);
+        assert_eq!(second.functions, first.functions);
+
+        create_test_python_file(&temp_dir, "a.py", "def f(x):\n    if x and x:\n        pass\n");
+        let touched = run(&[]);
+        assert!(!replayed(&touched));
+        assert_eq!(complexity(&touched, "f"), 3);
+        assert!(replayed(&run(&[])));
+
+        let reconfigured = run(&["--weight", "if=2"]);
+        assert!(!replayed(&reconfigured));
+        assert_eq!(complexity(&reconfigured, "f"), 4);
     }
 
     #[test]
-    fn test_output_formats() {
+    fn test_categories() {
         let temp_dir = TempDir::new().unwrap();
         create_test_python_file(
             &temp_dir,
-            "test.py",
-            r#"
-def test():
-    if True:
-        pass
-"#,
+            "app.py",
+            "from flask import Flask\n\
+             \n\
+             @app.route(\"/\", methods=[\"GET\"])\n\
+             @login_required\n\
+             def index(user):\n\
+             \x20   if user.admin:\n\
+             \x20       return 1\n\
+             \x20   if user.guest:\n\
+             \x20       return 2\n\
+             \x20   return 3\n\
+             \n\
+             class Orders(views.View, metaclass=Meta):\n\
+             \x20   @staticmethod\n\
+             \x20   def get(request):\n\
+             \x20       def helper():\n\
+             \x20           pass\n\
+             \x20       return helper\n\
+             \n\
+             @shared_task\n\
+             def send(x):\n\
+             \x20   return x\n",
+        );
+        std::fs::write(
+            temp_dir.path().join(config::FILE_NAME),
+            "[[categories]]\n\
+             name = \"http\"\n\
+             decorators = [\"app.route\"]\n\
+             bases = [\"View\"]\n\
+             max_cyclomatic = 2\n\
+             severity = \"error\"\n",
+        )
+        .unwrap();
+        let root = temp_dir.path().to_str().unwrap();
+        let matches = Cli::command().get_matches_from(["analyzr", root, "--group-by", "category"]);
+        let mut args = Cli::from_arg_matches(&matches).unwrap().args;
+        apply_config(&config::load_checked(temp_dir.path()).unwrap(), &mut args, &matches);
+        let result = analyze(&args, None).unwrap();
+
+        let categories: Vec<(&str, Option<&str>, Option<Severity>)> = result
+            .functions
+            .iter()
+            .map(|f| (f.name.as_str(), f.category.as_deref(), f.severity))
+            .collect();
+        assert_eq!(
+            categories,
+            [
+                ("index", Some("http"), Some(Severity::Error)),
+                ("Orders.get", Some("http"), None),
+                ("Orders.get.helper", Some("uncategorized"), None),
+                ("send", Some("uncategorized"), None),
+            ]
         );
-        
-        let result = analyze_directory(&temp_dir.path().to_path_buf(), 1).unwrap();
-        
-        // Test JSON serialization
-        let json = serde_json::to_string_pretty(&result).unwrap();
-        assert!(json.contains("test"));
-        assert!(json.contains("complexity"));
-        
-        // Test table output (we can't easily test the actual output, but we can verify it doesn't panic)
-        print_table(&result, 1);
+        let index = &result.functions[0];
+        assert_eq!(index.decorators, ["app.route", "login_required"]);
+        assert!(index.bases.is_empty());
+        // Keyword arguments of the class are not bases.
+        assert_eq!(result.functions[1].bases, ["views.View"]);
+        assert_eq!(result.functions[1].decorators, ["staticmethod"]);
+        // A function nested in a method is not a method.
+        assert!(result.functions[2].bases.is_empty());
+        let summary = result.summary.as_ref().unwrap();
+        let rollup: Vec<(&str, usize, u32, usize)> = summary
+            .categories
+            .iter()
+            .map(|c| {
+                let name = c.category.as_str();
+                (name, c.total_functions, c.threshold, c.functions_above_threshold)
+            })
+            .collect();
+        assert_eq!(rollup, [("http", 2, 2, 1), ("uncategorized", 2, 10, 0)]);
+        let text = summary_text(&result, args.threshold, None);
+        assert!(text.contains("\nCategories:\n  http: 2 functions, mean 2.00, max 3, 1 above threshold (2)\n"));
     }
-}
 
-fn main() -> Result<()> {
-    let args = Args::parse();
-    let result = analyze_directory(&args.path, args.threshold)?;
+    #[test]
+    fn test_select_and_order() {
+        let function = |name: &str, file: &str, line, complexity| FunctionComplexity {
+            name: name.to_string(),
+            file: file.to_string(),
+            line,
+            complexity,
+            ..Default::default()
+        };
+        let functions = vec![
+            function("parse", "b.py", 4, 7),
+            function("helper", "b.py", 1, 1),
+            function("render", "a.py", 9, 7),
+            function("main", "a.py", 2, 12),
+            function("noop", "a.py", 20, 1),
+        ];
+        let names = |functions: &[FunctionComplexity]| {
+            functions.iter().map(|f| f.name.clone()).collect::<Vec<_>>()
+        };
+        let metric = severity::Metric::Cyclomatic;
 
-    match args.output.as_str() {
-        "table" => print_table(&result, args.threshold),
-        "json" => println!("{}", serde_json::to_string_pretty(&result)?),
-        _ => anyhow::bail!("Invalid output format"),
+        // Ties on complexity fall back to the file and line.
+        let mut sorted = functions.clone();
+        sorted.sort_by(most_complex(metric));
+        assert_eq!(names(&sorted), ["main", "render", "parse", "noop", "helper"]);
+
+        let mut selected = functions.clone();
+        select(&mut selected, metric, Some(2), None);
+        assert_eq!(names(&selected), ["parse", "render", "main"]);
+        // The most complex functions, in the order they were in.
+        let mut selected = functions.clone();
+        select(&mut selected, metric, None, Some(2));
+        assert_eq!(names(&selected), ["render", "main"]);
+        let mut selected = functions.clone();
+        select(&mut selected, metric, Some(2), Some(10));
+        assert_eq!(selected.len(), 3);
     }
 
-    Ok(())
-} 
+    #[test]
+    fn test_exit_codes() {
+        assert_eq!(exit_code(&Ok(())), 0);
+        assert_eq!(exit_code(&Err(anyhow::anyhow!("Invalid output format"))), 1);
+        let failed = Failed {
+            code: EXIT_SKIPPED,
+            message: "1 file skipped (1 encoding)".to_string(),
+        };
+        let error = anyhow::Error::from(failed);
+        assert_eq!(exit_code(&Err(error)), 3);
+    }
+
+}
@@ -0,0 +1,343 @@
+use crate::meta::{self, Meta, SCHEMA_VERSION};
+use crate::severity::Metric;
+use crate::{summarize, AnalysisResult, FileIssue, FunctionComplexity, GeneratedFile};
+use anyhow::{bail, Result};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// Threshold assumed when none of the inputs record one.
+const DEFAULT_THRESHOLD: u32 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// Refuse to merge when a function has different scores in two inputs.
+    Fail,
+    /// Keep the function from the input with the most recent timestamp.
+    KeepNewest,
+}
+
+#[derive(Debug)]
+pub struct Merged {
+    pub result: AnalysisResult,
+    /// Conflicts that were resolved by the policy, for reporting.
+    pub conflicts: Vec<String>,
+}
+
+/// Combines several results into one, deduplicating functions on their
+/// fingerprint and recomputing the summary over the merged distribution.
+pub fn merge(
+    mut inputs: Vec<(PathBuf, AnalysisResult)>,
+    threshold: Option<u32>,
+    policy: ConflictPolicy,
+    with_meta: bool,
+) -> Result<Merged> {
+    check_schema_versions(&inputs)?;
+    let threshold = match threshold {
+        Some(threshold) => threshold,
+        None => common_threshold(&inputs)?.unwrap_or(DEFAULT_THRESHOLD),
+    };
+
+    // Later inputs win conflicts, so order them oldest first. Inputs without
+    // metadata keep their command-line position relative to each other.
+    inputs.sort_by(|(_, a), (_, b)| timestamp(a).cmp(timestamp(b)));
+
+    let mut functions: Vec<FunctionComplexity> = Vec::new();
+    let mut sources: Vec<&PathBuf> = Vec::new();
+    let mut by_fingerprint: HashMap<String, usize> = HashMap::new();
+    let mut conflicts = Vec::new();
+
+    for (path, result) in &inputs {
+        for func in &result.functions {
+            let Some(&index) = by_fingerprint.get(&func.fingerprint) else {
+                by_fingerprint.insert(func.fingerprint.clone(), functions.len());
+                functions.push(func.clone());
+                sources.push(path);
+                continue;
+            };
+
+            let existing = &functions[index];
+            if existing.complexity != func.complexity {
+                conflicts.push(format!(
+                    "{} in {} has complexity {} in {} but {} in {}",
+                    func.name,
+                    func.file,
+                    existing.complexity,
+                    sources[index].display(),
+                    func.complexity,
+                    path.display()
+                ));
+            }
+            functions[index] = func.clone();
+            sources[index] = path;
+        }
+    }
+
+    if policy == ConflictPolicy::Fail && !conflicts.is_empty() {
+        bail!(
+            "{} conflicting function(s) (use --keep-newest to keep the most recent run):\n  {}",
+            conflicts.len(),
+            conflicts.join("\n  ")
+        );
+    }
+
+    functions.sort_by(|a, b| a.file.cmp(&b.file).then(a.line.cmp(&b.line)));
+    let errors = merged_errors(&inputs);
+    let generated_files = merged_generated_files(&inputs);
+    let mut summary = summarize(&functions, threshold, Metric::Cyclomatic);
+    if let Some(summary) = &mut summary {
+        summary.generated_functions += generated_files.iter().map(|f| f.functions).sum::<usize>();
+    }
+    let meta = with_meta.then(|| merged_meta(&inputs, threshold));
+
+    Ok(Merged {
+        result: AnalysisResult {
+            functions,
+            generated_files,
+            summary,
+            errors,
+            meta,
+            ..Default::default()
+        },
+        conflicts,
+    })
+}
+
+fn check_schema_versions(inputs: &[(PathBuf, AnalysisResult)]) -> Result<()> {
+    let mismatched: Vec<String> = inputs
+        .iter()
+        .filter_map(|(path, result)| {
+            let version = result.meta.as_ref()?.schema_version;
+            (version != SCHEMA_VERSION)
+                .then(|| format!("{} (schema version {})", path.display(), version))
+        })
+        .collect();
+
+    if !mismatched.is_empty() {
+        bail!(
+            "cannot merge results with a schema version other than {}: {}",
+            SCHEMA_VERSION,
+            mismatched.join(", ")
+        );
+    }
+    Ok(())
+}
+
+fn common_threshold(inputs: &[(PathBuf, AnalysisResult)]) -> Result<Option<u32>> {
+    let mut thresholds: Vec<u32> = inputs
+        .iter()
+        .filter_map(|(_, result)| result.meta.as_ref().map(|meta| meta.threshold))
+        .collect();
+    thresholds.sort_unstable();
+    thresholds.dedup();
+
+    match thresholds.as_slice() {
+        [] => Ok(None),
+        [threshold] => Ok(Some(*threshold)),
+        _ => bail!(
+            "inputs were analyzed with different thresholds ({:?}); pass --threshold",
+            thresholds
+        ),
+    }
+}
+
+/// Skipped files of all inputs, reporting each path once.
+fn merged_errors(inputs: &[(PathBuf, AnalysisResult)]) -> Vec<FileIssue> {
+    let mut errors: Vec<FileIssue> = Vec::new();
+    for (_, result) in inputs {
+        for error in &result.errors {
+            if !errors.iter().any(|e| e.path == error.path) {
+                errors.push(error.clone());
+            }
+        }
+    }
+    errors.sort_by(|a, b| a.path.cmp(&b.path));
+    errors
+}
+
+/// Collapsed generated modules of all inputs, keeping the most recent row
+/// for each file.
+fn merged_generated_files(inputs: &[(PathBuf, AnalysisResult)]) -> Vec<GeneratedFile> {
+    let mut files: Vec<GeneratedFile> = Vec::new();
+    for (_, result) in inputs {
+        for file in &result.generated_files {
+            files.retain(|f| (&f.project, &f.file) != (&file.project, &file.file));
+            files.push(file.clone());
+        }
+    }
+    files.sort_by(|a, b| a.file.cmp(&b.file));
+    files
+}
+
+fn timestamp(result: &AnalysisResult) -> &str {
+    result
+        .meta
+        .as_ref()
+        .map_or("", |meta| meta.timestamp.as_str())
+}
+
+fn merged_meta(inputs: &[(PathBuf, AnalysisResult)], threshold: u32) -> Meta {
+    let metas: Vec<&Meta> = inputs
+        .iter()
+        .filter_map(|(_, result)| result.meta.as_ref())
+        .collect();
+
+    let mut roots = Vec::new();
+    let mut filters = Vec::new();
+    for meta in &metas {
+        for root in &meta.roots {
+            if !roots.contains(root) {
+                roots.push(root.clone());
+            }
+        }
+        for filter in &meta.filters {
+            if !filters.contains(filter) {
+                filters.push(filter.clone());
+            }
+        }
+    }
+
+    // Only claim a revision when every input was produced from the same one.
+    let git = match metas.split_first() {
+        Some((first, rest)) if rest.iter().all(|meta| meta.git == first.git) => first.git.clone(),
+        _ => None,
+    };
+    let (preset, weights) = match metas.split_first() {
+        Some((first, rest))
+            if rest
+                .iter()
+                .all(|meta| meta.preset == first.preset && meta.weights == first.weights) =>
+        {
+            (first.preset.clone(), first.weights.clone())
+        }
+        _ => (None, Vec::new()),
+    };
+    // Merged risk scores are only comparable when computed the same way.
+    let risk = match metas.split_first() {
+        Some((first, rest)) if rest.iter().all(|meta| meta.risk == first.risk) => first.risk.clone(),
+        _ => Vec::new(),
+    };
+    let error_threshold = metas
+        .first()
+        .map_or(threshold.saturating_mul(2), |meta| meta.error_threshold);
+    let info_threshold = metas.first().and_then(|meta| meta.info_threshold);
+
+    Meta {
+        tool_version: env!("CARGO_PKG_VERSION").to_string(),
+        schema_version: SCHEMA_VERSION,
+        timestamp: meta::now(),
+        roots,
+        git,
+        threshold,
+        error_threshold,
+        info_threshold,
+        filters,
+        preset,
+        // The merged summary counts functions above the cyclomatic threshold.
+        metric: None,
+        weights,
+        risk,
+        replayed: false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fingerprint::fingerprint;
+
+    fn function(name: &str, file: &str, line: u32, complexity: u32) -> FunctionComplexity {
+        FunctionComplexity {
+            name: name.to_string(),
+            file: file.to_string(),
+            line,
+            complexity,
+            fingerprint: fingerprint(file, name),
+            ..Default::default()
+        }
+    }
+
+    fn result(functions: Vec<FunctionComplexity>, timestamp: Option<&str>) -> AnalysisResult {
+        AnalysisResult {
+            summary: summarize(&functions, 5, Metric::Cyclomatic),
+            functions,
+            meta: timestamp.map(|timestamp| Meta {
+                tool_version: "0.1.0".to_string(),
+                schema_version: SCHEMA_VERSION,
+                timestamp: timestamp.to_string(),
+                roots: vec![".".to_string()],
+                git: None,
+                threshold: 5,
+                error_threshold: 10,
+                info_threshold: None,
+                filters: Vec::new(),
+                preset: None,
+                metric: None,
+                weights: Vec::new(),
+                risk: Vec::new(),
+                replayed: false,
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_merge_matches_single_run() {
+        let all = vec![
+            function("a", "a.py", 1, 1),
+            function("b", "a.py", 5, 3),
+            function("c", "b.py", 1, 8),
+            function("d", "c.py", 1, 12),
+        ];
+        let single = result(all.clone(), None);
+        // Overlapping shards: "b" and "c" appear in both.
+        let first = result(all[..3].to_vec(), None);
+        let second = result(all[1..].to_vec(), None);
+
+        let merged = merge(
+            vec![("1.json".into(), first), ("2.json".into(), second)],
+            Some(5),
+            ConflictPolicy::Fail,
+            false,
+        )
+        .unwrap();
+
+        assert!(merged.conflicts.is_empty());
+        assert_eq!(merged.result, single);
+        let summary = merged.result.summary.unwrap();
+        assert_eq!(summary.total_functions, 4);
+        assert_eq!(summary.p50_complexity, 3);
+        assert_eq!(summary.p95_complexity, 12);
+        assert_eq!(summary.functions_above_threshold, 2);
+    }
+
+    #[test]
+    fn test_conflicting_scores() {
+        let older = result(vec![function("a", "a.py", 1, 4)], Some("2024-01-01T00:00:00Z"));
+        let newer = result(vec![function("a", "a.py", 1, 7)], Some("2024-02-01T00:00:00Z"));
+        let inputs = vec![("new.json".into(), newer), ("old.json".into(), older)];
+
+        let err = merge(inputs.clone(), None, ConflictPolicy::Fail, false).unwrap_err();
+        assert!(err.to_string().contains("1 conflicting function(s)"));
+
+        let merged = merge(inputs, None, ConflictPolicy::KeepNewest, true).unwrap();
+        assert_eq!(merged.conflicts.len(), 1);
+        assert_eq!(merged.result.functions[0].complexity, 7);
+        assert_eq!(merged.result.meta.unwrap().threshold, 5);
+    }
+
+    #[test]
+    fn test_rejects_other_schema_versions() {
+        let mut old = result(vec![function("a", "a.py", 1, 1)], Some("2024-01-01T00:00:00Z"));
+        old.meta.as_mut().unwrap().schema_version = SCHEMA_VERSION + 1;
+        let current = result(Vec::new(), None);
+
+        let err = merge(
+            vec![("old.json".into(), old), ("new.json".into(), current)],
+            None,
+            ConflictPolicy::Fail,
+            false,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("old.json"));
+    }
+}
@@ -0,0 +1,170 @@
+use crate::severity::{Metric, Thresholds};
+use chrono::{SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Version of the JSON document layout. Bump whenever a field is renamed or
+/// removed so downstream consumers can detect incompatible reports.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Describes the run that produced a report.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Meta {
+    pub tool_version: String,
+    pub schema_version: u32,
+    pub timestamp: String,
+    pub roots: Vec<String>,
+    pub git: Option<GitInfo>,
+    pub threshold: u32,
+    pub error_threshold: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub info_threshold: Option<u32>,
+    pub filters: Vec<String>,
+    /// Complexity preset the functions were scored with.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preset: Option<String>,
+    /// Complexity the thresholds applied to, when not the cyclomatic one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metric: Option<String>,
+    /// Construct weights overridden on top of the preset, as `name=weight`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub weights: Vec<String>,
+    /// Coefficients of the risk score, as `metric=coefficient`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub risk: Vec<String>,
+    /// Whether the result was replayed from an identical earlier run by
+    /// `--run-cache`, in which case the other fields describe that run.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub replayed: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GitInfo {
+    pub sha: String,
+    pub branch: Option<String>,
+}
+
+impl Meta {
+    pub fn collect(roots: &[PathBuf], thresholds: &Thresholds, filters: Vec<String>) -> Self {
+        Meta {
+            tool_version: env!("CARGO_PKG_VERSION").to_string(),
+            schema_version: SCHEMA_VERSION,
+            timestamp: now(),
+            roots: roots
+                .iter()
+                .map(|root| root.to_string_lossy().to_string())
+                .collect(),
+            git: roots.first().and_then(|root| git_info(root)),
+            threshold: thresholds.warn,
+            error_threshold: thresholds.error,
+            info_threshold: thresholds.info,
+            filters,
+            preset: None,
+            metric: (thresholds.metric != Metric::Cyclomatic)
+                .then(|| thresholds.metric.name().to_string()),
+            weights: Vec::new(),
+            risk: Vec::new(),
+            replayed: false,
+        }
+    }
+
+    /// One-line rendering for the footer of human-readable formats.
+    pub fn footer(&self) -> String {
+        let mut parts = vec![
+            format!("{} {}", env!("CARGO_PKG_NAME"), self.tool_version),
+            self.timestamp.clone(),
+            self.roots.join(", "),
+        ];
+        if let Some(git) = &self.git {
+            match &git.branch {
+                Some(branch) => parts.push(format!("git {} ({})", short_sha(&git.sha), branch)),
+                None => parts.push(format!("git {}", short_sha(&git.sha))),
+            }
+        }
+        match self.info_threshold {
+            Some(info) => parts.push(format!(
+                "threshold {}/{}/{}",
+                info, self.threshold, self.error_threshold
+            )),
+            None => parts.push(format!(
+                "threshold {}/{}",
+                self.threshold, self.error_threshold
+            )),
+        }
+        if let Some(preset) = self.preset.as_deref().filter(|p| *p != "analyzr") {
+            parts.push(format!("preset {}", preset));
+        }
+        if let Some(metric) = &self.metric {
+            parts.push(format!("metric {}", metric));
+        }
+        if !self.weights.is_empty() {
+            parts.push(format!("weights {}", self.weights.join(" ")));
+        }
+        if !self.risk.is_empty() {
+            parts.push(format!("risk {}", self.risk.join(" ")));
+        }
+        if !self.filters.is_empty() {
+            parts.push(format!("filters {}", self.filters.join(" ")));
+        }
+        if self.replayed {
+            parts.push("replayed".to_string());
+        }
+        parts.join(" | ")
+    }
+}
+
+/// Current time as an ISO 8601 UTC timestamp.
+pub fn now() -> String {
+    Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+fn short_sha(sha: &str) -> &str {
+    &sha[..sha.len().min(12)]
+}
+
+/// Best-effort lookup of the commit and branch `path` belongs to. Any failure
+/// (git missing, not a repository, detached HEAD...) yields `None` or omits
+/// the branch rather than failing the run.
+pub fn git_info(path: &Path) -> Option<GitInfo> {
+    let dir = if path.is_dir() { path } else { path.parent()? };
+    let sha = git(dir, &["rev-parse", "HEAD"])?;
+    let branch = git(dir, &["rev-parse", "--abbrev-ref", "HEAD"]).filter(|b| b != "HEAD");
+    Some(GitInfo { sha, branch })
+}
+
+/// Trimmed stdout of `git <args>` run in `dir`, or `None` when it fails or
+/// prints nothing.
+pub fn git(dir: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let stdout = String::from_utf8(output.stdout).ok()?;
+    let trimmed = stdout.trim();
+    (!trimmed.is_empty()).then(|| trimmed.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_collect_outside_git_repository() {
+        let temp_dir = TempDir::new().unwrap();
+        let thresholds = Thresholds::new(10, Some(20), None);
+
+        let meta = Meta::collect(&[temp_dir.path().to_path_buf()], &thresholds, Vec::new());
+
+        assert!(meta.git.is_none());
+        assert_eq!(meta.schema_version, SCHEMA_VERSION);
+        assert!(meta.timestamp.ends_with('Z'));
+        assert!(meta.footer().contains("threshold 10/20"));
+    }
+}
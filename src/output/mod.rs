@@ -0,0 +1,107 @@
+pub mod rdjson;
+pub mod sarif;
+pub mod step_summary;
+pub mod table;
+pub mod text;
+pub mod warnings_ng;
+
+use crate::severity::{Metric, Thresholds};
+use crate::FunctionComplexity;
+use std::path::Path;
+
+/// Path of `file` relative to the analyzed `root`, always using `/` as the
+/// separator so reports are identical across platforms. When one of them is
+/// absolute and the other is not, both are resolved first; a file outside
+/// `root` keeps its path.
+pub fn relative_path(file: &str, root: &Path) -> String {
+    let join = |relative: &Path| {
+        relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/")
+    };
+    if let Ok(relative) = Path::new(file).strip_prefix(root) {
+        return join(relative);
+    }
+    if Path::new(file).is_absolute() != root.is_absolute() {
+        if let (Ok(file), Ok(root)) = (std::fs::canonicalize(file), std::fs::canonicalize(root)) {
+            if let Ok(relative) = file.strip_prefix(&root) {
+                return join(relative);
+            }
+        }
+    }
+    file.to_string()
+}
+
+/// What a report says about `func`: the complexity `thresholds` apply to,
+/// and the warn threshold.
+pub fn message(func: &FunctionComplexity, thresholds: &Thresholds) -> String {
+    let complexity = match thresholds.metric {
+        Metric::Cyclomatic => format!("a cyclomatic complexity of {}", func.complexity),
+        Metric::Cognitive => format!("a cognitive complexity of {}", func.cognitive_complexity),
+        Metric::Both => format!(
+            "a cyclomatic complexity of {} and a cognitive complexity of {}",
+            func.complexity, func.cognitive_complexity
+        ),
+    };
+    format!(
+        "Function '{}' has {} (threshold {})",
+        func.name, complexity, thresholds.warn
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relative_path() {
+        let root = Path::new("/repo");
+        assert_eq!(relative_path("/repo/pkg/mod.py", root), "pkg/mod.py");
+        assert_eq!(relative_path("/elsewhere/mod.py", root), "/elsewhere/mod.py");
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        std::fs::create_dir(temp_dir.path().join("pkg")).unwrap();
+        let file = temp_dir.path().join("pkg").join("mod.py");
+        std::fs::write(&file, "").unwrap();
+        let cwd = std::env::current_dir().unwrap();
+        let root = pathdiff(temp_dir.path(), &cwd);
+        assert_eq!(relative_path(&file.to_string_lossy(), &root), "pkg/mod.py");
+    }
+
+    /// `path` relative to the absolute `base`, through `..` components.
+    fn pathdiff(path: &Path, base: &Path) -> std::path::PathBuf {
+        let path = std::fs::canonicalize(path).unwrap();
+        let base = std::fs::canonicalize(base).unwrap();
+        let common = path
+            .components()
+            .zip(base.components())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let mut relative = std::path::PathBuf::new();
+        for _ in base.components().skip(common) {
+            relative.push("..");
+        }
+        relative.extend(path.components().skip(common));
+        relative
+    }
+
+    #[test]
+    fn test_cognitive_message() {
+        let func = FunctionComplexity {
+            name: "nested".to_string(),
+            complexity: 4,
+            cognitive_complexity: 6,
+            ..Default::default()
+        };
+        let thresholds = Thresholds {
+            metric: Metric::Both,
+            ..Thresholds::new(5, None, None)
+        };
+        assert_eq!(
+            message(&func, &thresholds),
+            "Function 'nested' has a cyclomatic complexity of 4 and a cognitive complexity of 6 (threshold 5)"
+        );
+    }
+}
@@ -0,0 +1,254 @@
+//! Reviewdog Diagnostic Format (rdjson).
+//!
+//! See <https://github.com/reviewdog/reviewdog/tree/master/proto/rdf>.
+
+use super::{message, relative_path};
+use crate::severity::{Severity, Thresholds};
+use crate::AnalysisResult;
+use anyhow::Result;
+use serde::Serialize;
+use std::path::Path;
+
+const CODE_URL: &str = "https://github.com/styxx3542/Analyzr#how-it-works";
+
+#[derive(Debug, Serialize)]
+struct DiagnosticResult {
+    source: Source,
+    diagnostics: Vec<Diagnostic>,
+}
+
+#[derive(Debug, Serialize)]
+struct Source {
+    name: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+struct Diagnostic {
+    message: String,
+    location: Location,
+    severity: &'static str,
+    code: Code,
+}
+
+#[derive(Debug, Serialize)]
+struct Location {
+    path: String,
+    range: Range,
+}
+
+#[derive(Debug, Serialize)]
+struct Range {
+    start: Position,
+    end: Position,
+}
+
+#[derive(Debug, Serialize)]
+struct Position {
+    line: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    column: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
+struct Code {
+    value: &'static str,
+    url: &'static str,
+}
+
+pub fn render(result: &AnalysisResult, root: &Path, thresholds: &Thresholds) -> Result<String> {
+    let diagnostics = result
+        .functions
+        .iter()
+        .filter_map(|func| {
+            let severity = match func.severity? {
+                Severity::Info => "INFO",
+                Severity::Warning => "WARNING",
+                Severity::Error => "ERROR",
+            };
+            // The range covers the signature so reviewdog anchors the comment
+            // on the `def` rather than on the whole body.
+            Some(Diagnostic {
+                message: message(func, thresholds),
+                location: Location {
+                    path: relative_path(&func.file, root),
+                    range: Range {
+                        start: Position {
+                            line: func.line,
+                            column: (func.column > 0).then_some(func.column),
+                        },
+                        end: Position {
+                            line: func.signature_end_line.max(func.line),
+                            column: None,
+                        },
+                    },
+                },
+                severity,
+                code: Code {
+                    value: "complexity",
+                    url: CODE_URL,
+                },
+            })
+        })
+        .collect();
+
+    Ok(serde_json::to_string_pretty(&DiagnosticResult {
+        source: Source { name: "analyzr" },
+        diagnostics,
+    })?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::severity::{annotate, Metric};
+    use crate::fingerprint::fingerprint;
+    use crate::FunctionComplexity;
+    use serde_json::Value;
+
+    fn function(name: &str, file: &str, line: u32, complexity: u32) -> FunctionComplexity {
+        FunctionComplexity {
+            name: name.to_string(),
+            file: file.to_string(),
+            line,
+            column: 1,
+            end_line: line + 9,
+            signature_end_line: line + 2,
+            complexity,
+            cognitive_complexity: 0,
+            locals: 0,
+            fingerprint: fingerprint(file.trim_start_matches("/repo/"), name),
+            body_hash: String::new(),
+            project: None,
+            url: None,
+            severity: None,
+            rule: None,
+            decorators: Vec::new(),
+            bases: Vec::new(),
+            category: None,
+            breakdown: Vec::new(),
+            dunder: false,
+            generated: false,
+            delta: None,
+            risk: None,
+            language: Default::default(),
+        }
+    }
+
+    /// Checks `value` against the subset of JSON Schema used by the reviewdog
+    /// schema: `$ref`, `type`, `properties`, `additionalProperties: false`,
+    /// `required`, `items` and `enum`.
+    fn validate(value: &Value, schema: &Value, root: &Value, path: &str) -> Vec<String> {
+        if let Some(reference) = schema["$ref"].as_str() {
+            let name = reference.trim_start_matches("#/definitions/");
+            return validate(value, &root["definitions"][name], root, path);
+        }
+
+        let mut errors = Vec::new();
+        let type_matches = |ty: &str| match ty {
+            "object" => value.is_object(),
+            "array" => value.is_array(),
+            "string" => value.is_string(),
+            "integer" => value.is_u64() || value.is_i64(),
+            _ => true,
+        };
+        let types: Vec<&str> = match &schema["type"] {
+            Value::String(ty) => vec![ty.as_str()],
+            Value::Array(types) => types.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        if !types.is_empty() && !types.iter().any(|ty| type_matches(ty)) {
+            errors.push(format!("{}: expected {:?}, got {}", path, types, value));
+        }
+        if let Some(allowed) = schema["enum"].as_array() {
+            if !allowed.contains(value) {
+                errors.push(format!("{}: {} is not one of {:?}", path, value, allowed));
+            }
+        }
+        for required in schema["required"].as_array().into_iter().flatten() {
+            let key = required.as_str().unwrap();
+            if value.get(key).is_none() {
+                errors.push(format!("{}: missing required property {}", path, key));
+            }
+        }
+        if let (Some(properties), Some(object)) =
+            (schema["properties"].as_object(), value.as_object())
+        {
+            for (key, field) in object {
+                match properties.get(key) {
+                    Some(field_schema) => errors.extend(validate(
+                        field,
+                        field_schema,
+                        root,
+                        &format!("{}.{}", path, key),
+                    )),
+                    None if schema["additionalProperties"] == false => {
+                        errors.push(format!("{}: unknown property {}", path, key))
+                    }
+                    None => {}
+                }
+            }
+        }
+        if let (Some(items), Some(array)) = (schema.get("items"), value.as_array()) {
+            for (index, item) in array.iter().enumerate() {
+                errors.extend(validate(item, items, root, &format!("{}[{}]", path, index)));
+            }
+        }
+        errors
+    }
+
+    #[test]
+    fn test_render_validates_against_schema() {
+        let mut result = AnalysisResult {
+            functions: vec![
+                function("simple", "/repo/app/simple.py", 1, 2),
+                function("busy", "/repo/app/busy.py", 4, 12),
+                function("tangled", "/repo/app/core/tangled.py", 20, 25),
+            ],
+            ..Default::default()
+        };
+        let thresholds = Thresholds::new(10, Some(20), None);
+        annotate(&mut result, |_| thresholds);
+
+        let rendered = render(&result, Path::new("/repo"), &thresholds).unwrap();
+        let value: Value = serde_json::from_str(&rendered).unwrap();
+        let schema: Value =
+            serde_json::from_str(include_str!("../../testdata/rdjson.schema.json")).unwrap();
+        assert_eq!(validate(&value, &schema, &schema, "$"), Vec::<String>::new());
+
+        let diagnostics = value["diagnostics"].as_array().unwrap();
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0]["location"]["path"], "app/busy.py");
+        assert_eq!(diagnostics[0]["location"]["range"]["start"]["line"], 4);
+        assert_eq!(diagnostics[0]["location"]["range"]["end"]["line"], 6);
+        assert_eq!(diagnostics[0]["severity"], "WARNING");
+        assert_eq!(diagnostics[1]["severity"], "ERROR");
+
+        annotate(&mut result, |_| Thresholds::new(10, Some(20), Some(1)));
+        let rendered = render(&result, Path::new("/repo"), &thresholds).unwrap();
+        let value: Value = serde_json::from_str(&rendered).unwrap();
+        assert_eq!(value["diagnostics"][0]["severity"], "INFO");
+    }
+
+    #[test]
+    fn test_cognitive_offender() {
+        let mut nested = function("nested", "/repo/app/nested.py", 1, 2);
+        nested.cognitive_complexity = 12;
+        let mut result = AnalysisResult {
+            functions: vec![nested],
+            ..Default::default()
+        };
+        let thresholds = Thresholds {
+            metric: Metric::Cognitive,
+            ..Thresholds::new(10, None, None)
+        };
+        annotate(&mut result, |_| thresholds);
+
+        let rendered = render(&result, Path::new("/repo"), &thresholds).unwrap();
+        let value: Value = serde_json::from_str(&rendered).unwrap();
+        assert_eq!(value["diagnostics"][0]["severity"], "WARNING");
+        assert_eq!(
+            value["diagnostics"][0]["message"],
+            "Function 'nested' has a cognitive complexity of 12 (threshold 10)"
+        );
+    }
+}
@@ -0,0 +1,306 @@
+//! SARIF 2.1.0, the format of GitHub code scanning.
+//!
+//! See <https://docs.oasis-open.org/sarif/sarif/v2.1.0/sarif-v2.1.0.html> and
+//! <https://docs.github.com/en/code-security/code-scanning/integrating-with-code-scanning/sarif-support-for-code-scanning>.
+
+use super::{message, relative_path};
+use crate::severity::{Severity, Thresholds};
+use crate::AnalysisResult;
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+const SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+const INFORMATION_URL: &str = "https://github.com/styxx3542/Analyzr";
+
+const HELP_URL: &str = "https://github.com/styxx3542/Analyzr#how-it-works";
+
+const RULE_ID: &str = "ANALYZR001";
+
+/// Base of the artifact URIs, which code scanning resolves against the
+/// repository checkout.
+const SOURCE_ROOT: &str = "%SRCROOT%";
+
+#[derive(Debug, Serialize)]
+struct Log {
+    #[serde(rename = "$schema")]
+    schema: &'static str,
+    version: &'static str,
+    runs: Vec<Run>,
+}
+
+#[derive(Debug, Serialize)]
+struct Run {
+    tool: Tool,
+    results: Vec<SarifResult>,
+}
+
+#[derive(Debug, Serialize)]
+struct Tool {
+    driver: Driver,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Driver {
+    name: &'static str,
+    version: &'static str,
+    information_uri: &'static str,
+    rules: Vec<Rule>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Rule {
+    id: &'static str,
+    name: &'static str,
+    short_description: Message,
+    full_description: Message,
+    help_uri: &'static str,
+    default_configuration: Configuration,
+}
+
+#[derive(Debug, Serialize)]
+struct Configuration {
+    level: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+struct Message {
+    text: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifResult {
+    rule_id: &'static str,
+    rule_index: usize,
+    level: &'static str,
+    message: Message,
+    locations: Vec<Location>,
+    partial_fingerprints: BTreeMap<&'static str, String>,
+    properties: Properties,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Location {
+    physical_location: PhysicalLocation,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PhysicalLocation {
+    artifact_location: ArtifactLocation,
+    region: Region,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ArtifactLocation {
+    uri: String,
+    uri_base_id: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Region {
+    start_line: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    start_column: Option<u32>,
+    end_line: u32,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Properties {
+    complexity: u32,
+    cognitive_complexity: u32,
+}
+
+/// `path` as a relative URI reference: characters other than unreserved
+/// ones and `/` are percent-encoded.
+fn uri(path: &str) -> String {
+    let mut out = String::new();
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                out.push(byte as char)
+            }
+            _ => out.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    out
+}
+
+pub fn render(result: &AnalysisResult, root: &Path, thresholds: &Thresholds) -> Result<String> {
+    let results = result
+        .functions
+        .iter()
+        .filter_map(|func| {
+            let level = match func.severity? {
+                Severity::Info => "note",
+                Severity::Warning => "warning",
+                Severity::Error => "error",
+            };
+            // As in rdjson, the region covers the signature so that the
+            // annotation sits on the `def` rather than on the whole body.
+            Some(SarifResult {
+                rule_id: RULE_ID,
+                rule_index: 0,
+                level,
+                message: Message {
+                    text: message(func, thresholds),
+                },
+                locations: vec![Location {
+                    physical_location: PhysicalLocation {
+                        artifact_location: ArtifactLocation {
+                            uri: uri(&relative_path(&func.file, root)),
+                            uri_base_id: SOURCE_ROOT,
+                        },
+                        region: Region {
+                            start_line: func.line,
+                            start_column: (func.column > 0).then_some(func.column),
+                            end_line: func.signature_end_line.max(func.line),
+                        },
+                    },
+                }],
+                partial_fingerprints: BTreeMap::from([(
+                    "analyzrFingerprint/v1",
+                    func.fingerprint.clone(),
+                )]),
+                properties: Properties {
+                    complexity: func.complexity,
+                    cognitive_complexity: func.cognitive_complexity,
+                },
+            })
+        })
+        .collect();
+
+    let rule = Rule {
+        id: RULE_ID,
+        name: "ComplexFunction",
+        short_description: Message {
+            text: "Function exceeds complexity threshold".to_string(),
+        },
+        full_description: Message {
+            text: format!(
+                "The function's {} complexity exceeds the threshold of {}; it is an error above {}.",
+                thresholds.metric.name(),
+                thresholds.warn,
+                thresholds.error
+            ),
+        },
+        help_uri: HELP_URL,
+        default_configuration: Configuration { level: "warning" },
+    };
+    Ok(serde_json::to_string_pretty(&Log {
+        schema: SCHEMA,
+        version: "2.1.0",
+        runs: vec![Run {
+            tool: Tool {
+                driver: Driver {
+                    name: "analyzr",
+                    version: env!("CARGO_PKG_VERSION"),
+                    information_uri: INFORMATION_URL,
+                    rules: vec![rule],
+                },
+            },
+            results,
+        }],
+    })?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fingerprint::fingerprint;
+    use crate::severity::annotate;
+    use crate::FunctionComplexity;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize)]
+    struct Log {
+        version: String,
+        runs: Vec<Run>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Run {
+        tool: serde_json::Value,
+        results: Vec<SarifResult>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct SarifResult {
+        rule_id: String,
+        level: String,
+        message: serde_json::Value,
+        locations: Vec<serde_json::Value>,
+        partial_fingerprints: BTreeMap<String, String>,
+    }
+
+    fn function(name: &str, file: &str, line: u32, complexity: u32) -> FunctionComplexity {
+        FunctionComplexity {
+            name: name.to_string(),
+            file: file.to_string(),
+            line,
+            column: 5,
+            end_line: line + 9,
+            signature_end_line: line + 2,
+            complexity,
+            fingerprint: fingerprint(file.trim_start_matches("/repo/"), name),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_render_structure() {
+        let mut result = AnalysisResult {
+            functions: vec![
+                function("simple", "/repo/app/simple.py", 1, 2),
+                function("Invoice.busy", "/repo/app/busy.py", 4, 12),
+                function("tangled", "/repo/app/core/my tangled.py", 20, 25),
+            ],
+            ..Default::default()
+        };
+        let thresholds = Thresholds::new(10, None, None);
+        annotate(&mut result, |_| thresholds);
+
+        let rendered = render(&result, Path::new("/repo"), &thresholds).unwrap();
+        let log: Log = serde_json::from_str(&rendered).unwrap();
+        assert_eq!(log.version, "2.1.0");
+        assert_eq!(log.runs.len(), 1);
+        let run = &log.runs[0];
+        assert_eq!(run.tool["driver"]["name"], "analyzr");
+        assert_eq!(run.tool["driver"]["rules"][0]["id"], RULE_ID);
+
+        let results = &run.results;
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(|result| result.rule_id == RULE_ID));
+        assert_eq!(
+            results[0].message["text"],
+            "Function 'Invoice.busy' has a cyclomatic complexity of 12 (threshold 10)"
+        );
+        let location = &results[0].locations[0]["physicalLocation"];
+        assert_eq!(location["artifactLocation"]["uri"], "app/busy.py");
+        assert_eq!(location["artifactLocation"]["uriBaseId"], "%SRCROOT%");
+        assert_eq!(location["region"]["startLine"], 4);
+        assert_eq!(location["region"]["startColumn"], 5);
+        assert_eq!(location["region"]["endLine"], 6);
+        assert_eq!(
+            results[0].partial_fingerprints["analyzrFingerprint/v1"],
+            result.functions[1].fingerprint
+        );
+        // Above twice the threshold, the default error threshold.
+        assert_eq!(results[0].level, "warning");
+        assert_eq!(results[1].level, "error");
+        assert_eq!(
+            results[1].locations[0]["physicalLocation"]["artifactLocation"]["uri"],
+            "app/core/my%20tangled.py"
+        );
+    }
+}
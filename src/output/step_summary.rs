@@ -0,0 +1,300 @@
+//! Markdown report for the GitHub Actions job summary.
+//!
+//! See <https://docs.github.com/en/actions/using-workflows/workflow-commands-for-github-actions#adding-a-job-summary>.
+
+use super::relative_path;
+use crate::baseline::{self, Delta};
+use crate::severity::{Severity, Thresholds};
+use crate::{skipped_line, AnalysisResult, FunctionComplexity};
+use anyhow::{Context, Result};
+use std::io::Write;
+use std::path::Path;
+
+/// GitHub rejects step summaries larger than this.
+pub const SIZE_LIMIT: usize = 1024 * 1024;
+
+const TOP_OFFENDERS: usize = 10;
+
+const TRUNCATED_NOTE: &str = "\n_Report truncated to fit the GitHub step summary size limit._\n";
+
+pub fn render(result: &AnalysisResult, root: &Path, thresholds: &Thresholds) -> String {
+    let mut offenders: Vec<_> = result
+        .functions
+        .iter()
+        .filter(|func| func.severity >= Some(Severity::Warning))
+        .collect();
+    let complexity = |func: &FunctionComplexity| thresholds.metric.value(func);
+    offenders.sort_by_key(|func| std::cmp::Reverse(complexity(func)));
+    let errors = offenders
+        .iter()
+        .filter(|func| func.severity == Some(Severity::Error))
+        .count();
+
+    let mut out = String::from("## Complexity report\n\n");
+    if offenders.is_empty() {
+        out.push_str(&format!(
+            ":white_check_mark: No functions above the threshold of {}.\n",
+            thresholds.warn
+        ));
+    } else {
+        out.push_str(&format!(
+            ":x: {} function{} above the threshold of {} ({} above the error threshold of {}).\n",
+            offenders.len(),
+            if offenders.len() == 1 { "" } else { "s" },
+            thresholds.warn,
+            errors,
+            thresholds.error
+        ));
+    }
+    if let Some(skipped) = skipped_line(&result.errors) {
+        out.push_str(&format!("\n:warning: {}.\n", skipped));
+    }
+
+    if let Some(summary) = &result.summary {
+        out.push_str("\n| Functions | Mean | Max | p50 | p90 | p95 | Gini | Top 10% share |\n");
+        out.push_str("|---:|---:|---:|---:|---:|---:|---:|---:|\n");
+        out.push_str(&format!(
+            "| {} | {:.2} | {} | {} | {} | {} | {:.2} | {:.1}% |\n",
+            summary.total_functions,
+            summary.mean_complexity,
+            summary.max_complexity,
+            summary.p50_complexity,
+            summary.p90_complexity,
+            summary.p95_complexity,
+            summary.gini_coefficient,
+            summary.top_decile_share * 100.0
+        ));
+    }
+
+    if !offenders.is_empty() {
+        out.push_str(&format!(
+            "\n### Top {} offenders\n\n",
+            TOP_OFFENDERS.min(offenders.len())
+        ));
+        out.push_str("| Function | File | Line | Complexity |\n");
+        out.push_str("|---|---|---:|---:|\n");
+        for func in offenders.iter().take(TOP_OFFENDERS) {
+            out.push_str(&format!(
+                "| {} | {} | {} | {} |\n",
+                name(func),
+                relative_path(&func.file, root),
+                func.line,
+                complexity(func)
+            ));
+        }
+    }
+
+    let mut changed: Vec<_> = result
+        .functions
+        .iter()
+        .filter(|func| match func.delta {
+            Some(Delta::New) => true,
+            Some(Delta::Change(change)) => change > 0,
+            None => false,
+        })
+        .collect();
+    if result.functions.iter().any(|func| func.delta.is_some()) {
+        changed.sort_by_key(|func| std::cmp::Reverse(complexity(func)));
+        let new = changed
+            .iter()
+            .filter(|func| func.delta == Some(Delta::New))
+            .count();
+        out.push_str("\n### Changes since the baseline\n\n");
+        out.push_str(&format!("{} regressed, {} new", changed.len() - new, new));
+        if !result.removed.is_empty() {
+            out.push_str(&format!(", {} removed", result.removed.len()));
+        }
+        out.push_str(".\n");
+        if !changed.is_empty() {
+            out.push_str("\n| Function | File | Line | Complexity | Change |\n");
+            out.push_str("|---|---|---:|---:|---:|\n");
+            for func in changed.iter().take(TOP_OFFENDERS) {
+                out.push_str(&format!(
+                    "| {} | {} | {} | {} | {} |\n",
+                    name(func),
+                    relative_path(&func.file, root),
+                    func.line,
+                    complexity(func),
+                    func.delta.map(baseline::label).unwrap_or_default()
+                ));
+            }
+        }
+    }
+
+    if let Some(summary) = result.summary.as_ref().filter(|s| !s.worst_files.is_empty()) {
+        out.push_str("\n### Worst files\n\n");
+        out.push_str("| File | Max | Total | Above threshold | Worst function |\n");
+        out.push_str("|---|---:|---:|---:|---|\n");
+        for file in &summary.worst_files {
+            out.push_str(&format!(
+                "| {} | {} | {} | {} | `{}` (line {}) |\n",
+                relative_path(&file.file, root),
+                file.max_complexity,
+                file.total_complexity,
+                file.functions_above_threshold,
+                file.worst_function,
+                file.worst_function_line
+            ));
+        }
+    }
+
+    if let Some(meta) = &result.meta {
+        out.push_str(&format!("\n<sub>{}</sub>\n", meta.footer()));
+    }
+    out
+}
+
+/// The name of `func` in a table, linked to its line when it has a URL.
+fn name(func: &FunctionComplexity) -> String {
+    match &func.url {
+        Some(url) => format!("[`{}`]({})", func.name, url),
+        None => format!("`{}`", func.name),
+    }
+}
+
+/// Cuts `markdown` at a line boundary so that it fits in `limit` bytes,
+/// including the note saying it was truncated.
+pub fn truncate(markdown: &str, limit: usize) -> String {
+    if markdown.len() <= limit {
+        return markdown.to_string();
+    }
+    let mut budget = limit.saturating_sub(TRUNCATED_NOTE.len());
+    while !markdown.is_char_boundary(budget) {
+        budget -= 1;
+    }
+    let cut = markdown[..budget]
+        .rfind('\n')
+        .map_or(0, |newline| newline + 1);
+    format!("{}{}", &markdown[..cut], TRUNCATED_NOTE)
+}
+
+/// Appends `markdown` to the summary file, keeping whatever earlier steps of
+/// the job wrote and staying under [`SIZE_LIMIT`] overall.
+pub fn append(path: &Path, markdown: &str) -> Result<()> {
+    let existing = std::fs::metadata(path).map_or(0, |meta| meta.len() as usize);
+    let markdown = truncate(markdown, SIZE_LIMIT.saturating_sub(existing));
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    file.write_all(markdown.as_bytes())
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::severity::{annotate, Metric};
+    use crate::{summarize, FunctionComplexity};
+    use tempfile::TempDir;
+
+    fn function(name: &str, complexity: u32) -> FunctionComplexity {
+        FunctionComplexity {
+            name: name.to_string(),
+            file: format!("/repo/{}.py", name),
+            line: 1,
+            complexity,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_render_lists_top_offenders() {
+        let functions: Vec<_> = (1..=30).map(|c| function(&format!("f{}", c), c)).collect();
+        let mut result = AnalysisResult {
+            summary: summarize(&functions, 10, Metric::Cyclomatic),
+            functions,
+            ..Default::default()
+        };
+        let thresholds = Thresholds::new(10, Some(20), None);
+        annotate(&mut result, |_| thresholds);
+
+        let markdown = render(&result, Path::new("/repo"), &thresholds);
+
+        assert!(markdown.contains(":x: 20 functions above the threshold of 10 (10 above"));
+        assert!(markdown.contains("| `f30` | f30.py | 1 | 30 |"));
+        assert!(markdown.contains("| `f21` |"));
+        assert!(!markdown.contains("| `f20` |"));
+    }
+
+    #[test]
+    fn test_render_ranks_by_the_thresholded_metric() {
+        let mut shallow = function("shallow", 12);
+        shallow.cognitive_complexity = 11;
+        let mut nested = function("nested", 4);
+        nested.cognitive_complexity = 15;
+        let mut result = AnalysisResult {
+            functions: vec![shallow, nested],
+            ..Default::default()
+        };
+        let thresholds = Thresholds {
+            metric: Metric::Cognitive,
+            ..Thresholds::new(10, None, None)
+        };
+        annotate(&mut result, |_| thresholds);
+
+        let markdown = render(&result, Path::new("/repo"), &thresholds);
+
+        let nested = markdown.find("| `nested` | nested.py | 1 | 15 |").unwrap();
+        let shallow = markdown.find("| `shallow` | shallow.py | 1 | 11 |").unwrap();
+        assert!(nested < shallow);
+    }
+
+    #[test]
+    fn test_render_lists_changes_since_the_baseline() {
+        let mut grew = function("grew", 7);
+        grew.delta = Some(Delta::Change(3));
+        let mut shrank = function("shrank", 2);
+        shrank.delta = Some(Delta::Change(-1));
+        let mut added = function("added", 4);
+        added.delta = Some(Delta::New);
+        let mut result = AnalysisResult {
+            functions: vec![grew, shrank, added],
+            removed: vec![function("gone", 3)],
+            ..Default::default()
+        };
+        let thresholds = Thresholds::new(10, None, None);
+        annotate(&mut result, |_| thresholds);
+
+        let markdown = render(&result, Path::new("/repo"), &thresholds);
+
+        assert!(
+            markdown.contains("### Changes since the baseline\n\n1 regressed, 1 new, 1 removed.\n")
+        );
+        assert!(markdown.contains(
+            "| `grew` | grew.py | 1 | 7 | +3 |\n| `added` | added.py | 1 | 4 | new |\n"
+        ));
+        assert!(!markdown.contains("`shrank`"));
+
+        // Without a baseline there is no such section.
+        let result = AnalysisResult {
+            functions: vec![function("plain", 3)],
+            ..Default::default()
+        };
+        assert!(!render(&result, Path::new("/repo"), &thresholds).contains("baseline"));
+    }
+
+    #[test]
+    fn test_truncate_cuts_at_line_boundary() {
+        let markdown = format!("line one\n{}\n", "x".repeat(100));
+        assert_eq!(truncate(&markdown, 200), markdown);
+
+        let truncated = truncate(&markdown, TRUNCATED_NOTE.len() + 12);
+        assert_eq!(truncated, format!("line one\n{}", TRUNCATED_NOTE));
+    }
+
+    #[test]
+    fn test_append_keeps_earlier_content() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("summary.md");
+        std::fs::write(&path, "# Earlier step\n").unwrap();
+
+        append(&path, "## Complexity report\n").unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "# Earlier step\n## Complexity report\n"
+        );
+    }
+}
@@ -0,0 +1,442 @@
+use crate::*;
+
+pub fn print_table(result: &AnalysisResult, thresholds: &Thresholds, risk_threshold: Option<f64>) {
+    if !result.files.is_empty() {
+        println!("{}", file_table(&result.files));
+    } else if !result.directories.is_empty() {
+        println!("{}", directory_table(&result.directories));
+    } else {
+        println!("{}", function_table(result, risk_threshold, thresholds.metric));
+    }
+    print_removed(result);
+    print_classes(result);
+    print_breakdown(result);
+    print_summary(result, thresholds.warn, risk_threshold);
+}
+
+pub fn print_removed(result: &AnalysisResult) {
+    if result.removed.is_empty() {
+        return;
+    }
+    let mut table = Table::new();
+    table.set_header(vec!["Function", "File", "Line", "Complexity"]);
+    for func in &result.removed {
+        table.add_row(vec![
+            Cell::new(text::truncate_end(&func.name, NAME_WIDTH)),
+            Cell::new(text::truncate_start(&func.file, PATH_WIDTH)),
+            Cell::new(func.line.to_string()),
+            Cell::new(func.complexity.to_string()),
+        ]);
+    }
+    println!("\nRemoved since the baseline:\n{}", table);
+}
+
+pub fn print_classes(result: &AnalysisResult) {
+    if result.classes.is_empty() {
+        return;
+    }
+    let mut table = Table::new();
+    table.set_header(vec!["Class", "File", "Line", "WMC", "Effective WMC", "Inherits from"]);
+    for class in &result.classes {
+        let mut bases: Vec<String> = class.ancestors.iter().map(|a| a.name.clone()).collect();
+        bases.extend(class.unknown_bases.iter().map(|name| format!("{} (unknown)", name)));
+        table.add_row(vec![
+            Cell::new(text::truncate_end(&class.name, NAME_WIDTH)),
+            Cell::new(text::truncate_start(&class.file, PATH_WIDTH)),
+            Cell::new(class.line.to_string()),
+            Cell::new(class.wmc.to_string()),
+            Cell::new(class.effective_wmc.to_string()),
+            Cell::new(bases.join(", ")),
+        ]);
+    }
+    println!("\nClasses:\n{}", table);
+}
+
+pub fn print_breakdown(result: &AnalysisResult) {
+    let explained: Vec<_> = result
+        .functions
+        .iter()
+        .filter(|func| !func.breakdown.is_empty())
+        .collect();
+    if explained.is_empty() {
+        return;
+    }
+    println!("\nBreakdown (count × weight):");
+    for func in explained {
+        let terms: Vec<String> = func
+            .breakdown
+            .iter()
+            .map(|term| format!(" + {} {}×{}", term.construct, term.count, term.weight))
+            .collect();
+        println!(
+            "  {} ({}:{}): 1{} = {}",
+            func.name,
+            func.file,
+            func.line,
+            terms.concat(),
+            func.complexity
+        );
+    }
+}
+
+/// Widest function name shown in tables before it is shortened.
+pub const NAME_WIDTH: usize = 40;
+/// Widest path shown in tables before it is shortened from the left.
+pub const PATH_WIDTH: usize = 60;
+
+/// Table of all functions, with the complexities `metric` shows. Long names
+/// and paths are shortened to fit the columns; machine-readable formats
+/// always carry them in full. Risk scores above `risk_threshold` are
+/// highlighted.
+pub fn function_table(
+    result: &AnalysisResult,
+    risk_threshold: Option<f64>,
+    metric: severity::Metric,
+) -> Table {
+    let projects = result
+        .summary
+        .as_ref()
+        .map_or(&[][..], |summary| summary.projects.as_slice());
+    let with_delta = result.functions.iter().any(|func| func.delta.is_some());
+    let with_risk = result.functions.iter().any(|func| func.risk.is_some());
+    let with_category = result.functions.iter().any(|func| func.category.is_some());
+    let columns = match metric {
+        severity::Metric::Both => vec![severity::Metric::Cyclomatic, severity::Metric::Cognitive],
+        metric => vec![metric],
+    };
+    let mut header = vec!["Function", "File", "Line"];
+    header.extend(columns.iter().map(|column| match column {
+        severity::Metric::Cognitive => "Cognitive",
+        _ => "Complexity",
+    }));
+    if !projects.is_empty() {
+        header.insert(0, "Project");
+    }
+    if with_category {
+        header.push("Category");
+    }
+    if with_risk {
+        header.push("Risk");
+    }
+    if with_delta {
+        header.push("Δ");
+    }
+    let mut table = Table::new();
+    table.set_header(header);
+
+    for func in &result.functions {
+        let mut row = vec![
+            Cell::new(text::truncate_end(&func.name, NAME_WIDTH)),
+            Cell::new(text::truncate_start(&func.file, PATH_WIDTH)),
+            Cell::new(func.line.to_string()),
+        ];
+        if !projects.is_empty() {
+            row.insert(0, Cell::new(func.project.as_deref().unwrap_or_default()));
+        }
+
+        for column in &columns {
+            let cell = Cell::new(column.value(func).to_string());
+            row.push(match func.severity {
+                Some(Severity::Error) => cell.fg(comfy_table::Color::Red),
+                Some(Severity::Warning) => cell.fg(comfy_table::Color::Yellow),
+                Some(Severity::Info) => cell.fg(comfy_table::Color::Cyan),
+                None => cell,
+            });
+        }
+        if with_category {
+            row.push(Cell::new(func.category.as_deref().unwrap_or_default()));
+        }
+        if with_risk {
+            let risk = func.risk.unwrap_or_default();
+            let cell = Cell::new(format!("{:.1}", risk));
+            row.push(match risk_threshold {
+                Some(threshold) if risk > threshold => cell.fg(comfy_table::Color::Red),
+                _ => cell,
+            });
+        }
+        if with_delta {
+            let label = func.delta.map(baseline::label).unwrap_or_default();
+            row.push(match func.delta {
+                Some(baseline::Delta::Change(change)) if change > 0 => {
+                    Cell::new(label).fg(comfy_table::Color::Red)
+                }
+                Some(baseline::Delta::Change(change)) if change < 0 => {
+                    Cell::new(label).fg(comfy_table::Color::Green)
+                }
+                _ => Cell::new(label),
+            });
+        }
+
+        table.add_row(row);
+    }
+
+    for file in &result.generated_files {
+        let mut row = vec![
+            Cell::new(format!("({} generated functions)", file.functions)),
+            Cell::new(text::truncate_start(&file.file, PATH_WIDTH)),
+            Cell::new(""),
+            Cell::new(format!("max {}", file.max_complexity)),
+        ];
+        if metric == severity::Metric::Both {
+            row.push(Cell::new(""));
+        }
+        if !projects.is_empty() {
+            row.insert(0, Cell::new(file.project.as_deref().unwrap_or_default()));
+        }
+        if with_category {
+            row.push(Cell::new(""));
+        }
+        if with_risk {
+            row.push(Cell::new(""));
+        }
+        if with_delta {
+            row.push(Cell::new(""));
+        }
+        table.add_row(row);
+    }
+
+    table
+}
+
+/// Cell of a rollup's maximum complexity, colored by the highest severity
+/// of its functions.
+pub fn max_cell(max_complexity: u32, severity: Option<Severity>) -> Cell {
+    let cell = Cell::new(max_complexity.to_string());
+    match severity {
+        Some(Severity::Error) => cell.fg(comfy_table::Color::Red),
+        Some(Severity::Warning) => cell.fg(comfy_table::Color::Yellow),
+        Some(Severity::Info) => cell.fg(comfy_table::Color::Cyan),
+        None => cell,
+    }
+}
+
+/// Table of the functions rolled up by file.
+pub fn file_table(files: &[rollup::FileComplexity]) -> Table {
+    let with_project = files.iter().any(|file| file.project.is_some());
+    let mut header = vec!["File", "Functions", "Total", "Mean", "Max", "Worst function"];
+    if with_project {
+        header.insert(0, "Project");
+    }
+    let mut table = Table::new();
+    table.set_header(header);
+    for file in files {
+        let mut row = vec![
+            Cell::new(text::truncate_start(&file.file, PATH_WIDTH)),
+            Cell::new(file.functions.to_string()),
+            Cell::new(file.total_complexity.to_string()),
+            Cell::new(format!("{:.2}", file.mean_complexity)),
+            max_cell(file.max_complexity, file.severity),
+            Cell::new(format!(
+                "{} (line {})",
+                text::truncate_end(&file.worst_function, NAME_WIDTH),
+                file.worst_function_line
+            )),
+        ];
+        if with_project {
+            row.insert(0, Cell::new(file.project.as_deref().unwrap_or_default()));
+        }
+        table.add_row(row);
+    }
+    table
+}
+
+/// Table of the functions rolled up by directory.
+pub fn directory_table(directories: &[rollup::DirectoryComplexity]) -> Table {
+    let with_project = directories.iter().any(|dir| dir.project.is_some());
+    let mut header = vec![
+        "Directory",
+        "Files",
+        "Functions",
+        "Total",
+        "Mean",
+        "Max",
+        "Worst function",
+    ];
+    if with_project {
+        header.insert(0, "Project");
+    }
+    let mut table = Table::new();
+    table.set_header(header);
+    for dir in directories {
+        let mut row = vec![
+            Cell::new(text::truncate_start(&dir.directory, PATH_WIDTH)),
+            Cell::new(dir.files.to_string()),
+            Cell::new(dir.functions.to_string()),
+            Cell::new(dir.total_complexity.to_string()),
+            Cell::new(format!("{:.2}", dir.mean_complexity)),
+            max_cell(dir.max_complexity, dir.severity),
+            Cell::new(text::truncate_end(&dir.worst_function, NAME_WIDTH)),
+        ];
+        if with_project {
+            row.insert(0, Cell::new(dir.project.as_deref().unwrap_or_default()));
+        }
+        table.add_row(row);
+    }
+    table
+}
+
+pub fn print_summary(result: &AnalysisResult, threshold: u32, risk_threshold: Option<f64>) {
+    print!("{}", summary_text(result, threshold, risk_threshold));
+}
+
+/// The summary printed under the table.
+pub fn summary_text(result: &AnalysisResult, threshold: u32, risk_threshold: Option<f64>) -> String {
+    let mut out = String::new();
+    if let Some(summary) = &result.summary {
+        writeln!(out, "\nSummary:").unwrap();
+        writeln!(out, "Mean Complexity: {:.2}", summary.mean_complexity).unwrap();
+        writeln!(out, "Max Complexity: {}", summary.max_complexity).unwrap();
+        writeln!(
+            out,
+            "Percentiles (p50/p90/p95): {}/{}/{}",
+            summary.p50_complexity, summary.p90_complexity, summary.p95_complexity
+        )
+        .unwrap();
+        writeln!(
+            out,
+            "Concentration: Gini {:.2}, top 10% hold {:.1}% of complexity",
+            summary.gini_coefficient,
+            summary.top_decile_share * 100.0
+        )
+        .unwrap();
+        writeln!(out, "Total Functions: {}", summary.total_functions).unwrap();
+        writeln!(
+            out,
+            "Functions above threshold ({}): {}",
+            threshold, summary.functions_above_threshold
+        )
+        .unwrap();
+        if summary.generated_functions > 0 {
+            writeln!(out, "Generated functions: {}", summary.generated_functions).unwrap();
+        }
+        if let (Some(above), Some(risk_threshold)) =
+            (summary.functions_above_risk_threshold, risk_threshold)
+        {
+            writeln!(
+                out,
+                "Functions above risk threshold ({}): {}",
+                risk_threshold, above
+            )
+            .unwrap();
+        }
+        if !summary.top_risk.is_empty() {
+            writeln!(out, "Riskiest functions:").unwrap();
+            for func in &summary.top_risk {
+                writeln!(
+                    out,
+                    "  {} ({}:{}): {:.1}",
+                    func.name, func.file, func.line, func.risk
+                )
+                .unwrap();
+            }
+        }
+        if !summary.projects.is_empty() {
+            writeln!(out, "Projects:").unwrap();
+            for project in &summary.projects {
+                writeln!(
+                    out,
+                    "  {}: {} functions, mean {:.2}, max {}, {} above threshold ({})",
+                    project.project,
+                    project.total_functions,
+                    project.mean_complexity,
+                    project.max_complexity,
+                    project.functions_above_threshold,
+                    project.threshold
+                )
+                .unwrap();
+            }
+        }
+        if !summary.categories.is_empty() {
+            writeln!(out, "Categories:").unwrap();
+            for category in &summary.categories {
+                let Some(mean) = category.mean_complexity else {
+                    writeln!(out, "  {}: no functions", category.category).unwrap();
+                    continue;
+                };
+                writeln!(
+                    out,
+                    "  {}: {} functions, mean {:.2}, max {}, {} above threshold ({})",
+                    category.category,
+                    category.total_functions,
+                    mean,
+                    category.max_complexity,
+                    category.functions_above_threshold,
+                    category.threshold
+                )
+                .unwrap();
+            }
+        }
+        if !summary.worst_files.is_empty() {
+            writeln!(out, "Worst files:").unwrap();
+            for file in &summary.worst_files {
+                let file_name = match &file.project {
+                    Some(project) => format!("{}:{}", project, file.file),
+                    None => file.file.clone(),
+                };
+                writeln!(
+                    out,
+                    "  {} (max {}, total {}, {} above threshold; worst: {} line {})",
+                    file_name,
+                    file.max_complexity,
+                    file.total_complexity,
+                    file.functions_above_threshold,
+                    file.worst_function,
+                    file.worst_function_line
+                )
+                .unwrap();
+            }
+        }
+    }
+
+    if !result.gates.is_empty() {
+        writeln!(out, "\nSummary gates:").unwrap();
+        for gate in &result.gates {
+            writeln!(out, "  {}", gate).unwrap();
+        }
+    }
+
+    if let Some(skipped) = skipped_line(&result.errors) {
+        writeln!(out, "\n{}", skipped).unwrap();
+    }
+
+    if let Some(meta) = &result.meta {
+        writeln!(out, "\n{}", meta.footer()).unwrap();
+    }
+    out
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delta_column() {
+        let function = |name: &str, complexity, delta| FunctionComplexity {
+            name: name.to_string(),
+            file: "app.py".to_string(),
+            complexity,
+            delta,
+            ..Default::default()
+        };
+        let mut result = AnalysisResult {
+            functions: vec![function("plain", 1, None)],
+            ..Default::default()
+        };
+        assert!(!function_table(&result, None, severity::Metric::Cyclomatic).to_string().contains('Δ'));
+
+        result.functions = vec![
+            function("grew", 7, Some(baseline::Delta::Change(3))),
+            function("same", 2, Some(baseline::Delta::Change(0))),
+            function("added", 4, Some(baseline::Delta::New)),
+        ];
+        let table = function_table(&result, None, severity::Metric::Cyclomatic).to_string();
+        let lines: Vec<&str> = table.lines().collect();
+        assert!(lines[1].contains('Δ'));
+        assert!(lines[3].contains("+3"));
+        let cells: Vec<&str> = lines[5].split(['|', '│', '┆']).map(str::trim).collect();
+        assert_eq!(cells[cells.len() - 2], "", "unchanged delta is not blank:\n{}", table);
+        assert!(lines[7].contains("new"));
+    }
+
+}
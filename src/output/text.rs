@@ -0,0 +1,82 @@
+//! Width-aware shortening of names and paths for human-readable output.
+//!
+//! Widths are terminal display widths, so CJK characters and most emoji
+//! count as two columns, and strings are only cut between grapheme clusters.
+
+use std::borrow::Cow;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+const ELLIPSIS: &str = "…";
+
+pub fn display_width(s: &str) -> usize {
+    UnicodeWidthStr::width(s)
+}
+
+/// `s` cut at the end to at most `max_width` columns, ellipsis included.
+pub fn truncate_end(s: &str, max_width: usize) -> Cow<'_, str> {
+    if display_width(s) <= max_width {
+        return Cow::Borrowed(s);
+    }
+    let budget = max_width.saturating_sub(display_width(ELLIPSIS));
+    let mut width = 0;
+    let mut kept = String::new();
+    for grapheme in s.graphemes(true) {
+        width += display_width(grapheme);
+        if width > budget {
+            break;
+        }
+        kept.push_str(grapheme);
+    }
+    Cow::Owned(kept + ELLIPSIS)
+}
+
+/// `s` cut at the start to at most `max_width` columns, ellipsis included,
+/// which keeps the file name of a long path.
+pub fn truncate_start(s: &str, max_width: usize) -> Cow<'_, str> {
+    if display_width(s) <= max_width {
+        return Cow::Borrowed(s);
+    }
+    let budget = max_width.saturating_sub(display_width(ELLIPSIS));
+    let mut width = 0;
+    let mut kept = Vec::new();
+    for grapheme in s.graphemes(true).rev() {
+        width += display_width(grapheme);
+        if width > budget {
+            break;
+        }
+        kept.push(grapheme);
+    }
+    kept.reverse();
+    Cow::Owned(format!("{}{}", ELLIPSIS, kept.concat()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_width() {
+        assert_eq!(display_width("abc"), 3);
+        assert_eq!(display_width("計算"), 4);
+        assert_eq!(display_width("📁"), 2);
+        assert_eq!(display_width("e\u{301}"), 1);
+    }
+
+    #[test]
+    fn test_truncate_end() {
+        assert_eq!(truncate_end("short", 10), "short");
+        assert_eq!(truncate_end("abcdefgh", 5), "abcd…");
+        // A wide character that does not fit is dropped whole.
+        assert_eq!(truncate_end("計算する関数", 6), "計算…");
+        // Combining marks stay with their base character.
+        assert_eq!(truncate_end("cafe\u{301}s and more", 6), "cafe\u{301}s…");
+        assert!(display_width(&truncate_end("計算する関数", 6)) <= 6);
+    }
+
+    #[test]
+    fn test_truncate_start() {
+        assert_eq!(truncate_start("src/app/views.py", 10), "…/views.py");
+        assert_eq!(truncate_start("📁/モジュール.py", 9), "…ール.py");
+    }
+}
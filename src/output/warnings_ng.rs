@@ -0,0 +1,115 @@
+//! Native issues format of the Jenkins Warnings NG plugin.
+//!
+//! See <https://github.com/jenkinsci/warnings-ng-plugin/blob/main/doc/Documentation.md#export-your-issues-into-a-supported-format>.
+
+use super::{message, relative_path};
+use crate::severity::{Severity, Thresholds};
+use crate::AnalysisResult;
+use anyhow::Result;
+use serde::Serialize;
+use std::path::Path;
+
+#[derive(Debug, Serialize)]
+struct Report {
+    issues: Vec<Issue>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Issue {
+    file_name: String,
+    line_start: u32,
+    line_end: u32,
+    severity: &'static str,
+    message: String,
+    category: &'static str,
+    fingerprint: String,
+}
+
+pub fn render(result: &AnalysisResult, root: &Path, thresholds: &Thresholds) -> Result<String> {
+    let issues = result
+        .functions
+        .iter()
+        .filter_map(|func| {
+            let severity = match func.severity? {
+                Severity::Info => "LOW",
+                Severity::Warning => "NORMAL",
+                Severity::Error => "ERROR",
+            };
+            Some(Issue {
+                file_name: relative_path(&func.file, root),
+                line_start: func.line,
+                line_end: func.end_line,
+                severity,
+                message: message(func, thresholds),
+                category: "Complexity",
+                fingerprint: func.fingerprint.clone(),
+            })
+        })
+        .collect();
+
+    Ok(serde_json::to_string_pretty(&Report { issues })?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::severity::{annotate, Metric};
+    use crate::fingerprint::fingerprint;
+    use crate::FunctionComplexity;
+
+    fn function(name: &str, file: &str, line: u32, complexity: u32) -> FunctionComplexity {
+        FunctionComplexity {
+            name: name.to_string(),
+            file: file.to_string(),
+            line,
+            end_line: line + 9,
+            complexity,
+            fingerprint: fingerprint(file.trim_start_matches("/repo/"), name),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_render_matches_golden_file() {
+        let mut result = AnalysisResult {
+            functions: vec![
+                function("simple", "/repo/app/simple.py", 1, 2),
+                function("busy", "/repo/app/busy.py", 4, 12),
+                function("tangled", "/repo/app/core/tangled.py", 20, 25),
+            ],
+            ..Default::default()
+        };
+        let thresholds = Thresholds::new(10, Some(20), None);
+        annotate(&mut result, |_| thresholds);
+
+        let rendered = render(&result, Path::new("/repo"), &thresholds).unwrap();
+        assert_eq!(
+            rendered,
+            include_str!("../../testdata/warnings_ng.json").trim_end()
+        );
+    }
+
+    #[test]
+    fn test_cognitive_offender() {
+        let mut nested = function("nested", "/repo/app/nested.py", 1, 2);
+        nested.cognitive_complexity = 12;
+        let mut result = AnalysisResult {
+            functions: vec![nested],
+            ..Default::default()
+        };
+        let thresholds = Thresholds {
+            metric: Metric::Cognitive,
+            ..Thresholds::new(10, None, None)
+        };
+        annotate(&mut result, |_| thresholds);
+
+        let rendered = render(&result, Path::new("/repo"), &thresholds).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&rendered).unwrap();
+        assert_eq!(value["issues"][0]["severity"], "NORMAL");
+        assert_eq!(
+            value["issues"][0]["message"],
+            "Function 'nested' has a cognitive complexity of 12 (threshold 10)"
+        );
+    }
+}
@@ -0,0 +1,213 @@
+//! Owners of the analyzed files, read from a GitHub-style `CODEOWNERS` file,
+//! and the budgets of functions above the threshold each of them is allowed,
+//! set with `[budgets]` in `analyzr.toml`.
+
+use crate::rules::glob;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Where `CODEOWNERS` is looked for, relative to the repository root, in the
+/// order GitHub looks for it.
+const LOCATIONS: [&str; 3] = [".github/CODEOWNERS", "CODEOWNERS", "docs/CODEOWNERS"];
+
+/// Budget key of the functions in files no `CODEOWNERS` pattern matches.
+pub const UNOWNED: &str = "unowned";
+
+/// Number of functions above the threshold an owner may have.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Budget {
+    pub owner: String,
+    pub max_above_threshold: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CodeOwners {
+    /// Directory the patterns are relative to.
+    pub base: PathBuf,
+    /// Patterns with their owners, in the order of the file.
+    rules: Vec<(String, Vec<String>)>,
+}
+
+impl CodeOwners {
+    pub fn parse(base: PathBuf, source: &str) -> Self {
+        let rules = source
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter_map(|line| {
+                let line = line.split(" #").next().unwrap_or(line);
+                let mut fields = line.split_whitespace();
+                let pattern = fields.next()?.to_string();
+                Some((pattern, fields.map(str::to_string).collect()))
+            })
+            .collect();
+        CodeOwners { base, rules }
+    }
+
+    /// The `CODEOWNERS` file of the repository `start` is in, found in the
+    /// usual locations of `start` and its parents.
+    pub fn find(start: &Path) -> Option<Self> {
+        let start = start.canonicalize().ok()?;
+        start.ancestors().find_map(|dir| {
+            LOCATIONS.iter().find_map(|location| {
+                let source = std::fs::read_to_string(dir.join(location)).ok()?;
+                Some(CodeOwners::parse(dir.to_path_buf(), &source))
+            })
+        })
+    }
+
+    /// Owners of the file at `relative` (to [`CodeOwners::base`], with `/`
+    /// separators): those of the last matching pattern, none when it has no
+    /// owners or no pattern matches.
+    pub fn owners(&self, relative: &str) -> &[String] {
+        self.rules
+            .iter()
+            .rev()
+            .find(|(pattern, _)| matches(pattern, relative))
+            .map_or(&[], |(_, owners)| owners)
+    }
+}
+
+/// Whether a `CODEOWNERS` pattern matches the file at `relative`. As in
+/// `.gitignore`, a pattern with no `/` but a trailing one matches at any
+/// depth, and a pattern matches the files under a directory it names.
+fn matches(pattern: &str, relative: &str) -> bool {
+    let (pattern, directory) = match pattern.strip_suffix('/') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+    let pattern = match pattern.strip_prefix('/') {
+        Some(anchored) => anchored.to_string(),
+        None if pattern.contains('/') => pattern.to_string(),
+        None => format!("**/{}", pattern),
+    };
+    (!directory && glob(&pattern, relative)) || glob(&format!("{}/**", pattern), relative)
+}
+
+/// Whether `key` in `[budgets]` names `owner`: either exactly, without the
+/// leading `@`, or as the team name of an `@org/team` owner.
+pub fn names(key: &str, owner: &str) -> bool {
+    key == owner
+        || owner.strip_prefix('@') == Some(key)
+        || owner.contains('/') && owner.rsplit('/').next() == Some(key)
+}
+
+/// An owner's functions above the threshold against its budget.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BudgetStatus {
+    pub owner: String,
+    pub above_threshold: usize,
+    /// The budget, 0 for an owner without one.
+    pub budget: usize,
+    pub passed: bool,
+}
+
+/// The status of every owner with functions above the threshold or a
+/// budget, with `usage` counting the functions above the threshold of each
+/// owner. An owner without a budget may have none.
+pub fn evaluate(usage: &BTreeMap<String, usize>, budgets: &[Budget]) -> Vec<BudgetStatus> {
+    let mut statuses: Vec<BudgetStatus> = usage
+        .iter()
+        .map(|(owner, &above_threshold)| {
+            let budget = budgets
+                .iter()
+                .rev()
+                .find(|budget| names(&budget.owner, owner))
+                .map_or(0, |budget| budget.max_above_threshold);
+            BudgetStatus {
+                owner: owner.clone(),
+                above_threshold,
+                budget,
+                passed: above_threshold <= budget,
+            }
+        })
+        .collect();
+    for budget in budgets {
+        if !usage.keys().any(|owner| names(&budget.owner, owner)) {
+            statuses.push(BudgetStatus {
+                owner: budget.owner.clone(),
+                above_threshold: 0,
+                budget: budget.max_above_threshold,
+                passed: true,
+            });
+        }
+    }
+    statuses
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_owners() {
+        let codeowners = CodeOwners::parse(
+            PathBuf::new(),
+            "# Default owners\n\
+             *       @org/platform\n\
+             *.md    @org/docs\n\
+             /services/payments/   @org/team-payments  @alice # comment\n\
+             build/                @org/infra\n\
+             /services/payments/legacy.py\n",
+        );
+        assert_eq!(codeowners.owners("app.py"), ["@org/platform"]);
+        assert_eq!(codeowners.owners("pkg/README.md"), ["@org/docs"]);
+        assert_eq!(
+            codeowners.owners("services/payments/api/views.py"),
+            ["@org/team-payments", "@alice"]
+        );
+        assert_eq!(codeowners.owners("tools/build/run.py"), ["@org/infra"]);
+        // The last matching pattern wins, even without owners.
+        assert!(codeowners.owners("services/payments/legacy.py").is_empty());
+        assert!(CodeOwners::parse(PathBuf::new(), "/src/ @a\n")
+            .owners("lib/src/x.py")
+            .is_empty());
+    }
+
+    #[test]
+    fn test_budget_keys() {
+        assert!(names("team-payments", "@org/team-payments"));
+        assert!(names("org/team-payments", "@org/team-payments"));
+        assert!(names("@alice", "@alice"));
+        assert!(names("alice", "@alice"));
+        assert!(!names("payments", "@org/team-payments"));
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let usage: BTreeMap<String, usize> = [
+            ("@org/team-payments".to_string(), 5),
+            ("@org/platform".to_string(), 3),
+            (UNOWNED.to_string(), 1),
+        ]
+        .into_iter()
+        .collect();
+        let budget = |owner: &str, max_above_threshold| Budget {
+            owner: owner.to_string(),
+            max_above_threshold,
+        };
+        let budgets = [
+            budget("team-payments", 5),
+            budget("platform", 2),
+            budget(UNOWNED, 4),
+            budget("search", 1),
+        ];
+        let statuses = evaluate(&usage, &budgets);
+        let statuses: Vec<(&str, usize, usize, bool)> = statuses
+            .iter()
+            .map(|s| (s.owner.as_str(), s.above_threshold, s.budget, s.passed))
+            .collect();
+        assert_eq!(
+            statuses,
+            [
+                ("@org/platform", 3, 2, false),
+                ("@org/team-payments", 5, 5, true),
+                (UNOWNED, 1, 4, true),
+                ("search", 0, 1, true),
+            ]
+        );
+        // Without a budget an owner may have none.
+        assert!(!evaluate(&usage, &[])[0].passed);
+    }
+}
@@ -0,0 +1,143 @@
+//! Machine-readable progress events for tools that wrap the CLI.
+
+use serde::Serialize;
+use std::fmt;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+/// Formats for `--progress`.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProgressFormat {
+    /// One JSON object per line on stderr
+    Json,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    /// Files found under a root, before any is analyzed.
+    Discovered { files: usize },
+    /// A file has been analyzed or skipped.
+    FileDone {
+        path: String,
+        functions: usize,
+        elapsed_ms: u64,
+    },
+    /// The run is over and the process exits with `exit_code`.
+    Finished { exit_code: i32 },
+}
+
+/// Writes events as newline-delimited JSON. Clones share the writer, and
+/// each event is written whole while holding its lock so that lines never
+/// interleave.
+#[derive(Clone)]
+pub struct Reporter {
+    out: Arc<Mutex<Box<dyn Write + Send>>>,
+}
+
+impl fmt::Debug for Reporter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Reporter")
+    }
+}
+
+impl Reporter {
+    pub fn new(out: Box<dyn Write + Send>) -> Self {
+        Reporter {
+            out: Arc::new(Mutex::new(out)),
+        }
+    }
+
+    pub fn stderr() -> Self {
+        Reporter::new(Box::new(std::io::stderr()))
+    }
+
+    pub fn emit(&self, event: &Event) {
+        let mut line = serde_json::to_vec(event).expect("events serialize");
+        line.push(b'\n');
+        let mut out = self.out.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        // Progress is best effort; a closed stderr must not fail the run.
+        let _ = out.write_all(&line).and_then(|_| out.flush());
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    /// A writer whose output stays readable after being handed to a
+    /// [`Reporter`].
+    #[derive(Clone, Default)]
+    pub struct Buffer(pub Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(bytes);
+            Ok(bytes.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Buffer {
+        pub fn events(&self) -> Vec<Value> {
+            let bytes = self.0.lock().unwrap();
+            std::str::from_utf8(&bytes)
+                .unwrap()
+                .lines()
+                .map(|line| serde_json::from_str(line).unwrap())
+                .collect()
+        }
+    }
+
+    #[test]
+    fn test_event_schema() {
+        let buffer = Buffer::default();
+        let reporter = Reporter::new(Box::new(buffer.clone()));
+        reporter.emit(&Event::Discovered { files: 1234 });
+        reporter.emit(&Event::FileDone {
+            path: "pkg/\"quoted\".py".to_string(),
+            functions: 12,
+            elapsed_ms: 8,
+        });
+        reporter.emit(&Event::Finished { exit_code: 1 });
+
+        let lines = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        assert_eq!(
+            lines,
+            "{\"event\":\"discovered\",\"files\":1234}\n\
+             {\"event\":\"file_done\",\"path\":\"pkg/\\\"quoted\\\".py\",\"functions\":12,\"elapsed_ms\":8}\n\
+             {\"event\":\"finished\",\"exit_code\":1}\n"
+        );
+    }
+
+    #[test]
+    fn test_concurrent_events_do_not_interleave() {
+        let buffer = Buffer::default();
+        let reporter = Reporter::new(Box::new(buffer.clone()));
+        let threads: Vec<_> = (0..8)
+            .map(|thread| {
+                let reporter = reporter.clone();
+                std::thread::spawn(move || {
+                    for file in 0..50 {
+                        reporter.emit(&Event::FileDone {
+                            path: format!("thread{}/{}.py", thread, "x".repeat(file)),
+                            functions: file,
+                            elapsed_ms: 0,
+                        });
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        let events = buffer.events();
+        assert_eq!(events.len(), 400);
+        assert!(events.iter().all(|event| event["event"] == "file_done"));
+    }
+}
@@ -0,0 +1,177 @@
+//! Conversion of radon's `cc -j` output into an [`AnalysisResult`].
+
+use crate::fingerprint::fingerprint;
+use crate::output::relative_path;
+use crate::severity::Metric;
+use crate::{summarize, AnalysisResult, FileIssue, FunctionComplexity, IssueCategory};
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Radon reports either the blocks of a file or an error for it.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum FileEntry {
+    Blocks(Vec<Block>),
+    Error { error: String },
+}
+
+#[derive(Debug, Deserialize)]
+struct Block {
+    #[serde(rename = "type")]
+    kind: String,
+    name: String,
+    /// The class of a method.
+    #[serde(default)]
+    classname: Option<String>,
+    lineno: u32,
+    col_offset: u32,
+    endline: Option<u32>,
+    complexity: u32,
+    #[serde(default)]
+    closures: Vec<Block>,
+}
+
+/// Parses radon JSON and converts its function and method blocks into
+/// functions, named by qualified name like those of an analysis
+/// (`Model.save`, `outer.inner`). Class blocks are skipped since their score
+/// aggregates their methods, which radon also lists on their own. Files
+/// radon failed on are recorded as parse errors.
+pub fn import(json: &str, root: &Path, threshold: u32) -> Result<AnalysisResult> {
+    let files: BTreeMap<String, FileEntry> =
+        serde_json::from_str(json).context("Input is not radon `cc -j` output")?;
+
+    let mut functions = Vec::new();
+    let mut errors = Vec::new();
+    for (file, entry) in files {
+        match entry {
+            FileEntry::Blocks(blocks) => {
+                let relative = relative_path(&file, root);
+                for block in &blocks {
+                    push_block(block, None, &file, &relative, &mut functions);
+                }
+            }
+            FileEntry::Error { error } => errors.push(FileIssue {
+                path: file,
+                category: IssueCategory::Parse,
+                message: error,
+            }),
+        }
+    }
+
+    functions.sort_by(|a, b| a.file.cmp(&b.file).then(a.line.cmp(&b.line)));
+    let summary = summarize(&functions, threshold, Metric::Cyclomatic);
+    Ok(AnalysisResult {
+        functions,
+        summary,
+        errors,
+        ..Default::default()
+    })
+}
+
+/// Pushes the function of `block`, defined in the function named `outer` if
+/// any, and those of its closures.
+fn push_block(
+    block: &Block,
+    outer: Option<&str>,
+    file: &str,
+    relative: &str,
+    functions: &mut Vec<FunctionComplexity>,
+) {
+    if block.kind == "class" {
+        return;
+    }
+    let name = match outer.or(block.classname.as_deref()) {
+        Some(scope) => format!("{}.{}", scope, block.name),
+        None => block.name.clone(),
+    };
+    functions.push(FunctionComplexity {
+        name: name.clone(),
+        file: file.to_string(),
+        line: block.lineno,
+        column: block.col_offset + 1,
+        end_line: block.endline.unwrap_or(block.lineno),
+        signature_end_line: block.lineno,
+        complexity: block.complexity,
+        cognitive_complexity: 0,
+        locals: 0,
+        fingerprint: fingerprint(relative, &name),
+        body_hash: String::new(),
+        project: None,
+        url: None,
+        severity: None,
+        rule: None,
+        decorators: Vec::new(),
+        bases: Vec::new(),
+        category: None,
+        breakdown: Vec::new(),
+        dunder: false,
+        generated: false,
+        delta: None,
+        risk: None,
+        language: Default::default(),
+    });
+    for closure in &block.closures {
+        push_block(closure, Some(&name), file, relative, functions);
+    }
+}
+
+/// Radon's letter rank for a cyclomatic complexity score.
+pub fn rank(complexity: u32) -> char {
+    match complexity {
+        0..=5 => 'A',
+        6..=10 => 'B',
+        11..=20 => 'C',
+        21..=30 => 'D',
+        31..=40 => 'E',
+        _ => 'F',
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RADON_OUTPUT: &str = r#"{
+        "src/app.py": [
+            {"type": "function", "rank": "B", "col_offset": 0, "complexity": 7,
+             "name": "outer", "lineno": 1, "endline": 12,
+             "closures": [
+                {"type": "function", "rank": "A", "col_offset": 4, "complexity": 2,
+                 "name": "inner", "lineno": 3, "endline": 6, "closures": []}
+             ]},
+            {"type": "class", "rank": "A", "col_offset": 0, "complexity": 3,
+             "name": "Model", "lineno": 14, "endline": 20, "methods": []},
+            {"type": "method", "rank": "A", "col_offset": 4, "complexity": 2,
+             "classname": "Model", "name": "save", "lineno": 15, "endline": 20,
+             "closures": []}
+        ],
+        "src/broken.py": {"error": "invalid syntax (<unknown>, line 3)"}
+    }"#;
+
+    #[test]
+    fn test_import_radon_output() {
+        let result = import(RADON_OUTPUT, Path::new("src"), 5).unwrap();
+
+        let names: Vec<&str> = result.functions.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, ["outer", "outer.inner", "Model.save"]);
+        assert_eq!(result.functions[0].complexity, 7);
+        assert_eq!(result.functions[2].end_line, 20);
+        assert_eq!(result.functions[0].fingerprint, fingerprint("app.py", "outer"));
+        assert_eq!(result.summary.unwrap().functions_above_threshold, 1);
+
+        assert_eq!(result.errors.len(), 1);
+        assert_eq!(result.errors[0].path, "src/broken.py");
+        assert_eq!(result.errors[0].category, IssueCategory::Parse);
+    }
+
+    #[test]
+    fn test_rank() {
+        assert_eq!(rank(1), 'A');
+        assert_eq!(rank(5), 'A');
+        assert_eq!(rank(6), 'B');
+        assert_eq!(rank(20), 'C');
+        assert_eq!(rank(41), 'F');
+    }
+}
@@ -0,0 +1,254 @@
+//! A composite risk score: a weighted sum of a function's metrics, with the
+//! coefficients chosen by each team.
+
+use crate::FunctionComplexity;
+use serde::{Deserialize, Serialize};
+
+/// Number of riskiest functions listed in the summary.
+pub const TOP_FUNCTIONS: usize = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Metric {
+    /// The function's cyclomatic complexity.
+    Cyclomatic,
+    Locals,
+    /// Number of lines from `def` to the end of the body.
+    Lines,
+    /// The function's cognitive complexity.
+    Cognitive,
+    MaxDepth,
+    Params,
+}
+
+impl Metric {
+    pub const ALL: [Metric; 6] = [
+        Metric::Cyclomatic,
+        Metric::Locals,
+        Metric::Lines,
+        Metric::Cognitive,
+        Metric::MaxDepth,
+        Metric::Params,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Metric::Cyclomatic => "cyclomatic",
+            Metric::Locals => "locals",
+            Metric::Lines => "lines",
+            Metric::Cognitive => "cognitive",
+            Metric::MaxDepth => "max_depth",
+            Metric::Params => "params",
+        }
+    }
+
+    /// The metric's value for `func`, or `None` when it is not computed.
+    fn value(self, func: &FunctionComplexity) -> Option<f64> {
+        match self {
+            Metric::Cyclomatic => Some(func.complexity as f64),
+            Metric::Locals => Some(func.locals as f64),
+            Metric::Lines => Some(func.end_line.saturating_sub(func.line) as f64 + 1.0),
+            Metric::Cognitive => Some(func.cognitive_complexity as f64),
+            Metric::MaxDepth | Metric::Params => None,
+        }
+    }
+}
+
+pub fn parse_metric(name: &str) -> Result<Metric, String> {
+    Metric::ALL.into_iter().find(|m| m.name() == name).ok_or_else(|| {
+        let names: Vec<&str> = Metric::ALL.iter().map(|m| m.name()).collect();
+        format!("unknown metric `{}` (expected one of {})", name, names.join(", "))
+    })
+}
+
+/// Parses a `--risk-threshold` argument.
+pub fn parse_threshold(spec: &str) -> Result<f64, String> {
+    spec.parse::<f64>()
+        .ok()
+        .filter(|threshold| threshold.is_finite())
+        .ok_or_else(|| format!("`{}` is not a valid risk score", spec))
+}
+
+/// Parses a `metric=coefficient` argument.
+pub fn parse_coefficient(spec: &str) -> Result<(Metric, f64), String> {
+    let (name, coefficient) = spec
+        .split_once('=')
+        .ok_or_else(|| format!("expected METRIC=COEFFICIENT, got `{}`", spec))?;
+    let coefficient = coefficient
+        .parse::<f64>()
+        .ok()
+        .filter(|c| c.is_finite())
+        .ok_or_else(|| format!("`{}` is not a valid coefficient", coefficient))?;
+    Ok((parse_metric(name)?, coefficient))
+}
+
+/// Coefficients of the risk score. A metric given several times keeps its
+/// last coefficient.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RiskModel {
+    coefficients: Vec<(Metric, f64)>,
+}
+
+impl RiskModel {
+    pub fn new(coefficients: &[(Metric, f64)]) -> Self {
+        let mut model = RiskModel::default();
+        for &(metric, coefficient) in coefficients {
+            model.coefficients.retain(|(m, _)| *m != metric);
+            model.coefficients.push((metric, coefficient));
+        }
+        model
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.coefficients.is_empty()
+    }
+
+    /// One warning per weighted metric that this run does not compute, and
+    /// which therefore contributes zero.
+    pub fn warnings(&self) -> Vec<String> {
+        let probe = FunctionComplexity::default();
+        self.coefficients
+            .iter()
+            .filter(|(metric, _)| metric.value(&probe).is_none())
+            .map(|(metric, _)| {
+                format!(
+                    "risk metric `{}` is not computed in this run and contributes 0",
+                    metric.name()
+                )
+            })
+            .collect()
+    }
+
+    /// The risk score of `func`, clamped to the finite range when huge
+    /// coefficients overflow it, and `None` when terms overflowing in both
+    /// directions leave it undefined.
+    pub fn score(&self, func: &FunctionComplexity) -> Option<f64> {
+        let score: f64 = self
+            .coefficients
+            .iter()
+            .map(|&(metric, coefficient)| metric.value(func).unwrap_or(0.0) * coefficient)
+            .sum();
+        (!score.is_nan()).then(|| score.clamp(f64::MIN, f64::MAX))
+    }
+
+    /// The coefficients as `metric=coefficient`, for the run metadata.
+    pub fn describe(&self) -> Vec<String> {
+        self.coefficients
+            .iter()
+            .map(|(metric, coefficient)| format!("{}={}", metric.name(), coefficient))
+            .collect()
+    }
+}
+
+/// A function in the summary's list of the riskiest ones.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RiskyFunction {
+    pub name: String,
+    pub file: String,
+    pub line: u32,
+    pub risk: f64,
+}
+
+/// The `count` functions with the highest risk, riskiest first.
+pub fn top(functions: &[FunctionComplexity], count: usize) -> Vec<RiskyFunction> {
+    let mut scored: Vec<(&FunctionComplexity, f64)> = functions
+        .iter()
+        .filter_map(|func| Some((func, func.risk?)))
+        .collect();
+    scored.sort_by(|(a, a_risk), (b, b_risk)| {
+        b_risk
+            .total_cmp(a_risk)
+            .then_with(|| (&a.file, a.line).cmp(&(&b.file, b.line)))
+    });
+    scored
+        .into_iter()
+        .take(count)
+        .map(|(func, risk)| RiskyFunction {
+            name: func.name.clone(),
+            file: func.file.clone(),
+            line: func.line,
+            risk,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn function(name: &str, complexity: u32, locals: u32, lines: u32) -> FunctionComplexity {
+        FunctionComplexity {
+            name: name.to_string(),
+            line: 10,
+            end_line: 10 + lines - 1,
+            complexity,
+            locals,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_score() {
+        let model = RiskModel::new(&[
+            (Metric::Cyclomatic, 1.0),
+            (Metric::Locals, 0.5),
+            (Metric::Lines, 0.1),
+            (Metric::Cognitive, 1.5),
+            (Metric::Params, 2.0),
+        ]);
+        let mut func = function("f", 4, 3, 20);
+        func.cognitive_complexity = 2;
+        assert_eq!(model.score(&func), Some(4.0 + 1.5 + 2.0 + 3.0));
+        assert_eq!(
+            model.warnings(),
+            ["risk metric `params` is not computed in this run and contributes 0"]
+        );
+        assert_eq!(
+            model.describe(),
+            ["cyclomatic=1", "locals=0.5", "lines=0.1", "cognitive=1.5", "params=2"]
+        );
+    }
+
+    #[test]
+    fn test_score_overflow() {
+        let huge = RiskModel::new(&[(Metric::Cyclomatic, f64::MAX), (Metric::Locals, f64::MAX)]);
+        assert_eq!(huge.score(&function("f", 4, 3, 20)), Some(f64::MAX));
+        let opposed = RiskModel::new(&[(Metric::Cyclomatic, f64::MAX), (Metric::Locals, -f64::MAX)]);
+        assert_eq!(opposed.score(&function("f", 4, 3, 20)), None);
+        // A single function, or one with every metric at zero, is scored.
+        assert_eq!(opposed.score(&function("f", 0, 0, 1)), Some(0.0));
+    }
+
+    #[test]
+    fn test_later_coefficients_win() {
+        let model = RiskModel::new(&[(Metric::Cyclomatic, 1.0), (Metric::Cyclomatic, 2.0)]);
+        assert_eq!(model.describe(), ["cyclomatic=2"]);
+        assert!(RiskModel::new(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_parse_coefficient() {
+        assert_eq!(parse_coefficient("locals=0.5"), Ok((Metric::Locals, 0.5)));
+        assert_eq!(parse_coefficient("max_depth=2"), Ok((Metric::MaxDepth, 2.0)));
+        assert!(parse_coefficient("locals").is_err());
+        assert!(parse_coefficient("locals=NaN").is_err());
+        assert!(parse_coefficient("halstead=1").unwrap_err().contains("unknown metric"));
+        assert_eq!(parse_threshold("12.5"), Ok(12.5));
+        assert!(parse_threshold("inf").is_err());
+        assert!(parse_threshold("NaN").is_err());
+    }
+
+    #[test]
+    fn test_top() {
+        let model = RiskModel::new(&[(Metric::Cyclomatic, 1.0), (Metric::Locals, 1.0)]);
+        let mut functions = vec![
+            function("a", 2, 0, 1),
+            function("b", 1, 9, 1),
+            function("c", 5, 1, 1),
+        ];
+        for func in &mut functions {
+            func.risk = model.score(func);
+        }
+        let names: Vec<String> = top(&functions, 2).into_iter().map(|f| f.name).collect();
+        assert_eq!(names, ["b", "c"]);
+    }
+}
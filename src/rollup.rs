@@ -0,0 +1,311 @@
+//! Complexity rolled up by file or by directory, with `--group-by file` and
+//! `--group-by directory`, to find the hotspots of a codebase too large to
+//! read function by function.
+//!
+//! Rows are ranked by total complexity, so that a file of many moderately
+//! complex functions outranks one with a single complex function, with the
+//! most complex function, then the path, as tie-breakers.
+
+use crate::output::relative_path;
+use crate::severity::{Metric, Severity};
+use crate::{above, FunctionComplexity};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Directory of the files directly in the analyzed one.
+const ROOT: &str = ".";
+
+/// The functions of one file, summed up.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileComplexity {
+    pub file: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project: Option<String>,
+    pub functions: usize,
+    pub total_complexity: u64,
+    pub mean_complexity: f64,
+    pub max_complexity: u32,
+    pub functions_above_threshold: usize,
+    /// Most complex function of the file.
+    pub worst_function: String,
+    pub worst_function_line: u32,
+    /// Highest severity of the file's functions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub severity: Option<Severity>,
+}
+
+/// The functions of the files under one directory, summed up.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DirectoryComplexity {
+    /// The first `--depth` components of the directory of its files,
+    /// relative to the analyzed path, or `.` for the files directly in it.
+    pub directory: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project: Option<String>,
+    pub files: usize,
+    pub functions: usize,
+    pub total_complexity: u64,
+    pub mean_complexity: f64,
+    pub max_complexity: u32,
+    pub functions_above_threshold: usize,
+    /// Most complex function under the directory, and its file.
+    pub worst_function: String,
+    pub worst_function_file: String,
+    /// Highest severity of the functions under the directory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub severity: Option<Severity>,
+}
+
+/// Running totals of one group of functions.
+struct Group<'a> {
+    files: Vec<&'a str>,
+    functions: usize,
+    total: u64,
+    above: usize,
+    worst: &'a FunctionComplexity,
+    severity: Option<Severity>,
+}
+
+impl Group<'_> {
+    fn mean(&self) -> f64 {
+        self.total as f64 / self.functions.max(1) as f64
+    }
+}
+
+/// Groups `functions` by `key` and ranks the groups.
+fn group<'a, K: Clone + Ord + std::hash::Hash>(
+    functions: &'a [FunctionComplexity],
+    threshold: u32,
+    metric: Metric,
+    key: impl Fn(&FunctionComplexity) -> K,
+) -> Vec<(K, Group<'a>)> {
+    let mut groups: Vec<(K, Group)> = Vec::new();
+    let mut index: HashMap<K, usize> = HashMap::new();
+    for func in functions {
+        let key = key(func);
+        let position = *index.entry(key.clone()).or_insert_with(|| {
+            groups.push((
+                key,
+                Group {
+                    files: Vec::new(),
+                    functions: 0,
+                    total: 0,
+                    above: 0,
+                    worst: func,
+                    severity: None,
+                },
+            ));
+            groups.len() - 1
+        });
+        let group = &mut groups[position].1;
+        if !group.files.contains(&func.file.as_str()) {
+            group.files.push(&func.file);
+        }
+        group.functions += 1;
+        group.total += func.complexity as u64;
+        if above(func, threshold, metric) {
+            group.above += 1;
+        }
+        if func.complexity > group.worst.complexity {
+            group.worst = func;
+        }
+        group.severity = group.severity.max(func.severity);
+    }
+    groups.sort_by(|(key_a, a), (key_b, b)| {
+        b.total
+            .cmp(&a.total)
+            .then(b.worst.complexity.cmp(&a.worst.complexity))
+            .then_with(|| key_a.cmp(key_b))
+    });
+    groups
+}
+
+/// Every file with functions, most complex in total first.
+pub fn files(
+    functions: &[FunctionComplexity],
+    threshold: u32,
+    metric: Metric,
+) -> Vec<FileComplexity> {
+    group(functions, threshold, metric, |func| {
+        (func.project.clone(), func.file.clone())
+    })
+    .into_iter()
+    .map(|((project, file), group)| FileComplexity {
+        file,
+        project,
+        functions: group.functions,
+        total_complexity: group.total,
+        mean_complexity: group.mean(),
+        max_complexity: group.worst.complexity,
+        functions_above_threshold: group.above,
+        worst_function: group.worst.name.clone(),
+        worst_function_line: group.worst.line,
+        severity: group.severity,
+    })
+    .collect()
+}
+
+/// The directory of `file` that it is grouped under: the first `depth`
+/// components of its directory relative to `root`.
+fn directory(file: &str, root: &Path, depth: usize) -> String {
+    let relative = relative_path(file, root);
+    let components: Vec<&str> = relative.split('/').collect();
+    let dirs = &components[..components.len() - 1];
+    let dirs = &dirs[..dirs.len().min(depth)];
+    if dirs.is_empty() {
+        ROOT.to_string()
+    } else {
+        dirs.join("/")
+    }
+}
+
+/// Every directory with functions, down to `depth` components under `root`,
+/// most complex in total first.
+pub fn directories(
+    functions: &[FunctionComplexity],
+    root: &Path,
+    depth: usize,
+    threshold: u32,
+    metric: Metric,
+) -> Vec<DirectoryComplexity> {
+    group(functions, threshold, metric, |func| {
+        (func.project.clone(), directory(&func.file, root, depth))
+    })
+    .into_iter()
+    .map(|((project, directory), group)| DirectoryComplexity {
+        directory,
+        project,
+        files: group.files.len(),
+        functions: group.functions,
+        total_complexity: group.total,
+        mean_complexity: group.mean(),
+        max_complexity: group.worst.complexity,
+        functions_above_threshold: group.above,
+        worst_function: group.worst.name.clone(),
+        worst_function_file: group.worst.file.clone(),
+        severity: group.severity,
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::severity::{annotate, Thresholds};
+    use crate::{analyze_directory, AnalysisResult};
+    use tempfile::TempDir;
+
+    /// A function with a cyclomatic complexity of 1 + `branches`.
+    fn function(name: &str, branches: usize) -> String {
+        let mut source = format!("def {}(x):\n", name);
+        for i in 0..branches {
+            source.push_str(&format!("    if x == {}:\n        return {}\n", i, i));
+        }
+        source.push_str("    return x\n");
+        source
+    }
+
+    fn analyzed(files: &[(&str, String)]) -> (TempDir, AnalysisResult) {
+        let temp_dir = TempDir::new().unwrap();
+        for (name, source) in files {
+            let path = temp_dir.path().join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, source).unwrap();
+        }
+        let mut result = analyze_directory(temp_dir.path(), 10).unwrap();
+        annotate(&mut result, |_| Thresholds::new(10, None, None));
+        (temp_dir, result)
+    }
+
+    #[test]
+    fn test_rollups() {
+        let (temp_dir, result) = analyzed(&[
+            ("setup.py", function("setup", 0)),
+            (
+                "shop/models.py",
+                function("save", 2) + &function("clean", 3),
+            ),
+            ("shop/views/cart.py", function("checkout", 11)),
+            (
+                "shop/views/list.py",
+                function("index", 1) + &function("search", 4),
+            ),
+            (
+                "billing/invoice.py",
+                function("total", 5) + &function("tax", 6),
+            ),
+        ]);
+        let root = temp_dir.path();
+
+        let files = files(&result.functions, 10, Metric::Cyclomatic);
+        let rows: Vec<(String, usize, u64, u32, &str)> = files
+            .iter()
+            .map(|f| {
+                let file = relative_path(&f.file, root);
+                (
+                    file,
+                    f.functions,
+                    f.total_complexity,
+                    f.max_complexity,
+                    f.worst_function.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                ("billing/invoice.py".to_string(), 2, 13, 7, "tax"),
+                ("shop/views/cart.py".to_string(), 1, 12, 12, "checkout"),
+                // Tied on total, the file with the most complex function first.
+                ("shop/views/list.py".to_string(), 2, 7, 5, "search"),
+                ("shop/models.py".to_string(), 2, 7, 4, "clean"),
+                ("setup.py".to_string(), 1, 1, 1, "setup"),
+            ]
+        );
+        assert_eq!(files[0].mean_complexity, 6.5);
+        assert_eq!(files[0].severity, None);
+        assert_eq!(files[1].functions_above_threshold, 1);
+        assert_eq!(files[1].severity, Some(Severity::Warning));
+
+        let rows = |depth| {
+            directories(&result.functions, root, depth, 10, Metric::Cyclomatic)
+                .into_iter()
+                .map(|d| {
+                    (
+                        d.directory,
+                        d.files,
+                        d.functions,
+                        d.total_complexity,
+                        d.max_complexity,
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            rows(1),
+            [
+                ("shop".to_string(), 3, 5, 26, 12),
+                ("billing".to_string(), 1, 2, 13, 7),
+                (".".to_string(), 1, 1, 1, 1),
+            ]
+        );
+        assert_eq!(
+            rows(2),
+            [
+                ("shop/views".to_string(), 2, 3, 19, 12),
+                ("billing".to_string(), 1, 2, 13, 7),
+                ("shop".to_string(), 1, 2, 7, 4),
+                (".".to_string(), 1, 1, 1, 1),
+            ]
+        );
+        let shop = &directories(&result.functions, root, 1, 10, Metric::Cyclomatic)[0];
+        assert_eq!(shop.mean_complexity, 5.2);
+        assert_eq!(shop.worst_function, "checkout");
+        assert_eq!(
+            relative_path(&shop.worst_function_file, root),
+            "shop/views/cart.py"
+        );
+        assert_eq!(shop.severity, Some(Severity::Warning));
+    }
+}
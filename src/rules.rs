@@ -0,0 +1,185 @@
+//! Thresholds for the functions under given paths, set with `[[rules]]` in
+//! `analyzr.toml`, e.g. strict for new services and lenient for a legacy
+//! monolith.
+
+use crate::generated::wildcard;
+use crate::severity::Severity;
+use crate::{AnalysisResult, FunctionComplexity};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rule {
+    /// Globs of the paths the rule applies to.
+    pub paths: Vec<String>,
+    /// Complexity above which a function is reported.
+    pub max_cyclomatic: u32,
+    /// Severity a function above `max_cyclomatic` is reported with.
+    pub severity: Severity,
+}
+
+/// The rules of a configuration file, whose paths are relative to the
+/// directory of the file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rules {
+    pub base: PathBuf,
+    pub rules: Vec<Rule>,
+}
+
+/// The rule that decided a function's severity: its position among the
+/// rules of the file, and which of its globs matched.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AppliedRule {
+    pub index: usize,
+    pub path: String,
+}
+
+/// Whether `path` (relative, with `/` separators) matches `pattern`, where
+/// `**` stands for any number of directories and `*` and `?` match within
+/// one component.
+pub fn glob(pattern: &str, path: &str) -> bool {
+    fn matches(pattern: &[&str], path: &[&str]) -> bool {
+        match pattern.split_first() {
+            None => path.is_empty(),
+            Some((&"**", rest)) => (0..=path.len()).any(|skip| matches(rest, &path[skip..])),
+            Some((part, rest)) => path.split_first().is_some_and(|(component, path)| {
+                wildcard(part, component) && matches(rest, path)
+            }),
+        }
+    }
+    let pattern = pattern.trim_start_matches("./");
+    let pattern: Vec<&str> = pattern.split('/').filter(|part| !part.is_empty()).collect();
+    let path: Vec<&str> = path.split('/').collect();
+    matches(&pattern, &path)
+}
+
+impl Rules {
+    /// The rule for `relative`: the last one with a matching glob.
+    pub fn find(&self, relative: &str) -> Option<(AppliedRule, &Rule)> {
+        self.rules.iter().enumerate().rev().find_map(|(index, rule)| {
+            let path = rule.paths.iter().find(|path| glob(path, relative))?;
+            let applied = AppliedRule {
+                index,
+                path: path.clone(),
+            };
+            Some((applied, rule))
+        })
+    }
+
+    /// Sets the severity of every function a rule applies to from that rule
+    /// instead of the thresholds. `relative` gives a function's path relative
+    /// to [`Rules::base`].
+    pub fn apply(
+        &self,
+        result: &mut AnalysisResult,
+        mut relative: impl FnMut(&FunctionComplexity) -> Option<String>,
+    ) {
+        for func in &mut result.functions {
+            let Some((applied, rule)) = relative(func).and_then(|path| self.find(&path)) else {
+                continue;
+            };
+            func.severity = (!func.dunder && func.complexity > rule.max_cyclomatic)
+                .then_some(rule.severity);
+            func.rule = Some(applied);
+        }
+    }
+
+    /// Globs that match none of `files`, most likely typos, with the index
+    /// of their rule.
+    pub fn unmatched<'a>(&'a self, files: &[String]) -> Vec<(usize, &'a str)> {
+        let mut unmatched = Vec::new();
+        for (index, rule) in self.rules.iter().enumerate() {
+            for path in &rule.paths {
+                if !files.iter().any(|file| glob(path, file)) {
+                    unmatched.push((index, path.as_str()));
+                }
+            }
+        }
+        unmatched
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(paths: &[&str], max_cyclomatic: u32, severity: Severity) -> Rule {
+        Rule {
+            paths: paths.iter().map(|p| p.to_string()).collect(),
+            max_cyclomatic,
+            severity,
+        }
+    }
+
+    #[test]
+    fn test_glob() {
+        assert!(glob("services/payments/**", "services/payments/api.py"));
+        assert!(glob("services/payments/**", "services/payments/v2/api.py"));
+        assert!(!glob("services/payments/**", "services/billing/api.py"));
+        assert!(glob("**/test_*.py", "test_app.py"));
+        assert!(glob("**/test_*.py", "pkg/tests/test_app.py"));
+        assert!(glob("./legacy/*.py", "legacy/app.py"));
+        assert!(!glob("legacy/*.py", "legacy/sub/app.py"));
+        assert!(glob("legacy/**/*.py", "legacy/app.py"));
+        assert!(!glob("legacy", "legacy/app.py"));
+    }
+
+    #[test]
+    fn test_last_matching_rule_wins() {
+        let rules = Rules {
+            base: PathBuf::new(),
+            rules: vec![
+                rule(&["**"], 10, Severity::Warning),
+                rule(&["services/payments/**"], 8, Severity::Error),
+                rule(&["legacy/**", "services/payments/legacy/**"], 30, Severity::Warning),
+            ],
+        };
+        let function = |file: &str, complexity| FunctionComplexity {
+            file: file.to_string(),
+            complexity,
+            ..Default::default()
+        };
+        let mut result = AnalysisResult {
+            functions: vec![
+                function("services/payments/api.py", 9),
+                function("services/payments/legacy/old.py", 25),
+                function("legacy/old.py", 31),
+                function("app.py", 9),
+            ],
+            ..Default::default()
+        };
+
+        rules.apply(&mut result, |func| Some(func.file.clone()));
+
+        let applied: Vec<(Option<Severity>, usize, &str)> = result
+            .functions
+            .iter()
+            .map(|f| {
+                let rule = f.rule.as_ref().unwrap();
+                (f.severity, rule.index, rule.path.as_str())
+            })
+            .collect();
+        assert_eq!(
+            applied,
+            [
+                (Some(Severity::Error), 1, "services/payments/**"),
+                (None, 2, "services/payments/legacy/**"),
+                (Some(Severity::Warning), 2, "legacy/**"),
+                (None, 0, "**"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unmatched() {
+        let rules = Rules {
+            base: PathBuf::new(),
+            rules: vec![
+                rule(&["services/**"], 8, Severity::Error),
+                rule(&["legacy/**", "lgacy/**"], 30, Severity::Warning),
+            ],
+        };
+        let files = ["services/api.py".to_string(), "legacy/old.py".to_string()];
+        assert_eq!(rules.unmatched(&files), [(1, "lgacy/**")]);
+    }
+}
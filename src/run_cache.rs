@@ -0,0 +1,166 @@
+//! The result of the previous run, replayed when nothing it depends on has
+//! changed since.
+
+use crate::meta::{git, git_info};
+use crate::walk::Filter;
+use crate::{discover, AnalysisResult};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+const FILE_NAME: &str = "last-run.json";
+
+/// 128-bit FNV-1a, wide enough that distinct inputs never share a digest in
+/// practice.
+struct Digest(u128);
+
+impl Digest {
+    const OFFSET_BASIS: u128 = 0x6c62_272e_07bb_0142_62b8_2175_6295_c58d;
+    const PRIME: u128 = 0x0000_0000_0100_0000_0000_0000_0000_013b;
+
+    fn new() -> Self {
+        Digest(Self::OFFSET_BASIS)
+    }
+
+    /// Adds `bytes` prefixed with their length, so that consecutive fields
+    /// cannot run into each other.
+    fn field(&mut self, bytes: &[u8]) {
+        for &byte in (bytes.len() as u64).to_le_bytes().iter().chain(bytes) {
+            self.0 ^= byte as u128;
+            self.0 = self.0.wrapping_mul(Self::PRIME);
+        }
+    }
+}
+
+/// Digest of everything a run's result depends on: the tool version, the
+/// effective `settings`, the git commit of the first root, and the path and
+/// content of every source file under `roots` that `filter` keeps.
+pub fn digest(roots: &[PathBuf], filter: &Filter, settings: &str) -> String {
+    let mut digest = Digest::new();
+    digest.field(env!("CARGO_PKG_VERSION").as_bytes());
+    digest.field(settings.as_bytes());
+    let git = roots.first().and_then(|root| git_info(root));
+    digest.field(format!("{:?}", git).as_bytes());
+    for root in roots {
+        digest.field(root.to_string_lossy().as_bytes());
+        let mut files: Vec<PathBuf> = discover(root, None, None, filter)
+            .filter_map(Result::ok)
+            .collect();
+        files.sort();
+        for file in files {
+            digest.field(file.to_string_lossy().as_bytes());
+            match std::fs::read(&file) {
+                Ok(content) => digest.field(&content),
+                // The same error gives the same result.
+                Err(error) => digest.field(error.kind().to_string().as_bytes()),
+            }
+        }
+    }
+    format!("{:032x}", digest.0)
+}
+
+#[derive(Serialize, Deserialize)]
+struct Stored {
+    digest: String,
+    result: AnalysisResult,
+}
+
+pub struct RunCache {
+    file: PathBuf,
+}
+
+impl RunCache {
+    /// Keeps the previous run in `dir`, by default in the git directory of
+    /// `root` or, outside a repository, in the system's temporary directory.
+    pub fn new(dir: Option<PathBuf>, root: &Path) -> Self {
+        let dir = dir.unwrap_or_else(|| {
+            let root = if root.is_dir() { root } else { root.parent().unwrap_or(root) };
+            git(root, &["rev-parse", "--path-format=absolute", "--git-common-dir"])
+                .map(PathBuf::from)
+                .unwrap_or_else(std::env::temp_dir)
+                .join("analyzr-runs")
+        });
+        RunCache {
+            file: dir.join(FILE_NAME),
+        }
+    }
+
+    /// The previous run's result, if it was stored under `digest`.
+    pub fn load(&self, digest: &str) -> Option<AnalysisResult> {
+        let json = std::fs::read(&self.file).ok()?;
+        let stored: Stored = serde_json::from_slice(&json).ok()?;
+        (stored.digest == digest).then_some(stored.result)
+    }
+
+    /// Replaces the stored run. Failing to store only costs the next run
+    /// its shortcut.
+    pub fn store(&self, digest: &str, result: &AnalysisResult) {
+        let stored = Stored {
+            digest: digest.to_string(),
+            result: result.clone(),
+        };
+        let _ = std::fs::create_dir_all(self.file.parent().unwrap())
+            .and_then(|_| std::fs::write(&self.file, serde_json::to_vec(&stored)?));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_digest_changes_with_any_input() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().to_path_buf();
+        let roots = [root.clone()];
+        std::fs::create_dir(root.join("pkg")).unwrap();
+        std::fs::write(root.join("pkg/a.py"), "def f():\n    pass\n").unwrap();
+        std::fs::write(root.join("b.py"), "def g():\n    pass\n").unwrap();
+        let base = digest(&roots, &Filter::default(), "threshold=10");
+        assert_eq!(digest(&roots, &Filter::default(), "threshold=10"), base);
+
+        assert_ne!(digest(&roots, &Filter::default(), "threshold=11"), base);
+
+        std::fs::write(root.join("b.py"), "def g():\n    pass \n").unwrap();
+        let touched = digest(&roots, &Filter::default(), "threshold=10");
+        assert_ne!(touched, base);
+        std::fs::write(root.join("b.py"), "def g():\n    pass\n").unwrap();
+        assert_eq!(digest(&roots, &Filter::default(), "threshold=10"), base);
+
+        std::fs::rename(root.join("b.py"), root.join("c.py")).unwrap();
+        assert_ne!(digest(&roots, &Filter::default(), "threshold=10"), base);
+        std::fs::rename(root.join("c.py"), root.join("b.py")).unwrap();
+
+        std::fs::write(root.join("pkg/new.py"), "").unwrap();
+        assert_ne!(digest(&roots, &Filter::default(), "threshold=10"), base);
+        std::fs::remove_file(root.join("pkg/new.py")).unwrap();
+
+        // Files that are not analyzed do not matter.
+        std::fs::write(root.join("notes.txt"), "x").unwrap();
+        assert_eq!(digest(&roots, &Filter::default(), "threshold=10"), base);
+    }
+
+    #[test]
+    fn test_fields_do_not_run_into_each_other() {
+        let mut a = Digest::new();
+        a.field(b"ab");
+        a.field(b"c");
+        let mut b = Digest::new();
+        b.field(b"a");
+        b.field(b"bc");
+        assert_ne!(a.0, b.0);
+    }
+
+    #[test]
+    fn test_only_the_previous_run_is_kept() {
+        let temp_dir = TempDir::new().unwrap();
+        let cache = RunCache::new(Some(temp_dir.path().to_path_buf()), temp_dir.path());
+        let result = AnalysisResult::default();
+        assert!(cache.load("one").is_none());
+        cache.store("one", &result);
+        assert_eq!(cache.load("one"), Some(result.clone()));
+        cache.store("two", &result);
+        assert!(cache.load("one").is_none());
+        assert!(cache.load("two").is_some());
+    }
+}
@@ -0,0 +1,201 @@
+//! How bad a function's complexity is, decided once for every output format.
+
+use crate::{AnalysisResult, FunctionComplexity};
+use serde::{Deserialize, Serialize};
+
+/// The complexity the thresholds apply to, and that tables show.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Metric {
+    #[default]
+    Cyclomatic,
+    Cognitive,
+    /// Both, a function exceeding a threshold when either does.
+    Both,
+}
+
+impl Metric {
+    pub fn name(self) -> &'static str {
+        match self {
+            Metric::Cyclomatic => "cyclomatic",
+            Metric::Cognitive => "cognitive",
+            Metric::Both => "both",
+        }
+    }
+
+    /// The complexity of `func` compared with the thresholds: the larger of
+    /// the two for [`Metric::Both`].
+    pub fn value(self, func: &FunctionComplexity) -> u32 {
+        match self {
+            Metric::Cyclomatic => func.complexity,
+            Metric::Cognitive => func.cognitive_complexity,
+            Metric::Both => func.complexity.max(func.cognitive_complexity),
+        }
+    }
+}
+
+/// Thresholds used to classify a function's complexity. A function is
+/// reported once its complexity exceeds a band's threshold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Thresholds {
+    /// Threshold of the optional band below `warn`.
+    pub info: Option<u32>,
+    pub warn: u32,
+    pub error: u32,
+    pub metric: Metric,
+}
+
+impl Thresholds {
+    /// The error threshold defaults to twice the warn threshold, and the
+    /// thresholds apply to the cyclomatic complexity.
+    pub fn new(warn: u32, error: Option<u32>, info: Option<u32>) -> Self {
+        Thresholds {
+            info,
+            warn,
+            error: error.unwrap_or_else(|| warn.saturating_mul(2)),
+            metric: Metric::Cyclomatic,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+/// Severity of a function with the given complexity, or `None` when it does
+/// not exceed any threshold.
+pub fn severity(complexity: u32, thresholds: &Thresholds) -> Option<Severity> {
+    if complexity > thresholds.error {
+        Some(Severity::Error)
+    } else if complexity > thresholds.warn {
+        Some(Severity::Warning)
+    } else if thresholds.info.is_some_and(|info| complexity > info) {
+        Some(Severity::Info)
+    } else {
+        None
+    }
+}
+
+/// Sets the severity of every function from the complexity the metric of
+/// the thresholds returned for its project applies to. Dunder methods set
+/// apart with `--dunder separate` are never reported.
+pub fn annotate(result: &mut AnalysisResult, thresholds: impl Fn(Option<&str>) -> Thresholds) {
+    for func in &mut result.functions {
+        func.severity = if func.dunder {
+            None
+        } else {
+            let thresholds = thresholds(func.project.as_deref());
+            severity(thresholds.metric.value(func), &thresholds)
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FunctionComplexity;
+
+    #[test]
+    fn test_severity() {
+        let thresholds = Thresholds::new(10, Some(20), None);
+        assert_eq!(severity(0, &thresholds), None);
+        assert_eq!(severity(10, &thresholds), None);
+        assert_eq!(severity(11, &thresholds), Some(Severity::Warning));
+        assert_eq!(severity(20, &thresholds), Some(Severity::Warning));
+        assert_eq!(severity(21, &thresholds), Some(Severity::Error));
+        assert_eq!(severity(u32::MAX, &thresholds), Some(Severity::Error));
+    }
+
+    #[test]
+    fn test_info_band() {
+        let thresholds = Thresholds::new(10, Some(20), Some(5));
+        assert_eq!(severity(5, &thresholds), None);
+        assert_eq!(severity(6, &thresholds), Some(Severity::Info));
+        assert_eq!(severity(10, &thresholds), Some(Severity::Info));
+        assert_eq!(severity(11, &thresholds), Some(Severity::Warning));
+        assert_eq!(severity(21, &thresholds), Some(Severity::Error));
+
+        // An info threshold at or above the warn threshold never applies.
+        let thresholds = Thresholds::new(10, Some(20), Some(15));
+        assert_eq!(severity(12, &thresholds), Some(Severity::Warning));
+    }
+
+    #[test]
+    fn test_degenerate_bands() {
+        // With equal warn and error thresholds there is no warning band.
+        let thresholds = Thresholds::new(10, Some(10), None);
+        assert_eq!(severity(10, &thresholds), None);
+        assert_eq!(severity(11, &thresholds), Some(Severity::Error));
+
+        // An error threshold below the warn threshold takes precedence.
+        let thresholds = Thresholds::new(10, Some(5), None);
+        assert_eq!(severity(6, &thresholds), Some(Severity::Error));
+
+        let thresholds = Thresholds::new(0, None, None);
+        assert_eq!(severity(0, &thresholds), None);
+        assert_eq!(severity(1, &thresholds), Some(Severity::Error));
+    }
+
+    #[test]
+    fn test_default_error_threshold() {
+        assert_eq!(Thresholds::new(10, None, None).error, 20);
+        assert_eq!(Thresholds::new(u32::MAX, None, None).error, u32::MAX);
+    }
+
+    #[test]
+    fn test_severity_order_and_serialization() {
+        assert!(Severity::Info < Severity::Warning && Severity::Warning < Severity::Error);
+        let json = serde_json::to_string(&[Severity::Info, Severity::Warning, Severity::Error]);
+        assert_eq!(json.unwrap(), r#"["info","warning","error"]"#);
+    }
+
+    #[test]
+    fn test_annotate_uses_project_thresholds() {
+        let function = |project: &str, complexity| FunctionComplexity {
+            complexity,
+            project: Some(project.to_string()),
+            ..Default::default()
+        };
+        let mut result = AnalysisResult {
+            functions: vec![function("api", 8), function("web", 8)],
+            ..Default::default()
+        };
+
+        annotate(&mut result, |project| match project {
+            Some("api") => Thresholds::new(5, None, None),
+            _ => Thresholds::new(10, None, Some(5)),
+        });
+
+        assert_eq!(result.functions[0].severity, Some(Severity::Warning));
+        assert_eq!(result.functions[1].severity, Some(Severity::Info));
+    }
+
+    #[test]
+    fn test_metric() {
+        let flat = FunctionComplexity {
+            complexity: 12,
+            cognitive_complexity: 3,
+            ..Default::default()
+        };
+        let nested = FunctionComplexity {
+            complexity: 4,
+            cognitive_complexity: 12,
+            ..Default::default()
+        };
+        let severities = |metric| {
+            let thresholds = Thresholds {
+                metric,
+                ..Thresholds::new(10, None, None)
+            };
+            [&flat, &nested].map(|func| severity(metric.value(func), &thresholds))
+        };
+        let warning = Some(Severity::Warning);
+        assert_eq!(severities(Metric::Cyclomatic), [warning, None]);
+        assert_eq!(severities(Metric::Cognitive), [None, warning]);
+        assert_eq!(severities(Metric::Both), [warning, warning]);
+    }
+}
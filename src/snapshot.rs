@@ -0,0 +1,305 @@
+//! Snapshots of a run's canonical outputs, checked in so that any change of
+//! complexity shows up as a diff in review, like snapshot tests but for a
+//! whole tree.
+//!
+//! A snapshot is a directory holding the result as compact JSON without run
+//! metadata, and the text summary. Both are normalized to be identical
+//! across machines: paths are relative to the analyzed root with `/`
+//! separators, every list is in a fixed order, and floats are rounded.
+
+use crate::output::relative_path;
+use crate::AnalysisResult;
+use anyhow::{bail, Context, Result};
+use std::path::Path;
+
+const REPORT: &str = "report.json";
+const SUMMARY: &str = "summary.txt";
+
+/// Decimal places floats are rounded to, well within what every platform
+/// computes identically.
+const FLOAT_DECIMALS: i32 = 6;
+
+/// `result` made independent of the machine and of ordering flags.
+pub fn canonical(result: &AnalysisResult, root: &Path) -> AnalysisResult {
+    let mut result = result.clone();
+    let path = |file: &mut String| *file = relative_path(file, root).replace('\\', "/");
+    for func in result.functions.iter_mut().chain(&mut result.removed) {
+        path(&mut func.file);
+    }
+    let order = |a: &crate::FunctionComplexity, b: &crate::FunctionComplexity| {
+        (&a.project, &a.file, a.line, &a.name).cmp(&(&b.project, &b.file, b.line, &b.name))
+    };
+    result.functions.sort_by(order);
+    result.removed.sort_by(order);
+    for file in &mut result.generated_files {
+        path(&mut file.file);
+    }
+    result
+        .generated_files
+        .sort_by(|a, b| (&a.project, &a.file).cmp(&(&b.project, &b.file)));
+    for file in &mut result.files {
+        path(&mut file.file);
+    }
+    for dir in &mut result.directories {
+        path(&mut dir.worst_function_file);
+    }
+    for class in &mut result.classes {
+        path(&mut class.file);
+    }
+    result
+        .classes
+        .sort_by(|a, b| (&a.project, &a.file, a.line).cmp(&(&b.project, &b.file, b.line)));
+    for issue in &mut result.errors {
+        path(&mut issue.path);
+    }
+    result.errors.sort_by(|a, b| a.path.cmp(&b.path));
+    if let Some(summary) = &mut result.summary {
+        for file in &mut summary.worst_files {
+            path(&mut file.file);
+        }
+        for func in &mut summary.top_risk {
+            path(&mut func.file);
+        }
+    }
+    result.meta = None;
+    result
+}
+
+/// The files of the snapshot of `result`, with their names.
+fn render(
+    result: &AnalysisResult,
+    threshold: u32,
+    risk_threshold: Option<f64>,
+) -> Result<[(&'static str, String); 2]> {
+    let mut report = serde_json::to_value(result)?;
+    round_floats(&mut report);
+    let summary = crate::output::table::summary_text(result, threshold, risk_threshold);
+    Ok([
+        (REPORT, format!("{}\n", serde_json::to_string(&report)?)),
+        (SUMMARY, summary.trim_start().to_string()),
+    ])
+}
+
+fn round_floats(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Number(number) if !number.is_i64() && !number.is_u64() => {
+            let scale = 10f64.powi(FLOAT_DECIMALS);
+            let rounded = (number.as_f64().unwrap_or_default() * scale).round() / scale;
+            if let Some(rounded) = serde_json::Number::from_f64(rounded) {
+                *number = rounded;
+            }
+        }
+        serde_json::Value::Array(values) => values.iter_mut().for_each(round_floats),
+        serde_json::Value::Object(map) => map.values_mut().for_each(round_floats),
+        _ => {}
+    }
+}
+
+/// Writes the snapshot of the canonical `result` to `dir`.
+pub fn accept(
+    dir: &Path,
+    result: &AnalysisResult,
+    threshold: u32,
+    risk_threshold: Option<f64>,
+) -> Result<()> {
+    std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    for (name, content) in render(result, threshold, risk_threshold)? {
+        let path = dir.join(name);
+        std::fs::write(&path, content)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+    }
+    Ok(())
+}
+
+/// Compares the canonical `result` with the snapshot in `dir`, failing with
+/// a diff of every file that differs.
+pub fn check(
+    dir: &Path,
+    result: &AnalysisResult,
+    threshold: u32,
+    risk_threshold: Option<f64>,
+) -> Result<()> {
+    let mut diffs = Vec::new();
+    for (name, actual) in render(result, threshold, risk_threshold)? {
+        let path = dir.join(name);
+        let expected = std::fs::read_to_string(&path).with_context(|| {
+            format!(
+                "Failed to read {} (write it with --accept-snapshot)",
+                path.display()
+            )
+        })?;
+        // Line endings may have been converted by git on checkout.
+        if expected.replace("\r\n", "\n") == actual {
+            continue;
+        }
+        let (expected, actual) = if name == REPORT {
+            (pretty(&expected), pretty(&actual))
+        } else {
+            (expected, actual)
+        };
+        diffs.push(format!(
+            "--- {} (snapshot)\n+++ current run\n{}",
+            path.display(),
+            diff(&expected, &actual)
+        ));
+    }
+    if !diffs.is_empty() {
+        bail!(
+            "the current run differs from the snapshot in {} (accept the changes with --accept-snapshot):\n{}",
+            dir.display(),
+            diffs.join("\n")
+        );
+    }
+    Ok(())
+}
+
+/// Compact JSON spread over lines so that a diff points at what changed.
+fn pretty(json: &str) -> String {
+    serde_json::from_str::<serde_json::Value>(json)
+        .and_then(|value| serde_json::to_string_pretty(&value))
+        .unwrap_or_else(|_| json.to_string())
+}
+
+/// Lines shown around a change.
+const CONTEXT: usize = 2;
+
+/// Above this many line pairs, the changed lines are shown as removed and
+/// added as a whole rather than aligned.
+const ALIGN_LIMIT: usize = 4_000_000;
+
+/// A line diff of `expected` and `actual`, with `-` before removed lines, `+`
+/// before added ones and a few unchanged lines around them.
+fn diff(expected: &str, actual: &str) -> String {
+    let old: Vec<&str> = expected.lines().collect();
+    let new: Vec<&str> = actual.lines().collect();
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_changed = &old[prefix..old.len() - suffix];
+    let new_changed = &new[prefix..new.len() - suffix];
+
+    let mut out = format!("@@ line {} @@\n", prefix.saturating_sub(CONTEXT) + 1);
+    for line in &old[prefix.saturating_sub(CONTEXT)..prefix] {
+        out.push_str(&format!(" {}\n", line));
+    }
+    if old_changed.len() * new_changed.len() <= ALIGN_LIMIT {
+        for (sign, line) in align(old_changed, new_changed) {
+            out.push_str(&format!("{}{}\n", sign, line));
+        }
+    } else {
+        for line in old_changed {
+            out.push_str(&format!("-{}\n", line));
+        }
+        for line in new_changed {
+            out.push_str(&format!("+{}\n", line));
+        }
+    }
+    let after = old.len() - suffix;
+    for line in &old[after..(after + CONTEXT).min(old.len())] {
+        out.push_str(&format!(" {}\n", line));
+    }
+    out
+}
+
+/// `old` and `new` aligned on their longest common subsequence.
+fn align<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<(char, &'a str)> {
+    // lengths[i][j]: length of the longest common subsequence of old[i..]
+    // and new[j..].
+    let mut lengths = vec![vec![0u32; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i][j] = if old[i] == new[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut lines = Vec::new();
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            lines.push((' ', old[i]));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || lengths[i + 1][j] >= lengths[i][j + 1]) {
+            lines.push(('-', old[i]));
+            i += 1;
+        } else {
+            lines.push(('+', new[j]));
+            j += 1;
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_diff() {
+        let expected = "a\nb\nc\nd\ne\nf\ng\n";
+        let actual = "a\nb\nc\nD\ne\nx\nf\ng\n";
+        assert_eq!(
+            diff(expected, actual),
+            "@@ line 2 @@\n b\n c\n-d\n+D\n e\n+x\n f\n g\n"
+        );
+    }
+
+    #[test]
+    fn test_round_floats() {
+        let mut value = serde_json::json!({"mean": 3.3333333333333335, "n": [1, 0.1234567891]});
+        round_floats(&mut value);
+        assert_eq!(value.to_string(), r#"{"mean":3.333333,"n":[1,0.123457]}"#);
+    }
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("src");
+        std::fs::create_dir_all(root.join("pkg")).unwrap();
+        std::fs::write(
+            root.join("pkg/b.py"),
+            "def b(x):\n    if x:\n        return 1\n",
+        )
+        .unwrap();
+        std::fs::write(root.join("a.py"), "def a():\n    pass\n").unwrap();
+        let snapshot = temp_dir.path().join("snapshot");
+
+        let mut result = crate::analyze_directory(&root, 10).unwrap();
+        // Ordering flags do not change the snapshot.
+        result.functions.reverse();
+        let result = canonical(&result, &root);
+        accept(&snapshot, &result, 10, None).unwrap();
+        let report = std::fs::read_to_string(snapshot.join(REPORT)).unwrap();
+        // Keys are sorted, and functions ordered by file and line.
+        assert!(report.starts_with(r#"{"errors":[],"functions":[{"body_hash":"#));
+        assert!(report.find(r#""file":"a.py""#) < report.find(r#""file":"pkg/b.py""#));
+        assert!(report.contains(r#""file":"pkg/b.py""#));
+        assert!(!report.contains("meta"));
+        check(&snapshot, &result, 10, None).unwrap();
+
+        std::fs::write(
+            root.join("pkg/b.py"),
+            "def b(x):\n    if x:\n        return 1\n    if x > 1:\n        return 2\n",
+        )
+        .unwrap();
+        let changed = canonical(&crate::analyze_directory(&root, 10).unwrap(), &root);
+        let error = check(&snapshot, &changed, 10, None)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("differs from the snapshot"));
+        assert!(error.contains(
+            "-      \"complexity\": 2,\n-      \"end_line\": 3,\n+      \"complexity\": 3,"
+        ));
+        assert!(
+            error.contains("\n-Max Complexity: 2\n") && error.contains("\n+Max Complexity: 3\n")
+        );
+    }
+}
@@ -0,0 +1,488 @@
+//! Refactoring priorities: ranks functions above the threshold and points at
+//! the block most worth extracting from each of them.
+
+use crate::depth;
+use crate::language::own_name;
+use crate::output::text::{truncate_end, truncate_start};
+use crate::output::table::{NAME_WIDTH, PATH_WIDTH};
+use crate::{python_files, AnalysisResult, FunctionComplexity};
+use anyhow::{Context, Result};
+use comfy_table::{Cell, Table};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use tree_sitter::{Node, Parser as TSParser, Query, QueryCursor, Tree};
+
+/// Blocks shorter than this are not worth extracting.
+const MIN_BLOCK_LINES: u32 = 3;
+/// Blocks needing more parameters than this are not self-contained enough.
+const MAX_PARAMETERS: usize = 5;
+
+const BLOCK_KINDS: &[&str] = &[
+    "if_statement",
+    "for_statement",
+    "while_statement",
+    "try_statement",
+    "with_statement",
+];
+
+#[derive(Debug, Serialize)]
+pub struct Suggestion {
+    pub name: String,
+    pub file: String,
+    pub line: u32,
+    pub end_line: u32,
+    pub complexity: u32,
+    pub lines: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fan_in: Option<u32>,
+    pub priority: u64,
+    pub extract: Option<ExtractCandidate>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ExtractCandidate {
+    /// Statement that starts the block (`for`, `if`, ...).
+    pub kind: String,
+    pub start_line: u32,
+    pub end_line: u32,
+    /// Variables the block reads before assigning them, which would have to
+    /// be passed to the extracted function.
+    pub parameters: Vec<String>,
+}
+
+/// Ranks the functions of `result` above `threshold` by complexity × size,
+/// multiplied by the number of call sites under `root` when `fan_in` is set.
+pub fn suggest(
+    result: &AnalysisResult,
+    root: &Path,
+    threshold: u32,
+    fan_in: bool,
+) -> Result<Vec<Suggestion>> {
+    let mut parser = TSParser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+
+    let call_counts = if fan_in {
+        Some(count_calls(&mut parser, root)?)
+    } else {
+        None
+    };
+
+    let mut by_file: HashMap<&str, Vec<&FunctionComplexity>> = HashMap::new();
+    for func in result.functions.iter().filter(|f| f.complexity > threshold) {
+        by_file.entry(func.file.as_str()).or_default().push(func);
+    }
+
+    let mut suggestions = Vec::new();
+    for (file, functions) in by_file {
+        let source =
+            std::fs::read_to_string(file).with_context(|| format!("Failed to read {}", file))?;
+        let tree = parse(&mut parser, &source)?;
+        // Finding an extract walks the tree recursively.
+        let shallow = depth::check(tree.root_node(), depth::DEFAULT_MAX_DEPTH).is_ok();
+
+        for func in functions {
+            let lines = func.end_line.saturating_sub(func.line) + 1;
+            let fan_in = call_counts
+                .as_ref()
+                .map(|counts| counts.get(own_name(&func.name)).copied().unwrap_or(0));
+            let priority =
+                func.complexity as u64 * lines as u64 * fan_in.unwrap_or(1).max(1) as u64;
+            let extract = shallow
+                .then(|| find_function(tree.root_node(), func.line))
+                .flatten()
+                .and_then(|node| extract_candidate(node, &source));
+
+            suggestions.push(Suggestion {
+                name: func.name.clone(),
+                file: func.file.clone(),
+                line: func.line,
+                end_line: func.end_line,
+                complexity: func.complexity,
+                lines,
+                fan_in,
+                priority,
+                extract,
+            });
+        }
+    }
+
+    suggestions.sort_by(|a, b| {
+        b.priority
+            .cmp(&a.priority)
+            .then_with(|| a.file.cmp(&b.file))
+            .then(a.line.cmp(&b.line))
+    });
+    Ok(suggestions)
+}
+
+fn parse(parser: &mut TSParser, source: &str) -> Result<Tree> {
+    parser
+        .parse(source, None)
+        .context("Failed to parse Python code")
+}
+
+/// Number of call sites of each name, counting `f(...)` and `obj.f(...)`.
+fn count_calls(parser: &mut TSParser, root: &Path) -> Result<HashMap<String, u32>> {
+    let query = Query::new(tree_sitter_python::language(), crate::grammar::CALLS_QUERY)?;
+
+    let mut counts = HashMap::new();
+    for path in python_files(root) {
+        let Ok(source) = std::fs::read_to_string(&path) else {
+            continue;
+        };
+        let tree = parse(parser, &source)?;
+        let mut cursor = QueryCursor::new();
+        for m in cursor.matches(&query, tree.root_node(), source.as_bytes()) {
+            let callee = m.captures[0].node.utf8_text(source.as_bytes())?;
+            *counts.entry(callee.to_string()).or_insert(0) += 1;
+        }
+    }
+    Ok(counts)
+}
+
+/// The function definition starting on the 1-based `line`.
+fn find_function(node: Node, line: u32) -> Option<Node> {
+    if node.kind() == "function_definition" && node.start_position().row as u32 + 1 == line {
+        return Some(node);
+    }
+    let row = line.saturating_sub(1) as usize;
+    let mut cursor = node.walk();
+    let found = node
+        .children(&mut cursor)
+        .filter(|child| child.start_position().row <= row && child.end_position().row >= row)
+        .find_map(|child| find_function(child, line));
+    found
+}
+
+/// The longest compound statement of `function` that can be moved into a
+/// function of its own: it does not leave the function early and needs at
+/// most [`MAX_PARAMETERS`] of the function's variables.
+fn extract_candidate(function: Node, source: &str) -> Option<ExtractCandidate> {
+    let body = function.child_by_field_name("body")?;
+    let mut bound = Scope::default();
+    if let Some(parameters) = function.child_by_field_name("parameters") {
+        bind_parameters(parameters, source, &mut bound);
+    }
+    walk(body, source, &mut bound);
+
+    let mut blocks = Vec::new();
+    collect_blocks(body, &mut blocks);
+
+    blocks
+        .into_iter()
+        .filter(|block| lines(*block) >= MIN_BLOCK_LINES && !escapes(*block, false))
+        .filter_map(|block| {
+            let mut scope = Scope::default();
+            walk(block, source, &mut scope);
+            let parameters: Vec<String> = scope
+                .reads_before_write
+                .into_iter()
+                .filter(|name| bound.written.contains(name))
+                .collect();
+            (parameters.len() <= MAX_PARAMETERS).then(|| ExtractCandidate {
+                kind: block.kind().trim_end_matches("_statement").to_string(),
+                start_line: block.start_position().row as u32 + 1,
+                end_line: block.end_position().row as u32 + 1,
+                parameters,
+            })
+        })
+        .max_by(|a, b| {
+            (a.end_line - a.start_line)
+                .cmp(&(b.end_line - b.start_line))
+                .then(b.parameters.len().cmp(&a.parameters.len()))
+                .then(b.start_line.cmp(&a.start_line))
+        })
+}
+
+fn lines(node: Node) -> u32 {
+    (node.end_position().row - node.start_position().row) as u32 + 1
+}
+
+/// Compound statements nested anywhere in `node`, excluding those inside
+/// nested functions and classes.
+fn collect_blocks<'a>(node: Node<'a>, blocks: &mut Vec<Node<'a>>) {
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        match child.kind() {
+            "function_definition" | "class_definition" => {}
+            kind => {
+                if BLOCK_KINDS.contains(&kind) {
+                    blocks.push(child);
+                }
+                collect_blocks(child, blocks);
+            }
+        }
+    }
+}
+
+/// Whether `node` contains a `return` or `yield`, or a `break`/`continue`
+/// that does not belong to a loop inside it.
+fn escapes(node: Node, in_loop: bool) -> bool {
+    match node.kind() {
+        "return_statement" | "yield" => return true,
+        "break_statement" | "continue_statement" => return !in_loop,
+        "function_definition" | "class_definition" | "lambda" => return false,
+        _ => {}
+    }
+    let in_loop = in_loop || matches!(node.kind(), "for_statement" | "while_statement");
+    let mut cursor = node.walk();
+    let escaped = node
+        .named_children(&mut cursor)
+        .any(|child| escapes(child, in_loop));
+    escaped
+}
+
+#[derive(Debug, Default)]
+struct Scope {
+    written: HashSet<String>,
+    /// Names read before the scope assigned them, in order of first read.
+    reads_before_write: Vec<String>,
+}
+
+impl Scope {
+    fn read(&mut self, name: &str) {
+        if !self.written.contains(name) && !self.reads_before_write.iter().any(|n| n == name) {
+            self.reads_before_write.push(name.to_string());
+        }
+    }
+
+    fn write(&mut self, name: &str) {
+        self.written.insert(name.to_string());
+    }
+}
+
+fn bind_parameters(parameters: Node, source: &str, scope: &mut Scope) {
+    let mut cursor = parameters.walk();
+    for parameter in parameters.named_children(&mut cursor) {
+        let name = match parameter.kind() {
+            "identifier" => Some(parameter),
+            _ => parameter.child_by_field_name("name").or_else(|| {
+                let mut cursor = parameter.walk();
+                let found = parameter
+                    .named_children(&mut cursor)
+                    .find(|child| child.kind() == "identifier");
+                found.or_else(|| parameter.named_child(0)?.named_child(0))
+            }),
+        };
+        if let Some(name) = name.filter(|n| n.kind() == "identifier") {
+            scope.write(text(name, source));
+        }
+    }
+}
+
+/// Records reads and writes of `node` in evaluation order, so that in
+/// `x = x + 1` the read of `x` comes before the write.
+fn walk(node: Node, source: &str, scope: &mut Scope) {
+    let field = |name| node.child_by_field_name(name);
+    match node.kind() {
+        "identifier" => scope.read(text(node, source)),
+        "assignment" => {
+            if let Some(right) = field("right") {
+                walk(right, source, scope);
+            }
+            if let Some(left) = field("left") {
+                bind(left, source, scope);
+            }
+        }
+        "augmented_assignment" => {
+            if let Some(right) = field("right") {
+                walk(right, source, scope);
+            }
+            if let Some(left) = field("left") {
+                walk(left, source, scope);
+                bind(left, source, scope);
+            }
+        }
+        "named_expression" => {
+            if let Some(value) = field("value") {
+                walk(value, source, scope);
+            }
+            if let Some(name) = field("name") {
+                bind(name, source, scope);
+            }
+        }
+        "for_statement" | "for_in_clause" => {
+            if let Some(right) = field("right") {
+                walk(right, source, scope);
+            }
+            if let Some(left) = field("left") {
+                bind(left, source, scope);
+            }
+            for name in ["body", "alternative"] {
+                if let Some(child) = field(name) {
+                    walk(child, source, scope);
+                }
+            }
+        }
+        "as_pattern" => {
+            let mut cursor = node.walk();
+            for child in node.named_children(&mut cursor) {
+                if child.kind() == "as_pattern_target" {
+                    bind(child, source, scope);
+                } else {
+                    walk(child, source, scope);
+                }
+            }
+        }
+        "except_clause" => {
+            let mut cursor = node.walk();
+            for child in node.named_children(&mut cursor) {
+                if child.prev_sibling().is_some_and(|prev| prev.kind() == "as") {
+                    bind(child, source, scope);
+                } else {
+                    walk(child, source, scope);
+                }
+            }
+        }
+        "attribute" => {
+            if let Some(object) = field("object") {
+                walk(object, source, scope);
+            }
+        }
+        "keyword_argument" => {
+            if let Some(value) = field("value") {
+                walk(value, source, scope);
+            }
+        }
+        "function_definition" | "class_definition" => {
+            if let Some(name) = field("name") {
+                bind(name, source, scope);
+            }
+        }
+        "lambda" | "global_statement" | "nonlocal_statement" => {}
+        _ => {
+            let mut cursor = node.walk();
+            for child in node.named_children(&mut cursor) {
+                walk(child, source, scope);
+            }
+        }
+    }
+}
+
+/// Binds the names assigned by an assignment target. Attribute and
+/// subscript targets assign nothing but read their object.
+fn bind(target: Node, source: &str, scope: &mut Scope) {
+    match target.kind() {
+        "identifier" => scope.write(text(target, source)),
+        "attribute" | "subscript" => walk(target, source, scope),
+        _ => {
+            let mut cursor = target.walk();
+            for child in target.named_children(&mut cursor) {
+                bind(child, source, scope);
+            }
+        }
+    }
+}
+
+fn text<'a>(node: Node, source: &'a str) -> &'a str {
+    node.utf8_text(source.as_bytes()).unwrap_or_default()
+}
+
+pub fn print_table(suggestions: &[Suggestion]) {
+    let mut table = Table::new();
+    table.set_header(vec![
+        "Function",
+        "File",
+        "Line",
+        "Complexity",
+        "Priority",
+        "Extract candidate",
+    ]);
+    for suggestion in suggestions {
+        table.add_row(vec![
+            Cell::new(truncate_end(&suggestion.name, NAME_WIDTH)),
+            Cell::new(truncate_start(&suggestion.file, PATH_WIDTH)),
+            Cell::new(suggestion.line.to_string()),
+            Cell::new(suggestion.complexity.to_string()),
+            Cell::new(suggestion.priority.to_string()),
+            Cell::new(describe(suggestion.extract.as_ref())),
+        ]);
+    }
+    println!("{}", table);
+}
+
+pub fn render_markdown(suggestions: &[Suggestion]) -> String {
+    let mut out = String::from(
+        "| Function | File | Line | Complexity | Priority | Extract candidate |\n\
+         |---|---|---:|---:|---:|---|\n",
+    );
+    for suggestion in suggestions {
+        out.push_str(&format!(
+            "| `{}` | {} | {} | {} | {} | {} |\n",
+            suggestion.name,
+            suggestion.file,
+            suggestion.line,
+            suggestion.complexity,
+            suggestion.priority,
+            describe(suggestion.extract.as_ref())
+        ));
+    }
+    out
+}
+
+fn describe(candidate: Option<&ExtractCandidate>) -> String {
+    match candidate {
+        Some(c) if c.parameters.is_empty() => {
+            format!("`{}` block, lines {}-{}", c.kind, c.start_line, c.end_line)
+        }
+        Some(c) => format!(
+            "`{}` block, lines {}-{} (parameters: {})",
+            c.kind,
+            c.start_line,
+            c.end_line,
+            c.parameters.join(", ")
+        ),
+        None => "-".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(source: &str) -> Option<ExtractCandidate> {
+        let mut parser = TSParser::new();
+        parser.set_language(tree_sitter_python::language()).unwrap();
+        let tree = parse(&mut parser, source).unwrap();
+        let function = find_function(tree.root_node(), 1).unwrap();
+        extract_candidate(function, source)
+    }
+
+    #[test]
+    fn test_extract_candidate_parameters() {
+        let source = r#"def process(items, limit):
+    total = 0
+    seen = set()
+    for item in items:
+        if item in seen:
+            continue
+        seen.add(item)
+        total += item.size * limit
+    if total > limit:
+        print("too big")
+    return total
+"#;
+        assert_eq!(
+            candidate(source),
+            Some(ExtractCandidate {
+                kind: "for".to_string(),
+                start_line: 4,
+                end_line: 8,
+                parameters: vec!["items".into(), "seen".into(), "limit".into(), "total".into()],
+            })
+        );
+    }
+
+    #[test]
+    fn test_blocks_that_return_are_not_candidates() {
+        let source = r#"def find(items, wanted):
+    for item in items:
+        if item == wanted:
+            return item
+        print(item)
+    return None
+"#;
+        assert_eq!(candidate(source), None);
+    }
+}
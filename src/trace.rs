@@ -0,0 +1,150 @@
+//! How the analysis treated one file, recorded by the analysis itself as it
+//! goes so that the explanation cannot diverge from what it does.
+
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// One decision taken about the traced file.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "step", rename_all = "snake_case")]
+pub enum Step {
+    /// Left out of the walk: not a file of a supported language.
+    Unsupported { extension: Option<String> },
+    /// Left out of the walk: a file of another language than `--language`.
+    OtherLanguage { language: String, only: String },
+    /// Left out of the walk: it is in a cache or virtualenv directory.
+    ExcludedDirectory { pattern: String },
+    /// Left out of the walk: a `.gitignore` pattern, from `file`, ignores
+    /// it or a directory it is in.
+    Ignored {
+        pattern: String,
+        file: Option<String>,
+    },
+    /// Left out of the walk by an `--exclude` glob.
+    Excluded { pattern: String },
+    /// Left out of the walk: no `--include` glob matches it.
+    NotIncluded,
+    /// Found by the walk.
+    Discovered,
+    /// Skipped as vendored code.
+    Vendored { reason: String },
+    /// Skipped because it could not be read.
+    Unreadable { error: String },
+    /// Skipped because it could not be parsed.
+    ParseError { message: String },
+    /// Recognized as generated, with what `--generated` does with it.
+    Generated { reason: String, mode: String },
+    /// Dunder methods left out with `--dunder exclude`.
+    DunderExcluded { functions: usize },
+    /// Scored, with the number of functions kept.
+    Analyzed { functions: usize },
+}
+
+impl Step {
+    /// Whether the step ends the file's analysis.
+    pub fn is_skip(&self) -> bool {
+        matches!(
+            self,
+            Step::Unsupported { .. }
+                | Step::OtherLanguage { .. }
+                | Step::ExcludedDirectory { .. }
+                | Step::Ignored { .. }
+                | Step::Excluded { .. }
+                | Step::NotIncluded
+                | Step::Vendored { .. }
+                | Step::Unreadable { .. }
+                | Step::ParseError { .. }
+        )
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Step::Unsupported { extension: None } => {
+                "not analyzed: only Python, JavaScript and TypeScript files are, and it has no extension".to_string()
+            }
+            Step::Unsupported {
+                extension: Some(extension),
+            } => format!(
+                "not analyzed: only Python, JavaScript and TypeScript files are, not .{}",
+                extension
+            ),
+            Step::OtherLanguage { language, only } => {
+                format!("not analyzed: a {} file, with --language {}", language, only.to_lowercase())
+            }
+            Step::ExcludedDirectory { pattern } => {
+                format!("not analyzed: `{}` directories are excluded", pattern)
+            }
+            Step::Ignored { pattern, file } => format!(
+                "not analyzed: ignored by `{}` in {} (--no-ignore analyzes it)",
+                pattern,
+                file.as_deref().unwrap_or(".gitignore")
+            ),
+            Step::Excluded { pattern } => format!("not analyzed: excluded by --exclude {}", pattern),
+            Step::NotIncluded => "not analyzed: no --include glob matches it".to_string(),
+            Step::Discovered => "found by the directory walk".to_string(),
+            Step::Vendored { reason } => format!("skipped as vendored code: {}", reason),
+            Step::Unreadable { error } => format!("skipped, could not be read: {}", error),
+            Step::ParseError { message } => format!("skipped, could not be parsed: {}", message),
+            Step::Generated { reason, mode } => {
+                let outcome = match mode.as_str() {
+                    "exclude" => "its functions are left out",
+                    "collapse" => "its functions are collapsed into one row",
+                    _ => "its functions are listed, tagged as generated",
+                };
+                format!("generated ({}): {} (--generated {})", reason, outcome, mode)
+            }
+            Step::DunderExcluded { functions } => {
+                format!("{} dunder methods left out (--dunder exclude)", functions)
+            }
+            Step::Analyzed { functions } => format!("analyzed: {} functions", functions),
+        }
+    }
+}
+
+/// Collects the steps taken about one file. Clones share the steps.
+#[derive(Debug, Clone)]
+pub struct Trace {
+    target: PathBuf,
+    steps: Arc<Mutex<Vec<Step>>>,
+}
+
+impl Trace {
+    /// Traces `target`, spelled the way the directory walk reaches it: the
+    /// analyzed root joined with the path relative to it.
+    pub fn new(target: PathBuf) -> Self {
+        Trace {
+            target,
+            steps: Arc::default(),
+        }
+    }
+
+    pub fn concerns(&self, path: &Path) -> bool {
+        path == self.target
+    }
+
+    pub fn record(&self, path: &Path, step: Step) {
+        if self.concerns(path) {
+            self.steps.lock().unwrap().push(step);
+        }
+    }
+
+    /// Records `step` when the target is under `dir`, which the walk does
+    /// not enter.
+    pub fn record_within(&self, dir: &Path, step: Step) {
+        if self.target.starts_with(dir) {
+            self.steps.lock().unwrap().push(step);
+        }
+    }
+
+    pub fn steps(&self) -> Vec<Step> {
+        self.steps.lock().unwrap().clone()
+    }
+}
+
+/// Records `step` about `path` when there is a trace.
+pub fn record(trace: Option<&Trace>, path: &Path, step: impl FnOnce() -> Step) {
+    if let Some(trace) = trace.filter(|trace| trace.concerns(path)) {
+        trace.record(path, step());
+    }
+}
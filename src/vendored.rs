@@ -0,0 +1,119 @@
+//! Heuristics recognizing third-party code copied into a repository.
+
+use std::path::Path;
+
+/// Path components that conventionally hold third-party code.
+pub const DEFAULT_COMPONENTS: &[&str] = &[
+    "vendor",
+    "_vendor",
+    "third_party",
+    "thirdparty",
+    "site-packages",
+    "node_modules",
+    ".eggs",
+];
+
+/// Phrases that mark a file as a vendored copy when they appear in its first
+/// [`MARKER_LINES`] lines.
+const MARKERS: &[&str] = &[
+    "vendored from",
+    "vendored copy",
+    "vendored version",
+    "this file is vendored",
+    "copied verbatim from",
+];
+
+const MARKER_LINES: usize = 10;
+
+#[derive(Debug, Clone)]
+pub struct VendorDetector {
+    components: Vec<String>,
+}
+
+impl Default for VendorDetector {
+    fn default() -> Self {
+        VendorDetector {
+            components: DEFAULT_COMPONENTS.iter().map(|c| c.to_string()).collect(),
+        }
+    }
+}
+
+impl VendorDetector {
+    /// The default components plus `extra` ones.
+    pub fn with_components(extra: &[String]) -> Self {
+        let mut detector = VendorDetector::default();
+        detector.components.extend(extra.iter().cloned());
+        detector
+    }
+
+    /// The components added to the built-in ones.
+    pub fn extra(&self) -> &[String] {
+        &self.components[DEFAULT_COMPONENTS.len()..]
+    }
+
+    /// Why `relative` (a path relative to the analyzed root) looks vendored.
+    /// Only whole components match, so `vendors/` is not `vendor/`.
+    pub fn check_path(&self, relative: &Path) -> Option<String> {
+        relative.components().find_map(|component| {
+            let component = component.as_os_str().to_string_lossy();
+            self.components
+                .iter()
+                .any(|c| *c == component)
+                .then(|| format!("path component `{}`", component))
+        })
+    }
+
+    /// Why `source` looks vendored, judging from its header.
+    pub fn check_source(&self, source: &str) -> Option<String> {
+        source.lines().take(MARKER_LINES).find_map(|line| {
+            let line = line.to_lowercase();
+            MARKERS
+                .iter()
+                .find(|marker| line.contains(*marker))
+                .map(|marker| format!("marker \"{}\"", marker))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vendored_paths() {
+        let detector = VendorDetector::default();
+        assert!(detector.check_path(Path::new("pkg/_vendor/requests/adapters.py")).is_some());
+        assert!(detector.check_path(Path::new("third_party/six.py")).is_some());
+        assert!(detector.check_path(Path::new("lib/site-packages/yaml/reader.py")).is_some());
+        assert!(detector.check_path(Path::new(".eggs/foo/bar.py")).is_some());
+    }
+
+    #[test]
+    fn test_first_party_lookalikes_are_kept() {
+        let detector = VendorDetector::default();
+        assert_eq!(detector.check_path(Path::new("shop/vendors/models.py")), None);
+        assert_eq!(detector.check_path(Path::new("app/vendor.py")), None);
+        assert_eq!(detector.check_path(Path::new("app/vendor_api/client.py")), None);
+    }
+
+    #[test]
+    fn test_extra_components() {
+        let detector = VendorDetector::with_components(&["externals".to_string()]);
+        assert!(detector.check_path(Path::new("externals/lib.py")).is_some());
+        assert!(detector.check_path(Path::new("vendor/lib.py")).is_some());
+    }
+
+    #[test]
+    fn test_markers() {
+        let detector = VendorDetector::default();
+        assert!(detector
+            .check_source("# -*- coding: utf-8 -*-\n# Vendored from https://github.com/x/y\n")
+            .is_some());
+        assert_eq!(
+            detector.check_source("\"\"\"Talks to our vendors' APIs.\"\"\"\n"),
+            None
+        );
+        let late = format!("{}# vendored from elsewhere\n", "\n".repeat(MARKER_LINES));
+        assert_eq!(detector.check_source(&late), None);
+    }
+}
@@ -0,0 +1,234 @@
+//! Checking the radon preset against radon itself.
+
+use crate::compare::compare;
+use crate::output::relative_path;
+use crate::output::text::truncate_start;
+use crate::output::table::PATH_WIDTH;
+use crate::{AnalysisResult, FunctionComplexity};
+use anyhow::{bail, Context, Result};
+use comfy_table::{Cell, Table};
+use serde::{Deserialize, Serialize};
+use std::io::ErrorKind;
+use std::path::Path;
+use std::process::Command;
+
+/// A known, intentional difference from radon, declared in an allowlist file
+/// as `{"function": "pkg/mod.py::name", "reason": "..."}`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Divergence {
+    pub function: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Discrepancy {
+    /// `path::name`, with the path relative to the verified directory.
+    pub function: String,
+    pub line: u32,
+    /// Score reported by radon, if radon lists the function at all.
+    pub radon: Option<u32>,
+    pub analyzr: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Report {
+    pub matching: usize,
+    /// Differences no allowlist entry explains.
+    pub discrepancies: Vec<Discrepancy>,
+    pub allowed: Vec<Discrepancy>,
+    /// Allowlist entries that matched no difference, and can be removed.
+    pub unused_allowlist: Vec<String>,
+}
+
+pub fn read_allowlist(path: &Path) -> Result<Vec<Divergence>> {
+    let json = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    serde_json::from_str(&json)
+        .with_context(|| format!("{} is not a JSON list of divergences", path.display()))
+}
+
+/// Runs `radon cc -j` over `path` and returns its output.
+pub fn run_radon(path: &Path) -> Result<String> {
+    let output = match Command::new("radon").args(["cc", "-j"]).arg(path).output() {
+        Ok(output) => output,
+        Err(error) if error.kind() == ErrorKind::NotFound => {
+            bail!("radon is not installed (pip install radon)")
+        }
+        Err(error) => return Err(error).context("Failed to run radon"),
+    };
+    if !output.status.success() {
+        bail!(
+            "radon exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    String::from_utf8(output.stdout).context("radon output is not UTF-8")
+}
+
+/// Whether `func` is a Python lambda.
+fn is_lambda(func: &FunctionComplexity) -> bool {
+    crate::language::own_name(&func.name) == "<lambda>"
+}
+
+/// Compares per-function scores of `radon` and `analyzr`, both analyses of
+/// `root`, setting aside the differences `allowlist` explains.
+pub fn verify(
+    radon: &AnalysisResult,
+    analyzr: &AnalysisResult,
+    root: &Path,
+    allowlist: &[Divergence],
+) -> Report {
+    let comparison = compare(radon, analyzr);
+    let key = |file: &str, name: &str| format!("{}::{}", relative_path(file, root), name);
+    // radon scores lambdas as part of the function they are in, never on
+    // their own.
+    let only_right = comparison.only_right.iter().filter(|f| !is_lambda(f));
+
+    let mut differences: Vec<Discrepancy> = comparison
+        .mismatches
+        .iter()
+        .map(|m| Discrepancy {
+            function: key(&m.file, &m.name),
+            line: m.line,
+            radon: Some(m.left),
+            analyzr: Some(m.right),
+            reason: None,
+        })
+        .chain(comparison.only_left.iter().map(|f| Discrepancy {
+            function: key(&f.file, &f.name),
+            line: f.line,
+            radon: Some(f.complexity),
+            analyzr: None,
+            reason: None,
+        }))
+        .chain(only_right.map(|f| Discrepancy {
+            function: key(&f.file, &f.name),
+            line: f.line,
+            radon: None,
+            analyzr: Some(f.complexity),
+            reason: None,
+        }))
+        .collect();
+    differences.sort_by(|a, b| a.function.cmp(&b.function).then(a.line.cmp(&b.line)));
+
+    let mut report = Report {
+        matching: comparison.matching,
+        discrepancies: Vec::new(),
+        allowed: Vec::new(),
+        unused_allowlist: Vec::new(),
+    };
+    for mut difference in differences {
+        match allowlist.iter().find(|d| d.function == difference.function) {
+            Some(divergence) => {
+                difference.reason = Some(divergence.reason.clone());
+                report.allowed.push(difference);
+            }
+            None => report.discrepancies.push(difference),
+        }
+    }
+    report.unused_allowlist = allowlist
+        .iter()
+        .filter(|d| !report.allowed.iter().any(|a| a.function == d.function))
+        .map(|d| d.function.clone())
+        .collect();
+    report
+}
+
+pub fn print_report(report: &Report) {
+    println!(
+        "{} matching, {} discrepancies, {} allowed",
+        report.matching,
+        report.discrepancies.len(),
+        report.allowed.len()
+    );
+
+    let score = |score: Option<u32>| score.map_or("-".to_string(), |s| s.to_string());
+    for (title, differences) in [
+        ("Discrepancies", &report.discrepancies),
+        ("Allowed divergences", &report.allowed),
+    ] {
+        if differences.is_empty() {
+            continue;
+        }
+        let mut table = Table::new();
+        let mut header = vec!["Function", "Line", "radon", "analyzr"];
+        if title == "Allowed divergences" {
+            header.push("Reason");
+        }
+        table.set_header(header);
+        for difference in differences {
+            let mut row = vec![
+                Cell::new(truncate_start(&difference.function, PATH_WIDTH)),
+                Cell::new(difference.line.to_string()),
+                Cell::new(score(difference.radon)),
+                Cell::new(score(difference.analyzr)),
+            ];
+            if let Some(reason) = &difference.reason {
+                row.push(Cell::new(reason));
+            }
+            table.add_row(row);
+        }
+        println!("\n{}:\n{}", title, table);
+    }
+
+    for function in &report.unused_allowlist {
+        println!("\nnote: allowlist entry `{}` matches no divergence", function);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::weights::{Preset, Weights};
+    use crate::{analyze_path, radon, AnalyzeOptions};
+
+    const CORPUS: &str = "testdata/radon_corpus";
+
+    #[test]
+    fn test_radon_corpus_parity() {
+        let root = Path::new(CORPUS);
+        let expected = std::fs::read_to_string(root.join("expected.json")).unwrap();
+        let expected = radon::import(&expected, root, 10).unwrap();
+        let options = AnalyzeOptions {
+            weights: Weights::preset(Preset::Radon),
+            ..AnalyzeOptions::default()
+        };
+        let result = analyze_path(root, &options).unwrap();
+        let allowlist = read_allowlist(&root.join("allowlist.json")).unwrap();
+
+        let report = verify(&expected, &result, root, &allowlist);
+
+        assert!(report.discrepancies.is_empty(), "{:#?}", report.discrepancies);
+        assert!(report.unused_allowlist.is_empty(), "{:?}", report.unused_allowlist);
+        assert!(report.matching >= 40);
+    }
+
+    #[test]
+    fn test_unexplained_and_stale_entries() {
+        let root = Path::new(CORPUS);
+        let expected = std::fs::read_to_string(root.join("expected.json")).unwrap();
+        let expected = radon::import(&expected, root, 10).unwrap();
+        let mut result = expected.clone();
+        result.functions[0].complexity += 1;
+        result.functions.pop();
+        let allowlist = vec![Divergence {
+            function: "gone.py::f".to_string(),
+            reason: "removed".to_string(),
+        }];
+
+        let report = verify(&expected, &result, root, &allowlist);
+
+        assert_eq!(report.discrepancies.len(), 2);
+        let bumped = report
+            .discrepancies
+            .iter()
+            .find(|d| d.function == "auth.py::check_password")
+            .unwrap();
+        assert_eq!((bumped.radon, bumped.analyzr), (Some(5), Some(6)));
+        assert!(report.discrepancies.iter().any(|d| d.analyzr.is_none()));
+        assert_eq!(report.unused_allowlist, ["gone.py::f"]);
+    }
+}
@@ -0,0 +1,294 @@
+//! What the directory walk leaves out besides files of unsupported
+//! languages: bytecode caches and virtualenvs, paths git ignores, and those
+//! `--exclude` and `--include` select.
+//!
+//! Ignored and excluded directories are not entered at all, so that
+//! `node_modules` or a build directory cost nothing to skip.
+
+use crate::output::relative_path;
+use crate::rules::glob;
+use crate::trace::Step;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Names of the directories no walk enters.
+pub const EXCLUDED_DIRECTORIES: &[&str] = &["__pycache__", "venv", ".venv"];
+
+/// Which paths under the analyzed one the walk keeps.
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    /// Globs of the paths to leave out, relative to the analyzed path.
+    pub exclude: Vec<String>,
+    /// Globs of the only files to analyze, when there are any.
+    pub include: Vec<String>,
+    /// Analyze the paths `.gitignore` files ignore too.
+    pub no_ignore: bool,
+}
+
+impl Filter {
+    /// Why the walk from `root` leaves out `path`, a directory when `is_dir`,
+    /// if it does. `root` itself is never left out.
+    pub fn exclusion(
+        &self,
+        root: &Path,
+        path: &Path,
+        is_dir: bool,
+        ignores: &mut Ignores,
+    ) -> Option<Step> {
+        if path == root {
+            return None;
+        }
+        let name = path.file_name().map(|name| name.to_string_lossy());
+        if let Some(name) =
+            name.filter(|name| is_dir && EXCLUDED_DIRECTORIES.contains(&name.as_ref()))
+        {
+            return Some(Step::ExcludedDirectory {
+                pattern: name.to_string(),
+            });
+        }
+        let relative = relative_path(&path.to_string_lossy(), root);
+        if let Some(pattern) = self.exclude.iter().find(|pattern| glob(pattern, &relative)) {
+            return Some(Step::Excluded {
+                pattern: pattern.clone(),
+            });
+        }
+        if !self.no_ignore {
+            if let Some(step) = ignores.ignored(path, is_dir) {
+                return Some(step);
+            }
+        }
+        let included = self.include.iter().any(|pattern| glob(pattern, &relative));
+        (!is_dir && !self.include.is_empty() && !included).then_some(Step::NotIncluded)
+    }
+}
+
+/// The `.gitignore` files that apply to the paths of one walk: those of the
+/// directories walked, read as the walk enters them, and those of the
+/// directories above the analyzed one up to the root of its repository, with
+/// the repository's `.git/info/exclude`.
+pub struct Ignores {
+    root: PathBuf,
+    /// Matchers of the directories above the root, innermost first, each
+    /// with the path of the root relative to its directory.
+    outer: Vec<(Gitignore, PathBuf)>,
+    /// Matchers of the directories walked, by their path as walked.
+    inner: HashMap<PathBuf, Option<Gitignore>>,
+}
+
+/// Matcher of the ignore file at `file` under `dir`, if there is one.
+fn matcher(dir: &Path, file: &str) -> Option<Gitignore> {
+    let path = dir.join(file);
+    if !path.is_file() {
+        return None;
+    }
+    // Paths are matched relative to `dir`; an absolute root keeps the
+    // matcher from stripping what looks like it from them.
+    let mut builder = GitignoreBuilder::new(dir.canonicalize().unwrap_or_else(|_| dir.into()));
+    builder.add(&path);
+    builder.build().ok()
+}
+
+impl Ignores {
+    pub fn new(root: &Path) -> Self {
+        let mut outer = Vec::new();
+        if let Ok(canonical) = root.canonicalize() {
+            let mut above = Vec::new();
+            for dir in canonical.ancestors() {
+                let prefix = canonical
+                    .strip_prefix(dir)
+                    .unwrap_or(&canonical)
+                    .to_path_buf();
+                if dir != canonical {
+                    above.push((dir.to_path_buf(), prefix.clone()));
+                }
+                if dir.join(".git").exists() {
+                    // Outside of a repository, only the walked directories'
+                    // own `.gitignore` files apply.
+                    outer = above
+                        .iter()
+                        .filter_map(|(dir, prefix)| {
+                            Some((matcher(dir, ".gitignore")?, prefix.clone()))
+                        })
+                        .collect();
+                    outer
+                        .extend(matcher(dir, ".git/info/exclude").map(|exclude| (exclude, prefix)));
+                    break;
+                }
+            }
+        }
+        Ignores {
+            root: root.to_path_buf(),
+            outer,
+            inner: HashMap::new(),
+        }
+    }
+
+    /// Whether `path` is ignored, as the pattern that ignores it: the
+    /// `.gitignore` of the innermost directory with a matching pattern
+    /// decides, and a `!pattern` in it keeps the path.
+    fn ignored(&mut self, path: &Path, is_dir: bool) -> Option<Step> {
+        let decide = |matcher: &Gitignore, relative: &Path| match matcher.matched(relative, is_dir)
+        {
+            Match::None => None,
+            Match::Ignore(glob) => Some(Some(Step::Ignored {
+                pattern: glob.original().to_string(),
+                file: glob.from().map(|file| file.to_string_lossy().into_owned()),
+            })),
+            Match::Whitelist(_) => Some(None),
+        };
+        let within = path.strip_prefix(&self.root).ok()?.to_path_buf();
+        for dir in path.ancestors().skip(1) {
+            let matcher = self
+                .inner
+                .entry(dir.to_path_buf())
+                .or_insert_with(|| matcher(dir, ".gitignore"));
+            let relative = path.strip_prefix(dir).ok()?;
+            if let Some(decision) = matcher
+                .as_ref()
+                .and_then(|matcher| decide(matcher, relative))
+            {
+                return decision;
+            }
+            if dir == self.root {
+                break;
+            }
+        }
+        self.outer
+            .iter()
+            .find_map(|(matcher, prefix)| decide(matcher, &prefix.join(&within)))
+            .flatten()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn tree(files: &[&str]) -> TempDir {
+        let temp_dir = TempDir::new().unwrap();
+        for file in files {
+            let path = temp_dir.path().join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "def f():\n    pass\n").unwrap();
+        }
+        temp_dir
+    }
+
+    fn walked(root: &Path, filter: &Filter) -> Vec<String> {
+        let mut files: Vec<String> = crate::discover(root, None, None, filter)
+            .filter_map(Result::ok)
+            .map(|path| relative_path(&path.to_string_lossy(), root))
+            .collect();
+        files.sort();
+        files
+    }
+
+    #[test]
+    fn test_gitignore() {
+        let temp_dir = tree(&[
+            "app.py",
+            "build/lib/app.py",
+            "api/user_pb2.py",
+            "api/keep_pb2.py",
+            "api/views.py",
+            "web/dist/bundle.js",
+            "web/src/index.ts",
+            ".tox/py311/lib/site.py",
+            "venv/lib/site.py",
+            "my_venv_tools.py",
+        ]);
+        let root = temp_dir.path();
+        std::fs::write(
+            root.join(".gitignore"),
+            "build/\n*_pb2.py\n!keep_pb2.py\n.tox/\n",
+        )
+        .unwrap();
+        std::fs::write(root.join("web/.gitignore"), "/dist\n").unwrap();
+
+        assert_eq!(
+            walked(root, &Filter::default()),
+            [
+                "api/keep_pb2.py",
+                "api/views.py",
+                "app.py",
+                "my_venv_tools.py",
+                "web/src/index.ts"
+            ]
+        );
+        let everything = Filter {
+            no_ignore: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            walked(root, &everything),
+            [
+                ".tox/py311/lib/site.py",
+                "api/keep_pb2.py",
+                "api/user_pb2.py",
+                "api/views.py",
+                "app.py",
+                "build/lib/app.py",
+                "my_venv_tools.py",
+                "web/dist/bundle.js",
+                "web/src/index.ts",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_repository_gitignore_above_the_root() {
+        let temp_dir = tree(&[
+            "src/app.py",
+            "src/generated/models.py",
+            "src/notes/draft.py",
+        ]);
+        let root = temp_dir.path();
+        std::fs::create_dir_all(root.join(".git/info")).unwrap();
+        std::fs::write(root.join(".gitignore"), "src/generated/\n").unwrap();
+        std::fs::write(root.join(".git/info/exclude"), "draft.py\n").unwrap();
+        assert_eq!(walked(&root.join("src"), &Filter::default()), ["app.py"]);
+    }
+
+    #[test]
+    fn test_exclude_and_include() {
+        let temp_dir = tree(&[
+            "shop/models.py",
+            "shop/migrations/0001_initial.py",
+            "shop/migrations/helpers/squash.py",
+            "shop/tests/test_models.py",
+            "billing/invoice.py",
+            "scripts/deploy.py",
+        ]);
+        let root = temp_dir.path();
+        let filter = Filter {
+            exclude: vec!["**/migrations/*.py".to_string(), "scripts".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            walked(root, &filter),
+            [
+                "billing/invoice.py",
+                "shop/migrations/helpers/squash.py",
+                "shop/models.py",
+                "shop/tests/test_models.py",
+            ]
+        );
+        // Exclusions win over inclusions.
+        let filter = Filter {
+            include: vec!["shop/**".to_string()],
+            exclude: vec!["**/tests/**".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            walked(root, &filter),
+            [
+                "shop/migrations/0001_initial.py",
+                "shop/migrations/helpers/squash.py",
+                "shop/models.py",
+            ]
+        );
+    }
+}
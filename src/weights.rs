@@ -0,0 +1,535 @@
+//! What adds to a function's complexity, and presets reproducing the numbers
+//! of other tools.
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use tree_sitter::Node;
+
+/// A syntactic construct that can add to a function's complexity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Construct {
+    If,
+    Elif,
+    For,
+    While,
+    Try,
+    Except,
+    With,
+    BoolOp,
+    Ternary,
+    ComprehensionFor,
+    ComprehensionIf,
+    Assert,
+    LoopElse,
+    TryElse,
+    Finally,
+    Case,
+    Walrus,
+}
+
+impl Construct {
+    pub const ALL: [Construct; 17] = [
+        Construct::If,
+        Construct::Elif,
+        Construct::For,
+        Construct::While,
+        Construct::Try,
+        Construct::Except,
+        Construct::With,
+        Construct::BoolOp,
+        Construct::Ternary,
+        Construct::ComprehensionFor,
+        Construct::ComprehensionIf,
+        Construct::Assert,
+        Construct::LoopElse,
+        Construct::TryElse,
+        Construct::Finally,
+        Construct::Case,
+        Construct::Walrus,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Construct::If => "if",
+            Construct::Elif => "elif",
+            Construct::For => "for",
+            Construct::While => "while",
+            Construct::Try => "try",
+            Construct::Except => "except",
+            Construct::With => "with",
+            Construct::BoolOp => "bool-op",
+            Construct::Ternary => "ternary",
+            Construct::ComprehensionFor => "comprehension-for",
+            Construct::ComprehensionIf => "comprehension-if",
+            Construct::Assert => "assert",
+            Construct::LoopElse => "loop-else",
+            Construct::TryElse => "try-else",
+            Construct::Finally => "finally",
+            Construct::Case => "case",
+            Construct::Walrus => "walrus",
+        }
+    }
+
+    /// The construct `node` is an instance of, if any, in the Python,
+    /// JavaScript or TypeScript grammar. JavaScript's `else if` is an `if`
+    /// in an `else`, and `do ... while` a `while`.
+    pub fn of(node: Node) -> Option<Construct> {
+        Some(match node.kind() {
+            "if_statement" => Construct::If,
+            "elif_clause" => Construct::Elif,
+            "for_statement" | "for_in_statement" => Construct::For,
+            "while_statement" | "do_statement" => Construct::While,
+            "try_statement" => Construct::Try,
+            "except_clause" | "except_group_clause" | "catch_clause" => Construct::Except,
+            "with_statement" => Construct::With,
+            "boolean_operator" => Construct::BoolOp,
+            "binary_expression" => match node.child_by_field_name("operator")?.kind() {
+                "&&" | "||" | "??" => Construct::BoolOp,
+                _ => return None,
+            },
+            "conditional_expression" | "ternary_expression" => Construct::Ternary,
+            "for_in_clause" => Construct::ComprehensionFor,
+            // The guard of a `case` decides a branch like an `if`.
+            "if_clause" if node.parent()?.kind() == "case_clause" => Construct::If,
+            "if_clause" => Construct::ComprehensionIf,
+            "assert_statement" => Construct::Assert,
+            "finally_clause" => Construct::Finally,
+            "case_clause" | "switch_case" => Construct::Case,
+            "named_expression" => Construct::Walrus,
+            "else_clause" => match node.parent()?.kind() {
+                "for_statement" | "while_statement" => Construct::LoopElse,
+                "try_statement" => Construct::TryElse,
+                _ => return None,
+            },
+            _ => return None,
+        })
+    }
+}
+
+/// Bundled weight tables.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Preset {
+    /// This tool's own counting
+    #[default]
+    Analyzr,
+    /// `radon cc`
+    Radon,
+    /// The mccabe checker used by flake8 and pylint
+    Mccabe,
+    /// SonarQube's Python cyclomatic complexity
+    Sonar,
+}
+
+impl Preset {
+    pub fn name(self) -> &'static str {
+        match self {
+            Preset::Analyzr => "analyzr",
+            Preset::Radon => "radon",
+            Preset::Mccabe => "mccabe",
+            Preset::Sonar => "sonar",
+        }
+    }
+}
+
+/// Where boolean operators add to the complexity.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BoolOps {
+    /// Anywhere in the function, including `flag = a or b`
+    #[default]
+    All,
+    /// Only in the tests of `if`, `elif`, `while`, conditional expressions,
+    /// comprehension filters and `assert`
+    ConditionsOnly,
+}
+
+/// How often a construct occurs in a function and what each occurrence adds,
+/// for `--explain`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Term {
+    pub construct: String,
+    pub count: u32,
+    pub weight: u32,
+}
+
+/// How much each construct adds to the base complexity of 1.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Weights {
+    weights: [u32; Construct::ALL.len()],
+    /// Whether control flow inside nested functions and classes counts
+    /// towards the enclosing function as well.
+    pub nested_functions: bool,
+    /// Whether control flow inside lambdas counts towards the enclosing
+    /// function as well.
+    #[serde(default)]
+    pub nested_lambdas: bool,
+    pub bool_ops: BoolOps,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Weights::preset(Preset::Analyzr)
+    }
+}
+
+impl Weights {
+    pub fn preset(preset: Preset) -> Self {
+        use Construct::*;
+        let (counted, nested_functions): (&[Construct], bool) = match preset {
+            // Each branch counts in the innermost function or lambda only.
+            Preset::Analyzr => (
+                &[
+                    If,
+                    Elif,
+                    For,
+                    While,
+                    Try,
+                    Except,
+                    With,
+                    BoolOp,
+                    Ternary,
+                    ComprehensionIf,
+                    Assert,
+                    Case,
+                ],
+                false,
+            ),
+            // radon scores `try` by its handlers and `else`, and a loop
+            // `else` as one more path. Closures are separate blocks, but
+            // lambdas are part of the function they are in.
+            Preset::Radon => (
+                &[
+                    If,
+                    Elif,
+                    For,
+                    While,
+                    Except,
+                    With,
+                    BoolOp,
+                    Ternary,
+                    ComprehensionFor,
+                    ComprehensionIf,
+                    Assert,
+                    LoopElse,
+                    TryElse,
+                ],
+                false,
+            ),
+            // mccabe builds a control-flow graph of statements only, into
+            // which closures are inlined.
+            Preset::Mccabe => (&[If, Elif, For, While, Except], true),
+            Preset::Sonar => (
+                &[
+                    If,
+                    Elif,
+                    For,
+                    While,
+                    BoolOp,
+                    Ternary,
+                    ComprehensionFor,
+                    ComprehensionIf,
+                ],
+                true,
+            ),
+        };
+        let mut weights = Weights {
+            weights: [0; Construct::ALL.len()],
+            nested_functions,
+            nested_lambdas: preset != Preset::Analyzr,
+            // mccabe leaves boolean operators out altogether; should they be
+            // weighed in, only those deciding a branch are.
+            bool_ops: match preset {
+                Preset::Mccabe => BoolOps::ConditionsOnly,
+                _ => BoolOps::All,
+            },
+        };
+        for &construct in counted {
+            weights.set(construct, 1);
+        }
+        weights
+    }
+
+    pub fn get(&self, construct: Construct) -> u32 {
+        self.weights[construct as usize]
+    }
+
+    pub fn set(&mut self, construct: Construct, weight: u32) {
+        self.weights[construct as usize] = weight;
+    }
+
+    /// The weights, and the scoring of boolean operators, that differ from
+    /// those of `preset`, as `name=weight` and `bool-ops=mode`.
+    pub fn overrides(&self, preset: Preset) -> Vec<String> {
+        let preset = Weights::preset(preset);
+        let weights = Construct::ALL
+            .iter()
+            .filter(|&&construct| self.get(construct) != preset.get(construct))
+            .map(|&construct| format!("{}={}", construct.name(), self.get(construct)));
+        let bool_ops = (self.bool_ops != preset.bool_ops).then(|| {
+            let mode = self.bool_ops.to_possible_value().unwrap();
+            format!("bool-ops={}", mode.get_name())
+        });
+        weights.chain(bool_ops).collect()
+    }
+
+    /// Number of occurrences of each construct inside `node`, not counting
+    /// `node` itself, indexed like [`Construct::ALL`].
+    pub fn counts(&self, node: Node) -> [u32; Construct::ALL.len()] {
+        let mut counts = [0; Construct::ALL.len()];
+        // Nodes whose children are still to count, with whether they are in
+        // a condition: a stack of its own rather than recursion, so that no
+        // nesting overflows the thread's.
+        let mut stack = vec![(node, false)];
+        while let Some((node, in_condition)) = stack.pop() {
+            let mut cursor = node.walk();
+            for (index, child) in node.named_children(&mut cursor).enumerate() {
+                let nested = match child.kind() {
+                    "lambda" => self.nested_lambdas,
+                    _ => self.nested_functions,
+                };
+                if !nested && crate::language::is_definition(child.kind()) {
+                    continue;
+                }
+                let in_condition = in_condition || is_condition(node, child, index);
+                match Construct::of(child) {
+                    Some(Construct::BoolOp)
+                        if self.bool_ops == BoolOps::ConditionsOnly && !in_condition => {}
+                    Some(construct) => counts[construct as usize] += 1,
+                    None => {}
+                }
+                stack.push((child, in_condition));
+            }
+        }
+        counts
+    }
+
+    /// Complexity added by the constructs counted in `counts`, saturating at
+    /// `u32::MAX` rather than overflowing with huge weights.
+    pub fn total(&self, counts: &[u32; Construct::ALL.len()]) -> u32 {
+        counts
+            .iter()
+            .zip(self.weights)
+            .fold(0u32, |total, (count, weight)| {
+                total.saturating_add(count.saturating_mul(weight))
+            })
+    }
+
+    /// Every construct that occurs, including those weighing nothing, so
+    /// that what is left uncounted is visible too.
+    pub fn explain(&self, counts: &[u32; Construct::ALL.len()]) -> Vec<Term> {
+        Construct::ALL
+            .into_iter()
+            .filter(|&construct| counts[construct as usize] > 0)
+            .map(|construct| Term {
+                construct: construct.name().to_string(),
+                count: counts[construct as usize],
+                weight: self.get(construct),
+            })
+            .collect()
+    }
+}
+
+/// Whether `child`, the `index`th named child of `parent`, is the test
+/// deciding a branch.
+fn is_condition(parent: Node, child: Node, index: usize) -> bool {
+    match parent.kind() {
+        "if_statement" | "elif_clause" | "while_statement" | "do_statement"
+        | "ternary_expression" => parent
+            .child_by_field_name("condition")
+            .is_some_and(|condition| condition.id() == child.id()),
+        // `consequence if condition else alternative`
+        "conditional_expression" => index == 1,
+        "if_clause" => true,
+        "assert_statement" => index == 0,
+        _ => false,
+    }
+}
+
+/// Parses a `construct=weight` override such as `with=0`.
+pub fn parse_override(spec: &str) -> Result<(Construct, u32), String> {
+    let (name, weight) = spec
+        .split_once('=')
+        .ok_or_else(|| format!("expected CONSTRUCT=WEIGHT, got `{}`", spec))?;
+    let construct = Construct::ALL
+        .into_iter()
+        .find(|c| c.name() == name)
+        .ok_or_else(|| {
+            let names: Vec<&str> = Construct::ALL.iter().map(|c| c.name()).collect();
+            format!("unknown construct `{}` (expected one of {})", name, names.join(", "))
+        })?;
+    let weight = weight
+        .parse()
+        .map_err(|_| format!("`{}` is not a valid weight", weight))?;
+    Ok((construct, weight))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calculate_complexity;
+
+    const PRESETS: [Preset; 4] = [Preset::Analyzr, Preset::Radon, Preset::Mccabe, Preset::Sonar];
+
+    /// Every function of the fixture is preceded by an
+    /// `# expected: analyzr=N radon=N mccabe=N sonar=N` comment.
+    #[test]
+    fn test_presets_match_reference_scores() {
+        let source = include_str!("../testdata/presets.py");
+        let mut expected = Vec::new();
+        let mut lines = source.lines().peekable();
+        while let Some(line) = lines.next() {
+            let Some(scores) = line.strip_prefix("# expected: ") else {
+                continue;
+            };
+            let name = lines.peek().unwrap()["def ".len()..].split('(').next().unwrap();
+            for score in scores.split_whitespace() {
+                let (preset, value) = score.split_once('=').unwrap();
+                expected.push((name, preset, value.parse::<u32>().unwrap()));
+            }
+        }
+        assert_eq!(expected.len() % PRESETS.len(), 0);
+
+        for preset in PRESETS {
+            let functions = calculate_complexity(source, &Weights::preset(preset)).unwrap();
+            for &(name, _, score) in expected.iter().filter(|(_, p, _)| *p == preset.name()) {
+                let func = functions.iter().find(|f| f.name == name).unwrap();
+                assert_eq!(
+                    func.complexity,
+                    score,
+                    "{} under the {} preset",
+                    name,
+                    preset.name()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_loop_else_and_finally_are_opt_in() {
+        let source = include_str!("../testdata/loop_else_finally.py");
+        let default = Weights::default();
+        let mut opted_in = Weights::default();
+        opted_in.set(Construct::LoopElse, 1);
+        opted_in.set(Construct::Finally, 1);
+        let default_scores = calculate_complexity(source, &default).unwrap();
+        let opted_in_scores = calculate_complexity(source, &opted_in).unwrap();
+
+        let mut checked = 0;
+        let mut lines = source.lines().peekable();
+        while let Some(line) = lines.next() {
+            let Some(scores) = line.strip_prefix("# expected: ") else {
+                continue;
+            };
+            let name = lines.peek().unwrap()["def ".len()..].split('(').next().unwrap();
+            for score in scores.split_whitespace() {
+                let (setting, value) = score.split_once('=').unwrap();
+                let functions = match setting {
+                    "default" => &default_scores,
+                    _ => &opted_in_scores,
+                };
+                let func = functions.iter().find(|f| f.name == name).unwrap();
+                assert_eq!(func.complexity, value.parse::<u32>().unwrap(), "{} {}", name, setting);
+                checked += 1;
+            }
+        }
+        assert_eq!(checked, 12);
+
+        // The breakdown lists both even when they weigh nothing.
+        let load = default_scores.iter().find(|f| f.name == "load").unwrap();
+        let terms: Vec<(&str, u32, u32)> = load
+            .breakdown
+            .iter()
+            .map(|t| (t.construct.as_str(), t.count, t.weight))
+            .collect();
+        assert_eq!(
+            terms,
+            [("try", 1, 1), ("except", 2, 1), ("try-else", 1, 0), ("finally", 1, 0)]
+        );
+    }
+
+    #[test]
+    fn test_branching_expressions() {
+        let score = |source: &str| {
+            calculate_complexity(source, &Weights::default()).unwrap()[0].complexity
+        };
+        let source = "\
+def command(event):
+    match event:
+        case {\"type\": \"click\", \"x\": x}:
+            return x
+        case Key(code=code):
+            return code
+        case [first, *rest]:
+            return first
+        case str() | bytes():
+            return event
+        case _:
+            return None
+";
+        assert_eq!(score(source), 6);
+        let guarded = source.replace("case Key(code=code):", "case Key(code=code) if code > 0:");
+        assert_eq!(score(&guarded), 7);
+        let source = "def evens(rows):\n    return [r for r in rows if r if r % 2 == 0]\n";
+        assert_eq!(score(source), 3);
+        let source = "def sign(x):\n    return 1 if x > 0 else -1 if x < 0 else 0\n";
+        assert_eq!(score(source), 3);
+        let source = "def check(a, b):\n    assert a and b\n";
+        assert_eq!(score(source), 3);
+    }
+
+    #[test]
+    fn test_nested_functions() {
+        let source = "def outer(x):\n    def inner(y):\n        if y:\n            pass\n    if x:\n        pass\n";
+        let outer = |preset| {
+            calculate_complexity(source, &Weights::preset(preset)).unwrap()[0].complexity
+        };
+        assert_eq!(outer(Preset::Analyzr), 2);
+        assert_eq!(outer(Preset::Mccabe), 3);
+        assert_eq!(outer(Preset::Radon), 2);
+
+        let source = "def f(items):\n    return sorted(items, key=lambda x: x.a or x.b)\n";
+        let scores = |preset| -> Vec<u32> {
+            let results = calculate_complexity(source, &Weights::preset(preset)).unwrap();
+            results.iter().map(|f| f.complexity).collect()
+        };
+        assert_eq!(scores(Preset::Analyzr), [1, 2]);
+        assert_eq!(scores(Preset::Radon), [2, 2]);
+    }
+
+    #[test]
+    fn test_bool_ops_in_conditions_only() {
+        let score = |source: &str, bool_ops| {
+            let weights = Weights {
+                bool_ops,
+                ..Weights::default()
+            };
+            calculate_complexity(source, &weights).unwrap()[0].complexity
+        };
+        let cases = [
+            ("def f(a, b):\n    x = a or b\n", 2, 1),
+            ("def f(a, b):\n    if a or b:\n        pass\n", 3, 3),
+            ("def f(a, b, c):\n    if a:\n        x = b and c\n", 3, 2),
+            ("def f(a, b):\n    while a and (b or a):\n        pass\n", 4, 4),
+            ("def f(a, b, c):\n    if a:\n        pass\n    elif b or c:\n        pass\n", 4, 4),
+            ("def f(a, b):\n    return (a or b) if a and b else None\n", 4, 3),
+            ("def f(xs, b):\n    return [x for x in xs if x or b]\n", 3, 3),
+            ("def f(a, b):\n    assert a or b, a and b\n", 4, 3),
+            ("def f(a, b):\n    return g(a or b)\n", 2, 1),
+        ];
+        for (source, all, conditions_only) in cases {
+            assert_eq!(score(source, BoolOps::All), all, "{}", source);
+            assert_eq!(score(source, BoolOps::ConditionsOnly), conditions_only, "{}", source);
+        }
+        assert_eq!(Weights::preset(Preset::Mccabe).bool_ops, BoolOps::ConditionsOnly);
+        assert_eq!(Weights::preset(Preset::Radon).bool_ops, BoolOps::All);
+    }
+
+    #[test]
+    fn test_parse_override() {
+        assert_eq!(parse_override("with=0"), Ok((Construct::With, 0)));
+        assert_eq!(parse_override("loop-else=2"), Ok((Construct::LoopElse, 2)));
+        assert!(parse_override("goto=1").unwrap_err().contains("unknown construct"));
+        assert!(parse_override("if").is_err());
+        assert!(parse_override("if=-1").is_err());
+    }
+}
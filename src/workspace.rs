@@ -0,0 +1,200 @@
+//! Analysis of several labelled roots (`--root api=repos/api`) in one run.
+
+use crate::fingerprint::fingerprint;
+use crate::links::Linker;
+use crate::output::relative_path;
+use crate::{finish, scan, summarize, AnalysisResult, AnalyzeOptions, ProjectSummary};
+use anyhow::{bail, Result};
+use std::path::PathBuf;
+
+#[derive(Debug)]
+pub struct Project {
+    pub label: String,
+    pub path: PathBuf,
+    /// Options of its analysis, with its own threshold.
+    pub options: AnalyzeOptions,
+}
+
+/// Parses a `label=path` root argument.
+pub fn parse_root(spec: &str) -> Result<(String, PathBuf), String> {
+    let (label, path) = split_label(spec)?;
+    Ok((label, PathBuf::from(path)))
+}
+
+/// Parses a `label=threshold` argument.
+pub fn parse_threshold(spec: &str) -> Result<(String, u32), String> {
+    let (label, threshold) = split_label(spec)?;
+    let threshold = threshold
+        .parse()
+        .map_err(|_| format!("`{}` is not a valid threshold", threshold))?;
+    Ok((label, threshold))
+}
+
+fn split_label(spec: &str) -> Result<(String, &str), String> {
+    match spec.split_once('=') {
+        Some((label, value)) if !label.is_empty() && !value.is_empty() => {
+            Ok((label.to_string(), value))
+        }
+        _ => Err(format!("expected LABEL=VALUE, got `{}`", spec)),
+    }
+}
+
+/// Rejects roots sharing a label, and per-project settings naming a label
+/// that is not a root.
+pub fn check_labels<'a>(
+    roots: &[(String, PathBuf)],
+    referenced: impl IntoIterator<Item = &'a String>,
+) -> Result<()> {
+    for (index, (label, path)) in roots.iter().enumerate() {
+        if let Some((_, other)) = roots[..index].iter().find(|(l, _)| l == label) {
+            bail!(
+                "project label `{}` is used for both {} and {}",
+                label,
+                other.display(),
+                path.display()
+            );
+        }
+    }
+    for label in referenced {
+        if !roots.iter().any(|(l, _)| l == label) {
+            bail!("no --root is labelled `{}`", label);
+        }
+    }
+    Ok(())
+}
+
+/// Analyzes every project with its own threshold and filters, and reports
+/// on them together as `options` asks. Functions are tagged with their
+/// project and their paths made relative to its root.
+pub fn analyze(projects: &[Project], options: &AnalyzeOptions) -> Result<AnalysisResult> {
+    let mut functions = Vec::new();
+    let mut errors = Vec::new();
+    let mut generated_files = Vec::new();
+    let mut generated_functions = 0;
+    let mut breakdown = Vec::new();
+
+    for project in projects {
+        let mut result = scan(&project.path, &project.options)?;
+        if let Some(links) = &options.links {
+            Linker::detect(&project.path, links.base.clone(), links.template.clone())
+                .annotate(&mut result);
+        }
+        for func in &mut result.functions {
+            let relative = relative_path(&func.file, &project.path);
+            func.fingerprint =
+                fingerprint(&format!("{}/{}", project.label, relative), &func.name);
+            func.file = relative;
+            func.project = Some(project.label.clone());
+        }
+        for file in &mut result.generated_files {
+            file.file = relative_path(&file.file, &project.path);
+            file.project = Some(project.label.clone());
+        }
+
+        if let Some(summary) = &result.summary {
+            generated_functions += summary.generated_functions;
+            breakdown.push(ProjectSummary {
+                project: project.label.clone(),
+                threshold: project.options.threshold,
+                total_functions: summary.total_functions,
+                mean_complexity: summary.mean_complexity,
+                max_complexity: summary.max_complexity,
+                functions_above_threshold: summary.functions_above_threshold,
+            });
+        }
+        functions.extend(result.functions);
+        generated_files.extend(result.generated_files);
+        errors.extend(result.errors);
+    }
+
+    let mut summary = summarize(&functions, options.threshold, options.metric);
+    if let Some(summary) = &mut summary {
+        summary.functions_above_threshold =
+            breakdown.iter().map(|p| p.functions_above_threshold).sum();
+        summary.generated_functions = generated_functions;
+        summary.projects = breakdown;
+    }
+    let mut result = AnalysisResult {
+        functions,
+        generated_files,
+        summary,
+        errors,
+        ..Default::default()
+    };
+    let roots: Vec<(Option<String>, PathBuf)> = projects
+        .iter()
+        .map(|project| (Some(project.label.clone()), project.path.clone()))
+        .collect();
+    finish(&mut result, &roots, projects, options);
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::severity::Severity;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse_root() {
+        assert_eq!(
+            parse_root("api=repos/api").unwrap(),
+            ("api".to_string(), PathBuf::from("repos/api"))
+        );
+        assert!(parse_root("repos/api").is_err());
+        assert!(parse_root("=repos/api").is_err());
+        assert_eq!(parse_threshold("web=15").unwrap(), ("web".to_string(), 15));
+        assert!(parse_threshold("web=-1").is_err());
+    }
+
+    #[test]
+    fn test_label_collision() {
+        let roots = vec![
+            ("api".to_string(), PathBuf::from("a")),
+            ("api".to_string(), PathBuf::from("b")),
+        ];
+        assert!(check_labels(&roots, []).is_err());
+        assert!(check_labels(&roots[..1], [&"web".to_string()]).is_err());
+        assert!(check_labels(&roots[..1], [&"api".to_string()]).is_ok());
+    }
+
+    #[test]
+    fn test_analyze_projects() {
+        let temp_dir = TempDir::new().unwrap();
+        let source = "def handler(x):\n    if x:\n        if x > 1:\n            pass\n";
+        for project in ["api", "web"] {
+            let dir = temp_dir.path().join(project);
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(dir.join("views.py"), source).unwrap();
+        }
+        let projects: Vec<Project> = [("api", 2), ("web", 5)]
+            .into_iter()
+            .map(|(label, threshold)| Project {
+                label: label.to_string(),
+                path: temp_dir.path().join(label),
+                options: AnalyzeOptions {
+                    threshold,
+                    ..Default::default()
+                },
+            })
+            .collect();
+
+        let result = analyze(&projects, &AnalyzeOptions::default()).unwrap();
+
+        assert_eq!(result.functions.len(), 2);
+        assert_eq!(result.functions[0].file, "views.py");
+        assert_eq!(result.functions[0].project.as_deref(), Some("api"));
+        assert_ne!(result.functions[0].fingerprint, result.functions[1].fingerprint);
+        // Each project's threshold decides the severity of its functions.
+        assert_eq!(result.functions[0].severity, Some(Severity::Warning));
+        assert_eq!(result.functions[1].severity, None);
+
+        let summary = result.summary.unwrap();
+        assert_eq!(summary.total_functions, 2);
+        // Complexity 3 is above api's threshold of 2 but not web's of 5.
+        assert_eq!(summary.functions_above_threshold, 1);
+        assert_eq!(summary.projects.len(), 2);
+        assert_eq!(summary.projects[1].project, "web");
+        assert_eq!(summary.projects[1].functions_above_threshold, 0);
+    }
+}
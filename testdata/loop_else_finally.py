@@ -0,0 +1,69 @@
+# Fixture for the loop-else and finally weights. `default` is the analyzr
+# preset, which gives both a weight of 0; `opted-in` adds
+# --weight loop-else=1 --weight finally=1.
+
+
+# expected: default=3 opted-in=4
+def find_first_negative(values):
+    for value in values:
+        if value < 0:
+            break
+    else:
+        return None
+    return value
+
+
+# expected: default=3 opted-in=4
+def drain(queue):
+    while queue:
+        item = queue.pop()
+        if item is None:
+            break
+    else:
+        return True
+    return False
+
+
+# expected: default=4 opted-in=5
+def load(path):
+    try:
+        handle = open(path)
+    except FileNotFoundError:
+        return None
+    except PermissionError:
+        raise
+    else:
+        data = handle.read()
+    finally:
+        cleanup()
+    return data
+
+
+# expected: default=2 opted-in=3
+def locked(lock, action):
+    lock.acquire()
+    try:
+        return action()
+    finally:
+        lock.release()
+
+
+# expected: default=4 opted-in=6
+def search(rows, key):
+    try:
+        for row in rows:
+            if row.key == key:
+                break
+        else:
+            row = None
+    finally:
+        rows.close()
+    return row
+
+
+# expected: default=2 opted-in=2
+def total(values):
+    result = 0
+    for value in values:
+        result += value
+    return result
@@ -0,0 +1,85 @@
+# Shared fixture for the complexity presets. The expected scores follow the
+# counting rules of each reference tool: radon's ComplexityVisitor, mccabe's
+# PathGraphingAstVisitor and SonarQube's Python ComplexityVisitor.
+
+
+# expected: analyzr=1 radon=1 mccabe=1 sonar=1
+def straight_line(a):
+    return a + 1
+
+
+# expected: analyzr=4 radon=4 mccabe=4 sonar=4
+def branches(x):
+    if x > 10:
+        return "big"
+    elif x > 5:
+        return "medium"
+    for i in range(x):
+        print(i)
+    return "small"
+
+
+# expected: analyzr=3 radon=3 mccabe=1 sonar=3
+def conditions(a, b, c):
+    flag = a and b or c
+    return flag
+
+
+# expected: analyzr=5 radon=5 mccabe=3 sonar=5
+def validate(user, strict):
+    if user is None or not user.active:
+        return False
+    while strict and user.pending:
+        user.refresh()
+    return True
+
+
+# expected: analyzr=4 radon=5 mccabe=4 sonar=4
+def loops_with_else(items):
+    for item in items:
+        if item:
+            break
+    else:
+        return None
+    while items:
+        items.pop()
+    return item
+
+
+# expected: analyzr=5 radon=5 mccabe=3 sonar=1
+def errors(path):
+    try:
+        f = open(path)
+    except OSError:
+        return None
+    except ValueError:
+        raise
+    else:
+        f.close()
+    finally:
+        print("done")
+    with open(path) as g:
+        return g.read()
+
+
+# expected: analyzr=4 radon=5 mccabe=1 sonar=4
+def expressions(items, limit):
+    evens = [i for i in items if i % 2 == 0]
+    label = "many" if len(evens) > limit else "few"
+    assert limit >= 0
+    return label
+
+
+# expected: analyzr=8 radon=8 mccabe=5 sonar=7
+def mixed(rows, threshold):
+    total = 0
+    for row in rows:
+        if row.value > threshold and not row.skip:
+            total += row.value
+        elif row.value < 0:
+            continue
+    try:
+        ratio = total / len(rows)
+    except ZeroDivisionError:
+        ratio = 0
+    return [r for r in rows if r.value > ratio]
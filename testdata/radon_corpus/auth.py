@@ -0,0 +1,19 @@
+import hmac
+
+
+def check_password(user, password):
+    if user is None or not user.active:
+        return False
+    if user.locked_until and user.locked_until > now():
+        return False
+    return hmac.compare_digest(user.password_hash, hash_password(password))
+
+
+def now():
+    import time
+
+    return time.time()
+
+
+def hash_password(password):
+    return password[::-1]
@@ -0,0 +1,22 @@
+"""Invoice totals."""
+
+
+def compute_totals(lines, discounts, tax_rate):
+    subtotal = 0
+    for line in lines:
+        if line.quantity <= 0:
+            continue
+        price = line.unit_price * line.quantity
+        if line.sku in discounts and discounts[line.sku] > 0:
+            price -= price * discounts[line.sku]
+        subtotal += price
+    tax = subtotal * tax_rate if tax_rate else 0
+    return subtotal + tax
+
+
+def format_amount(amount, currency="EUR"):
+    if currency == "EUR":
+        return f"{amount:.2f} €"
+    elif currency == "USD":
+        return f"${amount:.2f}"
+    return f"{amount:.2f} {currency}"
@@ -0,0 +1,17 @@
+class Cache:
+    def __init__(self, capacity):
+        self.capacity = capacity
+        self.entries = {}
+
+    def get(self, key, default=None):
+        entry = self.entries.get(key)
+        if entry is None or entry.expired():
+            self.entries.pop(key, None)
+            return default
+        return entry.value
+
+    def put(self, key, value):
+        if len(self.entries) >= self.capacity and key not in self.entries:
+            oldest = min(self.entries, key=lambda k: self.entries[k].created)
+            del self.entries[oldest]
+        self.entries[key] = value
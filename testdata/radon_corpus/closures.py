@@ -0,0 +1,21 @@
+def make_validator(rules):
+    def validate(value):
+        for rule in rules:
+            if not rule(value):
+                return False
+        return True
+
+    if not rules:
+        return lambda value: True
+    return validate
+
+
+def counter():
+    count = 0
+
+    def increment(step=1):
+        nonlocal count
+        count += step if step > 0 else 1
+        return count
+
+    return increment
@@ -0,0 +1,13 @@
+import json
+import os
+
+
+def load_config(path, env=None):
+    env = env or os.environ
+    with open(path) as handle:
+        data = json.load(handle)
+    for key, value in list(data.items()):
+        override = env.get("APP_" + key.upper())
+        if override is not None:
+            data[key] = type(value)(override) if value is not None else override
+    return data
@@ -0,0 +1,26 @@
+def dispatch(handlers, event):
+    handled = False
+    for handler in handlers.get(event.kind, []):
+        try:
+            handler(event)
+            handled = True
+        except Exception:
+            if event.critical:
+                raise
+    if not handled and event.kind != "noop":
+        log_unhandled(event)
+    return handled
+
+
+def log_unhandled(event):
+    print("unhandled", event)
+
+
+def make_registry():
+    class Registry(dict):
+        def register(self, kind, handler):
+            if kind not in self:
+                self[kind] = []
+            self[kind].append(handler)
+
+    return Registry()
@@ -0,0 +1,18 @@
+import os
+
+
+def find_files(root, extensions):
+    found = []
+    for directory, subdirs, names in os.walk(root):
+        subdirs[:] = [d for d in subdirs if not d.startswith(".")]
+        for name in names:
+            if os.path.splitext(name)[1] in extensions:
+                found.append(os.path.join(directory, name))
+    return found
+
+
+def read_first_line(path):
+    with open(path) as handle, open(os.devnull, "w") as sink:
+        line = handle.readline()
+        sink.write(line)
+    return line.rstrip("\n")
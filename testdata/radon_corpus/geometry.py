@@ -0,0 +1,14 @@
+def classify_triangle(a, b, c):
+    if a <= 0 or b <= 0 or c <= 0:
+        raise ValueError("sides must be positive")
+    if a + b <= c or a + c <= b or b + c <= a:
+        return "invalid"
+    if a == b == c:
+        return "equilateral"
+    if a == b or b == c or a == c:
+        return "isosceles"
+    return "scalene"
+
+
+def area(width, height):
+    return width * height
@@ -0,0 +1,19 @@
+def request(session, method, url, retries=2, **kwargs):
+    response = None
+    attempt = 0
+    while attempt <= retries:
+        try:
+            response = session.request(method, url, **kwargs)
+        except ConnectionError:
+            attempt += 1
+            continue
+        if response.status_code >= 500 and attempt < retries:
+            attempt += 1
+            continue
+        break
+    return response
+
+
+def is_json(response):
+    content_type = response.headers.get("Content-Type", "")
+    return content_type.startswith("application/json") or content_type.endswith("+json")
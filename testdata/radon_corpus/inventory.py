@@ -0,0 +1,19 @@
+def restock(inventory, orders, threshold=5):
+    to_order = {}
+    for sku, quantity in inventory.items():
+        pending = sum(o.quantity for o in orders if o.sku == sku and not o.cancelled)
+        available = quantity - pending
+        if available < threshold:
+            to_order[sku] = threshold * 2 - available
+    return to_order
+
+
+def remove_item(inventory, sku):
+    try:
+        del inventory[sku]
+    except KeyError:
+        return False
+    else:
+        return True
+    finally:
+        inventory.pop(None, None)
@@ -0,0 +1,10 @@
+def transpose(matrix):
+    return [[row[i] for row in matrix] for i in range(len(matrix[0]))] if matrix else []
+
+
+def flatten(matrix):
+    return [value for row in matrix for value in row if value is not None]
+
+
+def identity(n):
+    return [[1 if i == j else 0 for j in range(n)] for i in range(n)]
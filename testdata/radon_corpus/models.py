@@ -0,0 +1,19 @@
+class Order:
+    def __init__(self, items, customer=None):
+        self.items = list(items)
+        self.customer = customer
+
+    def total(self):
+        return sum(item.price for item in self.items if not item.free)
+
+    def status(self):
+        if not self.items:
+            return "empty"
+        elif all(item.shipped for item in self.items):
+            return "shipped"
+        elif any(item.shipped for item in self.items):
+            return "partial"
+        return "pending"
+
+    def __eq__(self, other):
+        return isinstance(other, Order) and self.items == other.items
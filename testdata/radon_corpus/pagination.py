@@ -0,0 +1,11 @@
+async def fetch_all(client, url, page_size=100):
+    results = []
+    page = 1
+    while True:
+        async with client.get(url, params={"page": page, "size": page_size}) as response:
+            batch = await response.json()
+        results.extend(batch)
+        if len(batch) < page_size:
+            break
+        page += 1
+    return results
@@ -0,0 +1,19 @@
+def parse_header(line):
+    key, _, value = line.partition(":")
+    key = key.strip().lower()
+    if not key:
+        raise ValueError("empty header name")
+    return key, value.strip()
+
+
+def parse_headers(text):
+    headers = {}
+    for line in text.splitlines():
+        if not line or line.startswith("#"):
+            continue
+        key, value = parse_header(line)
+        if key in headers:
+            headers[key] = headers[key] + "," + value
+        else:
+            headers[key] = value
+    return headers
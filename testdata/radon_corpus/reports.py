@@ -0,0 +1,16 @@
+def summarize(rows):
+    by_region = {}
+    for row in rows:
+        bucket = by_region.setdefault(row.region, {"count": 0, "total": 0})
+        bucket["count"] += 1
+        bucket["total"] += row.amount
+    return {
+        region: {**stats, "mean": stats["total"] / stats["count"]}
+        for region, stats in by_region.items()
+        if stats["count"]
+    }
+
+
+def top_regions(summary, n=3):
+    ranked = sorted(summary.items(), key=lambda kv: kv[1]["total"], reverse=True)
+    return [region for region, _ in ranked[:n]]
@@ -0,0 +1,15 @@
+import time
+
+
+def retry(func, attempts=3, delay=0.1):
+    last_error = None
+    for attempt in range(attempts):
+        try:
+            return func()
+        except (ConnectionError, TimeoutError) as error:
+            last_error = error
+            time.sleep(delay * (2 ** attempt))
+        except KeyboardInterrupt:
+            raise
+    else:
+        raise RuntimeError("gave up") from last_error
@@ -0,0 +1,11 @@
+def next_run(job, now):
+    if job.paused:
+        return None
+    while job.next_at <= now:
+        job.next_at += job.interval
+        if job.max_runs and job.runs >= job.max_runs:
+            job.paused = True
+            break
+    else:
+        return job.next_at
+    return None
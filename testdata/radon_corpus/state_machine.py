@@ -0,0 +1,18 @@
+class Door:
+    def __init__(self):
+        self.state = "closed"
+
+    def handle(self, event):
+        if self.state == "closed":
+            if event == "open":
+                self.state = "open"
+            elif event == "lock":
+                self.state = "locked"
+        elif self.state == "open":
+            if event == "close":
+                self.state = "closed"
+        elif self.state == "locked" and event == "unlock":
+            self.state = "closed"
+        else:
+            raise ValueError(event)
+        return self.state
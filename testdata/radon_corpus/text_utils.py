@@ -0,0 +1,17 @@
+def slugify(text, max_length=50):
+    words = [w.lower() for w in text.split() if w.isalnum()]
+    slug = "-".join(words)
+    return slug[:max_length] if len(slug) > max_length else slug
+
+
+def wrap(text, width):
+    lines, current = [], ""
+    for word in text.split():
+        if current and len(current) + len(word) + 1 > width:
+            lines.append(current)
+            current = word
+        else:
+            current = f"{current} {word}" if current else word
+    if current:
+        lines.append(current)
+    return lines
@@ -0,0 +1,18 @@
+def validate_user(data):
+    errors = []
+    assert isinstance(data, dict)
+    name = data.get("name")
+    if not name or len(name) > 100:
+        errors.append("name")
+    email = data.get("email", "")
+    if "@" not in email:
+        errors.append("email")
+    age = data.get("age")
+    if age is not None and (age < 0 or age > 150):
+        errors.append("age")
+    return errors
+
+
+def validate_batch(items):
+    assert items and all(isinstance(i, dict) for i in items)
+    return [validate_user(item) for item in items]
@@ -0,0 +1,48 @@
+# Fixture for assignment expressions. `complexity` is the analyzr preset,
+# which gives the walrus construct a weight of 0; `walrus` adds
+# --weight walrus=1. `locals` counts the names each function binds.
+
+
+# expected: complexity=2 walrus=3 locals=2
+def read_chunks(stream, size):
+    chunks = []
+    while (chunk := stream.read(size)):
+        chunks.append(chunk)
+    return chunks
+
+
+# expected: complexity=2 walrus=3 locals=1
+def normalized(values):
+    return [clean for value in values if (clean := value.strip())]
+
+
+# expected: complexity=3 walrus=4 locals=1
+def log_size(items, logger):
+    logger.info("%d items", n := len(items))
+    if n > 100 and logger.verbose:
+        logger.warning("large batch")
+    return n
+
+
+# expected: complexity=3 walrus=4 locals=2
+def bucket(items):
+    if (n := len(items)) > 10 and n < 100:
+        label = "medium"
+    else:
+        label = "other"
+    return label, n
+
+
+# expected: complexity=4 walrus=5 locals=7
+def parse(lines):
+    total = 0
+    for index, line in enumerate(lines):
+        key, _, value = line.partition("=")
+        if (stripped := value.strip()) and not key.startswith("#"):
+            total += len(stripped)
+    return total
+
+
+# expected: complexity=1 walrus=3 locals=2
+def sizes(groups):
+    return [(first := group[0], last := group[-1]) for group in groups]
@@ -0,0 +1,336 @@
+//! The binary run on fixtures: its exit codes above and below the
+//! threshold, what the listing filters leave out, a single file or source
+//! piped to stdin analyzed as an editor would, JSON that is what the library
+//! returns, and the rule globs `config check` warns about.
+
+use complexity_audit::severity::Severity;
+use complexity_audit::{analyze_path, config, AnalysisResult, AnalyzeOptions};
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+use tempfile::TempDir;
+
+/// A function with a cyclomatic complexity of 1 + `branches`.
+fn function(name: &str, branches: usize) -> String {
+    let mut source = format!("def {}(x):\n", name);
+    for i in 0..branches {
+        source.push_str(&format!("    if x == {}:\n        return {}\n", i, i));
+    }
+    source.push_str("    return x\n");
+    source
+}
+
+fn fixture(files: &[(&str, String)]) -> TempDir {
+    let temp_dir = TempDir::new().unwrap();
+    for (name, source) in files {
+        std::fs::write(temp_dir.path().join(name), source).unwrap();
+    }
+    temp_dir
+}
+
+fn run(path: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_complexity-audit"))
+        .arg(path)
+        .args(["--no-meta", "--output", "json"])
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+/// The binary run on `source` piped to stdin, printing compact JSON.
+fn run_stdin(source: &str, args: &[&str]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_complexity-audit"))
+        .arg("-")
+        .args(["--no-meta", "--output", "json", "--compact"])
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(source.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn test_fail_on_threshold() {
+    // Complexities 2 and 3, then 12.
+    let below = fixture(&[("simple.py", function("a", 1) + &function("b", 2))]);
+    let above = fixture(&[
+        ("simple.py", function("a", 1) + &function("b", 2)),
+        ("tangled.py", function("tangled", 11)),
+    ]);
+
+    let output = run(below.path(), &["--fail-on-threshold"]);
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+
+    // Without the flag, functions above the threshold are only reported.
+    assert_eq!(run(above.path(), &[]).status.code(), Some(0));
+
+    let output = run(above.path(), &["--fail-on-threshold"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).contains("1 function is above the threshold of 10"));
+    // The report is printed before failing.
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["summary"]["functions_above_threshold"], 1);
+
+    let output = run(above.path(), &["--fail-on-threshold", "--threshold", "12"]);
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn test_summary_gate_flags() {
+    // Complexities 2, 3 and 12: mean 5.6667, one function above 10.
+    let dir = fixture(&[
+        ("simple.py", function("a", 1) + &function("b", 2)),
+        ("tangled.py", function("tangled", 11)),
+    ]);
+
+    let output = run(dir.path(), &["--max-average", "6", "--max-total-functions-above", "1"]);
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+
+    let output = run(dir.path(), &["--max-average", "5"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output)
+        .contains("summary gate `mean` failed: mean is 5.6667, 0.6667 above the gate of 5"));
+
+    let output = run(
+        dir.path(),
+        &["--max-average", "5", "--max-total-functions-above", "0"],
+    );
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = stderr(&output);
+    assert!(stderr.contains("summary gate `mean` failed"));
+    assert!(stderr.contains(
+        "summary gate `above_threshold` failed: above_threshold is 1, 1 above the gate of 0"
+    ));
+    assert!(!output.stdout.is_empty());
+}
+
+#[test]
+fn test_errors_exit_with_1() {
+    let dir = fixture(&[("simple.py", function("a", 1))]);
+    let output = run(dir.path(), &["--max-average=-1"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("summary gate `mean` cannot be negative"));
+
+    let output = run(dir.path(), &["--fail-on-threshold", "--sort-by", "delta"]);
+    assert_eq!(output.status.code(), Some(1));
+
+    // Unlike clap's default, whose 2 would read as a failed check.
+    let output = run(dir.path(), &["--max-average", "many"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("invalid value 'many'"));
+    for limit in ["nan", "inf"] {
+        let output = run(dir.path(), &["--max-average", limit]);
+        assert_eq!(output.status.code(), Some(1));
+        assert!(stderr(&output).contains("summary gate `mean` must be a finite number"));
+    }
+}
+
+#[test]
+fn test_check_exits_with_2() {
+    let dir = fixture(&[("tangled.py", function("tangled", 11))]);
+    let check = |threshold: &str| {
+        Command::new(env!("CARGO_BIN_EXE_complexity-audit"))
+            .args(["check", "--threshold", threshold])
+            .arg(dir.path())
+            .output()
+            .unwrap()
+    };
+    let output = check("12");
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+
+    let output = check("10");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stdout).starts_with("FAIL: 1 function exceeds 10"));
+}
+
+#[test]
+fn test_listing_filters_leave_the_summary_alone() {
+    // Complexities 1, 1, 2, 3, 5 and 12.
+    let dir = fixture(&[
+        ("simple.py", function("a", 0) + &function("b", 0) + &function("c", 1)),
+        ("busy.py", function("d", 2) + &function("e", 4)),
+        ("tangled.py", function("tangled", 11)),
+    ]);
+    let report = |args: &[&str]| {
+        let output = run(dir.path(), args);
+        assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+        serde_json::from_slice::<serde_json::Value>(&output.stdout).unwrap()
+    };
+    let names = |report: &serde_json::Value| {
+        report["functions"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|f| f["name"].as_str().unwrap().to_string())
+            .collect::<Vec<_>>()
+    };
+
+    let all = report(&[]);
+    assert_eq!(names(&all), ["tangled", "e", "d", "c", "a", "b"]);
+    let filtered = report(&["--min-complexity", "2", "--top", "2", "--sort", "name"]);
+    assert_eq!(names(&filtered), ["e", "tangled"]);
+    // The summary still covers every function.
+    assert_eq!(filtered["summary"], all["summary"]);
+    assert_eq!(filtered["summary"]["total_functions"], 6);
+    assert_eq!(filtered["summary"]["mean_complexity"], 4.0);
+
+    assert_eq!(names(&report(&["--min-complexity", "3"])), ["tangled", "e", "d"]);
+    assert_eq!(names(&report(&["--sort-by", "line", "--top", "3"])), ["d", "tangled", "e"]);
+    assert_eq!(
+        names(&report(&["--sort", "file"])),
+        ["d", "e", "a", "b", "c", "tangled"]
+    );
+
+    let help = Command::new(env!("CARGO_BIN_EXE_complexity-audit"))
+        .arg("--help")
+        .output()
+        .unwrap();
+    assert!(String::from_utf8_lossy(&help.stdout).contains("[aliases: sort]"));
+}
+
+#[test]
+fn test_stdin() {
+    let source = function("a", 0) + "\n" + &function("b", 2);
+    let output = run_stdin(&source, &["--stdin-filename", "src/app.py"]);
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.lines().count(), 1);
+    let report: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    let functions: Vec<(&str, &str, u64, u64)> = report["functions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|f| {
+            (
+                f["name"].as_str().unwrap(),
+                f["file"].as_str().unwrap(),
+                f["line"].as_u64().unwrap(),
+                f["complexity"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        functions,
+        [("b", "src/app.py", 4, 3), ("a", "src/app.py", 1, 1)]
+    );
+    assert_eq!(report["summary"]["total_functions"], 2);
+
+    // The language flag wins over the extension.
+    let output = run_stdin(
+        "\nconst pick = (a, b) => a ?? b;\n",
+        &["--stdin-filename", "src/app.py", "--lang", "javascript"],
+    );
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let pick = &report["functions"][0];
+    assert_eq!(pick["name"], "pick");
+    assert_eq!(pick["line"], 2);
+    assert_eq!(pick["column"], 14);
+    assert_eq!(pick["language"], "javascript");
+    assert_eq!(pick["file"], "src/app.py");
+
+    let output = run_stdin("", &["--lang", "python"]);
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["functions"], serde_json::json!([]));
+    assert_eq!(report["errors"], serde_json::json!([]));
+}
+
+#[test]
+fn test_stdin_needs_a_language() {
+    let output = run_stdin("def f():\n    pass\n", &[]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("needs --stdin-filename or --lang"));
+
+    let dir = fixture(&[("simple.py", function("a", 1))]);
+    let output = run(dir.path(), &["--stdin-filename", "simple.py"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("--stdin-filename only applies"));
+}
+
+#[test]
+fn test_single_file() {
+    let dir = fixture(&[
+        ("simple.py", function("a", 1)),
+        ("tangled.py", function("tangled", 11)),
+    ]);
+    let file = dir.path().join("simple.py");
+    let output = run(&file, &["--compact"]);
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let functions = report["functions"].as_array().unwrap();
+    assert_eq!(functions.len(), 1);
+    assert_eq!(functions[0]["name"], "a");
+    assert_eq!(functions[0]["file"], file.to_string_lossy().as_ref());
+    assert_eq!(functions[0]["line"], 1);
+}
+
+#[test]
+fn test_json_is_what_the_library_returns() {
+    let config = "[thresholds]\nwarn = 4\n\
+                  [risk]\ncyclomatic = 0.5\n\
+                  [[rules]]\npaths = [\"legacy/**\"]\nmax_cyclomatic = 8\n\
+                  [[categories]]\nname = \"task\"\ndecorators = [\"shared_task\"]\n";
+    let temp_dir = fixture(&[
+        ("app.py", function("small", 1) + "@shared_task\n" + &function("big", 5)),
+        ("analyzr.toml", config.to_string()),
+    ]);
+    std::fs::create_dir(temp_dir.path().join("legacy")).unwrap();
+    std::fs::write(temp_dir.path().join("legacy/old.py"), function("old", 6)).unwrap();
+
+    let output = run(temp_dir.path(), &["--inheritance"]);
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    let mut printed: AnalysisResult = serde_json::from_slice(&output.stdout).unwrap();
+    let options = AnalyzeOptions {
+        inheritance: true,
+        ..config::load_checked(temp_dir.path()).unwrap().options()
+    };
+    let mut returned = analyze_path(temp_dir.path(), &options).unwrap();
+    // The binary lists the most complex functions first.
+    for result in [&mut printed, &mut returned] {
+        result.functions.sort_by(|a, b| (&a.file, a.line).cmp(&(&b.file, b.line)));
+    }
+    assert_eq!(printed, returned);
+
+    let severities: Vec<(&str, Option<Severity>)> = returned
+        .functions
+        .iter()
+        .map(|f| (f.name.as_str(), f.severity))
+        .collect();
+    assert_eq!(
+        severities,
+        [("small", None), ("big", Some(Severity::Warning)), ("old", None)]
+    );
+    assert_eq!(returned.functions[1].category.as_deref(), Some("task"));
+    assert_eq!(returned.functions[2].risk, Some(3.5));
+}
+
+#[test]
+fn test_config_check_matches_rules_against_every_language() {
+    let config = "[[rules]]\npaths = [\"web/**/*.ts\", \"api/**\"]\nmax_cyclomatic = 5\n";
+    let temp_dir = fixture(&[("analyzr.toml", config.to_string())]);
+    std::fs::create_dir_all(temp_dir.path().join("web/src")).unwrap();
+    std::fs::write(temp_dir.path().join("web/src/app.ts"), "const f = () => 1;\n").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_complexity-audit"))
+        .args(["config", "check"])
+        .arg(temp_dir.path())
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("warning: `api/**` in rules[0]"), "{}", stdout);
+    assert!(stdout.contains("matches no analyzed file"));
+    assert!(!stdout.contains("warning: `web/**/*.ts`"), "{}", stdout);
+}
@@ -0,0 +1,123 @@
+//! The library used the way another tool embeds it, without the binary.
+
+use complexity_audit::fingerprint::fingerprint;
+use complexity_audit::language::Language;
+use complexity_audit::walk::Filter;
+use complexity_audit::{
+    analyze_buffer, analyze_path, analyze_source, AnalysisResult, AnalyzeOptions,
+};
+use std::path::Path;
+use tempfile::TempDir;
+
+/// A function with a cyclomatic complexity of 1 + `branches`.
+fn function(name: &str, branches: usize) -> String {
+    let mut source = format!("def {}(x):\n", name);
+    for i in 0..branches {
+        source.push_str(&format!("    if x == {}:\n        return {}\n", i, i));
+    }
+    source.push_str("    return x\n");
+    source
+}
+
+#[test]
+fn test_analyze_source() {
+    let python = format!("{}\n{}", function("a", 0), function("b", 3));
+    let functions = analyze_source(&python, Language::Python).unwrap();
+    let scores: Vec<(&str, u32, u32)> = functions
+        .iter()
+        .map(|f| (f.name.as_str(), f.line, f.complexity))
+        .collect();
+    assert_eq!(scores, [("a", 1, 1), ("b", 4, 4)]);
+    // Source has no file, and no fingerprint relative to one.
+    assert!(functions
+        .iter()
+        .all(|f| f.file.is_empty() && f.fingerprint.is_empty()));
+
+    let typescript = "function pick<T>(items: T[], i?: number): T {\n  return items[i ?? 0];\n}\n";
+    let functions = analyze_source(typescript, Language::TypeScript).unwrap();
+    assert_eq!(functions[0].name, "pick");
+    assert_eq!(functions[0].complexity, 2);
+    assert_eq!(functions[0].language, Language::TypeScript);
+
+    assert!(analyze_source("", Language::JavaScript).unwrap().is_empty());
+}
+
+#[test]
+fn test_analyze_path() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    std::fs::create_dir_all(root.join("tests")).unwrap();
+    std::fs::write(
+        root.join("app.py"),
+        function("small", 1) + &function("big", 5),
+    )
+    .unwrap();
+    std::fs::write(root.join("tests/test_app.py"), function("test_big", 8)).unwrap();
+
+    let options = AnalyzeOptions {
+        threshold: 4,
+        walk: Filter {
+            exclude: vec!["tests/**".to_string()],
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let result = analyze_path(root, &options).unwrap();
+    let names: Vec<&str> = result.functions.iter().map(|f| f.name.as_str()).collect();
+    assert_eq!(names, ["small", "big"]);
+    let app = root.join("app.py").to_string_lossy().to_string();
+    assert!(result.functions.iter().all(|f| f.file == app));
+    // Fingerprints are relative to the analyzed directory.
+    assert_eq!(
+        result.functions[0].fingerprint,
+        fingerprint("app.py", "small")
+    );
+    let summary = result.summary.as_ref().unwrap();
+    assert_eq!(summary.total_functions, 2);
+    assert_eq!(summary.functions_above_threshold, 1);
+
+    // Results are what `--output json` prints with the same options, and
+    // read back the same.
+    let json = serde_json::to_string(&result).unwrap();
+    let read: AnalysisResult = serde_json::from_str(&json).unwrap();
+    assert_eq!(read, result);
+
+    let everything = analyze_path(root, &AnalyzeOptions::default()).unwrap();
+    assert_eq!(everything.functions.len(), 3);
+    assert_eq!(everything.summary.unwrap().functions_above_threshold, 0);
+}
+
+#[test]
+fn test_analyze_a_single_file() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    std::fs::write(root.join("app.py"), function("main", 1)).unwrap();
+    std::fs::write(root.join("build.sh"), "const f = (a) => a ?? 0;\n").unwrap();
+    std::fs::write(root.join(".gitignore"), "app.py\n").unwrap();
+
+    // A file named directly is analyzed even when the walk would skip it.
+    let result = analyze_path(&root.join("app.py"), &AnalyzeOptions::default()).unwrap();
+    assert_eq!(result.functions.len(), 1);
+    assert_eq!(
+        result.functions[0].fingerprint,
+        fingerprint("app.py", "main")
+    );
+
+    let options = AnalyzeOptions {
+        language: Some(Language::JavaScript),
+        ..Default::default()
+    };
+    let result = analyze_path(&root.join("build.sh"), &options).unwrap();
+    assert_eq!(result.functions[0].name, "f");
+    assert_eq!(result.functions[0].complexity, 2);
+
+    // Unsaved content replaces what is on disk.
+    let result = analyze_buffer(
+        &function("main", 4),
+        &root.join("app.py"),
+        &AnalyzeOptions::default(),
+    );
+    assert_eq!(result.functions[0].complexity, 5);
+    let result = analyze_buffer("", Path::new("new.py"), &AnalyzeOptions::default());
+    assert!(result.functions.is_empty() && result.summary.is_none());
+}